@@ -192,13 +192,7 @@ impl BootInfo {
                 kbi.memory_map.truncated = true;
                 break;
             }
-            kbi.memory_map.descriptors[count] = KernelMemoryDescriptor {
-                ty: memory_region_type_to_uefi_u32(&region.region_type),
-                _pad: 0,
-                phys_start: region.start,
-                page_count: region.size / 4096,
-                attribute: region.attributes,
-            };
+            kbi.memory_map.descriptors[count] = to_kernel_descriptor(region);
             count += 1;
         }
         kbi.memory_map.count = count;
@@ -228,6 +222,39 @@ impl BootInfo {
     }
 }
 
+impl BootInfo {
+    /// Copies every memory region into `buffer` for the version 2 extended
+    /// map and returns the number written.
+    ///
+    /// Unlike [`to_kernel_boot_info`](Self::to_kernel_boot_info) this has no
+    /// fixed ceiling; the caller sizes `buffer` from
+    /// [`MemoryMap::region_count`]. Returns `None` if `buffer` is too small.
+    pub fn write_extended_memory_map(
+        &self,
+        buffer: &mut [KernelMemoryDescriptor],
+    ) -> Option<usize> {
+        let count = self.memory_map.region_count();
+        if count > buffer.len() {
+            return None;
+        }
+        for (slot, region) in buffer.iter_mut().zip(self.memory_map.regions()) {
+            *slot = to_kernel_descriptor(region);
+        }
+        Some(count)
+    }
+}
+
+/// Converts one region to the kernel ABI descriptor.
+fn to_kernel_descriptor(region: &crate::memory::MemoryRegion) -> KernelMemoryDescriptor {
+    KernelMemoryDescriptor {
+        ty: memory_region_type_to_uefi_u32(&region.region_type),
+        _pad: 0,
+        phys_start: region.start,
+        page_count: region.size / 4096,
+        attribute: region.attributes,
+    }
+}
+
 /// Maps our `MemoryRegionType` back to the raw UEFI memory type u32.
 fn memory_region_type_to_uefi_u32(ty: &crate::memory::MemoryRegionType) -> u32 {
     use crate::memory::MemoryRegionType::*;
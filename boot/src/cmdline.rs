@@ -0,0 +1,97 @@
+//! Kernel command line.
+//!
+//! Taken from the first of these that is non-empty:
+//!
+//! 1. The image's UEFI LoadOptions — set by a firmware boot option
+//!    (`efibootmgr -u`) or by the UEFI shell, which prepends the image
+//!    path; a leading `*.efi` word is therefore dropped.
+//! 2. The `cmdline` of the boot entry selected in `boot.cfg`.
+//! 3. [`CMDLINE_PATH`] on the boot volume. Lines starting with `#` are
+//!    comments; the remaining lines are joined with spaces.
+//!
+//! The result is passed to the kernel as a `CMDLINE` boot info tag. The
+//! kernel parses it; the bootloader only normalises whitespace.
+
+use alloc::string::String;
+
+use ferrous_boot_info::MAX_CMDLINE_LEN;
+use uefi::fs::FileSystem;
+use uefi::proto::loaded_image::LoadedImage;
+use uefi::{cstr16, CStr16};
+
+/// Path of the command line file on the boot volume.
+pub const CMDLINE_PATH: &CStr16 = cstr16!("\\EFI\\ferrous\\cmdline.txt");
+
+/// Where the command line came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CmdlineSource {
+    LoadOptions,
+    Config,
+    File,
+}
+
+/// A normalised command line.
+pub struct Cmdline {
+    /// Words separated by single spaces; at most [`MAX_CMDLINE_LEN`] bytes.
+    pub text: String,
+    pub source: CmdlineSource,
+    /// True if words were dropped to respect [`MAX_CMDLINE_LEN`].
+    pub truncated: bool,
+}
+
+/// Read the command line, or `None` if no source provides one.
+///
+/// `configured` is the selected boot entry's `cmdline`, if any.
+pub fn read_cmdline(configured: Option<&str>) -> Option<Cmdline> {
+    if let Some(options) = load_options() {
+        let words = options.split_whitespace().skip_while(|w| {
+            w.len() > 4 && w.as_bytes()[w.len() - 4..].eq_ignore_ascii_case(b".efi")
+        });
+        if let Some(cmdline) = normalise(words, CmdlineSource::LoadOptions) {
+            return Some(cmdline);
+        }
+    }
+
+    if let Some(cmdline) =
+        configured.and_then(|text| normalise(text.split_whitespace(), CmdlineSource::Config))
+    {
+        return Some(cmdline);
+    }
+
+    let sfs = uefi::boot::get_image_file_system(uefi::boot::image_handle()).ok()?;
+    let text = FileSystem::new(sfs).read_to_string(CMDLINE_PATH).ok()?;
+    let words = text
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .flat_map(str::split_whitespace);
+    normalise(words, CmdlineSource::File)
+}
+
+/// The LoadOptions of this image as a string, if they are UCS-2 text.
+fn load_options() -> Option<String> {
+    let image =
+        uefi::boot::open_protocol_exclusive::<LoadedImage>(uefi::boot::image_handle()).ok()?;
+    image.load_options_as_cstr16().ok().map(String::from)
+}
+
+/// Join `words` with single spaces, stopping before the length limit.
+fn normalise<'a>(words: impl Iterator<Item = &'a str>, source: CmdlineSource) -> Option<Cmdline> {
+    let mut text = String::new();
+    let mut truncated = false;
+    for word in words {
+        let needed = word.len() + usize::from(!text.is_empty());
+        if text.len() + needed > MAX_CMDLINE_LEN {
+            truncated = true;
+            break;
+        }
+        if !text.is_empty() {
+            text.push(' ');
+        }
+        text.push_str(word);
+    }
+    (!text.is_empty()).then_some(Cmdline {
+        text,
+        source,
+        truncated,
+    })
+}
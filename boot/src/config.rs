@@ -0,0 +1,262 @@
+//! Boot configuration file.
+//!
+//! `\EFI\ferrous\boot.cfg` lists one or more boot entries, each naming a
+//! kernel and optionally a command line, an initrd and boot modules:
+//!
+//! ```text
+//! # Ferrous boot configuration
+//! timeout 5
+//! default test
+//!
+//! entry Ferrous (known good)
+//!     kernel  kernel.elf
+//!     cmdline log=info
+//!     initrd  initrd.tar
+//!
+//! entry test
+//!     kernel  test/kernel.elf
+//!     cmdline log=debug test=paging
+//!     initrd  initrd.tar
+//!     module  init init.elf
+//! ```
+//!
+//! - `timeout <seconds>` — how long the menu waits before booting the
+//!   default entry; `0` boots it without showing the menu. Defaults to
+//!   [`DEFAULT_TIMEOUT_SECS`].
+//! - `default <index|title>` — the preselected entry, by 0-based index or
+//!   by title. Defaults to the first entry.
+//! - `entry <title>` starts an entry; the keys below apply to it. Every
+//!   entry needs a `kernel`.
+//! - `cmdline` takes the rest of the line; `module <name> <path>` may be
+//!   repeated.
+//!
+//! Paths go through [`resolve_path`]. Lines starting with `#` are
+//! comments; indentation is optional.
+//!
+//! [`parse`] never fails: problems are reported through the `warn` callback
+//! and the offending line (or entry) is skipped.
+//!
+//! Everything here depends only on `core` and `alloc`, so
+//! `tests/boot_tests.rs` compiles this file directly and checks the parser
+//! on the host.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// Menu timeout when the file does not set one.
+pub const DEFAULT_TIMEOUT_SECS: u32 = 5;
+
+/// Directory that relative paths are resolved against.
+pub const BOOT_DIR: &str = "\\EFI\\ferrous\\";
+
+/// A named file to load as a boot module (`module <name> <path>`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModuleSpec<'a> {
+    pub name: &'a str,
+    pub path: &'a str,
+}
+
+/// One bootable configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BootEntry<'a> {
+    /// Text shown in the menu.
+    pub title: &'a str,
+    pub kernel: &'a str,
+    pub cmdline: Option<&'a str>,
+    pub initrd: Option<&'a str>,
+    pub modules: Vec<ModuleSpec<'a>>,
+}
+
+/// A parsed `boot.cfg`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BootConfig<'a> {
+    /// Entries in file order; all have a kernel.
+    pub entries: Vec<BootEntry<'a>>,
+    /// Index into `entries`; `0` if there are none.
+    pub default: usize,
+    pub timeout_secs: u32,
+}
+
+/// A problem found by [`parse`], with its 1-based line number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Warning<'a> {
+    pub line: usize,
+    pub kind: WarningKind<'a>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WarningKind<'a> {
+    /// A key this parser does not know.
+    UnknownKey(&'a str),
+    /// A key without the value it needs.
+    MissingValue(&'a str),
+    /// A value that could not be used.
+    InvalidValue { key: &'a str, value: &'a str },
+    /// An entry key before the first `entry` line.
+    OutsideEntry(&'a str),
+    /// The entry starting at this line has no `kernel`; it is dropped.
+    NoKernel(&'a str),
+    /// `default` names no entry; the first entry is used.
+    UnknownDefault(&'a str),
+}
+
+impl fmt::Display for Warning<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match self.kind {
+            WarningKind::UnknownKey(key) => write!(f, "unknown key `{}`", key),
+            WarningKind::MissingValue(key) => write!(f, "`{}` needs a value", key),
+            WarningKind::InvalidValue { key, value } => {
+                write!(f, "invalid value `{}` for `{}`", value, key)
+            }
+            WarningKind::OutsideEntry(key) => write!(f, "`{}` outside an entry", key),
+            WarningKind::NoKernel(title) => write!(f, "entry `{}` has no kernel; dropped", title),
+            WarningKind::UnknownDefault(value) => {
+                write!(f, "default `{}` names no entry; using the first", value)
+            }
+        }
+    }
+}
+
+/// An entry being parsed; becomes a [`BootEntry`] once it has a kernel.
+struct PartialEntry<'a> {
+    title: &'a str,
+    line: usize,
+    kernel: Option<&'a str>,
+    cmdline: Option<&'a str>,
+    initrd: Option<&'a str>,
+    modules: Vec<ModuleSpec<'a>>,
+}
+
+/// Parse `text`, reporting problems through `warn`.
+pub fn parse<'a>(text: &'a str, mut warn: impl FnMut(Warning<'a>)) -> BootConfig<'a> {
+    let mut entries = Vec::new();
+    let mut current: Option<PartialEntry<'a>> = None;
+    let mut default: Option<(usize, &'a str)> = None;
+    let mut timeout_secs = DEFAULT_TIMEOUT_SECS;
+
+    for (index, raw) in text.lines().enumerate() {
+        let line = index + 1;
+        let trimmed = raw.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let (key, value) = match trimmed.split_once(char::is_whitespace) {
+            Some((key, value)) => (key, value.trim()),
+            None => (trimmed, ""),
+        };
+        let mut report = |kind| warn(Warning { line, kind });
+        if value.is_empty() {
+            report(WarningKind::MissingValue(key));
+            continue;
+        }
+
+        match key {
+            "timeout" => match value.parse() {
+                Ok(secs) => timeout_secs = secs,
+                Err(_) => report(WarningKind::InvalidValue { key, value }),
+            },
+            "default" => default = Some((line, value)),
+            "entry" => {
+                if let Some(entry) = current.take() {
+                    finish(entry, &mut entries, &mut warn);
+                }
+                current = Some(PartialEntry {
+                    title: value,
+                    line,
+                    kernel: None,
+                    cmdline: None,
+                    initrd: None,
+                    modules: Vec::new(),
+                });
+            }
+            "kernel" | "cmdline" | "initrd" | "module" => {
+                let Some(entry) = current.as_mut() else {
+                    report(WarningKind::OutsideEntry(key));
+                    continue;
+                };
+                match key {
+                    "kernel" => entry.kernel = Some(value),
+                    "cmdline" => entry.cmdline = Some(value),
+                    "initrd" => entry.initrd = Some(value),
+                    _ => match parse_module(value) {
+                        Some(spec) => entry.modules.push(spec),
+                        None => report(WarningKind::InvalidValue { key, value }),
+                    },
+                }
+            }
+            _ => report(WarningKind::UnknownKey(key)),
+        }
+    }
+    if let Some(entry) = current.take() {
+        finish(entry, &mut entries, &mut warn);
+    }
+
+    let default = match default {
+        None => 0,
+        Some((line, value)) => {
+            let by_index = value.parse::<usize>().ok().filter(|&i| i < entries.len());
+            let by_title = || entries.iter().position(|e| e.title == value);
+            by_index.or_else(by_title).unwrap_or_else(|| {
+                if !entries.is_empty() {
+                    warn(Warning {
+                        line,
+                        kind: WarningKind::UnknownDefault(value),
+                    });
+                }
+                0
+            })
+        }
+    };
+
+    BootConfig {
+        entries,
+        default,
+        timeout_secs,
+    }
+}
+
+/// Move a complete entry to `entries`, or drop it if it has no kernel.
+fn finish<'a>(
+    entry: PartialEntry<'a>,
+    entries: &mut Vec<BootEntry<'a>>,
+    warn: &mut impl FnMut(Warning<'a>),
+) {
+    match entry.kernel {
+        Some(kernel) => entries.push(BootEntry {
+            title: entry.title,
+            kernel,
+            cmdline: entry.cmdline,
+            initrd: entry.initrd,
+            modules: entry.modules,
+        }),
+        None => warn(Warning {
+            line: entry.line,
+            kind: WarningKind::NoKernel(entry.title),
+        }),
+    }
+}
+
+/// Parse a `<name> <path>` module line (the `module` value in `boot.cfg`,
+/// or a whole line of `modules.txt`).
+pub fn parse_module(line: &str) -> Option<ModuleSpec<'_>> {
+    let mut words = line.split_whitespace();
+    match (words.next(), words.next(), words.next()) {
+        (Some(name), Some(path), None) => Some(ModuleSpec { name, path }),
+        _ => None,
+    }
+}
+
+/// Turn a configured path into an absolute UEFI path: `/` becomes `\`, and
+/// a path without a leading separator is taken relative to [`BOOT_DIR`].
+pub fn resolve_path(path: &str) -> String {
+    let path = path.replace('/', "\\");
+    if path.starts_with('\\') {
+        path
+    } else {
+        let mut full = String::from(BOOT_DIR);
+        full.push_str(&path);
+        full
+    }
+}
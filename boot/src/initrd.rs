@@ -0,0 +1,89 @@
+//! Initial ramdisk loader.
+//!
+//! Reads the initrd — a ustar or newc cpio archive, [`INITRD_PATH`] unless
+//! the selected boot entry names another file — from the boot volume into
+//! freshly allocated `LOADER_DATA` pages and reports its physical range.
+//! The bytes are passed on unchanged; the kernel opens them with
+//! [`ferrous_initrd::Archive`]. The archive is also parsed here, purely so
+//! that a broken file is reported on the console before the handoff.
+//!
+//! The initrd is optional: a missing file is not an error.
+//!
+//! Must be called before the final memory map snapshot is taken, so that the
+//! pages show up as `LOADER_DATA` in the map handed to the kernel.
+
+use core::fmt;
+
+use ferrous_initrd::{Archive, InitrdError};
+use uefi::fs::FileSystem;
+use uefi::{cstr16, CStr16};
+
+/// Default path of the initial ramdisk on the boot volume.
+pub const INITRD_PATH: &CStr16 = cstr16!("\\EFI\\ferrous\\initrd.tar");
+
+/// Errors that can occur while loading the initrd.
+#[derive(Debug)]
+pub enum InitrdLoadError {
+    /// The boot volume could not be opened.
+    FileSystem(uefi::Error),
+    /// The initrd exists but could not be read.
+    Read(uefi::fs::Error),
+    /// Firmware refused to hand out pages for the archive.
+    Allocate {
+        /// Requested number of 4 KiB pages.
+        pages: usize,
+        /// Underlying UEFI error.
+        error: uefi::Error,
+    },
+}
+
+impl fmt::Display for InitrdLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InitrdLoadError::FileSystem(e) => write!(f, "cannot open boot volume: {:?}", e),
+            InitrdLoadError::Read(e) => write!(f, "cannot read initrd: {:?}", e),
+            InitrdLoadError::Allocate { pages, error } => {
+                write!(f, "cannot allocate {} pages: {:?}", pages, error)
+            }
+        }
+    }
+}
+
+/// An initrd copied into `LOADER_DATA` memory.
+#[derive(Debug, Clone, Copy)]
+pub struct LoadedInitrd {
+    /// Page-aligned physical address of the first byte.
+    pub phys_addr: u64,
+    /// Size of the archive in bytes (the pages behind it are rounded up).
+    pub size: u64,
+    /// Result of parsing the archive: its entry count, or why the kernel
+    /// will reject it.
+    pub entries: Result<usize, InitrdError>,
+}
+
+/// Load the initrd at `path` on the boot volume.
+///
+/// Returns `Ok(None)` if the file does not exist. The file buffer is
+/// freed before returning; the pages holding the copy are never freed.
+pub fn load_initrd(path: &CStr16) -> Result<Option<LoadedInitrd>, InitrdLoadError> {
+    let sfs = uefi::boot::get_image_file_system(uefi::boot::image_handle())
+        .map_err(InitrdLoadError::FileSystem)?;
+    let mut fs = FileSystem::new(sfs);
+    let file = match fs.read(path) {
+        Ok(file) => file,
+        Err(e) if crate::loader::is_not_found(&e) => return Ok(None),
+        Err(e) => return Err(InitrdLoadError::Read(e)),
+    };
+
+    let copy =
+        crate::memory::copy_to_loader_data(&file).map_err(|error| InitrdLoadError::Allocate {
+            pages: crate::memory::pages_for(file.len()),
+            error,
+        })?;
+
+    Ok(Some(LoadedInitrd {
+        phys_addr: copy.as_ptr() as u64,
+        size: copy.len() as u64,
+        entries: Archive::parse(copy).map(|archive| archive.len()),
+    }))
+}
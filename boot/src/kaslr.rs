@@ -0,0 +1,207 @@
+//! Kernel address space layout randomisation.
+//!
+//! A position-independent (`ET_DYN`) kernel is loaded at a random
+//! [`KASLR_ALIGN`]-aligned physical base inside [`KASLR_WINDOW`], mapped at
+//! an independently chosen random virtual base inside [`KASLR_VIRT_WINDOW`]
+//! and relocated there by [`crate::loader`]. A fixed-address (`ET_EXEC`)
+//! kernel always loads at its link address, as does any kernel booted with
+//! `nokaslr` on the command line. The virtual slide is reported to the
+//! kernel as a `KASLR` boot info tag so that addresses in crash reports can
+//! be mapped back to the ELF file.
+//!
+//! # Entropy
+//!
+//! The first source that works is used:
+//!
+//! 1. EFI_RNG_PROTOCOL, default algorithm.
+//! 2. RDSEED, then RDRAND, if CPUID reports them; each is retried up to
+//!    [`HW_RETRIES`] times, since both may transiently run dry.
+//! 3. A mix of TSC deltas measured across short firmware stalls. This is
+//!    weak — it only keeps machines without any RNG from always picking the
+//!    same base — and is flagged on the console.
+
+use core::arch::x86_64::{__cpuid, __cpuid_count, _rdrand64_step, _rdseed64_step, _rdtsc};
+use core::ops::Range;
+
+use ferrous_boot_info::layout::KERNEL_VIRT_BASE;
+use uefi::boot::MemoryType;
+use uefi::mem::memory_map::MemoryMap;
+use uefi::proto::rng::Rng;
+
+/// Physical range the randomised image must lie in: above the legacy
+/// low-memory area and the default load address, and below 4 GiB, which
+/// firmware identity-maps on every machine we boot on.
+pub const KASLR_WINDOW: Range<u64> = 0x100_0000..0x1_0000_0000;
+
+/// Virtual range the randomised image must lie in: the lower half of the
+/// kernel image region, leaving the upper gigabyte for later use.
+pub const KASLR_VIRT_WINDOW: Range<u64> = KERNEL_VIRT_BASE..KERNEL_VIRT_BASE + 0x4000_0000;
+
+/// Alignment of a randomised base, physical or virtual, so the kernel can
+/// later be mapped with 2 MiB pages.
+pub const KASLR_ALIGN: u64 = 0x20_0000;
+
+/// Attempts per hardware RNG instruction before moving on.
+const HW_RETRIES: usize = 10;
+
+/// TSC samples mixed into the fallback value.
+const TSC_ROUNDS: usize = 64;
+
+/// UEFI page size.
+const PAGE_SIZE: u64 = 4096;
+
+/// Where the random value came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntropySource {
+    EfiRng,
+    Rdseed,
+    Rdrand,
+    /// TSC jitter; weak.
+    TscJitter,
+}
+
+/// How the kernel image was placed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placement {
+    /// The image is `ET_EXEC` and can only run at its link address.
+    Fixed,
+    /// `nokaslr`: loaded at its link address.
+    Disabled,
+    /// Loaded at a random base chosen with entropy from this source.
+    Random(EntropySource),
+    /// No free slot in [`KASLR_WINDOW`]; loaded at its link address.
+    NoRoom,
+}
+
+/// A random `u64` and its source.
+pub fn random_u64() -> (u64, EntropySource) {
+    if let Some(value) = efi_rng() {
+        return (value, EntropySource::EfiRng);
+    }
+    if let Some(value) = rdseed() {
+        return (value, EntropySource::Rdseed);
+    }
+    if let Some(value) = rdrand() {
+        return (value, EntropySource::Rdrand);
+    }
+    (tsc_jitter(), EntropySource::TscJitter)
+}
+
+/// Pick a [`KASLR_ALIGN`]-aligned base for `size` bytes inside
+/// [`KASLR_WINDOW`] and free (`CONVENTIONAL`) memory, using `random` to
+/// choose uniformly (up to modulo bias) among all candidate slots. `None`
+/// if there is no room or the memory map cannot be read.
+pub fn choose_base(size: u64, random: u64) -> Option<u64> {
+    let map = uefi::boot::memory_map(MemoryType::LOADER_DATA).ok()?;
+    // Candidate bases of each free region: `first + i * KASLR_ALIGN` for
+    // `i < count`.
+    let slots = map
+        .entries()
+        .filter(|desc| desc.ty == MemoryType::CONVENTIONAL)
+        .filter_map(|desc| {
+            let end = desc.phys_start + desc.page_count * PAGE_SIZE;
+            let first = desc
+                .phys_start
+                .max(KASLR_WINDOW.start)
+                .next_multiple_of(KASLR_ALIGN);
+            let last = end.min(KASLR_WINDOW.end).checked_sub(size)?;
+            (last >= first).then(|| (first, (last - first) / KASLR_ALIGN + 1))
+        });
+
+    let total: u64 = slots.clone().map(|(_, count)| count).sum();
+    if total == 0 {
+        return None;
+    }
+    let mut index = random % total;
+    for (first, count) in slots {
+        if index < count {
+            return Some(first + index * KASLR_ALIGN);
+        }
+        index -= count;
+    }
+    None
+}
+
+/// Pick a [`KASLR_ALIGN`]-aligned virtual base for `size` bytes inside
+/// [`KASLR_VIRT_WINDOW`], using `random` to choose uniformly (up to modulo
+/// bias). `None` if the image is larger than the window.
+pub fn choose_virt_base(size: u64, random: u64) -> Option<u64> {
+    // Room between the first and the last possible base.
+    let span = (KASLR_VIRT_WINDOW.end - KASLR_VIRT_WINDOW.start).checked_sub(size)?;
+    let slots = span / KASLR_ALIGN + 1;
+    Some(KASLR_VIRT_WINDOW.start + random % slots * KASLR_ALIGN)
+}
+
+/// Eight bytes from EFI_RNG_PROTOCOL, if firmware provides it.
+fn efi_rng() -> Option<u64> {
+    let handle = uefi::boot::get_handle_for_protocol::<Rng>().ok()?;
+    let mut rng = uefi::boot::open_protocol_exclusive::<Rng>(handle).ok()?;
+    let mut bytes = [0u8; 8];
+    rng.get_rng(None, &mut bytes).ok()?;
+    Some(u64::from_le_bytes(bytes))
+}
+
+fn rdseed() -> Option<u64> {
+    // CPUID.(EAX=07H, ECX=0):EBX[bit 18].
+    if __cpuid(0).eax < 7 || __cpuid_count(7, 0).ebx & (1 << 18) == 0 {
+        return None;
+    }
+    let mut value = 0;
+    // SAFETY: CPUID reports RDSEED.
+    (0..HW_RETRIES)
+        .any(|_| unsafe { rdseed_step(&mut value) })
+        .then_some(value)
+}
+
+fn rdrand() -> Option<u64> {
+    // CPUID.01H:ECX[bit 30].
+    if __cpuid(1).ecx & (1 << 30) == 0 {
+        return None;
+    }
+    let mut value = 0;
+    // SAFETY: CPUID reports RDRAND.
+    (0..HW_RETRIES)
+        .any(|_| unsafe { rdrand_step(&mut value) })
+        .then_some(value)
+}
+
+/// # Safety
+///
+/// The CPU must support RDSEED.
+#[target_feature(enable = "rdseed")]
+unsafe fn rdseed_step(value: &mut u64) -> bool {
+    _rdseed64_step(value) == 1
+}
+
+/// # Safety
+///
+/// The CPU must support RDRAND.
+#[target_feature(enable = "rdrand")]
+unsafe fn rdrand_step(value: &mut u64) -> bool {
+    _rdrand64_step(value) == 1
+}
+
+/// Mix the TSC deltas of [`TSC_ROUNDS`] one-microsecond firmware stalls.
+/// Their low bits vary with timer and cache behaviour.
+fn tsc_jitter() -> u64 {
+    // SAFETY: RDTSC is available on every x86-64 CPU and UEFI leaves
+    // CR4.TSD clear.
+    let mut state = unsafe { _rdtsc() };
+    for _ in 0..TSC_ROUNDS {
+        // SAFETY: as above.
+        let before = unsafe { _rdtsc() };
+        uefi::boot::stall(1);
+        // SAFETY: as above.
+        let delta = unsafe { _rdtsc() }.wrapping_sub(before);
+        state = mix(state ^ delta);
+    }
+    state
+}
+
+/// SplitMix64 finaliser.
+fn mix(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
@@ -0,0 +1,245 @@
+//! Kernel ELF loader.
+//!
+//! Reads the kernel — [`KERNEL_PATH`] unless the selected boot entry names
+//! another file — from the volume the bootloader itself was loaded from,
+//! validates it with [`ferrous_elf`], and copies every `PT_LOAD` segment to
+//! its physical load address.
+//!
+//! # Placement
+//!
+//! The kernel is linked in the higher half (`kernel/linker.ld`) with every
+//! segment's `p_paddr` at the same distance below its `p_vaddr`. The image
+//! is copied to physical memory here and mapped at its virtual addresses by
+//! [`crate::paging`].
+//!
+//! A fixed-address (`ET_EXEC`) kernel is loaded at its `p_paddr`s, so the
+//! whole page span covered by its `PT_LOAD` segments is requested from
+//! firmware with `AllocateType::Address`, and mapped at its `p_vaddr`s. A
+//! position-independent (`ET_DYN`) kernel gets a physical and a virtual
+//! base chosen by [`crate::kaslr`] — or its link addresses with `nokaslr` —
+//! and its `R_X86_64_RELATIVE` relocations are applied for the virtual base
+//! before the jump. The pages are typed `LOADER_CODE`, which makes them
+//! show up in the memory map handed to the kernel — the kernel image is
+//! never mistaken for free memory.
+//!
+//! Must be called before the final memory map snapshot is taken, and before
+//! `exit_boot_services()`.
+
+use core::fmt;
+
+use ferrous_boot_info::layout;
+use ferrous_elf::{ElfError, ElfFile};
+use uefi::boot::{AllocateType, MemoryType};
+use uefi::fs::{FileSystem, IoErrorContext};
+use uefi::{cstr16, CStr16, CString16, Status};
+
+use crate::config::resolve_path;
+use crate::kaslr::{self, Placement};
+
+/// Default path of the kernel image on the boot volume.
+pub const KERNEL_PATH: &CStr16 = cstr16!("\\EFI\\ferrous\\kernel.elf");
+
+/// UEFI page size.
+const PAGE_SIZE: u64 = 4096;
+
+/// Errors that can occur while loading the kernel image.
+#[derive(Debug)]
+pub enum LoadError {
+    /// The boot volume could not be opened.
+    FileSystem(uefi::Error),
+    /// The kernel file could not be read (missing, I/O error, …).
+    Read(uefi::fs::Error),
+    /// The file is not a loadable ELF64 x86-64 image.
+    Elf(ElfError),
+    /// A `PT_LOAD` segment's `p_vaddr - p_paddr` differs from the first
+    /// segment's, so the image cannot be loaded physically contiguous.
+    InconsistentLoadAddress {
+        /// Virtual address of the offending segment.
+        vaddr: u64,
+        /// Physical address of the offending segment.
+        paddr: u64,
+    },
+    /// The image is not linked in the higher half, which the kernel needs
+    /// once it drops the low identity mapping.
+    NotHigherHalf {
+        /// Lowest virtual address of the image.
+        vaddr: u64,
+    },
+    /// Firmware refused to hand out the pages at the kernel's load address.
+    Allocate {
+        /// Requested physical base address.
+        base: u64,
+        /// Requested number of 4 KiB pages.
+        pages: usize,
+        /// Underlying UEFI error.
+        error: uefi::Error,
+    },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::FileSystem(e) => write!(f, "cannot open boot volume: {:?}", e),
+            LoadError::Read(e) => write!(f, "cannot read kernel: {:?}", e),
+            LoadError::Elf(e) => write!(f, "invalid kernel ELF: {:?}", e),
+            LoadError::InconsistentLoadAddress { vaddr, paddr } => write!(
+                f,
+                "segment vaddr {:#x} / paddr {:#x} is not at the same offset as the others",
+                vaddr, paddr
+            ),
+            LoadError::NotHigherHalf { vaddr } => write!(
+                f,
+                "kernel linked at {:#x}, must be in the higher half",
+                vaddr
+            ),
+            LoadError::Allocate { base, pages, error } => write!(
+                f,
+                "cannot allocate {} pages at {:#x}: {:?}",
+                pages, base, error
+            ),
+        }
+    }
+}
+
+impl From<ElfError> for LoadError {
+    fn from(e: ElfError) -> Self {
+        LoadError::Elf(e)
+    }
+}
+
+/// True if `error` means the file does not exist, as opposed to a volume
+/// or I/O failure. Optional files (initrd, modules) use this to tell
+/// "absent" from "broken".
+pub fn is_not_found(error: &uefi::fs::Error) -> bool {
+    matches!(
+        error,
+        uefi::fs::Error::Io(e)
+            if e.context == IoErrorContext::OpenError
+                && e.uefi_error.status() == Status::NOT_FOUND
+    )
+}
+
+/// A path from `boot.cfg` or `modules.txt` as an absolute UEFI path (see
+/// [`resolve_path`]), or `None` if it has characters UCS-2 cannot hold.
+pub fn uefi_path(path: &str) -> Option<CString16> {
+    CString16::try_from(resolve_path(path).as_str()).ok()
+}
+
+/// A kernel image that has been copied to its load address.
+#[derive(Debug, Clone, Copy)]
+pub struct LoadedKernel {
+    /// Entry point — virtual address of `kernel_entry` (`e_entry` plus
+    /// `slide`).
+    pub entry: u64,
+    /// Page-aligned physical base of the loaded image.
+    pub phys_base: u64,
+    /// Page-aligned virtual base the image must be mapped at.
+    pub virt_base: u64,
+    /// `virt_base` minus the page-aligned virtual link address (two's
+    /// complement).
+    pub slide: u64,
+    /// How `phys_base` and `virt_base` were chosen.
+    pub placement: Placement,
+    /// Page-aligned size of the loaded image in bytes.
+    pub size: u64,
+    /// Size of the ELF file read from disk.
+    pub file_size: usize,
+}
+
+/// Load the kernel ELF at `path` on the boot volume, at a random base if
+/// it is position-independent and `randomize` is set.
+///
+/// On success the image occupies `[phys_base, phys_base + size)` as
+/// `LOADER_CODE` memory and `entry` may be jumped to once boot services
+/// have exited and the image is mapped at `virt_base`. The file buffer is
+/// freed before returning.
+pub fn load_kernel(path: &CStr16, randomize: bool) -> Result<LoadedKernel, LoadError> {
+    let sfs = uefi::boot::get_image_file_system(uefi::boot::image_handle())
+        .map_err(LoadError::FileSystem)?;
+    let mut fs = FileSystem::new(sfs);
+    let file = fs.read(path).map_err(LoadError::Read)?;
+
+    let elf = ElfFile::parse(&file)?;
+
+    // Virtual minus physical address, shared by every segment.
+    let mut virt_offset = None;
+    for ph in elf.load_segments() {
+        let offset = ph.p_vaddr.wrapping_sub(ph.p_paddr);
+        if *virt_offset.get_or_insert(offset) != offset {
+            return Err(LoadError::InconsistentLoadAddress {
+                vaddr: ph.p_vaddr,
+                paddr: ph.p_paddr,
+            });
+        }
+    }
+
+    // `parse` rejects images without a PT_LOAD segment, but every segment
+    // could still have p_memsz == 0.
+    let (lo, hi) = elf
+        .virtual_bounds()
+        .ok_or(LoadError::Elf(ElfError::NoLoadableSegments))?;
+    if !layout::is_higher_half(lo) {
+        return Err(LoadError::NotHigherHalf { vaddr: lo });
+    }
+    let link_base = lo & !(PAGE_SIZE - 1);
+    let link_end = (hi + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+    let phys_link_base = link_base.wrapping_sub(virt_offset.unwrap_or(0));
+    let size = link_end - link_base;
+    let pages = (size / PAGE_SIZE) as usize;
+
+    let allocate = |base| {
+        uefi::boot::allocate_pages(AllocateType::Address(base), MemoryType::LOADER_CODE, pages)
+            .map_err(|error| LoadError::Allocate { base, pages, error })
+    };
+    let random_base = if !elf.is_position_independent() {
+        Err(Placement::Fixed)
+    } else if !randomize {
+        Err(Placement::Disabled)
+    } else {
+        // The low half picks the physical slot, the high half the virtual.
+        let (random, source) = kaslr::random_u64();
+        kaslr::choose_base(size, random & 0xFFFF_FFFF)
+            .zip(kaslr::choose_virt_base(size, random >> 32))
+            .ok_or(Placement::NoRoom)
+            .and_then(|(base, virt_base)| {
+                allocate(base)
+                    .map(|region| (region, virt_base, Placement::Random(source)))
+                    .map_err(|_| Placement::NoRoom)
+            })
+    };
+    let (region, virt_base, placement) = match random_base {
+        Ok(placed) => placed,
+        Err(placement) => (allocate(phys_link_base)?, link_base, placement),
+    };
+    let phys_base = region.as_ptr() as u64;
+    let slide = virt_base.wrapping_sub(link_base);
+
+    // SAFETY: `region` is a freshly allocated, identity-mapped block of
+    // `pages` pages owned exclusively by us.
+    let image = unsafe { core::slice::from_raw_parts_mut(region.as_ptr(), size as usize) };
+    // Zeroing up front covers the .bss tails and any gaps between segments.
+    image.fill(0);
+
+    for ph in elf.load_segments() {
+        let data = elf.segment_data(&ph);
+        // link_base <= p_vaddr and p_vaddr + p_memsz <= link_end by
+        // construction of the bounds above, and p_filesz <= p_memsz is
+        // checked by `ElfFile::parse`, so the range lies inside the image.
+        let dst = (ph.p_vaddr - link_base) as usize;
+        image[dst..dst + data.len()].copy_from_slice(data);
+    }
+
+    // Also needed at slide 0: the linker need not store the addends in
+    // place. A fixed-address image has no relocations to apply.
+    elf.relocate(image, link_base, slide)?;
+
+    Ok(LoadedKernel {
+        entry: elf.entry().wrapping_add(slide),
+        phys_base,
+        virt_base,
+        slide,
+        placement,
+        size,
+        file_size: file.len(),
+    })
+}
@@ -1,16 +1,30 @@
 //! Ferrous Kernel UEFI Bootloader
 //!
 //! This is the UEFI entry point for the Ferrous kernel. It initializes
-//! UEFI boot services, retrieves system information, and hands off to
-//! the kernel entry point.
+//! UEFI boot services, loads the kernel ELF image from the boot volume,
+//! retrieves system information, and hands off to the kernel entry point.
 //!
 //! # Handoff sequence
 //!
-//! 1. Collect memory map, ACPI RSDP, and framebuffer info via UEFI.
-//! 2. Build a `KernelBootInfo` in a static buffer (no heap after this).
-//! 3. Call `exit_boot_services()` — the point of no return.
-//! 4. Disable interrupts, switch to the bootstrap stack.
-//! 5. Jump to `kernel_entry` with `&KernelBootInfo` as the first argument.
+//! 0. If `\EFI\ferrous\boot.cfg` exists, show the boot menu and use the
+//!    selected entry's kernel, command line, initrd and modules.
+//! 1. Read the kernel command line. Load the kernel (default
+//!    `\EFI\ferrous\kernel.elf`) to its physical load address — or, if it
+//!    is position-independent and the command line has no `nokaslr`, to a
+//!    random base, relocating it for a random virtual base — and the initrd
+//!    (default `\EFI\ferrous\initrd.tar`) and modules (default: listed in
+//!    `\EFI\ferrous\modules.txt`), if present, into LOADER_DATA pages.
+//! 2. Build the kernel's page tables: direct map, temporary identity map
+//!    and the higher-half kernel image (see [`paging`]).
+//! 3. Collect memory map, ACPI RSDP and framebuffer info via UEFI.
+//! 4. Build a `KernelBootInfo` in a static buffer (no heap after this).
+//!    Memory maps longer than the inline array also go to a LOADER_DATA
+//!    buffer referenced by the version 2 `memory_map_ext` field.
+//! 5. Call `exit_boot_services()` — the point of no return.
+//! 6. Disable interrupts, load the new page tables into CR3, switch to the
+//!    bootstrap stack.
+//! 7. Jump to the ELF entry point with the direct-map address of
+//!    `KernelBootInfo` as the first argument.
 
 #![no_std]
 #![no_main]
@@ -18,17 +32,29 @@
 extern crate alloc;
 
 mod boot_info;
+mod cmdline;
+mod config;
 mod console;
+mod initrd;
+mod kaslr;
+mod loader;
 mod memory;
+mod menu;
+mod modules;
+mod paging;
 
+use alloc::vec::Vec;
 use core::fmt::Write;
 use uefi::boot::MemoryType;
 use uefi::prelude::*;
+use uefi::CString16;
 
 use crate::boot_info::BootInfo;
 use crate::console::Console;
+use crate::kaslr::{EntropySource, Placement};
 use crate::memory::MemoryMap;
-use ferrous_boot_info::KernelBootInfo;
+use ferrous_boot_info::layout::phys_to_virt;
+use ferrous_boot_info::{tag_type, BootInfoBuffer, ExtendedMemoryMap, KernelMemoryDescriptor};
 
 // ---------------------------------------------------------------------------
 // Bootstrap stack
@@ -38,7 +64,7 @@ use ferrous_boot_info::KernelBootInfo;
 ///
 /// This stack is used only during the UEFI handoff sequence
 /// (exit_boot_services → kernel_entry). It is intentionally small —
-/// `kernel_main` immediately switches to the larger KERNEL_STACK.
+/// `kernel_entry` immediately switches to the kernel's own, larger stack.
 const BOOTSTRAP_STACK_SIZE: usize = 16 * 1024;
 
 /// Bootstrap stack used after `exit_boot_services()`.
@@ -53,45 +79,15 @@ struct BootstrapStack([u8; BOOTSTRAP_STACK_SIZE]);
 /// into it, but that is managed by the CPU, not by Rust references).
 static mut BOOTSTRAP_STACK: BootstrapStack = BootstrapStack([0u8; BOOTSTRAP_STACK_SIZE]);
 
-// ---------------------------------------------------------------------------
-// Kernel primary stack
-// ---------------------------------------------------------------------------
-
-/// Total size of the kernel primary execution stack (64 KiB).
-///
-/// Layout:
-///   [bottom .. bottom+4KiB]  soft guard region — zeroed; future: non-present page
-///   [bottom+4KiB .. top]     60 KiB usable stack depth
-///
-/// When the kernel becomes a separate ELF binary this constant and the static
-/// below move to `kernel/src/arch/x86_64/stack.rs`, which also exports the
-/// `KernelStack<N>` type for use with a linker script.
-const KERNEL_STACK_SIZE: usize = 64 * 1024;
-
-/// Soft guard region at the bottom of the kernel stack (one 4 KiB page).
-///
-/// Not enforced until page-table management is implemented (Task 1.3.3).
-const KERNEL_STACK_GUARD_SIZE: usize = 4 * 1024;
-
-/// The kernel's primary execution stack.
-///
-/// `kernel_main` switches RSP to the top of this buffer immediately on entry,
-/// leaving the bootstrap stack behind. The stack is 16-byte aligned and large
-/// enough for typical kernel call chains in Phase 1 (no deep recursion, no
-/// interrupt frames yet).
-///
-/// SAFETY: switched to exactly once from `kernel_main` before any other
-/// stack writes occur. Single-core, interrupts-disabled environment.
-#[repr(C, align(16))]
-struct KernelStack([u8; KERNEL_STACK_SIZE]);
-
-static mut KERNEL_STACK: KernelStack = KernelStack([0u8; KERNEL_STACK_SIZE]);
-
 // ---------------------------------------------------------------------------
 // KernelBootInfo static
 // ---------------------------------------------------------------------------
 
-/// The boot information buffer passed to the kernel.
+/// Bytes reserved for boot info tags after the fixed header.
+const BOOT_INFO_TAGS_SIZE: usize = 16 * 1024;
+
+/// The boot information buffer passed to the kernel: the fixed header
+/// followed by the tag list (boot info version 3).
 ///
 /// Populated before `exit_boot_services()`, its address is passed to
 /// `kernel_entry`. Must be `static` so it outlives the bootloader stack.
@@ -99,7 +95,7 @@ static mut KERNEL_STACK: KernelStack = KernelStack([0u8; KERNEL_STACK_SIZE]);
 /// SAFETY: written exactly once in `efi_main` before the handoff, then
 /// treated as read-only by both the bootloader (during the jump) and
 /// the kernel.
-static mut KERNEL_BOOT_INFO: KernelBootInfo = KernelBootInfo::new();
+static mut KERNEL_BOOT_INFO: BootInfoBuffer<BOOT_INFO_TAGS_SIZE> = BootInfoBuffer::new();
 
 // ---------------------------------------------------------------------------
 // Panic handler
@@ -124,6 +120,18 @@ fn efi_main() -> Status {
     uefi::helpers::init().expect("Failed to initialize UEFI helpers");
 
     let mut console = Console::new();
+
+    // --- Boot configuration and menu ---
+    //
+    // Before the banner: the menu takes over the screen. Parse warnings are
+    // kept and printed with the rest of the log.
+    let config_text = menu::read_config(&mut console);
+    let mut config_warnings = Vec::new();
+    let config = config_text
+        .as_deref()
+        .map(|text| config::parse(text, |w| config_warnings.push(w)));
+    let entry = config.as_ref().and_then(|c| menu::choose(&mut console, c));
+
     console.clear();
 
     writeln!(console, "").unwrap();
@@ -153,9 +161,153 @@ fn efi_main() -> Status {
     )
     .unwrap();
 
+    for warning in &config_warnings {
+        writeln!(console, "[WARN] {}: {}", menu::CONFIG_PATH, warning).unwrap();
+    }
+    match (&config, entry) {
+        (_, Some(entry)) => writeln!(console, "[OK] Boot entry: {}", entry.title).unwrap(),
+        (Some(_), None) => writeln!(
+            console,
+            "[WARN] {} has no usable entries; using defaults",
+            menu::CONFIG_PATH
+        )
+        .unwrap(),
+        (None, None) => {}
+    }
+
+    // --- Kernel command line ---
+    //
+    // Read before the kernel is loaded: `nokaslr` affects where it goes.
+    let cmdline = cmdline::read_cmdline(entry.and_then(|e| e.cmdline));
+    match &cmdline {
+        Some(c) => writeln!(
+            console,
+            "[OK] Command line ({:?}{}): {}",
+            c.source,
+            if c.truncated { ", truncated" } else { "" },
+            c.text
+        )
+        .unwrap(),
+        None => writeln!(console, "[INFO] No kernel command line").unwrap(),
+    }
+
+    // --- Load the kernel image ---
+    //
+    // Must happen before the memory map is retrieved so that the kernel's
+    // LOADER_CODE pages appear in the map handed to the kernel.
+    let kernel_path = match entry.map(|e| e.kernel) {
+        None => CString16::from(loader::KERNEL_PATH),
+        Some(path) => match loader::uefi_path(path) {
+            Some(path) => path,
+            None => {
+                writeln!(console, "[FAIL] Bad kernel path: {}", path).unwrap();
+                return Status::LOAD_ERROR;
+            }
+        },
+    };
+    writeln!(console, "[...] Loading kernel {}", kernel_path).unwrap();
+    let nokaslr = cmdline
+        .as_ref()
+        .is_some_and(|c| c.text.split(' ').any(|word| word == "nokaslr"));
+    let kernel = match loader::load_kernel(&kernel_path, !nokaslr) {
+        Ok(kernel) => {
+            writeln!(
+                console,
+                "[OK] Kernel loaded: {:#x} - {:#x} ({} KiB file) at {:#x}, entry {:#x}",
+                kernel.phys_base,
+                kernel.phys_base + kernel.size,
+                kernel.file_size / 1024,
+                kernel.virt_base,
+                kernel.entry
+            )
+            .unwrap();
+            print_placement(&kernel, &mut console);
+            kernel
+        }
+        Err(e) => {
+            writeln!(console, "[FAIL] Failed to load kernel: {}", e).unwrap();
+            return Status::LOAD_ERROR;
+        }
+    };
+
+    // --- Load the initial ramdisk (optional) ---
+    //
+    // Also before the memory map, for the same reason: its LOADER_DATA
+    // pages must be in the map.
+    // A boot entry without `initrd` boots without one.
+    let initrd_path = match entry.map(|e| e.initrd) {
+        None => Some(CString16::from(initrd::INITRD_PATH)),
+        Some(path) => path.and_then(loader::uefi_path),
+    };
+    if let Some(path) = &initrd_path {
+        writeln!(console, "[...] Loading initrd {}", path).unwrap();
+    }
+    let initrd = match initrd_path.map(|path| initrd::load_initrd(&path)) {
+        None => None,
+        Some(Ok(Some(initrd))) => {
+            writeln!(
+                console,
+                "[OK] Initrd loaded: {:#x} - {:#x} ({} KiB)",
+                initrd.phys_addr,
+                initrd.phys_addr + initrd.size,
+                initrd.size / 1024
+            )
+            .unwrap();
+            match initrd.entries {
+                Ok(count) => writeln!(console, "[OK] Initrd archive: {} entries", count).unwrap(),
+                Err(e) => writeln!(
+                    console,
+                    "[WARN] Initrd is not a valid archive ({:?}); passing it on anyway",
+                    e
+                )
+                .unwrap(),
+            }
+            Some(initrd)
+        }
+        Some(Ok(None)) => {
+            writeln!(console, "[INFO] No initrd").unwrap();
+            None
+        }
+        Some(Err(e)) => {
+            writeln!(console, "[WARN] Initrd not loaded: {}", e).unwrap();
+            None
+        }
+    };
+
+    // --- Load boot modules (optional) ---
+    let module_list;
+    let module_specs = match entry {
+        Some(entry) => entry.modules.clone(),
+        None => {
+            module_list = modules::read_module_list(&mut console);
+            modules::parse_module_list(&mut console, &module_list)
+        }
+    };
+    let modules = modules::load_modules(&mut console, &module_specs);
+
+    // --- Build the kernel page tables ---
+    //
+    // Last allocation before the memory map, so that the table pages are in
+    // it and everything the bootloader loaded is covered by the direct map.
+    let page_tables = match paging::build(&kernel) {
+        Ok(tables) => {
+            writeln!(
+                console,
+                "[OK] Page tables: PML4 at {:#x}, {} tables, physical memory to {:#x} mapped",
+                tables.root, tables.tables, tables.phys_limit
+            )
+            .unwrap();
+            tables
+        }
+        Err(e) => {
+            writeln!(console, "[FAIL] Failed to build page tables: {}", e).unwrap();
+            return Status::LOAD_ERROR;
+        }
+    };
+
     // --- Collect memory map ---
     writeln!(console, "[...] Retrieving memory map").unwrap();
-    let memory_map = match retrieve_memory_map(&mut console) {
+    let mut memory_map = match retrieve_memory_map(&mut console) {
         Ok(map) => {
             writeln!(console, "[OK] Memory map retrieved").unwrap();
             map
@@ -165,6 +317,41 @@ fn efi_main() -> Status {
             return Status::ABORTED;
         }
     };
+
+    // --- Allocate the extended memory map (boot info version 2) ---
+    //
+    // Only needed when the map does not fit the inline array. The buffer is
+    // allocated first and the map retrieved again, so that the buffer shows
+    // up as LOADER_DATA in the map it carries.
+    let mut extended_map = None;
+    if memory_map.region_count() > ferrous_boot_info::MAX_MEMORY_DESCRIPTORS {
+        match allocate_extended_memory_map(memory_map.region_count()) {
+            Ok(buffer) => match retrieve_memory_map(&mut console) {
+                Ok(map) => {
+                    writeln!(
+                        console,
+                        "[OK] Extended memory map: {} descriptors at {:#x}",
+                        map.region_count(),
+                        buffer.as_ptr() as u64
+                    )
+                    .unwrap();
+                    memory_map = map;
+                    extended_map = Some(buffer);
+                }
+                Err(e) => {
+                    writeln!(console, "[FAIL] Failed to retrieve memory map: {:?}", e).unwrap();
+                    return Status::ABORTED;
+                }
+            },
+            Err(e) => writeln!(
+                console,
+                "[WARN] No extended memory map ({:?}); passing the first {} descriptors",
+                e,
+                ferrous_boot_info::MAX_MEMORY_DESCRIPTORS
+            )
+            .unwrap(),
+        }
+    }
     print_memory_summary(&memory_map, &mut console);
 
     // --- Collect ACPI RSDP ---
@@ -204,7 +391,17 @@ fn efi_main() -> Status {
         boot_info.set_framebuffer(kfb);
     }
 
-    let kernel_boot_info = boot_info.to_kernel_boot_info();
+    let mut kernel_boot_info = boot_info.to_kernel_boot_info();
+    if let Some(buffer) = extended_map {
+        // `None` only if the second retrieval grew past the slack; the inline
+        // (truncated) map is still valid then.
+        if let Some(count) = boot_info.write_extended_memory_map(buffer) {
+            kernel_boot_info.memory_map_ext = ExtendedMemoryMap {
+                phys_addr: buffer.as_ptr() as u64,
+                count: count as u64,
+            };
+        }
+    }
 
     writeln!(console, "").unwrap();
     writeln!(
@@ -226,20 +423,39 @@ fn efi_main() -> Status {
     writeln!(console, "========================================").unwrap();
     writeln!(console, "").unwrap();
 
-    // --- Write KernelBootInfo to the static buffer ---
+    // --- Write KernelBootInfo and its tags to the static buffer ---
     //
     // SAFETY: We are the only writer. This runs before the handoff, on the
     // single-threaded UEFI executor. KERNEL_BOOT_INFO is never aliased here.
-    unsafe {
-        core::ptr::write(core::ptr::addr_of_mut!(KERNEL_BOOT_INFO), kernel_boot_info);
-    }
+    let tags = unsafe {
+        let buffer = &mut *core::ptr::addr_of_mut!(KERNEL_BOOT_INFO);
+        buffer.info = kernel_boot_info;
+        buffer.write_tags(|tags| {
+            if let Some(c) = &cmdline {
+                tags.add(tag_type::CMDLINE, c.text.as_bytes())?;
+            }
+            if let Some(initrd) = &initrd {
+                tags.add_u64s(tag_type::INITRD, &[initrd.phys_addr, initrd.size])?;
+            }
+            for module in &modules {
+                tags.add_module(&module.as_module())?;
+            }
+            tags.add_u64s(tag_type::KASLR, &[kernel.slide, kernel.phys_base])?;
+            Ok(())
+        })
+    };
 
     writeln!(
         console,
-        "[OK] KernelBootInfo populated (magic={:#x})",
-        ferrous_boot_info::BOOT_INFO_MAGIC
+        "[OK] KernelBootInfo populated (magic={:#x}, version {})",
+        ferrous_boot_info::BOOT_INFO_MAGIC,
+        ferrous_boot_info::BOOT_INFO_VERSION
     )
     .unwrap();
+    match tags {
+        Ok(size) => writeln!(console, "[OK] Boot info tags: {} bytes", size).unwrap(),
+        Err(e) => writeln!(console, "[WARN] Boot info tags dropped: {:?}", e).unwrap(),
+    }
 
     // --- Exit UEFI boot services — point of no return ---
     //
@@ -260,1035 +476,84 @@ fn efi_main() -> Status {
     // no longer valid. The memory persists as LOADER_DATA.
     core::mem::forget(_final_map);
 
-    // --- Switch stack and jump to kernel_entry ---
+    // --- Switch page tables and stack, jump to the kernel entry point ---
     //
     // From this point the UEFI stack is invalid (reclaimed). We switch to
     // our statically allocated bootstrap stack before calling any Rust code.
     //
     // SAFETY:
+    // - The new page tables identity-map all memory the bootloader uses
+    //   (this code, BOOTSTRAP_STACK, KERNEL_BOOT_INFO), so execution
+    //   continues across the CR3 write; they also map the kernel image at
+    //   `kernel.virt_base` and all physical memory at the direct map.
     // - BOOTSTRAP_STACK is a valid 16 KiB, 16-byte-aligned static buffer.
     // - stack_top points one byte past the end, which is the initial RSP
     //   value (x86-64 stack grows downward).
     // - Interrupts are disabled with `cli` to prevent an interrupt handler
     //   from using the now-invalid UEFI stack during the transition.
-    // - `kernel_entry` is `-> !` and never returns, so the `call`
-    //   instruction's implicit return address on the stack is never used.
-    // - RDI is set to the address of KERNEL_BOOT_INFO per the SysV AMD64
-    //   calling convention (first argument).
+    // - `kernel.entry` is the validated ELF entry point inside the image
+    //   copied by `loader::load_kernel`, which now lives in LOADER_CODE
+    //   pages that survive exit_boot_services(). `kernel_entry` is `-> !`,
+    //   so the `call` instruction's return address is never used.
+    // - RDI carries the direct-map address of KERNEL_BOOT_INFO (its header
+    //   comes first, so this is the address of the KernelBootInfo) per the
+    //   SysV AMD64 calling convention (first argument); the kernel drops
+    //   the identity mapping. Pinning it as an explicit operand keeps the
+    //   allocator from placing `entry` or `stack` in RDI.
     unsafe {
         let stack_top =
             (core::ptr::addr_of!(BOOTSTRAP_STACK) as usize + BOOTSTRAP_STACK_SIZE) as u64;
-        let boot_info_ptr = core::ptr::addr_of!(KERNEL_BOOT_INFO) as u64;
+        let boot_info_ptr = phys_to_virt(core::ptr::addr_of!(KERNEL_BOOT_INFO) as u64);
+        let entry_addr = kernel.entry;
 
         core::arch::asm!(
             "cli",
+            "mov cr3, {root}",
             "mov rsp, {stack}",
             "xor rbp, rbp",
-            "mov rdi, {info}",
             "call {entry}",
+            root = in(reg) page_tables.root,
             stack = in(reg) stack_top,
-            info  = in(reg) boot_info_ptr,
-            entry = sym kernel_entry,
+            entry = in(reg) entry_addr,
+            in("rdi") boot_info_ptr,
             options(noreturn),
         );
     }
 }
 
 // ---------------------------------------------------------------------------
-// Kernel entry point
+// UEFI helper functions (only used pre-handoff)
 // ---------------------------------------------------------------------------
 
-/// Kernel entry point — called after UEFI boot services have exited.
-///
-/// This function runs on the bootstrap stack with interrupts disabled.
-/// It validates the `KernelBootInfo`, zeroes BSS, and calls `kernel_main`.
-///
-/// # Safety
-///
-/// Must only be called from the handoff asm block in `efi_main`:
-/// - RSP must point to a valid stack (the bootstrap stack).
-/// - RDI must contain the address of a fully populated `KernelBootInfo`.
-/// - Interrupts must be disabled (`cli` must have been executed).
-/// - UEFI boot services must have already exited.
-#[no_mangle]
-extern "C" fn kernel_entry(boot_info: *const KernelBootInfo) -> ! {
-    // Validate the boot info pointer before touching anything else.
-    //
-    // SAFETY: `boot_info` is the address of KERNEL_BOOT_INFO, a valid
-    // static. We check it is non-null and has the correct magic before
-    // treating it as a reference.
-    // SAFETY: `boot_info` is the address of KERNEL_BOOT_INFO, a valid static
-    // populated before exit_boot_services(). We check non-null and magic
-    // before constructing a reference.
-    if boot_info.is_null() {
-        serial_write_str("FATAL: kernel_entry received null BootInfo pointer\r\n");
-        halt();
-    }
-
-    let info = unsafe { &*boot_info };
-    if !info.is_valid() {
-        serial_write_str("FATAL: KernelBootInfo magic/version mismatch\r\n");
-        halt();
-    }
-
-    // Note: BSS zeroing is not needed here because this is a UEFI PE/COFF
-    // binary — the UEFI firmware zero-initialises BSS before calling efi_main.
-    // When the kernel becomes a separate flat binary, zero_bss() will be
-    // performed at the start of kernel_entry in kernel/src/arch/x86_64/entry.rs.
+/// Descriptors added to the extended map buffer beyond the current count,
+/// covering entries created by the allocation itself and later UEFI calls.
+const EXTENDED_MAP_SLACK: usize = 32;
 
-    kernel_main(info);
-}
-
-/// First Rust function executing in the kernel context.
+/// Allocates a LOADER_DATA buffer for `count` descriptors plus slack.
 ///
-/// On entry RSP points to the bootstrap stack (set up by the bootloader).
-/// The very first action is to switch to the kernel's own primary stack
-/// (`KERNEL_STACK`, 64 KiB) so we have adequate depth for kernel execution.
-///
-/// At this point:
-/// - Boot services have exited.
-/// - We are on the bootstrap stack with interrupts disabled.
-/// - `boot_info` is a reference into the `KERNEL_BOOT_INFO` static — valid
-///   for the lifetime of the kernel.
-fn kernel_main(boot_info: &KernelBootInfo) -> ! {
-    // -----------------------------------------------------------------------
-    // Step 1: Switch to the kernel primary stack.
-    //
-    // We leave the bootstrap stack behind. From this point forward RSP
-    // points into KERNEL_STACK.
-    //
-    // Implementation note — register spilling:
-    //
-    // After `mov rsp`, the compiler's RSP-relative loads for any locals
-    // computed before the switch would read from the zeroed kernel stack
-    // rather than the bootstrap stack, producing garbage values. We avoid
-    // this by using an `inlateout` constraint to keep `boot_info` in a
-    // physical register throughout the switch; the register is not modified
-    // by the asm, so `info_out == info_in` after. Stack bounds are computed
-    // fresh from the static address AFTER the switch — accessed by absolute
-    // address, never RSP-relative.
-    //
-    // SAFETY:
-    // - KERNEL_STACK is a valid 64 KiB, 16-byte-aligned static buffer.
-    // - stack_top is one past the end of the array — the correct initial RSP
-    //   for a downward-growing x86-64 stack.
-    // - boot_info points to KERNEL_BOOT_INFO, a valid static that outlives
-    //   the kernel. Reconstructing the reference after the switch is safe.
-    // - kernel_main is `-> !`; the return address on the bootstrap stack is
-    //   never consumed.
-    // - Interrupts are disabled; no context switch can race this.
-    // Switch RSP to the kernel stack. After this instruction the bootstrap
-    // stack is abandoned. We must not read any local variables that the
-    // compiler might have spilled to the old stack after this point.
-    //
-    // Solution: use only static-address loads after the switch.
-    // - KERNEL_STACK and KERNEL_BOOT_INFO are statics; their addresses are
-    //   embedded as absolute values in the code, never RSP-relative.
-    // - We ignore the `boot_info` parameter and re-derive it from
-    //   KERNEL_BOOT_INFO directly after the switch.
+/// The buffer survives `exit_boot_services()`; the kernel copies it before
+/// reclaiming loader memory.
+fn allocate_extended_memory_map(
+    count: usize,
+) -> Result<&'static mut [KernelMemoryDescriptor], uefi::Error> {
+    let len = count + EXTENDED_MAP_SLACK;
+    let bytes = len * core::mem::size_of::<KernelMemoryDescriptor>();
+    let pages = bytes.div_ceil(4096);
+    let region = uefi::boot::allocate_pages(
+        uefi::boot::AllocateType::AnyPages,
+        MemoryType::LOADER_DATA,
+        pages,
+    )?;
+    let ptr = region.as_ptr() as *mut KernelMemoryDescriptor;
+    // SAFETY: `region` is a fresh, page-aligned allocation of at least
+    // `bytes` bytes owned exclusively by us and never freed. It is zeroed
+    // before the slice is formed, and all-zero is a valid descriptor.
     unsafe {
-        let stack_top = (core::ptr::addr_of!(KERNEL_STACK) as usize + KERNEL_STACK_SIZE) as u64;
-        core::arch::asm!(
-            "mov rsp, {top}",
-            top = in(reg) stack_top,
-            options(nomem, nostack, preserves_flags),
-        );
+        core::ptr::write_bytes(region.as_ptr(), 0, bytes);
+        Ok(core::slice::from_raw_parts_mut(ptr, len))
     }
-
-    // Re-derive boot_info from the static — absolute address, never spilled.
-    // SAFETY: KERNEL_BOOT_INFO is fully populated before exit_boot_services()
-    // and is immutable from this point forward.
-    let boot_info = unsafe { &*core::ptr::addr_of!(KERNEL_BOOT_INFO) };
-
-    // Stack bounds computed from static address, not RSP-relative.
-    let stack_bottom = unsafe { core::ptr::addr_of!(KERNEL_STACK) as usize };
-    let stack_top = stack_bottom + KERNEL_STACK_SIZE;
-
-    // -----------------------------------------------------------------------
-    // Step 2: UART init — kernel now owns COM1 configuration.
-    serial_init();
-
-    serial_write_str("\r\n");
-    serial_write_str("=== Ferrous Kernel ===\r\n");
-    serial_write_str("[OK] kernel_entry: BootInfo validated\r\n");
-    serial_write_str("[OK] Kernel stack active\r\n");
-
-    // Print stack bounds so we can verify the switch worked.
-    serial_write_str("[INFO] Kernel stack: 0x");
-    serial_write_usize_hex(stack_bottom);
-    serial_write_str(" - 0x");
-    serial_write_usize_hex(stack_top);
-    serial_write_str(" (");
-    serial_write_usize(KERNEL_STACK_SIZE / 1024);
-    serial_write_str(" KiB, guard=");
-    serial_write_usize(KERNEL_STACK_GUARD_SIZE / 1024);
-    serial_write_str(" KiB)\r\n");
-
-    // -----------------------------------------------------------------------
-    // Step 3: Load GDT — set up kernel code/data segments.
-    //
-    // The UEFI firmware may have installed its own GDT, which is no longer
-    // mapped or valid after exit_boot_services().  We install a minimal GDT
-    // with exactly the segments needed for kernel operation.
-    //
-    // SAFETY:
-    // - We are at CPL=0 (ring 0) throughout the boot sequence.
-    // - Interrupts have been disabled since the `cli` in efi_main.
-    // - GDT is a valid static in permanently mapped memory.
-    unsafe { gdt_init() };
-
-    serial_write_str("[OK] GDT loaded (null / kernel-code 0x08 / kernel-data 0x10)\r\n");
-
-    // -----------------------------------------------------------------------
-    // Step 4: Load IDT — install exception stubs, load IDTR.
-    //
-    // After this call, any CPU exception (divide-by-zero, GPF, page fault,
-    // etc.) will be caught by our stub handlers instead of triple-faulting
-    // immediately. Interrupts remain disabled (no STI) — the IDT is ready
-    // for exceptions only at this stage.
-    //
-    // SAFETY:
-    // - We are at CPL=0.
-    // - Interrupts are disabled (cli executed in efi_main).
-    // - IDT static is fully initialised inside idt_init() before LIDT.
-    unsafe { idt_init() };
-
-    serial_write_str("[OK] IDT loaded (32 exception handlers with error codes + RIP + CR2, interrupts disabled)\r\n");
-
-    // -----------------------------------------------------------------------
-    serial_write_str("[OK] Kernel entered successfully!\r\n");
-    serial_write_str("Hello from Ferrous!\r\n");
-    serial_write_str("\r\n");
-
-    // -----------------------------------------------------------------------
-    // Step 5: Parse and report the physical memory map.
-    //
-    // Iterates over the KernelMemoryMap received from the bootloader, prints
-    // each region with its classification and size, then summarises totals.
-    //
-    // Note: in Phase 1 this is an inline analysis. The canonical MemoryMap
-    // type (Phase 1.3.1) lives in ferrous-boot-info and is accessible via
-    // kernel::memory after the kernel binary is separated in Phase 2.
-    print_memory_map(&boot_info.memory_map);
-
-    if boot_info.acpi_rsdp != 0 {
-        serial_write_str("[INFO] ACPI RSDP: 0x");
-        serial_write_usize_hex(boot_info.acpi_rsdp as usize);
-        serial_write_str("\r\n");
-    }
-
-    if boot_info.has_framebuffer {
-        serial_write_str("[INFO] Framebuffer: ");
-        serial_write_usize(boot_info.framebuffer.width as usize);
-        serial_write_str("x");
-        serial_write_usize(boot_info.framebuffer.height as usize);
-        serial_write_str(" @ 0x");
-        serial_write_usize_hex(boot_info.framebuffer.base as usize);
-        serial_write_str("\r\n");
-    }
-
-    serial_write_str(
-        "\r\nKernel halting. Exception handlers active — any CPU exception will be caught.\r\n",
-    );
-
-    halt()
-}
-
-// ---------------------------------------------------------------------------
-// Minimal serial output (COM1, 0x3F8)
-//
-// UEFI console is gone after exit_boot_services(). We write directly to
-// the 16550-compatible UART at I/O port 0x3F8 (COM1, 115200 8N1).
-//
-// The full, reusable serial driver lives in kernel/src/drivers/serial.rs.
-// These boot-side helpers are a lightweight duplicate kept here so the
-// bootloader has zero dependencies on the kernel crate.
-// ---------------------------------------------------------------------------
-
-/// Initialise the UART to 115200 baud, 8N1.
-///
-/// Mirrors `SerialPort::init()` in `kernel/src/drivers/serial.rs`.
-/// Called once at the top of `kernel_main` so the kernel owns the UART
-/// configuration from this point forward.
-/// Write `value` to I/O port `port`.
-///
-/// # Safety
-///
-/// Caller must be at CPL=0. `port` must be a valid I/O address for the
-/// device being configured.
-unsafe fn outb(port: u16, value: u8) {
-    core::arch::asm!(
-        "out dx, al",
-        in("dx") port,
-        in("al") value,
-        options(nomem, nostack, preserves_flags),
-    );
 }
 
-fn serial_init() {
-    // SAFETY: we are at CPL=0 and no other code touches COM1 at this stage.
-    unsafe {
-        outb(0x3F8 + 1, 0x00); // disable interrupts
-        outb(0x3F8 + 3, 0x80); // enable DLAB
-        outb(0x3F8, 0x01); // divisor low byte (115200 baud)
-        outb(0x3F8 + 1, 0x00); // divisor high byte
-        outb(0x3F8 + 3, 0x03); // 8N1, clear DLAB
-        outb(0x3F8 + 2, 0xC7); // enable + flush FIFOs
-        outb(0x3F8 + 4, 0x0B); // DTR + RTS + AUX2
-    }
-}
-
-/// Write a byte to COM1 (I/O port 0x3F8), polling until the THR is empty.
-///
-/// SAFETY: Direct PIO to a known-safe I/O port. On x86 this requires CPL=0
-/// (we are in ring 0 after boot services exit).
-unsafe fn serial_write_byte(byte: u8) {
-    // Poll Line Status Register (0x3F8 + 5) until bit 5 (THRE) is set.
-    loop {
-        let lsr: u8;
-        core::arch::asm!(
-            "in al, dx",
-            in("dx") 0x3F8u16 + 5,
-            out("al") lsr,
-            options(nomem, nostack),
-        );
-        if lsr & 0x20 != 0 {
-            break;
-        }
-    }
-    core::arch::asm!(
-        "out dx, al",
-        in("dx") 0x3F8u16,
-        in("al") byte,
-        options(nomem, nostack),
-    );
-}
-
-fn serial_write_str(s: &str) {
-    for byte in s.bytes() {
-        // SAFETY: see `serial_write_byte`.
-        unsafe { serial_write_byte(byte) };
-    }
-}
-
-fn serial_write_usize(mut n: usize) {
-    if n == 0 {
-        serial_write_str("0");
-        return;
-    }
-    let mut buf = [0u8; 20];
-    let mut i = 0;
-    while n > 0 {
-        buf[i] = b'0' + (n % 10) as u8;
-        n /= 10;
-        i += 1;
-    }
-    for j in (0..i).rev() {
-        // SAFETY: see `serial_write_byte`.
-        unsafe { serial_write_byte(buf[j]) };
-    }
-}
-
-fn serial_write_usize_hex(n: usize) {
-    const HEX: &[u8] = b"0123456789abcdef";
-    let mut buf = [0u8; 16];
-    let mut i = 0;
-    let mut val = n;
-    if val == 0 {
-        serial_write_str("0");
-        return;
-    }
-    while val > 0 {
-        buf[i] = HEX[val & 0xf];
-        val >>= 4;
-        i += 1;
-    }
-    for j in (0..i).rev() {
-        // SAFETY: see `serial_write_byte`.
-        unsafe { serial_write_byte(buf[j]) };
-    }
-}
-
-// ---------------------------------------------------------------------------
-// GDT (Global Descriptor Table)
-//
-// Even in 64-bit long mode, the GDT must be present and loaded.  The CPU
-// uses the code-segment descriptor to confirm 64-bit execution mode (L=1)
-// and the data-segment selector to satisfy segment-register requirements.
-//
-// Minimal layout for Phase 1:
-//   Index 0 — 0x0000 : Null descriptor (architecturally required)
-//   Index 1 — 0x0008 : Kernel code segment (64-bit, ring 0)
-//   Index 2 — 0x0010 : Kernel data segment (ring 0)
-//
-// The canonical types live in kernel/src/arch/x86_64/gdt.rs.
-// This is the Phase-1 inline copy used while boot and kernel share a binary.
-// ---------------------------------------------------------------------------
-
-/// 64-bit kernel code segment: P=1, DPL=0, S=1, Type=0xA, L=1, D=0, G=1.
-const GDT_KERNEL_CODE: u64 = 0x00AF_9A00_0000_FFFF;
-
-/// Kernel data segment: P=1, DPL=0, S=1, Type=0x2, D=1, G=1.
-const GDT_KERNEL_DATA: u64 = 0x00CF_9200_0000_FFFF;
-
-/// Kernel code segment selector (GDT index 1, TI=0, RPL=0).
-const KERNEL_CODE_SELECTOR: u64 = 0x08;
-
-/// Kernel data segment selector (GDT index 2, TI=0, RPL=0).
-const KERNEL_DATA_SELECTOR: u16 = 0x10;
-
-/// The kernel's GDT — three entries, 8-byte aligned.
-#[repr(C, align(8))]
-struct Gdt([u64; 3]);
-
-/// SAFETY: written once at compile time and never mutated; the CPU reads it
-/// as raw bytes via the GDTR, which does not go through Rust's reference model.
-static GDT: Gdt = Gdt([0x0000_0000_0000_0000, GDT_KERNEL_CODE, GDT_KERNEL_DATA]);
-
-/// Pointer structure passed to the `LGDT` instruction.
-///
-/// Must be `#[repr(C, packed)]` so the CPU sees exactly 2 bytes of limit
-/// followed by 8 bytes of base — no padding.
-#[repr(C, packed)]
-struct GdtPointer {
-    limit: u16,
-    base: u64,
-}
-
-/// Load the GDT and reload all segment registers.
-///
-/// # Safety
-///
-/// - Must be called at CPL=0 with interrupts disabled.
-/// - `GDT` must be mapped and accessible at its linear address for the
-///   lifetime of the kernel.
-unsafe fn gdt_init() {
-    let ptr = GdtPointer {
-        limit: (core::mem::size_of::<Gdt>() - 1) as u16,
-        base: core::ptr::addr_of!(GDT) as u64,
-    };
-
-    // Load GDTR.
-    //
-    // SAFETY: `ptr` is a valid GdtPointer on the current stack; its address
-    // is stable for the duration of this inline-asm block. LGDT is a
-    // privileged instruction valid at CPL=0.
-    core::arch::asm!(
-        "lgdt [{ptr}]",
-        ptr = in(reg) &ptr,
-        options(readonly, nostack, preserves_flags),
-    );
-
-    // Reload CS via far return.
-    //
-    // There is no direct way to load CS in 64-bit mode; a far return is the
-    // standard technique.  Stack layout for RETFQ (grows downward):
-    //   RSP+0  new RIP  (address of label 1f, immediately after RETFQ)
-    //   RSP+8  new CS   (KERNEL_CODE_SELECTOR = 0x08)
-    //
-    // After RETFQ the CPU fetches the next instruction from label 2: with
-    // CS holding the kernel code selector.
-    //
-    // SAFETY: KERNEL_CODE_SELECTOR references a valid 64-bit code descriptor
-    // in GDT.  The far return lands on the very next instruction (label 1:),
-    // so control flow remains within this function.
-    core::arch::asm!(
-        "push {cs}",
-        "lea {tmp}, [rip + 2f]",
-        "push {tmp}",
-        "retfq",
-        "2:",
-        cs  = in(reg) KERNEL_CODE_SELECTOR,
-        tmp = lateout(reg) _,
-    );
-
-    // Reload data segment registers.
-    //
-    // DS, ES, FS, GS, SS must hold a valid selector; in 64-bit mode their
-    // base/limit are ignored, but a null or invalid selector causes a #GP.
-    //
-    // SAFETY: KERNEL_DATA_SELECTOR references a valid data descriptor.
-    core::arch::asm!(
-        "mov ds, ax",
-        "mov es, ax",
-        "mov fs, ax",
-        "mov gs, ax",
-        "mov ss, ax",
-        in("ax") KERNEL_DATA_SELECTOR,
-        options(nomem, nostack, preserves_flags),
-    );
-}
-
-// ---------------------------------------------------------------------------
-// IDT (Interrupt Descriptor Table)
-//
-// The IDT maps interrupt/exception vectors 0–255 to handler stubs.
-// Each descriptor is 16 bytes. Phase 1 installs stub handlers for all
-// 32 CPU exception vectors plus a generic stub for IRQ vectors 32–255.
-// Interrupts remain DISABLED after init — this IDT is ready to catch
-// CPU exceptions (divide-by-zero, GPF, page faults, etc.) if they
-// arise from a kernel bug.
-//
-// The canonical types live in kernel/src/arch/x86_64/idt.rs.
-// This is the Phase-1 inline copy used while boot and kernel share a binary.
-//
-// Stub design (stable Rust — no abi_x86_interrupt / #[naked] required):
-//
-//   Two macro variants handle the difference in CPU stack layout:
-//
-//   isr_stub v    — vectors WITHOUT a CPU-pushed error code:
-//     RDI = vector, RSI = 0, RDX = RSP (→ ExceptionFrame)
-//
-//   isr_stub_ec v — vectors WITH a CPU-pushed error code (8,10-14,17,21,29,30):
-//     CPU pushes error code below RIP, so at entry RSP → error_code.
-//     `pop rsi` consumes it; RSP then → ExceptionFrame, same as above.
-//     RDI = vector, RSI = error_code, RDX = RSP (→ ExceptionFrame)
-//
-//   Both variants jump to __exception_common which calls exception_handler()
-//   with three args: (vector, error_code, *ExceptionFrame).
-//   exception_handler() prints diagnostics and halts forever.
-//   Since we never return, no IRETQ / stack rebalancing is required.
-//
-// Error-code vectors per Intel SDM Vol 3A §6.13:
-//   8 (#DF), 10 (#TS), 11 (#NP), 12 (#SS), 13 (#GP), 14 (#PF),
-//   17 (#AC), 21 (#CP), 29 (#VC), 30 (#SX)
-// ---------------------------------------------------------------------------
-
-use core::arch::global_asm;
-
-// Assembly stubs — one per CPU exception vector (0–31) plus one generic
-// stub for hardware IRQ vectors (32–255).
-//
-// Intel syntax is used (.intel_syntax noprefix) for consistency with the
-// inline asm elsewhere in this file.
-global_asm!(
-    ".intel_syntax noprefix",
-    // ---------------------------------------------------------------------------
-    // Common landing pad.
-    //
-    // At entry: RDI = vector, RSI = error_code, RDX = &ExceptionFrame.
-    // Calls exception_handler(vector, error_code, frame) which never returns.
-    // ---------------------------------------------------------------------------
-    ".global __exception_common",
-    "__exception_common:",
-    "cli",
-    "call exception_handler",
-    "ud2", // unreachable — traps if the call somehow returns
-    // ---------------------------------------------------------------------------
-    // isr_stub v — no CPU-pushed error code.
-    //   RSP → [RIP, CS, RFLAGS, old_RSP, SS]  (ExceptionFrame) on entry.
-    // ---------------------------------------------------------------------------
-    ".macro isr_stub v",
-    ".global __isr_\\v",
-    "__isr_\\v:",
-    "mov rdi, \\v", // arg1: vector number
-    "xor rsi, rsi", // arg2: error_code = 0 (none for this vector)
-    "mov rdx, rsp", // arg3: pointer to ExceptionFrame at current RSP
-    "jmp __exception_common",
-    ".endm",
-    // ---------------------------------------------------------------------------
-    // isr_stub_ec v — CPU pushes an error code before the handler runs.
-    //   RSP → [error_code, RIP, CS, RFLAGS, old_RSP, SS]  on entry.
-    //   `pop rsi` consumes the error code; RSP then → ExceptionFrame.
-    // ---------------------------------------------------------------------------
-    ".macro isr_stub_ec v",
-    ".global __isr_\\v",
-    "__isr_\\v:",
-    "mov rdi, \\v", // arg1: vector number (clobbers original RDI — we never return)
-    "pop rsi",      // arg2: error_code (CPU-pushed; RSP now → ExceptionFrame)
-    "mov rdx, rsp", // arg3: pointer to ExceptionFrame
-    "jmp __exception_common",
-    ".endm",
-    // CPU exception stubs — vectors 0–31
-    "isr_stub 0",     // #DE  Divide Error               (no error code)
-    "isr_stub 1",     // #DB  Debug                      (no error code)
-    "isr_stub 2",     // #NMI Non-Maskable Interrupt      (no error code)
-    "isr_stub 3",     // #BP  Breakpoint                 (no error code)
-    "isr_stub 4",     // #OF  Overflow                   (no error code)
-    "isr_stub 5",     // #BR  Bound Range Exceeded        (no error code)
-    "isr_stub 6",     // #UD  Invalid Opcode             (no error code)
-    "isr_stub 7",     // #NM  Device Not Available        (no error code)
-    "isr_stub_ec 8",  // #DF  Double Fault               (error code = 0 always)
-    "isr_stub 9",     // (obsolete Coprocessor Segment Overrun, no error code)
-    "isr_stub_ec 10", // #TS  Invalid TSS                (error code: selector)
-    "isr_stub_ec 11", // #NP  Segment Not Present         (error code: selector)
-    "isr_stub_ec 12", // #SS  Stack-Segment Fault         (error code: selector)
-    "isr_stub_ec 13", // #GP  General Protection Fault    (error code: selector or 0)
-    "isr_stub_ec 14", // #PF  Page Fault                 (error code: flags; CR2: address)
-    "isr_stub 15",    // (reserved)
-    "isr_stub 16",    // #MF  x87 FPU Floating-Point Error (no error code)
-    "isr_stub_ec 17", // #AC  Alignment Check            (error code = 0)
-    "isr_stub 18",    // #MC  Machine Check               (no error code)
-    "isr_stub 19",    // #XF  SIMD Floating-Point Exception (no error code)
-    "isr_stub 20",    // #VE  Virtualization Exception    (no error code)
-    "isr_stub_ec 21", // #CP  Control Protection Exception (error code)
-    "isr_stub 22",    // (reserved)
-    "isr_stub 23",    // (reserved)
-    "isr_stub 24",    // (reserved)
-    "isr_stub 25",    // (reserved)
-    "isr_stub 26",    // (reserved)
-    "isr_stub 27",    // (reserved)
-    "isr_stub 28",    // #HV  Hypervisor Injection Exception (no error code)
-    "isr_stub_ec 29", // #VC  VMM Communication Exception  (error code)
-    "isr_stub_ec 30", // #SX  Security Exception           (error code)
-    "isr_stub 31",    // (reserved)
-    // Generic stub for hardware IRQ vectors 32–255.
-    ".global __isr_irq",
-    "__isr_irq:",
-    "mov rdi, 255", // sentinel: hardware IRQ (vector not decoded further)
-    "xor rsi, rsi", // no error code
-    "mov rdx, rsp", // pointer to stack top (not a formal ExceptionFrame, but safe to ignore)
-    "jmp __exception_common",
-    ".att_syntax prefix", // restore assembler default
-);
-
-// Extern declarations for the stubs generated above.
-extern "C" {
-    fn __isr_0();
-    fn __isr_1();
-    fn __isr_2();
-    fn __isr_3();
-    fn __isr_4();
-    fn __isr_5();
-    fn __isr_6();
-    fn __isr_7();
-    fn __isr_8();
-    fn __isr_9();
-    fn __isr_10();
-    fn __isr_11();
-    fn __isr_12();
-    fn __isr_13();
-    fn __isr_14();
-    fn __isr_15();
-    fn __isr_16();
-    fn __isr_17();
-    fn __isr_18();
-    fn __isr_19();
-    fn __isr_20();
-    fn __isr_21();
-    fn __isr_22();
-    fn __isr_23();
-    fn __isr_24();
-    fn __isr_25();
-    fn __isr_26();
-    fn __isr_27();
-    fn __isr_28();
-    fn __isr_29();
-    fn __isr_30();
-    fn __isr_31();
-    fn __isr_irq();
-}
-
-/// CPU-pushed exception frame — layout at RSP when an exception handler runs.
-///
-/// In 64-bit mode the CPU always pushes this 5-quadword frame (regardless of
-/// privilege-level change). For vectors that push an error code, the stubs
-/// consume it with `pop rsi` first, so RSP points here in both cases.
-///
-/// ```text
-/// [RSP +  0]  RIP    — instruction pointer that caused / follows the exception
-/// [RSP +  8]  CS     — code segment (upper 48 bits zero)
-/// [RSP + 16]  RFLAGS — CPU flags at time of exception
-/// [RSP + 24]  RSP    — stack pointer at time of exception
-/// [RSP + 32]  SS     — stack segment (upper 48 bits zero)
-/// ```
-#[repr(C)]
-struct ExceptionFrame {
-    rip: u64,
-    cs: u64,
-    rflags: u64,
-    rsp: u64,
-    ss: u64,
-}
-
-/// Human-readable names for the 32 CPU exception vectors.
-static EXCEPTION_NAMES: [&str; 32] = [
-    "#DE: Divide Error",
-    "#DB: Debug",
-    "#NMI: Non-Maskable Interrupt",
-    "#BP: Breakpoint",
-    "#OF: Overflow",
-    "#BR: Bound Range Exceeded",
-    "#UD: Invalid Opcode",
-    "#NM: Device Not Available",
-    "#DF: Double Fault",
-    "(obsolete Coprocessor Segment Overrun)",
-    "#TS: Invalid TSS",
-    "#NP: Segment Not Present",
-    "#SS: Stack-Segment Fault",
-    "#GP: General Protection Fault",
-    "#PF: Page Fault",
-    "(reserved)",
-    "#MF: x87 FPU Floating-Point Error",
-    "#AC: Alignment Check",
-    "#MC: Machine Check",
-    "#XF: SIMD Floating-Point Exception",
-    "#VE: Virtualization Exception",
-    "#CP: Control Protection Exception",
-    "(reserved)",
-    "(reserved)",
-    "(reserved)",
-    "(reserved)",
-    "(reserved)",
-    "(reserved)",
-    "#HV: Hypervisor Injection Exception",
-    "#VC: VMM Communication Exception",
-    "#SX: Security Exception",
-    "(reserved)",
-];
-
-/// Common exception handler — never returns.
-///
-/// Called from all exception stubs with:
-/// - `vector`     : exception vector number (0–31 = CPU exception, 255 = IRQ)
-/// - `error_code` : CPU-pushed error code for vectors that supply one, else 0
-/// - `frame`      : pointer to the CPU-pushed [`ExceptionFrame`] on the stack
-///
-/// Prints a diagnostic over serial and halts the CPU forever.
-///
-/// # Safety (caller — the asm stubs)
-///
-/// - Called from assembly via the SysV AMD64 convention: RDI, RSI, RDX.
-/// - `frame` points into the interrupt stack and is valid for the lifetime of
-///   this function (which never returns).
-/// - Interrupts are disabled (`cli` executed in the stubs).
-/// - Must be `#[no_mangle]` so the linker name matches the `call` in asm.
-#[no_mangle]
-extern "C" fn exception_handler(vector: u64, error_code: u64, frame: *const ExceptionFrame) -> ! {
-    serial_write_str("\r\n");
-    serial_write_str("========== KERNEL EXCEPTION ==========\r\n");
-
-    // --- Exception name ---
-    if (vector as usize) < EXCEPTION_NAMES.len() {
-        serial_write_str("Vector ");
-        serial_write_usize(vector as usize);
-        serial_write_str(": ");
-        serial_write_str(EXCEPTION_NAMES[vector as usize]);
-    } else {
-        serial_write_str("Hardware IRQ / unknown vector #");
-        serial_write_usize(vector as usize);
-    }
-    serial_write_str("\r\n");
-
-    // --- Error code (only meaningful for the subset of vectors that push one) ---
-    //
-    // Bitmask of vectors that push an error code (Intel SDM Vol 3A §6.13):
-    //   bit 8  = #DF, bit 10 = #TS, bit 11 = #NP, bit 12 = #SS, bit 13 = #GP,
-    //   bit 14 = #PF, bit 17 = #AC, bit 21 = #CP, bit 29 = #VC, bit 30 = #SX
-    const EC_MASK: u64 = (1 << 8)
-        | (1 << 10)
-        | (1 << 11)
-        | (1 << 12)
-        | (1 << 13)
-        | (1 << 14)
-        | (1 << 17)
-        | (1 << 21)
-        | (1 << 29)
-        | (1 << 30);
-    if vector < 64 && (EC_MASK >> vector) & 1 == 1 {
-        serial_write_str("Error code:   0x");
-        serial_write_usize_hex(error_code as usize);
-        serial_write_str("\r\n");
-    }
-
-    // --- Page fault: read CR2 (faulting virtual address) ---
-    //
-    // CR2 is set by the CPU before the #PF handler runs and remains valid
-    // until the next page fault (which cannot happen here — interrupts off).
-    if vector == 14 {
-        let cr2: u64;
-        // SAFETY: reading CR2 at CPL=0 is unconditionally safe.
-        unsafe { core::arch::asm!("mov {}, cr2", out(reg) cr2, options(nomem, nostack)) };
-        serial_write_str("CR2 (fault):  0x");
-        serial_write_usize_hex(cr2 as usize);
-        serial_write_str("\r\n");
-    }
-
-    // --- Exception frame: RIP, RFLAGS, RSP ---
-    //
-    // SAFETY: `frame` is derived from RSP at handler entry — it points to the
-    // CPU-pushed exception frame on the interrupt stack, which is valid for the
-    // lifetime of this function (we never return).
-    if !frame.is_null() {
-        let rip = unsafe { (*frame).rip };
-        let rflags = unsafe { (*frame).rflags };
-        let old_rsp = unsafe { (*frame).rsp };
-
-        serial_write_str("RIP:          0x");
-        serial_write_usize_hex(rip as usize);
-        serial_write_str("\r\n");
-
-        serial_write_str("RFLAGS:       0x");
-        serial_write_usize_hex(rflags as usize);
-        serial_write_str("\r\n");
-
-        serial_write_str("RSP (before): 0x");
-        serial_write_usize_hex(old_rsp as usize);
-        serial_write_str("\r\n");
-    }
-
-    serial_write_str("======================================\r\n");
-    serial_write_str("System halted.\r\n");
-
-    loop {
-        // SAFETY: hlt suspends the CPU until the next interrupt. With cli
-        // already executed in the stub, this loops forever.
-        unsafe { core::arch::asm!("hlt") };
-    }
-}
-
-/// IDT gate descriptor (16 bytes).
-#[repr(C, packed)]
-#[derive(Clone, Copy)]
-struct IdtEntry {
-    offset_low: u16,
-    selector: u16,
-    ist: u8,
-    type_attr: u8, // 0x8E = P=1, DPL=0, interrupt gate
-    offset_mid: u16,
-    offset_high: u32,
-    reserved: u32,
-}
-
-impl IdtEntry {
-    const fn missing() -> Self {
-        Self {
-            offset_low: 0,
-            selector: 0,
-            ist: 0,
-            type_attr: 0,
-            offset_mid: 0,
-            offset_high: 0,
-            reserved: 0,
-        }
-    }
-
-    fn new(handler: u64) -> Self {
-        Self {
-            offset_low: (handler & 0xFFFF) as u16,
-            selector: 0x0008, // kernel code segment
-            ist: 0,
-            type_attr: 0x8E, // P=1, DPL=0, interrupt gate
-            offset_mid: ((handler >> 16) & 0xFFFF) as u16,
-            offset_high: ((handler >> 32) & 0xFFFF_FFFF) as u32,
-            reserved: 0,
-        }
-    }
-}
-
-/// Descriptor-table pointer for the `LIDT` instruction.
-#[repr(C, packed)]
-struct IdtPointer {
-    limit: u16,
-    base: u64,
-}
-
-/// Wrapper that gives the IDT array 16-byte alignment (required by the CPU).
-#[repr(C, align(16))]
-struct IdtTable([IdtEntry; 256]);
-
-/// The kernel IDT — 256 entries, 16-byte aligned.
-///
-/// SAFETY: populated exactly once in `idt_init` before `LIDT` is called,
-/// then treated as read-only by the CPU.
-static mut IDT: IdtTable = IdtTable([IdtEntry::missing(); 256]);
-
-/// Populate the IDT with exception stubs and load the IDTR.
-///
-/// # Safety
-///
-/// - Must be called at CPL=0.
-/// - Interrupts must be disabled.
-/// - Must be called at most once (reinitialising IDTR while interrupts are
-///   disabled is safe, but the previous IDT is abandoned).
-unsafe fn idt_init() {
-    // --- Install exception stubs (vectors 0–31) ---
-    IDT.0[0] = IdtEntry::new(__isr_0 as u64);
-    IDT.0[1] = IdtEntry::new(__isr_1 as u64);
-    IDT.0[2] = IdtEntry::new(__isr_2 as u64);
-    IDT.0[3] = IdtEntry::new(__isr_3 as u64);
-    IDT.0[4] = IdtEntry::new(__isr_4 as u64);
-    IDT.0[5] = IdtEntry::new(__isr_5 as u64);
-    IDT.0[6] = IdtEntry::new(__isr_6 as u64);
-    IDT.0[7] = IdtEntry::new(__isr_7 as u64);
-    IDT.0[8] = IdtEntry::new(__isr_8 as u64);
-    IDT.0[9] = IdtEntry::new(__isr_9 as u64);
-    IDT.0[10] = IdtEntry::new(__isr_10 as u64);
-    IDT.0[11] = IdtEntry::new(__isr_11 as u64);
-    IDT.0[12] = IdtEntry::new(__isr_12 as u64);
-    IDT.0[13] = IdtEntry::new(__isr_13 as u64);
-    IDT.0[14] = IdtEntry::new(__isr_14 as u64);
-    IDT.0[15] = IdtEntry::new(__isr_15 as u64);
-    IDT.0[16] = IdtEntry::new(__isr_16 as u64);
-    IDT.0[17] = IdtEntry::new(__isr_17 as u64);
-    IDT.0[18] = IdtEntry::new(__isr_18 as u64);
-    IDT.0[19] = IdtEntry::new(__isr_19 as u64);
-    IDT.0[20] = IdtEntry::new(__isr_20 as u64);
-    IDT.0[21] = IdtEntry::new(__isr_21 as u64);
-    IDT.0[22] = IdtEntry::new(__isr_22 as u64);
-    IDT.0[23] = IdtEntry::new(__isr_23 as u64);
-    IDT.0[24] = IdtEntry::new(__isr_24 as u64);
-    IDT.0[25] = IdtEntry::new(__isr_25 as u64);
-    IDT.0[26] = IdtEntry::new(__isr_26 as u64);
-    IDT.0[27] = IdtEntry::new(__isr_27 as u64);
-    IDT.0[28] = IdtEntry::new(__isr_28 as u64);
-    IDT.0[29] = IdtEntry::new(__isr_29 as u64);
-    IDT.0[30] = IdtEntry::new(__isr_30 as u64);
-    IDT.0[31] = IdtEntry::new(__isr_31 as u64);
-
-    // --- Install generic IRQ stub for hardware interrupt vectors 32–255 ---
-    let mut i = 32usize;
-    while i < 256 {
-        IDT.0[i] = IdtEntry::new(__isr_irq as u64);
-        i += 1;
-    }
-
-    // --- Load the IDTR ---
-    //
-    // SAFETY: IDT is a valid static, aligned to 16 bytes, fully populated
-    // above. LIDT writes only to the IDTR register. CPL=0 and interrupts
-    // are disabled per the caller's contract.
-    let ptr = IdtPointer {
-        limit: (core::mem::size_of::<IdtTable>() - 1) as u16,
-        base: core::ptr::addr_of!(IDT) as u64,
-    };
-    core::arch::asm!(
-        "lidt [{ptr}]",
-        ptr = in(reg) &ptr,
-        options(readonly, nostack, preserves_flags),
-    );
-}
-
-// ---------------------------------------------------------------------------
-// Memory map analysis (Phase 1.3.1 — inline boot-side implementation)
-//
-// The canonical MemoryMap type lives in ferrous-boot-info. This helper
-// prints the full map and summary statistics over serial during early boot.
-// ---------------------------------------------------------------------------
-
-/// Returns a short label for a UEFI memory type code.
-fn memory_type_label(ty: u32) -> &'static str {
-    use ferrous_boot_info::memory_type;
-    match ty {
-        memory_type::RESERVED => "Reserved",
-        memory_type::LOADER_CODE => "LoaderCode",
-        memory_type::LOADER_DATA => "LoaderData",
-        memory_type::BOOT_SERVICES_CODE => "BootServicesCode",
-        memory_type::BOOT_SERVICES_DATA => "BootServicesData",
-        memory_type::RUNTIME_SERVICES_CODE => "RuntimeServicesCode",
-        memory_type::RUNTIME_SERVICES_DATA => "RuntimeServicesData",
-        memory_type::CONVENTIONAL => "Conventional",
-        memory_type::UNUSABLE => "Unusable",
-        memory_type::ACPI_RECLAIM => "AcpiReclaim",
-        memory_type::ACPI_NON_VOLATILE => "AcpiNonVolatile",
-        memory_type::MMIO => "Mmio",
-        memory_type::MMIO_PORT_SPACE => "MmioPortSpace",
-        memory_type::PERSISTENT_MEMORY => "PersistentMemory",
-        _ => "Unknown",
-    }
-}
-
-/// Returns true for UEFI types that are usable after boot services exit.
-fn is_usable_after_boot(ty: u32) -> bool {
-    use ferrous_boot_info::memory_type;
-    matches!(
-        ty,
-        memory_type::CONVENTIONAL
-            | memory_type::BOOT_SERVICES_CODE
-            | memory_type::BOOT_SERVICES_DATA
-            | memory_type::LOADER_CODE
-            | memory_type::LOADER_DATA
-            | memory_type::ACPI_RECLAIM
-    )
-}
-
-/// Returns true for UEFI types that map to address-space holes (not RAM).
-fn is_mmio(ty: u32) -> bool {
-    use ferrous_boot_info::memory_type;
-    matches!(ty, memory_type::MMIO | memory_type::MMIO_PORT_SPACE)
-}
-
-/// Print the full physical memory map and summary statistics over serial.
-fn print_memory_map(map: &ferrous_boot_info::KernelMemoryMap) {
-    serial_write_str("[INFO] Physical memory map (");
-    serial_write_usize(map.count);
-    serial_write_str(" entries");
-    if map.truncated {
-        serial_write_str(", TRUNCATED");
-    }
-    serial_write_str("):\r\n");
-
-    let mut total_bytes: u64 = 0;
-    let mut usable_bytes: u64 = 0;
-    let mut reclaimable_bytes: u64 = 0;
-
-    for (i, desc) in map.entries().iter().enumerate() {
-        if desc.page_count == 0 {
-            continue;
-        }
-
-        let size = desc.page_count * 4096;
-        let end = desc.phys_start.saturating_add(size);
-
-        if !is_mmio(desc.ty) {
-            total_bytes = total_bytes.saturating_add(size);
-        }
-        if is_usable_after_boot(desc.ty) {
-            if desc.ty == ferrous_boot_info::memory_type::CONVENTIONAL {
-                usable_bytes = usable_bytes.saturating_add(size);
-            } else {
-                reclaimable_bytes = reclaimable_bytes.saturating_add(size);
-            }
-        }
-
-        // Format: "  [ 0] 0x00001000 - 0x00100000  1020 KiB  Conventional"
-        serial_write_str("  [");
-        serial_write_usize(i);
-        serial_write_str("] 0x");
-        serial_write_usize_hex(desc.phys_start as usize);
-        serial_write_str(" - 0x");
-        serial_write_usize_hex(end as usize);
-        serial_write_str("  ");
-        let kib = size / 1024;
-        if kib >= 1024 {
-            serial_write_usize((kib / 1024) as usize);
-            serial_write_str(" MiB");
-        } else {
-            serial_write_usize(kib as usize);
-            serial_write_str(" KiB");
-        }
-        serial_write_str("  ");
-        serial_write_str(memory_type_label(desc.ty));
-        serial_write_str("\r\n");
-    }
-
-    if map.truncated {
-        serial_write_str("[WARN] Memory map was truncated — some regions are missing!\r\n");
-    }
-
-    serial_write_str("[INFO] RAM: ");
-    serial_write_usize((total_bytes / 1024 / 1024) as usize);
-    serial_write_str(" MiB total | ");
-    serial_write_usize((usable_bytes / 1024 / 1024) as usize);
-    serial_write_str(" MiB usable | ");
-    serial_write_usize((reclaimable_bytes / 1024 / 1024) as usize);
-    serial_write_str(" MiB reclaimable\r\n");
-}
-
-/// Halt the CPU permanently.
-fn halt() -> ! {
-    loop {
-        // SAFETY: `hlt` suspends the CPU until the next interrupt. With
-        // interrupts disabled this loops forever, which is the intended
-        // behaviour at end-of-life for Phase 1.
-        unsafe { core::arch::asm!("hlt") };
-    }
-}
-
-// ---------------------------------------------------------------------------
-// UEFI helper functions (same as before, now only used pre-handoff)
-// ---------------------------------------------------------------------------
-
 fn retrieve_memory_map(console: &mut Console) -> Result<MemoryMap, uefi::Error> {
     let memory_map_owned = uefi::boot::memory_map(MemoryType::LOADER_DATA)?;
     let memory_map = MemoryMap::from_uefi_memory_map(&memory_map_owned);
@@ -1301,6 +566,38 @@ fn retrieve_memory_map(console: &mut Console) -> Result<MemoryMap, uefi::Error>
     Ok(memory_map)
 }
 
+/// Report how the kernel image was placed (see [`kaslr`]).
+fn print_placement(kernel: &loader::LoadedKernel, console: &mut Console) {
+    match kernel.placement {
+        Placement::Random(source) => {
+            writeln!(
+                console,
+                "[OK] KASLR: slide {:#x} (entropy: {:?})",
+                kernel.slide, source
+            )
+            .unwrap();
+            if source == EntropySource::TscJitter {
+                writeln!(
+                    console,
+                    "[WARN] KASLR: no RNG available; TSC jitter is weak"
+                )
+                .unwrap();
+            }
+        }
+        Placement::Disabled => writeln!(console, "[INFO] KASLR: disabled by nokaslr").unwrap(),
+        Placement::Fixed => writeln!(
+            console,
+            "[WARN] KASLR: kernel is not position-independent; loaded at its link address"
+        )
+        .unwrap(),
+        Placement::NoRoom => writeln!(
+            console,
+            "[WARN] KASLR: no free slot for the kernel; loaded at its link address"
+        )
+        .unwrap(),
+    }
+}
+
 fn print_memory_summary(memory_map: &MemoryMap, console: &mut Console) {
     writeln!(console, "").unwrap();
     writeln!(console, "Memory Map Summary:").unwrap();
@@ -1,10 +1,11 @@
 //! Memory map handling for UEFI boot.
 //!
 //! This module provides structures and utilities for working with the
-//! UEFI memory map.
+//! UEFI memory map, and [`copy_to_loader_data`] for blobs handed to the
+//! kernel.
 
 use alloc::vec::Vec;
-use uefi::boot::MemoryType as UefiMemoryType;
+use uefi::boot::{AllocateType, MemoryType as UefiMemoryType};
 use uefi::mem::memory_map::{MemoryMap as UefiMemoryMapTrait, MemoryMapOwned};
 
 /// Type of memory region.
@@ -193,3 +194,37 @@ impl Default for MemoryMap {
         Self::new()
     }
 }
+
+/// UEFI page size.
+const PAGE_SIZE: usize = 4096;
+
+/// Copy `bytes` into freshly allocated `LOADER_DATA` pages and return the
+/// copy.
+///
+/// The pages are never freed, so they survive `exit_boot_services()` and
+/// appear as `LOADER_DATA` in any memory map retrieved afterwards. Empty
+/// input still gets one page, so the address is never null.
+///
+/// # Errors
+///
+/// The firmware's error if `pages_for(bytes.len())` pages are not
+/// available.
+pub fn copy_to_loader_data(bytes: &[u8]) -> uefi::Result<&'static [u8]> {
+    let region = uefi::boot::allocate_pages(
+        AllocateType::AnyPages,
+        UefiMemoryType::LOADER_DATA,
+        pages_for(bytes.len()),
+    )?;
+    // SAFETY: `region` is a fresh allocation of at least `bytes.len()`
+    // bytes, identity-mapped by UEFI, never freed and not aliased; the
+    // source is pool memory and cannot overlap it.
+    unsafe {
+        core::ptr::copy_nonoverlapping(bytes.as_ptr(), region.as_ptr(), bytes.len());
+        Ok(core::slice::from_raw_parts(region.as_ptr(), bytes.len()))
+    }
+}
+
+/// Pages [`copy_to_loader_data`] allocates for `len` bytes.
+pub fn pages_for(len: usize) -> usize {
+    len.div_ceil(PAGE_SIZE).max(1)
+}
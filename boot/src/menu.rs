@@ -0,0 +1,137 @@
+//! Boot menu.
+//!
+//! Reads [`CONFIG_PATH`] (see [`crate::config`] for the format) and, unless
+//! its timeout is 0, lists the entries on the UEFI console. Up/Down move the
+//! selection, Enter boots it, and the default entry boots by itself when
+//! the timeout runs out. Any key stops the countdown.
+//!
+//! Input is polled through SimpleTextInput every [`POLL_INTERVAL_US`]; no
+//! timer events are needed.
+
+use alloc::string::String;
+use core::fmt::Write;
+
+use uefi::proto::console::text::{Color, Key, ScanCode};
+use uefi::{cstr16, CStr16};
+
+use crate::config::{BootConfig, BootEntry};
+use crate::console::Console;
+
+/// Path of the boot configuration on the boot volume.
+pub const CONFIG_PATH: &CStr16 = cstr16!("\\EFI\\ferrous\\boot.cfg");
+
+/// How often the keyboard is polled, in microseconds.
+const POLL_INTERVAL_US: usize = 50_000;
+
+/// Read [`CONFIG_PATH`]. `None` if it does not exist or cannot be read,
+/// which is reported on `console`.
+pub fn read_config(console: &mut Console) -> Option<String> {
+    let sfs = uefi::boot::get_image_file_system(uefi::boot::image_handle()).ok()?;
+    match uefi::fs::FileSystem::new(sfs).read_to_string(CONFIG_PATH) {
+        Ok(text) => Some(text),
+        Err(e) if crate::loader::is_not_found(&e) => None,
+        Err(e) => {
+            writeln!(console, "[WARN] Cannot read {}: {:?}", CONFIG_PATH, e).unwrap();
+            None
+        }
+    }
+}
+
+/// Let the user pick an entry of `config`; `None` if it has no entries.
+pub fn choose<'c, 'a>(
+    console: &mut Console,
+    config: &'c BootConfig<'a>,
+) -> Option<&'c BootEntry<'a>> {
+    if config.entries.is_empty() {
+        return None;
+    }
+    if config.timeout_secs == 0 {
+        return config.entries.get(config.default);
+    }
+
+    // Drop keys pressed before the menu appeared.
+    uefi::system::with_stdin(|stdin| {
+        let _ = stdin.reset(false);
+    });
+    let mut selected = config.default;
+    let mut remaining_us = Some(config.timeout_secs as usize * 1_000_000);
+    draw(console, config, selected, remaining_us);
+
+    loop {
+        let key = uefi::system::with_stdin(|stdin| stdin.read_key().ok().flatten());
+        let Some(key) = key else {
+            uefi::boot::stall(POLL_INTERVAL_US);
+            if let Some(us) = remaining_us {
+                let us = us.saturating_sub(POLL_INTERVAL_US);
+                if us == 0 {
+                    break;
+                }
+                // Redraw only when the displayed second changes.
+                let redraw = us.div_ceil(1_000_000) != (us + POLL_INTERVAL_US).div_ceil(1_000_000);
+                remaining_us = Some(us);
+                if redraw {
+                    draw(console, config, selected, remaining_us);
+                }
+            }
+            continue;
+        };
+
+        remaining_us = None;
+        match key {
+            Key::Special(ScanCode::UP) => selected = selected.saturating_sub(1),
+            Key::Special(ScanCode::DOWN) => selected = (selected + 1).min(config.entries.len() - 1),
+            Key::Printable(c) if c == '\r' || c == '\n' => break,
+            _ => {}
+        }
+        draw(console, config, selected, remaining_us);
+    }
+
+    console.clear();
+    config.entries.get(selected)
+}
+
+/// Draw the menu, highlighting `selected`.
+fn draw(
+    console: &mut Console,
+    config: &BootConfig<'_>,
+    selected: usize,
+    remaining_us: Option<usize>,
+) {
+    console.clear();
+    writeln!(console, "").unwrap();
+    writeln!(console, "  Ferrous Kernel - select a boot entry").unwrap();
+    writeln!(console, "").unwrap();
+    for (i, entry) in config.entries.iter().enumerate() {
+        let highlight = i == selected;
+        if highlight {
+            set_color(Color::Black, Color::LightGray);
+        }
+        write!(
+            console,
+            "  {} {}",
+            if highlight { '>' } else { ' ' },
+            entry.title
+        )
+        .unwrap();
+        if highlight {
+            set_color(Color::LightGray, Color::Black);
+        }
+        writeln!(console, "").unwrap();
+    }
+    writeln!(console, "").unwrap();
+    match remaining_us {
+        Some(us) => writeln!(
+            console,
+            "  Up/Down to choose, Enter to boot. Booting the default in {} s.",
+            us.div_ceil(1_000_000)
+        )
+        .unwrap(),
+        None => writeln!(console, "  Up/Down to choose, Enter to boot.").unwrap(),
+    }
+}
+
+fn set_color(foreground: Color, background: Color) {
+    uefi::system::with_stdout(|stdout| {
+        let _ = stdout.set_color(foreground, background);
+    });
+}
@@ -0,0 +1,172 @@
+//! Boot modules.
+//!
+//! Named files handed to the kernel alongside the initrd — an init binary,
+//! a driver bundle, a configuration blob. The selected `boot.cfg` entry
+//! lists them with `module <name> <path>` lines; without a `boot.cfg`,
+//! [`MODULES_PATH`] does, one module per line:
+//!
+//! ```text
+//! # name    path
+//! init      init.elf
+//! drivers   \EFI\ferrous\drivers.tar
+//! ```
+//!
+//! Paths are resolved as in `boot.cfg` ([`resolve_path`]). Lines starting
+//! with `#` are comments.
+//!
+//! Each file is copied into its own `LOADER_DATA` pages and reported as a
+//! `MODULE` boot info tag. A module that cannot be loaded is skipped with a
+//! warning; it never stops the boot. At most
+//! [`MAX_MODULES`](ferrous_boot_info::MAX_MODULES) are loaded.
+//!
+//! [`resolve_path`]: crate::config::resolve_path
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use ferrous_boot_info::{Module, MAX_MODULES, MAX_MODULE_NAME_LEN};
+use uefi::fs::{FileSystem, PathBuf};
+use uefi::{cstr16, CStr16};
+
+use crate::config::{parse_module, ModuleSpec};
+use crate::console::Console;
+
+/// Path of the module list used when there is no `boot.cfg`.
+pub const MODULES_PATH: &CStr16 = cstr16!("\\EFI\\ferrous\\modules.txt");
+
+/// A module copied into `LOADER_DATA` memory.
+#[derive(Debug, Clone)]
+pub struct LoadedModule {
+    pub name: String,
+    /// Page-aligned physical address of the first byte.
+    pub phys_addr: u64,
+    /// Size in bytes.
+    pub size: u64,
+}
+
+impl LoadedModule {
+    /// The boot info view of this module.
+    pub fn as_module(&self) -> Module<'_> {
+        Module {
+            name: &self.name,
+            phys_addr: self.phys_addr,
+            size: self.size,
+        }
+    }
+}
+
+/// Read [`MODULES_PATH`]; empty if it does not exist.
+pub fn read_module_list(console: &mut Console) -> String {
+    let Ok(sfs) = uefi::boot::get_image_file_system(uefi::boot::image_handle()) else {
+        return String::new();
+    };
+    match FileSystem::new(sfs).read_to_string(MODULES_PATH) {
+        Ok(list) => list,
+        Err(e) if crate::loader::is_not_found(&e) => String::new(),
+        Err(e) => {
+            writeln!(console, "[WARN] Cannot read {}: {:?}", MODULES_PATH, e).unwrap();
+            String::new()
+        }
+    }
+}
+
+/// Parse the contents of [`MODULES_PATH`], reporting bad lines on
+/// `console`.
+pub fn parse_module_list<'a>(console: &mut Console, list: &'a str) -> Vec<ModuleSpec<'a>> {
+    let mut specs = Vec::new();
+    for (line_no, line) in list.lines().enumerate().map(|(i, l)| (i + 1, l.trim())) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match parse_module(line) {
+            Some(spec) => specs.push(spec),
+            None => writeln!(
+                console,
+                "[WARN] {} line {}: expected `<name> <path>`",
+                MODULES_PATH, line_no
+            )
+            .unwrap(),
+        }
+    }
+    specs
+}
+
+/// Load the modules in `specs`, reporting each one on `console`.
+pub fn load_modules(console: &mut Console, specs: &[ModuleSpec<'_>]) -> Vec<LoadedModule> {
+    let mut modules = Vec::new();
+    if specs.is_empty() {
+        return modules;
+    }
+    let mut fs = match uefi::boot::get_image_file_system(uefi::boot::image_handle()) {
+        Ok(sfs) => FileSystem::new(sfs),
+        Err(e) => {
+            writeln!(console, "[WARN] Modules: cannot open boot volume: {:?}", e).unwrap();
+            return modules;
+        }
+    };
+
+    for spec in specs {
+        let name = spec.name;
+        if name.len() > MAX_MODULE_NAME_LEN {
+            writeln!(
+                console,
+                "[WARN] Module name `{}` longer than {} bytes; skipped",
+                name, MAX_MODULE_NAME_LEN
+            )
+            .unwrap();
+            continue;
+        }
+        if modules.len() == MAX_MODULES {
+            writeln!(
+                console,
+                "[WARN] More than {} modules; `{}` and later skipped",
+                MAX_MODULES, name
+            )
+            .unwrap();
+            break;
+        }
+
+        let Some(path) = crate::loader::uefi_path(spec.path) else {
+            writeln!(console, "[WARN] Module `{}`: bad path {}", name, spec.path).unwrap();
+            continue;
+        };
+        let file = match fs.read(PathBuf::from(path.clone())) {
+            Ok(file) => file,
+            Err(e) => {
+                writeln!(
+                    console,
+                    "[WARN] Module `{}`: cannot read {}: {:?}",
+                    name, path, e
+                )
+                .unwrap();
+                continue;
+            }
+        };
+        match crate::memory::copy_to_loader_data(&file) {
+            Ok(copy) => {
+                let module = LoadedModule {
+                    name: String::from(name),
+                    phys_addr: copy.as_ptr() as u64,
+                    size: copy.len() as u64,
+                };
+                writeln!(
+                    console,
+                    "[OK] Module `{}`: {} at {:#x} ({} bytes)",
+                    module.name, path, module.phys_addr, module.size
+                )
+                .unwrap();
+                modules.push(module);
+            }
+            Err(e) => writeln!(
+                console,
+                "[WARN] Module `{}`: cannot allocate {} pages: {:?}",
+                name,
+                crate::memory::pages_for(file.len()),
+                e
+            )
+            .unwrap(),
+        }
+    }
+    modules
+}
@@ -0,0 +1,162 @@
+//! Initial kernel page tables.
+//!
+//! The kernel runs on page tables built here rather than on the firmware's
+//! identity mapping. They follow [`ferrous_boot_info::layout`]:
+//!
+//! - the direct map: every physical address below [`KernelPageTables::phys_limit`]
+//!   at `DIRECT_MAP_BASE + phys`, with 2 MiB pages;
+//! - the same memory identity-mapped, so that the bootloader keeps running
+//!   between the CR3 switch and the jump. The low PML4 entries point at the
+//!   direct map's PDPTs; the kernel drops them once it no longer needs them;
+//! - the kernel image at its virtual load address, with 4 KiB pages.
+//!
+//! Everything is mapped writable and executable; permissions are not
+//! enforced yet. Table frames are `LOADER_DATA` pages, allocated before the
+//! final memory map snapshot so that they appear in it; the kernel keeps
+//! the ones still in use when it reclaims loader memory.
+
+use core::fmt;
+
+use ferrous_boot_info::layout::{DIRECT_MAP_BASE, DIRECT_MAP_MAX_SIZE};
+use ferrous_paging::{
+    FrameSource, MapError, OffsetTableAccess, PageTable, PageTableFlags, PageTableMapper, PhysAddr,
+    VirtAddr, HUGE_PAGE_SIZE, PAGE_SIZE,
+};
+use uefi::boot::{AllocateType, MemoryType};
+use uefi::mem::memory_map::MemoryMap;
+
+use crate::loader::LoadedKernel;
+
+/// The direct map always covers at least the low 4 GiB, where firmware
+/// puts MMIO windows that the memory map may not list.
+const MIN_PHYS_LIMIT: u64 = 0x1_0000_0000;
+
+/// Bytes covered by one PML4 entry (512 GiB).
+const PML4_SLOT_SIZE: u64 = 1 << 39;
+
+/// Page tables ready to be loaded into CR3.
+#[derive(Debug, Clone, Copy)]
+pub struct KernelPageTables {
+    /// Physical address of the PML4.
+    pub root: u64,
+    /// End of the physical range covered by the direct and identity maps.
+    pub phys_limit: u64,
+    /// Table frames allocated.
+    pub tables: usize,
+}
+
+/// Errors that can occur while building the page tables.
+#[derive(Debug)]
+pub enum PagingError {
+    /// The memory map could not be read.
+    MemoryMap(uefi::Error),
+    /// Firmware ran out of pages for tables.
+    OutOfMemory,
+    /// A mapping could not be added.
+    Map {
+        /// Virtual address being mapped.
+        virt: u64,
+        /// Underlying mapper error.
+        error: MapError,
+    },
+}
+
+impl fmt::Display for PagingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PagingError::MemoryMap(e) => write!(f, "cannot read memory map: {:?}", e),
+            PagingError::OutOfMemory => write!(f, "out of memory for page tables"),
+            PagingError::Map { virt, error } => write!(f, "cannot map {:#x}: {:?}", virt, error),
+        }
+    }
+}
+
+/// [`FrameSource`] handing out single `LOADER_DATA` pages from firmware.
+struct BootFrameSource {
+    allocated: usize,
+}
+
+impl FrameSource for BootFrameSource {
+    fn allocate_frame(&mut self) -> Option<PhysAddr> {
+        let page =
+            uefi::boot::allocate_pages(AllocateType::AnyPages, MemoryType::LOADER_DATA, 1).ok()?;
+        self.allocated += 1;
+        Some(PhysAddr::new_truncate(page.as_ptr() as u64))
+    }
+}
+
+/// Build the kernel's initial page tables for `kernel`.
+///
+/// # Errors
+///
+/// See [`PagingError`]. Table pages allocated before a failure are leaked.
+pub fn build(kernel: &LoadedKernel) -> Result<KernelPageTables, PagingError> {
+    let phys_limit = phys_limit()?;
+    let mut frames = BootFrameSource { allocated: 0 };
+    let root = frames.allocate_frame().ok_or(PagingError::OutOfMemory)?;
+    // SAFETY: `root` is a fresh page owned by us; UEFI identity-maps it.
+    unsafe { (*(root.as_u64() as *mut PageTable)).zero() };
+
+    // SAFETY: `root` and every frame from `frames` are fresh LOADER_DATA
+    // pages reachable through UEFI's identity mapping, and nothing else
+    // knows about this hierarchy.
+    let mut mapper = unsafe { PageTableMapper::new(root, OffsetTableAccess::identity()) };
+    let map_err = |virt: u64| move |error| PagingError::Map { virt, error };
+    let flags = PageTableFlags::WRITABLE;
+
+    for phys in (0..phys_limit).step_by(HUGE_PAGE_SIZE as usize) {
+        let virt = DIRECT_MAP_BASE + phys;
+        mapper
+            .map_2mib(
+                VirtAddr::new_truncate(virt),
+                PhysAddr::new_truncate(phys),
+                flags,
+                &mut frames,
+            )
+            .map_err(map_err(virt))?;
+    }
+
+    // Identity map: share the direct map's PDPTs.
+    // SAFETY: `root` is the zeroed-then-filled PML4 above; the mapper is
+    // not used while this reference is live.
+    let pml4 = unsafe { &mut *(root.as_u64() as *mut PageTable) };
+    let slots = phys_limit.div_ceil(PML4_SLOT_SIZE) as usize;
+    let first = VirtAddr::new_truncate(DIRECT_MAP_BASE).p4_index();
+    for slot in 0..slots {
+        pml4[slot] = pml4[first + slot];
+    }
+
+    for offset in (0..kernel.size).step_by(PAGE_SIZE as usize) {
+        let virt = kernel.virt_base + offset;
+        mapper
+            .map(
+                VirtAddr::new_truncate(virt),
+                PhysAddr::new_truncate(kernel.phys_base + offset),
+                flags,
+                &mut frames,
+            )
+            .map_err(map_err(virt))?;
+    }
+
+    Ok(KernelPageTables {
+        root: root.as_u64(),
+        phys_limit,
+        tables: frames.allocated,
+    })
+}
+
+/// End of the physical range to map: the highest address in the memory
+/// map, at least [`MIN_PHYS_LIMIT`], rounded up to 2 MiB and capped at the
+/// size of the direct map.
+fn phys_limit() -> Result<u64, PagingError> {
+    let map = uefi::boot::memory_map(MemoryType::LOADER_DATA).map_err(PagingError::MemoryMap)?;
+    let highest = map
+        .entries()
+        .map(|desc| desc.phys_start + desc.page_count * PAGE_SIZE)
+        .max()
+        .unwrap_or(0);
+    Ok(highest
+        .max(MIN_PHYS_LIMIT)
+        .next_multiple_of(HUGE_PAGE_SIZE)
+        .min(DIRECT_MAP_MAX_SIZE))
+}
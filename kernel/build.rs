@@ -0,0 +1,19 @@
+//! Build script for the kernel binary.
+//!
+//! Passes `linker.ld` to the linker when building for the bare-metal
+//! target. Host builds (`cargo check` on the workspace) are left untouched
+//! so they keep using the default host link layout.
+
+use std::env;
+
+fn main() {
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let target = env::var("TARGET").unwrap();
+
+    if target == "x86_64-unknown-none" {
+        println!("cargo:rustc-link-arg-bins=-T{manifest_dir}/linker.ld");
+    }
+
+    println!("cargo:rerun-if-changed=linker.ld");
+    println!("cargo:rerun-if-changed=build.rs");
+}
@@ -0,0 +1,68 @@
+//! Boot CPU identification.
+//!
+//! [`init`] executes CPUID once, early in `kernel_main`, and keeps the
+//! decoded [`CpuFeatures`] for the rest of the kernel's lifetime. Code that
+//! depends on optional hardware asks [`has`] instead of executing CPUID
+//! itself. The decoding lives in [`cpuid`](super::cpuid).
+
+use core::arch::x86_64::__cpuid_count;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use super::cpuid::CpuidLeaf;
+pub use super::cpuid::{CacheInfo, CacheKind, CpuFeatures, Feature, Signature, Topology, Vendor};
+
+/// Set once [`FEATURES`] holds the boot CPU's leaves.
+static INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+/// The boot CPU's decoded CPUID leaves.
+///
+/// # SAFETY invariant
+///
+/// Written only by [`init`] while `INITIALIZED` is false; never written
+/// again once it is true.
+static mut FEATURES: CpuFeatures = CpuFeatures::empty();
+
+/// Read and cache the boot CPU's CPUID leaves.
+///
+/// # Safety
+///
+/// Must be called exactly once, before [`get`] or [`has`], from a
+/// single-threaded context with interrupts disabled.
+pub unsafe fn init() -> &'static CpuFeatures {
+    debug_assert!(
+        !INITIALIZED.load(Ordering::Relaxed),
+        "cpu::init() called more than once"
+    );
+    let features = CpuFeatures::read(|leaf, subleaf| {
+        let r = __cpuid_count(leaf, subleaf);
+        CpuidLeaf {
+            eax: r.eax,
+            ebx: r.ebx,
+            ecx: r.ecx,
+            edx: r.edx,
+        }
+    });
+
+    // SAFETY: single-threaded, INITIALIZED is still false so no reader
+    // holds a reference.
+    unsafe { core::ptr::addr_of_mut!(FEATURES).write(features) };
+    INITIALIZED.store(true, Ordering::Release);
+    // SAFETY: FEATURES is never written again.
+    unsafe { &*core::ptr::addr_of!(FEATURES) }
+}
+
+/// The boot CPU's features; `None` before [`init`].
+pub fn get() -> Option<&'static CpuFeatures> {
+    if INITIALIZED.load(Ordering::Acquire) {
+        // SAFETY: FEATURES is fully written when INITIALIZED is true and
+        // never written again.
+        Some(unsafe { &*core::ptr::addr_of!(FEATURES) })
+    } else {
+        None
+    }
+}
+
+/// True if the boot CPU supports `feature`; false before [`init`].
+pub fn has(feature: Feature) -> bool {
+    get().is_some_and(|f| f.has(feature))
+}
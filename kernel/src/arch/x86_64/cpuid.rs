@@ -0,0 +1,566 @@
+//! CPUID leaf decoding.
+//!
+//! [`CpuFeatures::read`] walks the standard and extended leaves through a
+//! caller-supplied `cpuid(leaf, subleaf)` function and decodes them into a
+//! plain struct: vendor and brand strings, family/model/stepping, feature
+//! bits, address widths, the cache hierarchy and the package topology.
+//!
+//! | Leaf          | Decoded                                              |
+//! |---------------|------------------------------------------------------|
+//! | 0             | highest standard leaf, vendor string                 |
+//! | 1             | signature, feature bits, initial APIC ID             |
+//! | 4             | caches (Intel)                                       |
+//! | 7.0           | structured extended feature bits                     |
+//! | 0Bh           | SMT and core topology, x2APIC ID                     |
+//! | 8000_0000h    | highest extended leaf                                |
+//! | 8000_0001h    | extended feature bits                                |
+//! | 8000_0002–4h  | brand string                                         |
+//! | 8000_0007h    | invariant TSC                                        |
+//! | 8000_0008h    | physical and linear address widths                   |
+//! | 8000_001Dh    | caches (AMD, with TOPOEXT)                           |
+//!
+//! Leaves above the reported maximum are never queried. Everything here is
+//! a pure function of the register values and depends only on `core`, so
+//! `tests/boot_tests.rs` compiles this file directly and feeds it canned
+//! leaves; `arch::x86_64::cpu` runs it against the real instruction.
+
+use core::fmt;
+
+/// Register values returned by one CPUID query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CpuidLeaf {
+    pub eax: u32,
+    pub ebx: u32,
+    pub ecx: u32,
+    pub edx: u32,
+}
+
+// ---------------------------------------------------------------------------
+// Feature bits
+// ---------------------------------------------------------------------------
+
+/// Indices into [`CpuFeatures`]'s feature words.
+const LEAF1_EDX: u8 = 0;
+const LEAF1_ECX: u8 = 1;
+const LEAF7_EBX: u8 = 2;
+const LEAF7_ECX: u8 = 3;
+const EXT1_EDX: u8 = 4;
+const EXT1_ECX: u8 = 5;
+const EXT7_EDX: u8 = 6;
+const FEATURE_WORDS: usize = 7;
+
+/// A single CPUID feature bit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Feature {
+    word: u8,
+    bit: u8,
+    name: &'static str,
+}
+
+impl Feature {
+    const fn new(word: u8, bit: u8, name: &'static str) -> Self {
+        Self { word, bit, name }
+    }
+
+    /// Short lower-case name, as in Linux's `/proc/cpuinfo`.
+    pub const fn name(self) -> &'static str {
+        self.name
+    }
+
+    // Leaf 1 EDX.
+    pub const FPU: Feature = Feature::new(LEAF1_EDX, 0, "fpu");
+    pub const PSE: Feature = Feature::new(LEAF1_EDX, 3, "pse");
+    pub const TSC: Feature = Feature::new(LEAF1_EDX, 4, "tsc");
+    pub const MSR: Feature = Feature::new(LEAF1_EDX, 5, "msr");
+    pub const PAE: Feature = Feature::new(LEAF1_EDX, 6, "pae");
+    pub const APIC: Feature = Feature::new(LEAF1_EDX, 9, "apic");
+    pub const PGE: Feature = Feature::new(LEAF1_EDX, 13, "pge");
+    pub const PAT: Feature = Feature::new(LEAF1_EDX, 16, "pat");
+    pub const FXSR: Feature = Feature::new(LEAF1_EDX, 24, "fxsr");
+    pub const SSE: Feature = Feature::new(LEAF1_EDX, 25, "sse");
+    pub const SSE2: Feature = Feature::new(LEAF1_EDX, 26, "sse2");
+    pub const HTT: Feature = Feature::new(LEAF1_EDX, 28, "ht");
+
+    // Leaf 1 ECX.
+    pub const SSE3: Feature = Feature::new(LEAF1_ECX, 0, "sse3");
+    pub const SSSE3: Feature = Feature::new(LEAF1_ECX, 9, "ssse3");
+    pub const FMA: Feature = Feature::new(LEAF1_ECX, 12, "fma");
+    pub const CX16: Feature = Feature::new(LEAF1_ECX, 13, "cx16");
+    pub const PCID: Feature = Feature::new(LEAF1_ECX, 17, "pcid");
+    pub const SSE4_1: Feature = Feature::new(LEAF1_ECX, 19, "sse4_1");
+    pub const SSE4_2: Feature = Feature::new(LEAF1_ECX, 20, "sse4_2");
+    pub const X2APIC: Feature = Feature::new(LEAF1_ECX, 21, "x2apic");
+    pub const POPCNT: Feature = Feature::new(LEAF1_ECX, 23, "popcnt");
+    pub const TSC_DEADLINE: Feature = Feature::new(LEAF1_ECX, 24, "tsc_deadline_timer");
+    pub const XSAVE: Feature = Feature::new(LEAF1_ECX, 26, "xsave");
+    pub const OSXSAVE: Feature = Feature::new(LEAF1_ECX, 27, "osxsave");
+    pub const AVX: Feature = Feature::new(LEAF1_ECX, 28, "avx");
+    pub const F16C: Feature = Feature::new(LEAF1_ECX, 29, "f16c");
+    pub const RDRAND: Feature = Feature::new(LEAF1_ECX, 30, "rdrand");
+    pub const HYPERVISOR: Feature = Feature::new(LEAF1_ECX, 31, "hypervisor");
+
+    // Leaf 7 subleaf 0 EBX.
+    pub const FSGSBASE: Feature = Feature::new(LEAF7_EBX, 0, "fsgsbase");
+    pub const BMI1: Feature = Feature::new(LEAF7_EBX, 3, "bmi1");
+    pub const AVX2: Feature = Feature::new(LEAF7_EBX, 5, "avx2");
+    pub const SMEP: Feature = Feature::new(LEAF7_EBX, 7, "smep");
+    pub const BMI2: Feature = Feature::new(LEAF7_EBX, 8, "bmi2");
+    pub const ERMS: Feature = Feature::new(LEAF7_EBX, 9, "erms");
+    pub const INVPCID: Feature = Feature::new(LEAF7_EBX, 10, "invpcid");
+    pub const AVX512F: Feature = Feature::new(LEAF7_EBX, 16, "avx512f");
+    pub const RDSEED: Feature = Feature::new(LEAF7_EBX, 18, "rdseed");
+    pub const SMAP: Feature = Feature::new(LEAF7_EBX, 20, "smap");
+
+    // Leaf 7 subleaf 0 ECX.
+    pub const UMIP: Feature = Feature::new(LEAF7_ECX, 2, "umip");
+    pub const PKU: Feature = Feature::new(LEAF7_ECX, 3, "pku");
+    pub const LA57: Feature = Feature::new(LEAF7_ECX, 16, "la57");
+
+    // Leaf 8000_0001h EDX.
+    pub const SYSCALL: Feature = Feature::new(EXT1_EDX, 11, "syscall");
+    pub const NX: Feature = Feature::new(EXT1_EDX, 20, "nx");
+    pub const PAGE_1GB: Feature = Feature::new(EXT1_EDX, 26, "pdpe1gb");
+    pub const RDTSCP: Feature = Feature::new(EXT1_EDX, 27, "rdtscp");
+    pub const LONG_MODE: Feature = Feature::new(EXT1_EDX, 29, "lm");
+
+    // Leaf 8000_0001h ECX.
+    pub const TOPOEXT: Feature = Feature::new(EXT1_ECX, 22, "topoext");
+
+    // Leaf 8000_0007h EDX.
+    pub const INVARIANT_TSC: Feature = Feature::new(EXT7_EDX, 8, "invtsc");
+
+    /// Every feature above, in leaf and bit order.
+    pub const ALL: &'static [Feature] = &[
+        Feature::FPU,
+        Feature::PSE,
+        Feature::TSC,
+        Feature::MSR,
+        Feature::PAE,
+        Feature::APIC,
+        Feature::PGE,
+        Feature::PAT,
+        Feature::FXSR,
+        Feature::SSE,
+        Feature::SSE2,
+        Feature::HTT,
+        Feature::SSE3,
+        Feature::SSSE3,
+        Feature::FMA,
+        Feature::CX16,
+        Feature::PCID,
+        Feature::SSE4_1,
+        Feature::SSE4_2,
+        Feature::X2APIC,
+        Feature::POPCNT,
+        Feature::TSC_DEADLINE,
+        Feature::XSAVE,
+        Feature::OSXSAVE,
+        Feature::AVX,
+        Feature::F16C,
+        Feature::RDRAND,
+        Feature::HYPERVISOR,
+        Feature::FSGSBASE,
+        Feature::BMI1,
+        Feature::AVX2,
+        Feature::SMEP,
+        Feature::BMI2,
+        Feature::ERMS,
+        Feature::INVPCID,
+        Feature::AVX512F,
+        Feature::RDSEED,
+        Feature::SMAP,
+        Feature::UMIP,
+        Feature::PKU,
+        Feature::LA57,
+        Feature::SYSCALL,
+        Feature::NX,
+        Feature::PAGE_1GB,
+        Feature::RDTSCP,
+        Feature::LONG_MODE,
+        Feature::TOPOEXT,
+        Feature::INVARIANT_TSC,
+    ];
+}
+
+// ---------------------------------------------------------------------------
+// Identification, caches and topology
+// ---------------------------------------------------------------------------
+
+/// CPU vendor, from the leaf 0 vendor string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Vendor {
+    /// `GenuineIntel`.
+    Intel,
+    /// `AuthenticAMD`.
+    Amd,
+    /// Anything else (other vendors, some hypervisors' overrides).
+    Other,
+}
+
+/// Display family, model and stepping from leaf 1 EAX.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Signature {
+    /// Family, including the extended family for base family 0Fh.
+    pub family: u32,
+    /// Model, including the extended model for families 06h and 0Fh.
+    pub model: u32,
+    /// Stepping ID.
+    pub stepping: u32,
+}
+
+impl Signature {
+    /// Decode leaf 1 EAX (SDM Vol 2A, CPUID, "Version Information").
+    pub const fn decode(eax: u32) -> Self {
+        let base_family = (eax >> 8) & 0xF;
+        let base_model = (eax >> 4) & 0xF;
+        let family = if base_family == 0xF {
+            base_family + ((eax >> 20) & 0xFF)
+        } else {
+            base_family
+        };
+        let model = if base_family == 0x6 || base_family == 0xF {
+            base_model | ((eax >> 12) & 0xF0)
+        } else {
+            base_model
+        };
+        Self {
+            family,
+            model,
+            stepping: eax & 0xF,
+        }
+    }
+}
+
+/// What a cache holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheKind {
+    Data,
+    Instruction,
+    Unified,
+}
+
+/// One cache level, from leaf 4 or leaf 8000_001Dh.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheInfo {
+    /// Cache level, starting at 1.
+    pub level: u8,
+    pub kind: CacheKind,
+    /// Total size in bytes.
+    pub size: u64,
+    /// Line size in bytes.
+    pub line_size: u32,
+    /// Associativity.
+    pub ways: u32,
+    /// Number of sets.
+    pub sets: u32,
+    /// Logical processors sharing this cache (an upper bound on some CPUs).
+    pub shared_by: u32,
+}
+
+impl CacheInfo {
+    /// Decode one deterministic cache parameters subleaf; `None` once the
+    /// cache type field is 0 (no more caches).
+    pub const fn decode(leaf: CpuidLeaf) -> Option<Self> {
+        let kind = match leaf.eax & 0x1F {
+            1 => CacheKind::Data,
+            2 => CacheKind::Instruction,
+            3 => CacheKind::Unified,
+            _ => return None,
+        };
+        let line_size = (leaf.ebx & 0xFFF) + 1;
+        let partitions = ((leaf.ebx >> 12) & 0x3FF) + 1;
+        let ways = (leaf.ebx >> 22) + 1;
+        let sets = leaf.ecx.wrapping_add(1);
+        Some(Self {
+            level: ((leaf.eax >> 5) & 0x7) as u8,
+            kind,
+            size: ways as u64 * partitions as u64 * line_size as u64 * sets as u64,
+            line_size,
+            ways,
+            sets,
+            shared_by: ((leaf.eax >> 14) & 0xFFF) + 1,
+        })
+    }
+}
+
+impl fmt::Display for CacheInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            CacheKind::Data => "d",
+            CacheKind::Instruction => "i",
+            CacheKind::Unified => "",
+        };
+        write!(
+            f,
+            "L{}{} {} KiB, {}-way, {}-byte lines, shared by {}",
+            self.level,
+            kind,
+            self.size / 1024,
+            self.ways,
+            self.line_size,
+            self.shared_by
+        )
+    }
+}
+
+/// Where the boot CPU sits in its package.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Topology {
+    /// x2APIC ID from leaf 0Bh, or the 8-bit initial APIC ID from leaf 1.
+    pub apic_id: u32,
+    /// Logical processors per package; 1 when not reported.
+    pub logical_per_package: u32,
+    /// Logical processors per core; 1 when not reported.
+    pub threads_per_core: u32,
+}
+
+/// Most caches [`CpuFeatures`] records; real CPUs report four or five.
+pub const MAX_CACHES: usize = 8;
+
+/// Most subleaves of leaf 0Bh examined.
+const MAX_TOPOLOGY_LEVELS: u32 = 8;
+
+/// Leaf 0Bh level types (ECX[15:8]).
+const LEVEL_SMT: u32 = 1;
+const LEVEL_CORE: u32 = 2;
+
+// ---------------------------------------------------------------------------
+// CpuFeatures
+// ---------------------------------------------------------------------------
+
+/// Everything decoded from CPUID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuFeatures {
+    /// Highest standard leaf.
+    pub max_leaf: u32,
+    /// Highest extended leaf, or 0 if extended leaves are not supported.
+    pub max_ext_leaf: u32,
+    pub signature: Signature,
+    /// Physical address width in bits (MAXPHYADDR).
+    pub phys_addr_bits: u8,
+    /// Linear address width in bits.
+    pub virt_addr_bits: u8,
+    pub topology: Topology,
+    vendor_id: [u8; 12],
+    brand: [u8; 48],
+    words: [u32; FEATURE_WORDS],
+    caches: [Option<CacheInfo>; MAX_CACHES],
+}
+
+impl CpuFeatures {
+    /// No leaves, no features.
+    pub const fn empty() -> Self {
+        Self {
+            max_leaf: 0,
+            max_ext_leaf: 0,
+            signature: Signature {
+                family: 0,
+                model: 0,
+                stepping: 0,
+            },
+            phys_addr_bits: 0,
+            virt_addr_bits: 0,
+            topology: Topology {
+                apic_id: 0,
+                logical_per_package: 1,
+                threads_per_core: 1,
+            },
+            vendor_id: [0; 12],
+            brand: [0; 48],
+            words: [0; FEATURE_WORDS],
+            caches: [None; MAX_CACHES],
+        }
+    }
+
+    /// Query every leaf this module decodes through `cpuid(leaf, subleaf)`.
+    pub fn read(mut cpuid: impl FnMut(u32, u32) -> CpuidLeaf) -> Self {
+        let mut f = Self::empty();
+
+        let leaf0 = cpuid(0, 0);
+        f.max_leaf = leaf0.eax;
+        for (chunk, reg) in f
+            .vendor_id
+            .chunks_mut(4)
+            .zip([leaf0.ebx, leaf0.edx, leaf0.ecx])
+        {
+            chunk.copy_from_slice(&reg.to_le_bytes());
+        }
+
+        if f.max_leaf >= 1 {
+            let leaf1 = cpuid(1, 0);
+            f.signature = Signature::decode(leaf1.eax);
+            f.words[LEAF1_EDX as usize] = leaf1.edx;
+            f.words[LEAF1_ECX as usize] = leaf1.ecx;
+            f.topology.apic_id = leaf1.ebx >> 24;
+            if f.has(Feature::HTT) {
+                f.topology.logical_per_package = ((leaf1.ebx >> 16) & 0xFF).max(1);
+            }
+        }
+        if f.max_leaf >= 7 {
+            let leaf7 = cpuid(7, 0);
+            f.words[LEAF7_EBX as usize] = leaf7.ebx;
+            f.words[LEAF7_ECX as usize] = leaf7.ecx;
+        }
+        if f.max_leaf >= 0xB {
+            f.read_topology(&mut cpuid);
+        }
+
+        let ext0 = cpuid(0x8000_0000, 0);
+        // Without extended leaves EAX holds garbage (often the highest
+        // standard leaf's EAX), never an 8000_xxxxh value.
+        f.max_ext_leaf = if ext0.eax & 0xFFFF_0000 == 0x8000_0000 {
+            ext0.eax
+        } else {
+            0
+        };
+        if f.max_ext_leaf >= 0x8000_0001 {
+            let ext1 = cpuid(0x8000_0001, 0);
+            f.words[EXT1_EDX as usize] = ext1.edx;
+            f.words[EXT1_ECX as usize] = ext1.ecx;
+        }
+        if f.max_ext_leaf >= 0x8000_0004 {
+            for (i, leaf) in (0x8000_0002..=0x8000_0004).enumerate() {
+                let r = cpuid(leaf, 0);
+                for (j, reg) in [r.eax, r.ebx, r.ecx, r.edx].into_iter().enumerate() {
+                    let at = i * 16 + j * 4;
+                    f.brand[at..at + 4].copy_from_slice(&reg.to_le_bytes());
+                }
+            }
+        }
+        if f.max_ext_leaf >= 0x8000_0007 {
+            f.words[EXT7_EDX as usize] = cpuid(0x8000_0007, 0).edx;
+        }
+        if f.max_ext_leaf >= 0x8000_0008 {
+            let ext8 = cpuid(0x8000_0008, 0);
+            f.phys_addr_bits = ext8.eax as u8;
+            f.virt_addr_bits = (ext8.eax >> 8) as u8;
+        } else {
+            // SDM Vol 3A §4.1.4: MAXPHYADDR is 36 with PAE, 32 without.
+            f.phys_addr_bits = if f.has(Feature::PAE) { 36 } else { 32 };
+            f.virt_addr_bits = if f.has(Feature::LONG_MODE) { 48 } else { 32 };
+        }
+
+        let cache_leaf = if f.max_leaf >= 4 && f.vendor() != Vendor::Amd {
+            Some(4)
+        } else if f.has(Feature::TOPOEXT) && f.max_ext_leaf >= 0x8000_001D {
+            Some(0x8000_001D)
+        } else {
+            None
+        };
+        if let Some(leaf) = cache_leaf {
+            for (subleaf, slot) in (0..).zip(f.caches.iter_mut()) {
+                match CacheInfo::decode(cpuid(leaf, subleaf)) {
+                    Some(cache) => *slot = Some(cache),
+                    None => break,
+                }
+            }
+        }
+        f
+    }
+
+    /// Decode the SMT and core levels of leaf 0Bh.
+    fn read_topology(&mut self, cpuid: &mut impl FnMut(u32, u32) -> CpuidLeaf) {
+        for subleaf in 0..MAX_TOPOLOGY_LEVELS {
+            let r = cpuid(0xB, subleaf);
+            let count = r.ebx & 0xFFFF;
+            if count == 0 {
+                break;
+            }
+            self.topology.apic_id = r.edx;
+            match (r.ecx >> 8) & 0xFF {
+                LEVEL_SMT => self.topology.threads_per_core = count,
+                LEVEL_CORE => self.topology.logical_per_package = count,
+                _ => {}
+            }
+        }
+    }
+
+    /// True if the CPU reports `feature`.
+    pub const fn has(&self, feature: Feature) -> bool {
+        self.words[feature.word as usize] & (1 << feature.bit) != 0
+    }
+
+    /// Vendor, from [`vendor_id`](Self::vendor_id).
+    pub fn vendor(&self) -> Vendor {
+        match &self.vendor_id {
+            b"GenuineIntel" => Vendor::Intel,
+            b"AuthenticAMD" => Vendor::Amd,
+            _ => Vendor::Other,
+        }
+    }
+
+    /// The 12-character vendor string, e.g. `GenuineIntel`.
+    pub fn vendor_id(&self) -> &str {
+        ascii_str(&self.vendor_id)
+    }
+
+    /// The processor brand string without padding; empty if the CPU has
+    /// none.
+    pub fn brand(&self) -> &str {
+        ascii_str(&self.brand)
+    }
+
+    /// Caches in the order CPUID reports them (usually L1d, L1i, L2, L3).
+    pub fn caches(&self) -> impl Iterator<Item = &CacheInfo> {
+        self.caches.iter().map_while(Option::as_ref)
+    }
+
+    /// Every supported [`Feature`], in [`Feature::ALL`] order.
+    pub fn features(&self) -> impl Iterator<Item = Feature> + '_ {
+        Feature::ALL.iter().copied().filter(|&f| self.has(f))
+    }
+
+    /// Space-separated names of the supported features.
+    pub fn feature_names(&self) -> FeatureNames<'_> {
+        FeatureNames(self)
+    }
+}
+
+/// One-line summary: vendor, brand, signature, address widths, topology.
+impl fmt::Display for CpuFeatures {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.vendor_id())?;
+        if !self.brand().is_empty() {
+            write!(f, " \"{}\"", self.brand())?;
+        }
+        write!(
+            f,
+            ", family {:#x} model {:#x} stepping {}, {}-bit physical / {}-bit virtual, \
+             APIC ID {}, {} logical per package, {} per core",
+            self.signature.family,
+            self.signature.model,
+            self.signature.stepping,
+            self.phys_addr_bits,
+            self.virt_addr_bits,
+            self.topology.apic_id,
+            self.topology.logical_per_package,
+            self.topology.threads_per_core
+        )
+    }
+}
+
+/// [`Display`](fmt::Display) adapter returned by
+/// [`CpuFeatures::feature_names`].
+pub struct FeatureNames<'a>(&'a CpuFeatures);
+
+impl fmt::Display for FeatureNames<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, feature) in self.0.features().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            f.write_str(feature.name())?;
+        }
+        Ok(())
+    }
+}
+
+/// `bytes` up to the first NUL, without surrounding spaces; empty if not
+/// ASCII.
+fn ascii_str(bytes: &[u8]) -> &str {
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    match core::str::from_utf8(&bytes[..len]) {
+        Ok(s) if s.is_ascii() => s.trim(),
+        _ => "",
+    }
+}
@@ -1,29 +1,26 @@
 //! x86-64 kernel entry point.
 //!
-//! This module will contain the `kernel_entry` function once the bootloader
-//! is extended to load the kernel as a separate ELF binary (Phase 2+).
+//! `ferrous-boot` loads the kernel ELF from the ESP, exits boot services and
+//! jumps to the ELF entry symbol — [`kernel_entry`] — on its own bootstrap
+//! stack. This module performs the ADR-0001 "Stage 2" steps:
 //!
-//! For Phase 1, `kernel_entry` lives in the bootloader binary (since both
-//! are compiled as a single UEFI application). The structure here documents
-//! what `kernel_entry` must do when it moves to this module:
-//!
-//! 1. Receive `*const KernelBootInfo` in RDI (SysV AMD64 ABI).
-//! 2. Validate the magic and version fields — halt on mismatch.
+//! 1. Receive `*const KernelBootInfo` in RDI (SysV AMD64 ABI), a
+//!    direct-map address.
+//! 2. Validate the pointer, magic and version fields — halt on mismatch.
 //! 3. Zero the BSS section using linker-provided `__bss_start`/`__bss_end`.
 //! 4. Switch RSP to the kernel's primary stack (see [`stack`] module).
 //! 5. Call `kernel_main(boot_info: &'static KernelBootInfo)`.
 //!
 //! # Stack switch (step 4)
 //!
-//! After validating `boot_info`, the entry point must switch from the
-//! bootstrap stack (set up by the bootloader) to the kernel's own primary
-//! stack before calling into Rust code that may have deep call chains.
+//! The stack switch and the call into `kernel_main` happen in a single asm
+//! block. `boot_info` travels in RDI (the first SysV argument register), so
+//! nothing is read from the abandoned bootstrap stack after RSP changes:
 //!
 //! ```text
-//! ; After BSS zero, before kernel_main:
-//! mov rsp, [KERNEL_STACK_TOP]   ; switch to 64 KiB kernel stack
+//! mov rsp, KERNEL_STACK_TOP     ; switch to 64 KiB kernel stack
 //! xor rbp, rbp                  ; clear frame pointer (no caller)
-//! call kernel_main              ; RSP now points to kernel stack
+//! call kernel_main              ; RDI = boot_info, RSP = kernel stack
 //! ```
 //!
 //! # Safety requirements for `kernel_entry`
@@ -31,15 +28,159 @@
 //! - RSP must initially point to a valid bootstrap stack (16-byte aligned).
 //! - Interrupts must be disabled (`cli` executed before the call).
 //! - UEFI boot services must have already exited.
-//! - The pointer in RDI must be non-null and point to a valid `KernelBootInfo`.
+//! - The pointer in RDI must point to a valid `KernelBootInfo` in the direct
+//!   map.
+//! - CR3 must hold the bootloader-built tables (`ferrous_boot_info::layout`)
+//!   mapping this image at the virtual base its relocations were applied
+//!   for: the link address plus the KASLR slide.
+//!
+//! # Linker script symbols
 //!
-//! # Future linker script symbols
+//! `kernel/linker.ld` exports:
+//! - `__kernel_start` / `__kernel_end` — bounds of the loaded image
+//! - `__text_start` / `__text_end`, `__rodata_start` / `__rodata_end`,
+//!   `__data_start` — section bounds for page permissions
+//! - `__bss_start` / `__bss_end` — bounds of the `.bss` section
 //!
-//! The following symbols must be exported by `kernel.ld`:
-//! - `__bss_start`   — start of the `.bss` section
-//! - `__bss_end`     — end of the `.bss` section
-//! - `__stack_top`   — top of the kernel primary stack (RSP initial value)
-//! - `__stack_bottom`— bottom of the kernel primary stack (guard page base)
+//! [`stack`]: super::stack
+
+use core::ops::Range;
+
+use ferrous_boot_info::{layout, KernelBootInfo};
+
+use super::stack::{KERNEL_STACK, KERNEL_STACK_SIZE};
+use crate::drivers::serial::SerialPort;
+
+extern "C" {
+    static mut __bss_start: u8;
+    static mut __bss_end: u8;
+    static __kernel_start: u8;
+    static __kernel_end: u8;
+    static __text_start: u8;
+    static __text_end: u8;
+    static __rodata_start: u8;
+    static __rodata_end: u8;
+    static __data_start: u8;
+}
+
+/// Virtual address ranges of the kernel image by page permission.
+///
+/// Each starts page-aligned; the ends are not rounded.
+#[derive(Debug, Clone)]
+pub struct KernelSections {
+    /// `.text`: executable code.
+    pub text: Range<u64>,
+    /// `.rodata`: constants.
+    pub rodata: Range<u64>,
+    /// `.data`, `.got` and `.bss` up to the end of the image.
+    pub data: Range<u64>,
+}
+
+/// Virtual address range `[start, end)` occupied by the loaded kernel image.
+///
+/// Both ends are page-aligned by the linker script; the physical pages
+/// behind it are contiguous (see `memory::virt_to_phys`).
+pub fn kernel_image_range() -> (u64, u64) {
+    // Only the addresses of the linker symbols are taken; the symbols
+    // themselves are never read.
+    (
+        core::ptr::addr_of!(__kernel_start) as u64,
+        core::ptr::addr_of!(__kernel_end) as u64,
+    )
+}
+
+/// Section bounds from the linker script.
+pub fn kernel_sections() -> KernelSections {
+    // As in `kernel_image_range`: addresses only.
+    KernelSections {
+        text: core::ptr::addr_of!(__text_start) as u64..core::ptr::addr_of!(__text_end) as u64,
+        rodata: core::ptr::addr_of!(__rodata_start) as u64
+            ..core::ptr::addr_of!(__rodata_end) as u64,
+        data: core::ptr::addr_of!(__data_start) as u64..core::ptr::addr_of!(__kernel_end) as u64,
+    }
+}
+
+/// Kernel entry point — the ELF `e_entry` of `ferrous-kernel`.
+///
+/// Runs on the bootloader's bootstrap stack with interrupts disabled. It
+/// validates the `KernelBootInfo`, zeroes BSS, switches to
+/// [`KERNEL_STACK`] and calls `kernel_main`.
+///
+/// # Safety
+///
+/// Must only be called by the bootloader handoff sequence:
+/// - RSP must point to a valid stack (the bootstrap stack).
+/// - RDI must contain the direct-map address of a fully populated
+///   `KernelBootInfo`.
+/// - Interrupts must be disabled (`cli` must have been executed).
+/// - UEFI boot services must have already exited.
+#[no_mangle]
+pub unsafe extern "C" fn kernel_entry(boot_info: *const KernelBootInfo) -> ! {
+    // Validate the boot info pointer before touching anything else.
+    if boot_info.is_null() {
+        fatal("FATAL: kernel_entry received null BootInfo pointer\n");
+    }
+    // Physical memory is only reachable through the direct map once the
+    // identity mapping goes, so a bootloader passing anything else is too
+    // old for this kernel.
+    if layout::direct_map_to_phys(boot_info as u64).is_none() {
+        fatal("FATAL: BootInfo pointer is not a direct-map address\n");
+    }
+
+    // SAFETY: non-null and in the direct map, checked above; the bootloader
+    // passes the address of its KERNEL_BOOT_INFO static, which outlives the
+    // kernel. The magic and version are checked before any other field is
+    // used.
+    if !(*boot_info).is_valid() {
+        fatal("FATAL: KernelBootInfo magic/version mismatch\n");
+    }
+
+    // SAFETY: __bss_start..__bss_end is exactly the kernel's .bss per the
+    // linker script. We are still on the bootloader's stack, which lives
+    // outside the kernel image, so nothing live is overwritten. Volatile
+    // writes keep the optimiser from eliding the loop (ADR-0001).
+    zero_bss();
+
+    // SAFETY:
+    // - KERNEL_STACK is a valid 64 KiB, 16-byte-aligned static; stack_top is
+    //   one past its end — the correct initial RSP for a downward stack.
+    // - boot_info is passed in RDI and never read from the old stack.
+    // - kernel_main is `-> !`; the return address pushed by `call` is never
+    //   consumed. `ud2` traps if it somehow returns.
+    // - Interrupts are disabled; nothing can observe the half-switched state.
+    let stack_top = core::ptr::addr_of!(KERNEL_STACK) as u64 + KERNEL_STACK_SIZE as u64;
+    core::arch::asm!(
+        "mov rsp, {stack}",
+        "xor rbp, rbp",
+        "call {main}",
+        "ud2",
+        stack = in(reg) stack_top,
+        main = sym crate::kernel_main,
+        in("rdi") boot_info,
+        options(noreturn),
+    );
+}
+
+/// Zero the `.bss` section one byte at a time with volatile writes.
+///
+/// # Safety
+///
+/// Must run before any code reads a `.bss` static, and not while the
+/// current stack lives inside `.bss`.
+unsafe fn zero_bss() {
+    let start = core::ptr::addr_of_mut!(__bss_start);
+    let end = core::ptr::addr_of_mut!(__bss_end);
+    let len = end as usize - start as usize;
+    for i in 0..len {
+        core::ptr::write_volatile(start.add(i), 0);
+    }
+}
 
-// Placeholder: entry logic is currently in boot/src/main.rs.
-// This module will be populated when the kernel becomes a separate binary.
+/// Report an unrecoverable entry failure on COM1 and halt.
+fn fatal(msg: &str) -> ! {
+    let serial = SerialPort::new();
+    // SAFETY: ring 0, single-threaded, interrupts disabled.
+    unsafe { serial.init() };
+    serial.write_str(msg);
+    super::halt()
+}
@@ -0,0 +1,326 @@
+//! Exception error-code decoders.
+//!
+//! Turns the raw error codes pushed for #PF and for the selector-reporting
+//! exceptions (#TS, #NP, #SS, #GP), and the x87 and SSE status registers
+//! behind #MF and #XM, into structured values whose `Display` is a one-line
+//! explanation for the fatal report:
+//!
+//! ```text
+//! Error code:   0x2
+//! Cause:        write to non-present page from ring 0
+//! ```
+//!
+//! Everything here is a pure function of the error code and depends only on
+//! `core`, so `tests/boot_tests.rs` compiles this file directly and checks
+//! the decoding on the host.
+
+use core::fmt;
+
+// ---------------------------------------------------------------------------
+// Page fault (#PF, vector 14) — Intel SDM Vol 3A §4.7
+// ---------------------------------------------------------------------------
+
+/// P — 0: page not present; 1: protection violation.
+pub const PF_PRESENT: u64 = 1 << 0;
+/// W/R — the access was a write.
+pub const PF_WRITE: u64 = 1 << 1;
+/// U/S — the access was made in user mode (CPL=3).
+pub const PF_USER: u64 = 1 << 2;
+/// RSVD — a reserved bit was set in a paging-structure entry.
+pub const PF_RESERVED: u64 = 1 << 3;
+/// I/D — the access was an instruction fetch.
+pub const PF_INSTRUCTION_FETCH: u64 = 1 << 4;
+/// PK — protection-key violation.
+pub const PF_PROTECTION_KEY: u64 = 1 << 5;
+/// SS — shadow-stack access.
+pub const PF_SHADOW_STACK: u64 = 1 << 6;
+/// SGX — violation of SGX-specific access-control requirements.
+pub const PF_SGX: u64 = 1 << 15;
+
+/// Decoded #PF error code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageFaultCause {
+    /// The page was present (protection violation) rather than missing.
+    pub present: bool,
+    /// The faulting access was a write.
+    pub write: bool,
+    /// The faulting access came from ring 3.
+    pub user: bool,
+    /// A paging-structure entry had a reserved bit set.
+    pub reserved_bit: bool,
+    /// The faulting access was an instruction fetch.
+    pub instruction_fetch: bool,
+    /// The access violated a protection key.
+    pub protection_key: bool,
+    /// The access was a shadow-stack access.
+    pub shadow_stack: bool,
+    /// The access violated SGX access control.
+    pub sgx: bool,
+}
+
+/// Decode a #PF error code.
+pub const fn decode_page_fault(code: u64) -> PageFaultCause {
+    PageFaultCause {
+        present: code & PF_PRESENT != 0,
+        write: code & PF_WRITE != 0,
+        user: code & PF_USER != 0,
+        reserved_bit: code & PF_RESERVED != 0,
+        instruction_fetch: code & PF_INSTRUCTION_FETCH != 0,
+        protection_key: code & PF_PROTECTION_KEY != 0,
+        shadow_stack: code & PF_SHADOW_STACK != 0,
+        sgx: code & PF_SGX != 0,
+    }
+}
+
+impl fmt::Display for PageFaultCause {
+    /// E.g. `write to non-present page from ring 0`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (access, preposition) = if self.instruction_fetch {
+            ("instruction fetch", "from")
+        } else if self.write {
+            ("write", "to")
+        } else {
+            ("read", "from")
+        };
+        let page = if self.present {
+            "protected"
+        } else {
+            "non-present"
+        };
+        let ring = if self.user { 3 } else { 0 };
+
+        if self.shadow_stack {
+            f.write_str("shadow-stack ")?;
+        }
+        write!(f, "{access} {preposition} {page} page from ring {ring}")?;
+        if self.reserved_bit {
+            f.write_str(", reserved bit set in page table")?;
+        }
+        if self.protection_key {
+            f.write_str(", protection-key violation")?;
+        }
+        if self.sgx {
+            f.write_str(", SGX access-control violation")?;
+        }
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Selector error codes (#TS, #NP, #SS, #GP) — Intel SDM Vol 3A §6.13
+//
+//   Bit 0      EXT — raised while delivering an external event
+//   Bit 1      IDT — index refers to the IDT
+//   Bit 2      TI  — (when IDT = 0) 0: GDT, 1: LDT
+//   Bits 15:3  Segment selector index
+// ---------------------------------------------------------------------------
+
+/// EXT — the exception occurred while delivering an external event.
+pub const SEL_EXTERNAL: u64 = 1 << 0;
+/// IDT — the index refers to a gate descriptor in the IDT.
+pub const SEL_IDT: u64 = 1 << 1;
+/// TI — the index refers to the LDT (only meaningful when IDT is clear).
+pub const SEL_LDT: u64 = 1 << 2;
+
+/// Descriptor table a selector error code refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DescriptorTable {
+    /// Global Descriptor Table.
+    Gdt,
+    /// Interrupt Descriptor Table; the index is a vector number.
+    Idt,
+    /// Local Descriptor Table.
+    Ldt,
+}
+
+/// Decoded selector-style error code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SelectorErrorCode {
+    /// Raised while delivering an external event (interrupt, earlier fault).
+    pub external: bool,
+    /// Table the index refers to.
+    pub table: DescriptorTable,
+    /// Descriptor index (bits 15:3).
+    pub index: u16,
+}
+
+/// Decode a selector error code.
+///
+/// Returns `None` for 0, which #GP uses for faults unrelated to a segment
+/// (non-canonical address, privileged instruction, ...).
+pub const fn decode_selector(code: u64) -> Option<SelectorErrorCode> {
+    let code = code & 0xFFFF;
+    if code == 0 {
+        return None;
+    }
+    let table = if code & SEL_IDT != 0 {
+        DescriptorTable::Idt
+    } else if code & SEL_LDT != 0 {
+        DescriptorTable::Ldt
+    } else {
+        DescriptorTable::Gdt
+    };
+    Some(SelectorErrorCode {
+        external: code & SEL_EXTERNAL != 0,
+        table,
+        index: (code >> 3) as u16,
+    })
+}
+
+impl SelectorErrorCode {
+    /// The segment selector as software writes it (`index << 3 | TI`).
+    /// For [`DescriptorTable::Idt`] this is the index shifted, not a vector.
+    pub const fn selector(&self) -> u16 {
+        let ti = match self.table {
+            DescriptorTable::Ldt => 1 << 2,
+            _ => 0,
+        };
+        self.index << 3 | ti
+    }
+}
+
+impl fmt::Display for SelectorErrorCode {
+    /// E.g. `GDT selector 0x18 invalid` or `IDT vector 32 invalid`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.table {
+            DescriptorTable::Idt => write!(f, "IDT vector {} invalid", self.index)?,
+            DescriptorTable::Gdt => write!(f, "GDT selector {:#x} invalid", self.selector())?,
+            DescriptorTable::Ldt => write!(f, "LDT selector {:#x} invalid", self.selector())?,
+        }
+        if self.external {
+            f.write_str(" (during external event delivery)")?;
+        }
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Floating-point exceptions (#MF, vector 16; #XM, vector 19) — Intel SDM
+// Vol 1 §8.1.3, §10.2.3
+//
+// The x87 status word (FSW) and MXCSR share the layout of the six exception
+// flags (bits 5:0). They are masked by the x87 control word (FCW, same
+// bits) and by MXCSR bits 12:7 respectively; only unmasked flags raise
+// the exception.
+// ---------------------------------------------------------------------------
+
+/// IE — invalid operation.
+pub const FP_INVALID: u32 = 1 << 0;
+/// DE — denormal operand.
+pub const FP_DENORMAL: u32 = 1 << 1;
+/// ZE — divide by zero.
+pub const FP_ZERO_DIVIDE: u32 = 1 << 2;
+/// OE — numeric overflow.
+pub const FP_OVERFLOW: u32 = 1 << 3;
+/// UE — numeric underflow.
+pub const FP_UNDERFLOW: u32 = 1 << 4;
+/// PE — inexact result (precision).
+pub const FP_PRECISION: u32 = 1 << 5;
+/// FSW.SF — the invalid operation was an x87 register stack fault.
+pub const FSW_STACK_FAULT: u16 = 1 << 6;
+/// FSW.C1 — with SF set, 1 for stack overflow and 0 for underflow.
+pub const FSW_C1: u16 = 1 << 9;
+/// Shift from the MXCSR exception flags to their mask bits.
+const MXCSR_MASK_SHIFT: u32 = 7;
+/// All six exception flags.
+const FP_FLAGS: u32 = 0x3F;
+
+/// x87 register stack fault behind an invalid-operation exception.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackFault {
+    /// Push onto a full register stack.
+    Overflow,
+    /// Pop from (or read of) an empty register.
+    Underflow,
+}
+
+/// Unmasked floating-point exceptions pending in FSW or MXCSR.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FpExceptions {
+    /// Invalid operation.
+    pub invalid: bool,
+    /// Denormal operand.
+    pub denormal: bool,
+    /// Divide by zero.
+    pub zero_divide: bool,
+    /// Numeric overflow.
+    pub overflow: bool,
+    /// Numeric underflow.
+    pub underflow: bool,
+    /// Inexact result.
+    pub precision: bool,
+    /// For x87 invalid operations: the stack fault that caused it.
+    pub stack_fault: Option<StackFault>,
+}
+
+impl FpExceptions {
+    const fn from_flags(flags: u32) -> Self {
+        Self {
+            invalid: flags & FP_INVALID != 0,
+            denormal: flags & FP_DENORMAL != 0,
+            zero_divide: flags & FP_ZERO_DIVIDE != 0,
+            overflow: flags & FP_OVERFLOW != 0,
+            underflow: flags & FP_UNDERFLOW != 0,
+            precision: flags & FP_PRECISION != 0,
+            stack_fault: None,
+        }
+    }
+
+    /// True if no unmasked exception is pending.
+    pub const fn is_empty(&self) -> bool {
+        !(self.invalid
+            || self.denormal
+            || self.zero_divide
+            || self.overflow
+            || self.underflow
+            || self.precision)
+    }
+}
+
+/// Decode the unmasked exceptions pending in the x87 status word `fsw`
+/// under control word `fcw`.
+pub const fn decode_x87(fsw: u16, fcw: u16) -> FpExceptions {
+    let flags = fsw as u32 & !(fcw as u32) & FP_FLAGS;
+    let mut exceptions = FpExceptions::from_flags(flags);
+    if exceptions.invalid && fsw & FSW_STACK_FAULT != 0 {
+        exceptions.stack_fault = Some(if fsw & FSW_C1 != 0 {
+            StackFault::Overflow
+        } else {
+            StackFault::Underflow
+        });
+    }
+    exceptions
+}
+
+/// Decode the unmasked SIMD exceptions pending in `mxcsr`.
+pub const fn decode_mxcsr(mxcsr: u32) -> FpExceptions {
+    FpExceptions::from_flags(mxcsr & !(mxcsr >> MXCSR_MASK_SHIFT) & FP_FLAGS)
+}
+
+impl fmt::Display for FpExceptions {
+    /// E.g. `invalid operation (stack overflow), divide by zero`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return f.write_str("no unmasked exception pending");
+        }
+        let mut sep = "";
+        let mut item = |f: &mut fmt::Formatter<'_>, set: bool, name: &str| {
+            if set {
+                write!(f, "{sep}{name}")?;
+                sep = ", ";
+            }
+            Ok(())
+        };
+        let invalid = match self.stack_fault {
+            Some(StackFault::Overflow) => "invalid operation (stack overflow)",
+            Some(StackFault::Underflow) => "invalid operation (stack underflow)",
+            None => "invalid operation",
+        };
+        item(f, self.invalid, invalid)?;
+        item(f, self.denormal, "denormal operand")?;
+        item(f, self.zero_divide, "divide by zero")?;
+        item(f, self.overflow, "overflow")?;
+        item(f, self.underflow, "underflow")?;
+        item(f, self.precision, "inexact result")
+    }
+}
@@ -0,0 +1,349 @@
+//! Interrupt entry stubs and the fatal exception report.
+//!
+//! Stub design (stable Rust — no `abi_x86_interrupt` / `#[naked]` required):
+//!
+//! Every vector 0–255 has its own 16-byte stub, laid out back to back from
+//! `__isr_stubs` so that [`stub_address`] is a multiply rather than a
+//! table lookup. Two macro variants handle the difference in CPU stack
+//! layout:
+//!
+//! - `isr_stub v` — vectors WITHOUT a CPU-pushed error code: pushes a dummy
+//!   error code of 0, then the vector.
+//! - `isr_stub_ec v` — vectors WITH a CPU-pushed error code
+//!   (8, 10–14, 17, 21, 29, 30): pushes only the vector.
+//!
+//! Either way the stack then holds `[vector, error_code, ExceptionFrame]`
+//! and both jump to `__interrupt_common`, which pushes RAX–R15 to complete
+//! an [`InterruptContext`], calls [`interrupts::dispatch`](super::interrupts)
+//! with a pointer to it, restores the (possibly modified) registers, drops
+//! vector and error code, and returns with `IRETQ`.
+//!
+//! Callee-saved registers (RBX, RBP, R12–R15) would survive the call anyway;
+//! they are saved too so that handlers and the fatal report see — and can
+//! change — the complete interrupted state.
+//!
+//! Vectors nobody handles end in [`fatal`], which prints diagnostics over
+//! serial and halts forever.
+//!
+//! Error-code vectors per Intel SDM Vol 3A §6.13:
+//! 8 (#DF), 10 (#TS), 11 (#NP), 12 (#SS), 13 (#GP), 14 (#PF),
+//! 17 (#AC), 21 (#CP), 29 (#VC), 30 (#SX)
+
+use core::arch::global_asm;
+
+use super::error_code::{decode_page_fault, decode_selector};
+use super::interrupts::InterruptContext;
+use super::registers::{ControlRegisters, DataSegments};
+use crate::serial_println;
+
+/// Distance between consecutive entry stubs in bytes.
+const STUB_STRIDE: u64 = 16;
+
+// Assembly stubs — one per vector (0–255), each padded to STUB_STRIDE.
+global_asm!(
+    // ---------------------------------------------------------------------------
+    // Common landing pad.
+    //
+    // At entry: RSP → [vector, error_code, RIP, CS, RFLAGS, old_RSP, SS].
+    // The CPU aligned RSP to 16 before pushing its 5-quadword frame; with the
+    // error code, the vector and the 15 registers below that is 22 quadwords,
+    // so RSP is 16-byte aligned again at the `call` as the SysV ABI requires.
+    // ---------------------------------------------------------------------------
+    ".global __interrupt_common",
+    "__interrupt_common:",
+    "push rax",
+    "push rbx",
+    "push rcx",
+    "push rdx",
+    "push rsi",
+    "push rdi",
+    "push rbp",
+    "push r8",
+    "push r9",
+    "push r10",
+    "push r11",
+    "push r12",
+    "push r13",
+    "push r14",
+    "push r15",
+    "mov rdi, rsp", // arg1: &mut InterruptContext
+    "cld",          // SysV: DF clear on function entry
+    "call interrupt_dispatch",
+    "pop r15",
+    "pop r14",
+    "pop r13",
+    "pop r12",
+    "pop r11",
+    "pop r10",
+    "pop r9",
+    "pop r8",
+    "pop rbp",
+    "pop rdi",
+    "pop rsi",
+    "pop rdx",
+    "pop rcx",
+    "pop rbx",
+    "pop rax",
+    "add rsp, 16", // drop vector + error code
+    "iretq",
+    // ---------------------------------------------------------------------------
+    // isr_stub v — no CPU-pushed error code.
+    //   RSP → [RIP, CS, RFLAGS, old_RSP, SS]  (ExceptionFrame) on entry.
+    // ---------------------------------------------------------------------------
+    ".macro isr_stub v",
+    ".balign 16",
+    "push 0",   // dummy error code keeps the layout uniform
+    "push \\v", // vector number
+    "jmp __interrupt_common",
+    ".endm",
+    // ---------------------------------------------------------------------------
+    // isr_stub_ec v — CPU pushes an error code before the handler runs.
+    //   RSP → [error_code, RIP, CS, RFLAGS, old_RSP, SS]  on entry.
+    // ---------------------------------------------------------------------------
+    ".macro isr_stub_ec v",
+    ".balign 16",
+    "push \\v", // vector number
+    "jmp __interrupt_common",
+    ".endm",
+    ".balign 16",
+    ".global __isr_stubs",
+    "__isr_stubs:",
+    // CPU exception stubs — vectors 0–31
+    "isr_stub 0",     // #DE  Divide Error               (no error code)
+    "isr_stub 1",     // #DB  Debug                      (no error code)
+    "isr_stub 2",     // #NMI Non-Maskable Interrupt      (no error code)
+    "isr_stub 3",     // #BP  Breakpoint                 (no error code)
+    "isr_stub 4",     // #OF  Overflow                   (no error code)
+    "isr_stub 5",     // #BR  Bound Range Exceeded        (no error code)
+    "isr_stub 6",     // #UD  Invalid Opcode             (no error code)
+    "isr_stub 7",     // #NM  Device Not Available        (no error code)
+    "isr_stub_ec 8",  // #DF  Double Fault               (error code = 0 always)
+    "isr_stub 9",     // (obsolete Coprocessor Segment Overrun, no error code)
+    "isr_stub_ec 10", // #TS  Invalid TSS                (error code: selector)
+    "isr_stub_ec 11", // #NP  Segment Not Present         (error code: selector)
+    "isr_stub_ec 12", // #SS  Stack-Segment Fault         (error code: selector)
+    "isr_stub_ec 13", // #GP  General Protection Fault    (error code: selector or 0)
+    "isr_stub_ec 14", // #PF  Page Fault                 (error code: flags; CR2: address)
+    "isr_stub 15",    // (reserved)
+    "isr_stub 16",    // #MF  x87 FPU Floating-Point Error (no error code)
+    "isr_stub_ec 17", // #AC  Alignment Check            (error code = 0)
+    "isr_stub 18",    // #MC  Machine Check               (no error code)
+    "isr_stub 19",    // #XF  SIMD Floating-Point Exception (no error code)
+    "isr_stub 20",    // #VE  Virtualization Exception    (no error code)
+    "isr_stub_ec 21", // #CP  Control Protection Exception (error code)
+    "isr_stub 22",    // (reserved)
+    "isr_stub 23",    // (reserved)
+    "isr_stub 24",    // (reserved)
+    "isr_stub 25",    // (reserved)
+    "isr_stub 26",    // (reserved)
+    "isr_stub 27",    // (reserved)
+    "isr_stub 28",    // #HV  Hypervisor Injection Exception (no error code)
+    "isr_stub_ec 29", // #VC  VMM Communication Exception  (error code)
+    "isr_stub_ec 30", // #SX  Security Exception           (error code)
+    "isr_stub 31",    // (reserved)
+    // External interrupt / software vectors 32–255 — no error code.
+    // `.altmacro` makes `%vec` expand to the current value of `vec`.
+    ".altmacro",
+    ".set vec, 32",
+    ".rept 224",
+    "isr_stub %vec",
+    ".set vec, vec + 1",
+    ".endr",
+    ".noaltmacro",
+);
+
+extern "C" {
+    /// First entry stub; vector `v` starts at `__isr_stubs + 16 * v`.
+    static __isr_stubs: u8;
+}
+
+/// Address of the entry stub for `vector`, for installation in the IDT.
+pub(super) fn stub_address(vector: u8) -> u64 {
+    // Only the address of the symbol is taken.
+    core::ptr::addr_of!(__isr_stubs) as u64 + vector as u64 * STUB_STRIDE
+}
+
+/// Human-readable names for the 32 CPU exception vectors.
+pub static EXCEPTION_NAMES: [&str; 32] = [
+    "#DE: Divide Error",
+    "#DB: Debug",
+    "#NMI: Non-Maskable Interrupt",
+    "#BP: Breakpoint",
+    "#OF: Overflow",
+    "#BR: Bound Range Exceeded",
+    "#UD: Invalid Opcode",
+    "#NM: Device Not Available",
+    "#DF: Double Fault",
+    "(obsolete Coprocessor Segment Overrun)",
+    "#TS: Invalid TSS",
+    "#NP: Segment Not Present",
+    "#SS: Stack-Segment Fault",
+    "#GP: General Protection Fault",
+    "#PF: Page Fault",
+    "(reserved)",
+    "#MF: x87 FPU Floating-Point Error",
+    "#AC: Alignment Check",
+    "#MC: Machine Check",
+    "#XF: SIMD Floating-Point Exception",
+    "#VE: Virtualization Exception",
+    "#CP: Control Protection Exception",
+    "(reserved)",
+    "(reserved)",
+    "(reserved)",
+    "(reserved)",
+    "(reserved)",
+    "(reserved)",
+    "#HV: Hypervisor Injection Exception",
+    "#VC: VMM Communication Exception",
+    "#SX: Security Exception",
+    "(reserved)",
+];
+
+/// Bitmask of vectors that push an error code (Intel SDM Vol 3A §6.13):
+/// bit 8 = #DF, bit 10 = #TS, bit 11 = #NP, bit 12 = #SS, bit 13 = #GP,
+/// bit 14 = #PF, bit 17 = #AC, bit 21 = #CP, bit 29 = #VC, bit 30 = #SX.
+pub const EC_MASK: u64 = (1 << 8)
+    | (1 << 10)
+    | (1 << 11)
+    | (1 << 12)
+    | (1 << 13)
+    | (1 << 14)
+    | (1 << 17)
+    | (1 << 21)
+    | (1 << 29)
+    | (1 << 30);
+
+/// True if the CPU pushes an error code for `vector`.
+#[inline]
+pub fn has_error_code(vector: u64) -> bool {
+    vector < 64 && (EC_MASK >> vector) & 1 == 1
+}
+
+/// Fatal report for an unhandled (or unrecoverable) interrupt — never
+/// returns.
+///
+/// Prints the vector, error code (decoded for #PF and the selector-reporting
+/// faults), the complete interrupted register state and the control
+/// registers over serial and halts the CPU forever. Interrupts are already
+/// disabled: every gate is an interrupt gate.
+pub(super) fn fatal(ctx: &InterruptContext) -> ! {
+    let vector = ctx.vector;
+    serial_println!();
+    serial_println!("========== KERNEL EXCEPTION ==========");
+
+    // --- Exception name ---
+    match EXCEPTION_NAMES.get(vector as usize) {
+        Some(name) => serial_println!("Vector {}: {}", vector, name),
+        None => serial_println!("Unhandled interrupt vector #{}", vector),
+    }
+
+    // --- Error code (only meaningful for the subset of vectors that push one) ---
+    if has_error_code(vector) {
+        serial_println!("Error code:   {:#x}", ctx.error_code);
+    }
+
+    // --- Decoded cause (#TS / #NP / #SS / #GP selector, #PF access bits) ---
+    match vector {
+        10..=13 => match decode_selector(ctx.error_code) {
+            Some(selector) => serial_println!("Cause:        {}", selector),
+            None => serial_println!("Cause:        not selector-related"),
+        },
+        14 => serial_println!("Cause:        {}", decode_page_fault(ctx.error_code)),
+        _ => {}
+    }
+
+    // --- Page fault: read CR2 (faulting virtual address) ---
+    //
+    // CR2 is set by the CPU before the #PF handler runs and remains valid
+    // until the next page fault (which cannot happen here — interrupts off).
+    // A #DF caused by a #PF during delivery leaves CR2 from that #PF.
+    let cr = ControlRegisters::read();
+    let cr2 = cr.cr2;
+    if vector == 14 {
+        serial_println!("CR2 (fault):  {:#x}", cr2);
+    }
+
+    // --- Stack overflow: a fault on the KERNEL_STACK guard page ---
+    //
+    // Usually the #PF cannot be pushed onto the exhausted stack and arrives
+    // here as #DF on IST1; a large frame that skips past RSP may still yield
+    // a plain #PF.
+    if (vector == 8 || vector == 14) && super::stack::is_guard_page_address(cr2) {
+        serial_println!(
+            "kernel stack overflow (RSP={:#x}, guard page {:#x})",
+            ctx.frame.rsp,
+            cr2
+        );
+    }
+
+    // --- Interrupted state: RIP/RFLAGS, then all 16 GPRs, then segments ---
+    let f = &ctx.frame;
+    serial_println!("RIP:          {:#x}", f.rip);
+    let slide = crate::kaslr::slide();
+    if slide != 0 {
+        serial_println!(
+            "Kernel slide: {:#x} (link-time RIP {:#x})",
+            slide,
+            f.rip.wrapping_sub(slide)
+        );
+    }
+    serial_println!("RFLAGS:       {:#x}", f.rflags);
+    serial_println!(
+        "RAX={:016x} RBX={:016x} RCX={:016x}",
+        ctx.rax,
+        ctx.rbx,
+        ctx.rcx
+    );
+    serial_println!(
+        "RDX={:016x} RSI={:016x} RDI={:016x}",
+        ctx.rdx,
+        ctx.rsi,
+        ctx.rdi
+    );
+    serial_println!(
+        "RBP={:016x} RSP={:016x} R8 ={:016x}",
+        ctx.rbp,
+        f.rsp,
+        ctx.r8
+    );
+    serial_println!(
+        "R9 ={:016x} R10={:016x} R11={:016x}",
+        ctx.r9,
+        ctx.r10,
+        ctx.r11
+    );
+    serial_println!(
+        "R12={:016x} R13={:016x} R14={:016x}",
+        ctx.r12,
+        ctx.r13,
+        ctx.r14
+    );
+    serial_println!(
+        "R15={:016x} CS ={:04x}             SS ={:04x}",
+        ctx.r15,
+        f.cs,
+        f.ss
+    );
+    let seg = DataSegments::read();
+    serial_println!(
+        "DS ={:04x}             ES ={:04x}             FS ={:04x}",
+        seg.ds,
+        seg.es,
+        seg.fs
+    );
+    serial_println!("GS ={:04x}", seg.gs);
+
+    // --- Control registers (live values; unchanged by interrupt delivery) ---
+    serial_println!(
+        "CR0={:016x} CR2={:016x} CR3={:016x}",
+        cr.cr0,
+        cr.cr2,
+        cr.cr3
+    );
+    serial_println!("CR4={:016x} EFER={:016x}", cr.cr4, cr.efer);
+
+    serial_println!("======================================");
+    serial_println!("System halted.");
+
+    super::halt()
+}
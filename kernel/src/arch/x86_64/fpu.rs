@@ -0,0 +1,457 @@
+//! x87 FPU, SSE and AVX state.
+//!
+//! The kernel itself is built soft-float (`x86_64-unknown-none`) and never
+//! emits x87 or SSE instructions, but the state must still be set up before
+//! anything else may use it. [`init`] configures the CPU for it:
+//!
+//! | Register | Bits | Setting |
+//! |----------|------|---------|
+//! | CR0  | EM, TS       | cleared: instructions execute instead of raising #NM |
+//! | CR0  | MP, NE       | set: `WAIT` honours TS, errors are reported as #MF |
+//! | CR4  | OSFXSR, OSXMMEXCPT | set: SSE enabled, SIMD errors reported as #XM |
+//! | CR4  | OSXSAVE      | set with CPUID XSAVE |
+//! | XCR0 | x87, SSE, AVX, AVX-512 | every component both the CPU and the kernel support |
+//!
+//! x87, SSE2 and FXSAVE are architectural in long mode; everything else is
+//! gated on [`cpu`](super::cpu). The XSAVE area size for the enabled
+//! components comes from CPUID leaf 0Dh, which is read here rather than
+//! cached by `cpu` because its EBX depends on the current XCR0.
+//!
+//! [`FpuState`] is the save area for one task: [`FpuState::save`] and
+//! [`FpuState::restore`] use XSAVE/XRSTOR when available and FXSAVE/FXRSTOR
+//! otherwise. #NM, #MF and #XM get handlers in place of the fatal stub.
+
+use core::arch::x86_64::__cpuid_count;
+use core::sync::atomic::{AtomicU64, AtomicU8, AtomicUsize, Ordering};
+
+use super::cpu::{self, Feature};
+use super::error_code::{decode_mxcsr, decode_x87};
+use super::interrupts::{
+    self, Disposition, InterruptContext, DEVICE_NOT_AVAILABLE_VECTOR, SIMD_FP_VECTOR, X87_FP_VECTOR,
+};
+use super::registers;
+use crate::serial_println;
+
+/// CR0.MP — `WAIT`/`FWAIT` raise #NM when TS is set.
+const CR0_MP: u64 = 1 << 1;
+/// CR0.EM — x87 instructions raise #NM (no FPU).
+const CR0_EM: u64 = 1 << 2;
+/// CR0.TS — task switched; the next x87/SSE instruction raises #NM.
+const CR0_TS: u64 = 1 << 3;
+/// CR0.NE — report x87 errors as #MF rather than through IRQ 13.
+const CR0_NE: u64 = 1 << 5;
+/// CR4.OSFXSR — FXSAVE/FXRSTOR and SSE instructions enabled.
+const CR4_OSFXSR: u64 = 1 << 9;
+/// CR4.OSXMMEXCPT — unmasked SIMD exceptions raise #XM rather than #UD.
+const CR4_OSXMMEXCPT: u64 = 1 << 10;
+/// CR4.OSXSAVE — XSAVE family and XCR0 enabled.
+const CR4_OSXSAVE: u64 = 1 << 18;
+
+/// XCR0 state components.
+const XCR0_X87: u64 = 1 << 0;
+const XCR0_SSE: u64 = 1 << 1;
+const XCR0_AVX: u64 = 1 << 2;
+/// Opmask, ZMM_Hi256 and Hi16_ZMM: all three or none.
+const XCR0_AVX512: u64 = 0b111 << 5;
+
+/// MXCSR after reset: all SIMD exceptions masked, round to nearest.
+pub const MXCSR_DEFAULT: u32 = 0x1F80;
+/// x87 control word after `FNINIT`: all exceptions masked, 64-bit
+/// precision, round to nearest.
+pub const FCW_DEFAULT: u16 = 0x037F;
+
+/// Size of the legacy FXSAVE area.
+pub const FXSAVE_SIZE: usize = 512;
+/// Capacity of an [`FpuState`]. The components [`init`] enables end at
+/// 2688 bytes in the standard XSAVE format.
+pub const MAX_STATE_SIZE: usize = 4096;
+
+/// [`FpuState`] save mechanism, set by [`init`].
+const MODE_NONE: u8 = 0;
+const MODE_FXSAVE: u8 = 1;
+const MODE_XSAVE: u8 = 2;
+
+static MODE: AtomicU8 = AtomicU8::new(MODE_NONE);
+static XCR0: AtomicU64 = AtomicU64::new(0);
+static STATE_SIZE: AtomicUsize = AtomicUsize::new(0);
+
+/// What [`init`] enabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FpuConfig {
+    /// XSAVE/XRSTOR in use (otherwise FXSAVE/FXRSTOR).
+    pub xsave: bool,
+    /// XCR0; 0 without XSAVE.
+    pub xcr0: u64,
+    /// Bytes of [`FpuState`] the save instruction writes.
+    pub state_size: usize,
+}
+
+impl FpuConfig {
+    /// AVX registers are usable.
+    pub const fn avx(&self) -> bool {
+        self.xcr0 & XCR0_AVX != 0
+    }
+
+    /// AVX-512 registers are usable.
+    pub const fn avx512(&self) -> bool {
+        self.xcr0 & XCR0_AVX512 == XCR0_AVX512
+    }
+}
+
+/// Enable the x87 FPU, SSE and — where supported — XSAVE and AVX/AVX-512,
+/// load the default control words and install the #NM, #MF and #XM
+/// handlers.
+///
+/// # Safety
+///
+/// - Must be called once, with interrupts disabled, after
+///   [`cpu::init`](super::cpu::init).
+/// - Nothing may hold live x87/SSE state; it is reset.
+///
+/// # Panics
+///
+/// If one of the three vectors already has a handler, or if CPUID reports
+/// an XSAVE area larger than [`MAX_STATE_SIZE`].
+pub unsafe fn init() -> FpuConfig {
+    let xsave = cpu::has(Feature::XSAVE);
+    let cr0 = (registers::read_cr0() & !(CR0_EM | CR0_TS)) | CR0_MP | CR0_NE;
+    let mut cr4 = registers::read_cr4() | CR4_OSFXSR | CR4_OSXMMEXCPT;
+    if xsave {
+        cr4 |= CR4_OSXSAVE;
+    }
+    // SAFETY: FXSR and SSE are architectural in long mode; OSXSAVE is only
+    // set with CPUID XSAVE. The kernel holds no x87/SSE state to lose.
+    unsafe {
+        registers::write_cr0(cr0);
+        registers::write_cr4(cr4);
+        core::arch::asm!("fninit", options(nomem, nostack, preserves_flags));
+    }
+    write_mxcsr(MXCSR_DEFAULT);
+
+    let config = if xsave {
+        // Leaf 0Dh subleaf 0: EDX:EAX = XCR0 bits the CPU supports.
+        let leaf = __cpuid_count(0xD, 0);
+        let supported = (u64::from(leaf.edx) << 32) | u64::from(leaf.eax);
+        let mut xcr0 = XCR0_X87 | XCR0_SSE;
+        if cpu::has(Feature::AVX) && supported & XCR0_AVX != 0 {
+            xcr0 |= XCR0_AVX;
+            if cpu::has(Feature::AVX512F) && supported & XCR0_AVX512 == XCR0_AVX512 {
+                xcr0 |= XCR0_AVX512;
+            }
+        }
+        // SAFETY: OSXSAVE is set; every bit is supported and x87 is
+        // included, with AVX-512 only on top of AVX.
+        unsafe { xsetbv(0, xcr0) };
+        // EBX: XSAVE area size for the components now enabled in XCR0.
+        let state_size = __cpuid_count(0xD, 0).ebx as usize;
+        FpuConfig {
+            xsave,
+            xcr0,
+            state_size,
+        }
+    } else {
+        FpuConfig {
+            xsave,
+            xcr0: 0,
+            state_size: FXSAVE_SIZE,
+        }
+    };
+    assert!(
+        config.state_size <= MAX_STATE_SIZE,
+        "XSAVE area of {} bytes exceeds FpuState",
+        config.state_size
+    );
+
+    XCR0.store(config.xcr0, Ordering::Relaxed);
+    STATE_SIZE.store(config.state_size, Ordering::Relaxed);
+    MODE.store(
+        if xsave { MODE_XSAVE } else { MODE_FXSAVE },
+        Ordering::Release,
+    );
+
+    interrupts::register(DEVICE_NOT_AVAILABLE_VECTOR, device_not_available)
+        .expect("#NM handler already registered");
+    interrupts::register(X87_FP_VECTOR, x87_fp_exception).expect("#MF handler already registered");
+    interrupts::register(SIMD_FP_VECTOR, simd_fp_exception)
+        .expect("#XM handler already registered");
+    config
+}
+
+/// XCR0 as set by [`init`]; 0 before it or without XSAVE.
+pub fn xcr0() -> u64 {
+    XCR0.load(Ordering::Relaxed)
+}
+
+/// Bytes of an [`FpuState`] that [`FpuState::save`] writes; 0 before
+/// [`init`].
+pub fn state_size() -> usize {
+    STATE_SIZE.load(Ordering::Relaxed)
+}
+
+// ---------------------------------------------------------------------------
+// Save area
+// ---------------------------------------------------------------------------
+
+/// Saved x87/SSE/AVX register state of one task.
+///
+/// Fixed at [`MAX_STATE_SIZE`] bytes so it can live in a task structure
+/// without a heap; [`state_size`] of them are used. The area is only ever
+/// written by [`new`](Self::new) and [`save`](Self::save), so
+/// [`restore`](Self::restore) cannot load an invalid image.
+#[repr(C, align(64))]
+pub struct FpuState {
+    area: [u8; MAX_STATE_SIZE],
+}
+
+impl FpuState {
+    /// The state of a freshly initialised FPU: default control words, empty
+    /// register stack, zeroed registers (an XSAVE header with no components
+    /// present).
+    pub const fn new() -> Self {
+        let mut area = [0; MAX_STATE_SIZE];
+        // FXSAVE layout: FCW at byte 0, MXCSR at byte 24.
+        let fcw = FCW_DEFAULT.to_le_bytes();
+        area[0] = fcw[0];
+        area[1] = fcw[1];
+        let mxcsr = MXCSR_DEFAULT.to_le_bytes();
+        area[24] = mxcsr[0];
+        area[25] = mxcsr[1];
+        area[26] = mxcsr[2];
+        area[27] = mxcsr[3];
+        Self { area }
+    }
+
+    /// Save the CPU's current state into `self`. Does nothing before
+    /// [`init`].
+    pub fn save(&mut self) {
+        let area = self.area.as_mut_ptr();
+        match MODE.load(Ordering::Acquire) {
+            // SAFETY: OSXSAVE is enabled; the area is 64-byte aligned and
+            // holds `state_size()` bytes. EDX:EAX = all ones saves every
+            // component enabled in XCR0.
+            MODE_XSAVE => unsafe {
+                core::arch::asm!(
+                    "xsave64 [{}]",
+                    in(reg) area,
+                    in("eax") u32::MAX,
+                    in("edx") u32::MAX,
+                    options(nostack, preserves_flags)
+                )
+            },
+            // SAFETY: OSFXSR is enabled; the area is 16-byte aligned and
+            // holds 512 bytes.
+            MODE_FXSAVE => unsafe {
+                core::arch::asm!("fxsave64 [{}]", in(reg) area, options(nostack, preserves_flags))
+            },
+            _ => {}
+        }
+    }
+
+    /// Load the state in `self` into the CPU. Does nothing before [`init`].
+    pub fn restore(&self) {
+        let area = self.area.as_ptr();
+        match MODE.load(Ordering::Acquire) {
+            // SAFETY: as in `save`; the area holds either the image from
+            // `new` (standard format, no components present) or one written
+            // by XSAVE under the same XCR0.
+            MODE_XSAVE => unsafe {
+                core::arch::asm!(
+                    "xrstor64 [{}]",
+                    in(reg) area,
+                    in("eax") u32::MAX,
+                    in("edx") u32::MAX,
+                    options(readonly, nostack, preserves_flags)
+                )
+            },
+            // SAFETY: as in `save`; MXCSR in the area has no reserved bits
+            // set.
+            MODE_FXSAVE => unsafe {
+                core::arch::asm!(
+                    "fxrstor64 [{}]",
+                    in(reg) area,
+                    options(readonly, nostack, preserves_flags)
+                )
+            },
+            _ => {}
+        }
+    }
+}
+
+impl Default for FpuState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Exception handlers
+// ---------------------------------------------------------------------------
+
+/// #NM: an x87/SSE instruction ran with CR0.TS set.
+///
+/// Nothing sets TS yet; a lazy context switch would set it when switching
+/// tasks and restore the incoming task's [`FpuState`] here. For now the
+/// handler clears TS and re-executes the instruction. With CR0.EM set
+/// (`init` not run) the fault is fatal.
+fn device_not_available(_ctx: &mut InterruptContext) -> Disposition {
+    let cr0 = registers::read_cr0();
+    if cr0 & CR0_EM != 0 || cr0 & CR0_TS == 0 {
+        serial_println!("[FAIL] #NM with CR0={:#x}: FPU not initialised", cr0);
+        return Disposition::Fatal;
+    }
+    // SAFETY: `clts` only clears CR0.TS.
+    unsafe { core::arch::asm!("clts", options(nomem, nostack, preserves_flags)) };
+    Disposition::Resume
+}
+
+/// #MF: report the unmasked x87 exception. There are no user tasks to
+/// signal yet, so the fault is fatal.
+fn x87_fp_exception(ctx: &mut InterruptContext) -> Disposition {
+    let (fsw, fcw) = read_x87_status();
+    serial_println!(
+        "[FAIL] #MF in ring {}: {} (FSW={:#06x}, FCW={:#06x})",
+        ctx.frame.cs & 3,
+        decode_x87(fsw, fcw),
+        fsw,
+        fcw
+    );
+    Disposition::Fatal
+}
+
+/// #XM: report the unmasked SIMD exception. Fatal, as for #MF.
+fn simd_fp_exception(ctx: &mut InterruptContext) -> Disposition {
+    let mxcsr = read_mxcsr();
+    serial_println!(
+        "[FAIL] #XM in ring {}: {} (MXCSR={:#x})",
+        ctx.frame.cs & 3,
+        decode_mxcsr(mxcsr),
+        mxcsr
+    );
+    Disposition::Fatal
+}
+
+// ---------------------------------------------------------------------------
+// Self-test
+// ---------------------------------------------------------------------------
+
+/// Check that [`FpuState`] round-trips the control registers and that #NM
+/// is handled.
+///
+/// Saves the current state, changes the rounding mode in MXCSR and the
+/// x87 control word, restores and compares; then sets CR0.TS and executes
+/// `fnop`, which must come back through [`device_not_available`] with TS
+/// clear. Returns a short description of the failure.
+pub fn self_test() -> Result<(), &'static str> {
+    if MODE.load(Ordering::Acquire) == MODE_NONE {
+        return Err("FPU not initialised");
+    }
+    let mut state = FpuState::new();
+    state.save();
+    let (mxcsr, (_, fcw)) = (read_mxcsr(), read_x87_status());
+    // Rounding control: MXCSR bits 14:13, FCW bits 11:10.
+    write_mxcsr(mxcsr ^ (0b11 << 13));
+    write_fcw(fcw ^ (0b11 << 10));
+    state.restore();
+    if read_mxcsr() != mxcsr {
+        return Err("MXCSR not restored");
+    }
+    if read_x87_status().1 != fcw {
+        return Err("x87 control word not restored");
+    }
+
+    FpuState::new().restore();
+    if read_mxcsr() != MXCSR_DEFAULT || read_x87_status().1 != FCW_DEFAULT {
+        return Err("initial state has wrong control words");
+    }
+
+    // SAFETY: setting TS only makes the next x87/SSE instruction raise #NM,
+    // which `device_not_available` handles by clearing it again.
+    unsafe {
+        registers::write_cr0(registers::read_cr0() | CR0_TS);
+        core::arch::asm!("fnop", options(nomem, nostack, preserves_flags));
+    }
+    if registers::read_cr0() & CR0_TS != 0 {
+        return Err("#NM did not clear CR0.TS");
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Register access
+// ---------------------------------------------------------------------------
+
+/// Current x87 status and control words.
+fn read_x87_status() -> (u16, u16) {
+    let fsw: u16;
+    let mut fcw: u16 = 0;
+    // SAFETY: `fnstsw`/`fnstcw` only read x87 state (CR0.EM is clear after
+    // `init`, the only caller context) and write `fcw`.
+    unsafe {
+        core::arch::asm!("fnstsw ax", out("ax") fsw, options(nomem, nostack, preserves_flags));
+        core::arch::asm!(
+            "fnstcw [{}]",
+            in(reg) core::ptr::addr_of_mut!(fcw),
+            options(nostack, preserves_flags)
+        );
+    }
+    (fsw, fcw)
+}
+
+/// Load the x87 control word.
+fn write_fcw(fcw: u16) {
+    // SAFETY: `fldcw` only changes x87 control state; unmasking exceptions
+    // at most makes a later x87 instruction raise #MF.
+    unsafe {
+        core::arch::asm!(
+            "fldcw [{}]",
+            in(reg) core::ptr::addr_of!(fcw),
+            options(readonly, nostack, preserves_flags)
+        )
+    };
+}
+
+/// Current MXCSR. Requires CR4.OSFXSR.
+fn read_mxcsr() -> u32 {
+    let mut mxcsr: u32 = 0;
+    // SAFETY: `stmxcsr` only writes `mxcsr`; OSFXSR is set by `init` before
+    // any caller runs.
+    unsafe {
+        core::arch::asm!(
+            "stmxcsr [{}]",
+            in(reg) core::ptr::addr_of_mut!(mxcsr),
+            options(nostack, preserves_flags)
+        )
+    };
+    mxcsr
+}
+
+/// Load MXCSR. Requires CR4.OSFXSR; reserved bits must be clear.
+fn write_mxcsr(mxcsr: u32) {
+    // SAFETY: `ldmxcsr` only changes SSE control state; callers pass
+    // MXCSR_DEFAULT or a value read back with a control field flipped.
+    unsafe {
+        core::arch::asm!(
+            "ldmxcsr [{}]",
+            in(reg) core::ptr::addr_of!(mxcsr),
+            options(readonly, nostack, preserves_flags)
+        )
+    };
+}
+
+/// Write extended control register `xcr`.
+///
+/// # Safety
+///
+/// CR4.OSXSAVE must be set and `value` must be a valid setting for `xcr`.
+unsafe fn xsetbv(xcr: u32, value: u64) {
+    // SAFETY: forwarded to the caller.
+    unsafe {
+        core::arch::asm!(
+            "xsetbv",
+            in("ecx") xcr,
+            in("eax") value as u32,
+            in("edx") (value >> 32) as u32,
+            options(nomem, nostack, preserves_flags)
+        )
+    };
+}
@@ -8,10 +8,12 @@
 //!   64-bit mode (`L=1, D=0`) or legacy compatibility mode.
 //! - The **data segment** descriptors must be valid for `SS`, `DS`, `ES`, etc.
 //! - A **null descriptor** at index 0 is architecturally required.
-//! - The **TSS descriptor** (not in Phase 1) will be required for syscall and
-//!   interrupt handling once those subsystems are implemented.
+//! - The **TSS descriptor** points at the [`TaskStateSegment`], whose
+//!   Interrupt Stack Table gives #DF, NMI and #MC known-good stacks (IST1–3)
+//!   — a fault on a corrupted or overflowed stack is still reported instead
+//!   of triple-faulting.
 //!
-//! # GDT Layout (Phase 1 — minimal)
+//! # GDT Layout (Phase 1)
 //!
 //! ```text
 //! Index │ Selector │ Description
@@ -19,6 +21,7 @@
 //!   0   │  0x0000  │ Null descriptor (required)
 //!   1   │  0x0008  │ Kernel code segment (64-bit)
 //!   2   │  0x0010  │ Kernel data segment
+//!  3–4  │  0x0018  │ TSS (16-byte system descriptor)
 //! ```
 //!
 //! # Segment descriptor bit layout
@@ -51,12 +54,10 @@
 //! - Bit 41 = writable (1 = writeable)
 //! - Bit 40 = accessed (set by CPU on first use)
 //!
-//! # Phase notes
-//!
-//! In Phase 1, the `GDT` static and `init()` function are mirrored inline in
-//! `boot/src/main.rs` (since the bootloader and kernel run as the same binary).
-//! When the kernel becomes a separate ELF binary this module becomes the
-//! authoritative source and the duplicate in the boot crate is removed.
+//! The TSS descriptor is a *system* descriptor (S=0, Type=0x9 "available
+//! 64-bit TSS") and occupies two slots: the second holds base bits 63:32.
+
+use super::stack::{DOUBLE_FAULT_STACK, IST_STACK_SIZE, MACHINE_CHECK_STACK, NMI_STACK};
 
 // ---------------------------------------------------------------------------
 // Descriptor values
@@ -104,25 +105,107 @@ pub const KERNEL_CODE_SELECTOR: u16 = 0x08;
 /// Kernel data segment selector: GDT index 2, TI=0 (GDT), RPL=0.
 pub const KERNEL_DATA_SELECTOR: u16 = 0x10;
 
+/// TSS selector: GDT index 3 (spanning 3–4), TI=0 (GDT), RPL=0.
+pub const TSS_SELECTOR: u16 = 0x18;
+
+// ---------------------------------------------------------------------------
+// Task State Segment
+// ---------------------------------------------------------------------------
+
+// IST slots are 1-based, as stored in `IdtEntry::ist`; 0 means "no switch".
+
+/// IST slot used by the #DF (vector 8) gate.
+pub const DOUBLE_FAULT_IST_INDEX: u8 = 1;
+
+/// IST slot used by the NMI (vector 2) gate.
+pub const NMI_IST_INDEX: u8 = 2;
+
+/// IST slot used by the #MC (vector 18) gate.
+pub const MACHINE_CHECK_IST_INDEX: u8 = 3;
+
+/// 64-bit Task State Segment (Intel SDM Vol 3A §8.7).
+///
+/// Long mode does not use hardware task switching; the TSS only supplies
+/// stack pointers — RSP0–2 for privilege changes and IST1–7 for IDT gates
+/// that request a dedicated stack.
+#[repr(C, packed(4))]
+pub struct TaskStateSegment {
+    reserved_1: u32,
+    /// RSP0–RSP2: stacks loaded on a transition to CPL 0–2.
+    pub privilege_stack_table: [u64; 3],
+    reserved_2: u64,
+    /// IST1–IST7 (index 0 here is IST1).
+    pub interrupt_stack_table: [u64; 7],
+    reserved_3: u64,
+    reserved_4: u16,
+    /// Offset of the I/O permission bitmap; `size_of::<TaskStateSegment>()`
+    /// means "no bitmap".
+    pub iomap_base: u16,
+}
+
+impl TaskStateSegment {
+    /// A TSS with every stack pointer zero and no I/O bitmap.
+    pub const fn new() -> Self {
+        Self {
+            reserved_1: 0,
+            privilege_stack_table: [0; 3],
+            reserved_2: 0,
+            interrupt_stack_table: [0; 7],
+            reserved_3: 0,
+            reserved_4: 0,
+            iomap_base: core::mem::size_of::<TaskStateSegment>() as u16,
+        }
+    }
+}
+
+impl Default for TaskStateSegment {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The kernel's TSS.
+///
+/// SAFETY: written only by [`init`] before `LTR`; afterwards read by the CPU.
+static mut TSS: TaskStateSegment = TaskStateSegment::new();
+
+/// Encode the two GDT slots of an available 64-bit TSS descriptor.
+fn tss_descriptor(base: u64) -> [u64; 2] {
+    let limit = (core::mem::size_of::<TaskStateSegment>() - 1) as u64;
+    let low = (limit & 0xFFFF)
+        | ((base & 0xFF_FFFF) << 16)
+        | (0x9 << 40) // Type = available 64-bit TSS, S=0
+        | (1 << 47) // P
+        | (((limit >> 16) & 0xF) << 48)
+        | (((base >> 24) & 0xFF) << 56);
+    [low, base >> 32]
+}
+
 // ---------------------------------------------------------------------------
 // GDT structure
 // ---------------------------------------------------------------------------
 
 /// The kernel's Global Descriptor Table.
 ///
-/// Three entries: null, kernel code, kernel data. Aligned to 8 bytes so the
-/// CPU can fetch descriptors with a single aligned load.
+/// Five slots: null, kernel code, kernel data and the two-slot TSS
+/// descriptor. Aligned to 8 bytes so the CPU can fetch descriptors with a
+/// single aligned load.
 #[repr(C, align(8))]
-pub struct Gdt([u64; 3]);
+pub struct Gdt([u64; 5]);
 
 /// The kernel's static GDT instance.
 ///
 /// Declared `static` so the CPU can reference it indefinitely after the LGDT
-/// instruction executes. The `GdtPointer` below holds its address.
-pub static GDT: Gdt = Gdt([
+/// instruction executes. The TSS slots depend on the address of [`TSS`],
+/// which is not a compile-time constant, so [`init`] fills them in.
+///
+/// SAFETY: written only by [`init`] before `LGDT`.
+pub static mut GDT: Gdt = Gdt([
     NULL_DESCRIPTOR,
     KERNEL_CODE_DESCRIPTOR,
     KERNEL_DATA_DESCRIPTOR,
+    0,
+    0,
 ]);
 
 // ---------------------------------------------------------------------------
@@ -150,16 +233,20 @@ pub struct GdtPointer {
 
 /// Load the GDT and reload all segment registers.
 ///
-/// After this function returns, the CPU is executing with the new GDT active
-/// and all segment registers pointing to the Phase-1 kernel segments.
+/// After this function returns, the CPU is executing with the new GDT active,
+/// all segment registers pointing to the Phase-1 kernel segments, and the
+/// TSS loaded into TR.
 ///
 /// # Steps
 ///
+/// 0. Point IST1–3 at the #DF / NMI / #MC stacks and write the TSS
+///    descriptor.
 /// 1. Execute `LGDT` to load the GDT register with the address and size of
 ///    [`GDT`].
 /// 2. Reload `CS` via a far return (`RETFQ`) — the only reliable way to
 ///    change the code segment in 64-bit mode without a task switch.
 /// 3. Reload `DS`, `ES`, `FS`, `GS`, `SS` with [`KERNEL_DATA_SELECTOR`].
+/// 4. Load TR with [`TSS_SELECTOR`].
 ///
 /// # Safety
 ///
@@ -171,7 +258,34 @@ pub struct GdtPointer {
 /// - The [`GDT`] static must have been placed in memory that is mapped and
 ///   accessible at its linear address for the lifetime of the CPU's operation
 ///   with this GDT loaded.
+/// - Must be called at most once: `LTR` marks the TSS descriptor busy and a
+///   second `LTR` on it raises #GP.
 pub unsafe fn init() {
+    // Step 0: IST1–3 and the TSS descriptor.
+    //
+    // SAFETY: single-threaded early boot; TSS and GDT are not yet in use by
+    // the CPU, and no other reference to either exists. Only the addresses
+    // of the stack statics are taken.
+    let tss = &mut *core::ptr::addr_of_mut!(TSS);
+    let ist_stacks = [
+        (
+            DOUBLE_FAULT_IST_INDEX,
+            core::ptr::addr_of!(DOUBLE_FAULT_STACK) as u64,
+        ),
+        (NMI_IST_INDEX, core::ptr::addr_of!(NMI_STACK) as u64),
+        (
+            MACHINE_CHECK_IST_INDEX,
+            core::ptr::addr_of!(MACHINE_CHECK_STACK) as u64,
+        ),
+    ];
+    for (index, bottom) in ist_stacks {
+        tss.interrupt_stack_table[index as usize - 1] = bottom + IST_STACK_SIZE as u64;
+    }
+    let gdt = &mut *core::ptr::addr_of_mut!(GDT);
+    let [low, high] = tss_descriptor(core::ptr::addr_of!(TSS) as u64);
+    gdt.0[3] = low;
+    gdt.0[4] = high;
+
     let ptr = GdtPointer {
         limit: (core::mem::size_of::<Gdt>() - 1) as u16,
         base: core::ptr::addr_of!(GDT) as u64,
@@ -231,4 +345,14 @@ pub unsafe fn init() {
         in("ax") KERNEL_DATA_SELECTOR,
         options(nomem, nostack, preserves_flags),
     );
+
+    // Step 4: Load the task register.
+    //
+    // SAFETY: TSS_SELECTOR indexes the available-TSS descriptor written in
+    // step 0; LTR marks it busy in the GDT (hence no `nomem`).
+    core::arch::asm!(
+        "ltr {sel:x}",
+        sel = in(reg) TSS_SELECTOR,
+        options(nostack, preserves_flags),
+    );
 }
@@ -52,16 +52,17 @@
 //!
 //! Vectors that push an error code: 8, 10, 11, 12, 13, 14, 17, 21, 28, 29, 30.
 //!
-//! # Phase notes
+//! # Handlers
 //!
-//! In Phase 1, the `IDT` static and `idt_init()` live in `boot/src/main.rs`
-//! (same binary as the bootloader). When the kernel becomes a separate ELF the
-//! IDT moves here and the duplicate in `boot` is removed.
-//!
-//! Phase 1 installs stub handlers for all 32 CPU exception vectors and a
-//! generic stub for IRQ vectors 32–255. All stubs print the vector name over
-//! serial and halt (`HLT` loop). Interrupts are **not enabled** (`STI` is not
-//! called); the IDT is ready for CPU exceptions only.
+//! [`init`] installs the per-vector stubs from the
+//! [`exceptions`](super::exceptions) module for all 256 vectors; the NMI,
+//! #DF and #MC gates switch to IST2, IST1 and IST3 so they can still be
+//! reported from a broken stack. The stubs hand every vector to the
+//! [`interrupts`](super::interrupts) registry, which either resumes via
+//! `IRETQ` or prints the fatal report and halts. Interrupts are **not
+//! enabled** (`STI` is not called).
+
+use super::gdt;
 
 // ---------------------------------------------------------------------------
 // Gate type constants
@@ -89,7 +90,13 @@ const KERNEL_CS: u16 = 0x0008;
 /// A single IDT gate descriptor (16 bytes).
 ///
 /// Use [`IdtEntry::new`] to create a configured entry or [`IdtEntry::missing`]
-/// for an empty (not-present) placeholder.
+/// for an empty (not-present) placeholder. The `with_*` builder methods
+/// adjust an entry from `new`:
+///
+/// ```ignore
+/// idt.0[8] = IdtEntry::new(handler).with_ist(gdt::DOUBLE_FAULT_IST_INDEX);
+/// idt.0[3] = IdtEntry::new(handler).with_gate_type(GATE_TRAP).with_dpl(3);
+/// ```
 #[repr(C, packed)]
 #[derive(Clone, Copy)]
 pub struct IdtEntry {
@@ -101,7 +108,7 @@ pub struct IdtEntry {
     ///
     /// `0` = use the current stack (RSP0 from TSS, or the interrupted stack
     /// if already at ring 0). Values 1–7 switch to the corresponding IST
-    /// stack from the TSS — required for double-fault handlers in Phase 1.3+.
+    /// stack from the TSS — see [`IdtEntry::with_ist`].
     pub ist: u8,
     /// Gate type and attributes: `P | DPL[1:0] | 0 | Type[3:0]`.
     ///
@@ -158,6 +165,51 @@ impl IdtEntry {
             reserved: 0,
         }
     }
+
+    /// Switch to IST stack `index` (1–7) when this gate fires; `0` keeps the
+    /// current stack.
+    ///
+    /// # Panics
+    ///
+    /// If `index > 7`.
+    pub const fn with_ist(mut self, index: u8) -> Self {
+        assert!(index <= 7, "IST index must be 0-7");
+        self.ist = index;
+        self
+    }
+
+    /// Use gate type `gate` ([`GATE_INTERRUPT`] or [`GATE_TRAP`]).
+    ///
+    /// # Panics
+    ///
+    /// If `gate` is neither.
+    pub const fn with_gate_type(mut self, gate: u8) -> Self {
+        assert!(
+            gate == GATE_INTERRUPT || gate == GATE_TRAP,
+            "gate type must be 0xE or 0xF"
+        );
+        self.type_attr = (self.type_attr & 0xF0) | gate;
+        self
+    }
+
+    /// Set the descriptor privilege level — the lowest CPL allowed to reach
+    /// this gate with `int n` (3 for breakpoints / system calls).
+    ///
+    /// # Panics
+    ///
+    /// If `dpl > 3`.
+    pub const fn with_dpl(mut self, dpl: u8) -> Self {
+        assert!(dpl <= 3, "DPL must be 0-3");
+        self.type_attr = (self.type_attr & !0x60) | (dpl << 5);
+        self
+    }
+
+    /// Handler address encoded in the entry.
+    pub const fn handler_address(&self) -> u64 {
+        (self.offset_low as u64)
+            | ((self.offset_mid as u64) << 16)
+            | ((self.offset_high as u64) << 32)
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -199,10 +251,11 @@ pub struct IdtPointer {
 /// code is pushed **below** the saved RIP — i.e., at `RSP` and this frame
 /// starts at `RSP + 8`.
 ///
-/// The compiler may not generate correct code for reads from a `#[repr(C)]`
-/// struct pointed to by a stack pointer obtained from asm, so callers should
-/// use raw pointer reads or the `x86-interrupt` ABI (nightly).
+/// Handlers see it as the tail of
+/// [`InterruptContext`](super::interrupts::InterruptContext); `IRETQ`
+/// restores RIP, CS, RFLAGS, RSP and SS from it.
 #[repr(C)]
+#[derive(Debug, Clone, Copy)]
 pub struct ExceptionFrame {
     /// Instruction pointer to return to after `IRETQ`.
     pub rip: u64,
@@ -246,3 +299,45 @@ pub unsafe fn load(idt: &'static Idt) {
         options(readonly, nostack, preserves_flags),
     );
 }
+
+// ---------------------------------------------------------------------------
+// Kernel IDT
+// ---------------------------------------------------------------------------
+
+/// The kernel IDT — 256 entries, 16-byte aligned.
+///
+/// SAFETY: populated exactly once in [`init`] before `LIDT` is called,
+/// then treated as read-only by the CPU.
+static mut IDT: Idt = Idt([IdtEntry::missing(); 256]);
+
+/// Populate the kernel IDT with the exception stubs and load the IDTR.
+///
+/// # Safety
+///
+/// - Must be called at CPL=0.
+/// - Interrupts must be disabled.
+/// - Must be called at most once (reinitialising IDTR while interrupts are
+///   disabled is safe, but the previous IDT is abandoned).
+pub unsafe fn init() {
+    // SAFETY: single-threaded early boot with interrupts disabled; no other
+    // reference to IDT exists and the CPU does not read it until LIDT below.
+    let idt = &mut *core::ptr::addr_of_mut!(IDT);
+
+    // --- Install the entry stubs for all 256 vectors ---
+    //
+    // NMI, #DF and #MC switch to their own IST stacks: each can arrive while
+    // the current stack is overflowed, corrupt or half-switched.
+    for (vector, entry) in idt.0.iter_mut().enumerate() {
+        let vector = vector as u8;
+        let ist = match vector {
+            2 => gdt::NMI_IST_INDEX,
+            8 => gdt::DOUBLE_FAULT_IST_INDEX,
+            18 => gdt::MACHINE_CHECK_IST_INDEX,
+            _ => 0,
+        };
+        *entry = IdtEntry::new(super::exceptions::stub_address(vector)).with_ist(ist);
+    }
+
+    // SAFETY: IDT is a valid static, fully populated above, never moved.
+    load(&*core::ptr::addr_of!(IDT));
+}
@@ -0,0 +1,211 @@
+//! Interrupt dispatch and handler registration.
+//!
+//! Every vector enters through the stubs in [`exceptions`](super::exceptions),
+//! which save the interrupted state as an [`InterruptContext`] and call
+//! [`dispatch`]. Subsystems claim vectors with [`register`]; a handler may
+//! inspect and modify the context and then either resume the interrupted
+//! code (`IRETQ` with the updated context) or declare the event fatal.
+//!
+//! Vectors without a handler — and handlers that return
+//! [`Disposition::Fatal`] — end in the fatal report, exactly as before the
+//! registry existed.
+//!
+//! # Usage
+//!
+//! ```ignore
+//! fn on_breakpoint(ctx: &mut InterruptContext) -> Disposition {
+//!     serial_println!("int3 at {:#x}", ctx.frame.rip - 1);
+//!     Disposition::Resume // RIP already points past the 1-byte `int3`
+//! }
+//!
+//! interrupts::register(BREAKPOINT_VECTOR, on_breakpoint)?;
+//! ```
+//!
+//! # Concurrency
+//!
+//! The table is an array of atomics, so [`register`] never takes a lock an
+//! interrupt handler could be waiting on. All gates are interrupt gates:
+//! handlers run with IF clear.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use super::exceptions;
+use super::idt::ExceptionFrame;
+use crate::serial_println;
+
+/// #BP — raised by `int3`.
+pub const BREAKPOINT_VECTOR: u8 = 3;
+
+/// #NM — x87/SSE instruction with CR0.EM or CR0.TS set.
+pub const DEVICE_NOT_AVAILABLE_VECTOR: u8 = 7;
+
+/// #PF — page fault; CR2 holds the faulting address.
+pub const PAGE_FAULT_VECTOR: u8 = 14;
+
+/// #MF — unmasked x87 floating-point exception.
+pub const X87_FP_VECTOR: u8 = 16;
+
+/// #XM — unmasked SIMD floating-point exception.
+pub const SIMD_FP_VECTOR: u8 = 19;
+
+// ---------------------------------------------------------------------------
+// Interrupt context
+// ---------------------------------------------------------------------------
+
+/// State saved by the entry stubs, lowest address first.
+///
+/// The layout mirrors the push order in `__interrupt_common` and must not
+/// change without updating the assembly. All sixteen general-purpose
+/// registers are covered (RSP through `frame.rsp`). Handlers may modify any
+/// field; the stub restores every register and `IRETQ`s through `frame`.
+#[repr(C)]
+#[derive(Debug)]
+pub struct InterruptContext {
+    /// Saved R15.
+    pub r15: u64,
+    /// Saved R14.
+    pub r14: u64,
+    /// Saved R13.
+    pub r13: u64,
+    /// Saved R12.
+    pub r12: u64,
+    /// Saved R11.
+    pub r11: u64,
+    /// Saved R10.
+    pub r10: u64,
+    /// Saved R9.
+    pub r9: u64,
+    /// Saved R8.
+    pub r8: u64,
+    /// Saved RBP.
+    pub rbp: u64,
+    /// Saved RDI.
+    pub rdi: u64,
+    /// Saved RSI.
+    pub rsi: u64,
+    /// Saved RDX.
+    pub rdx: u64,
+    /// Saved RCX.
+    pub rcx: u64,
+    /// Saved RBX.
+    pub rbx: u64,
+    /// Saved RAX.
+    pub rax: u64,
+    /// Vector number pushed by the stub.
+    pub vector: u64,
+    /// CPU-pushed error code, or 0 for vectors without one.
+    pub error_code: u64,
+    /// CPU-pushed return frame; `IRETQ` resumes at `frame.rip`.
+    pub frame: ExceptionFrame,
+}
+
+/// What the dispatcher should do after a handler returns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Disposition {
+    /// Return to the interrupted code with the (possibly updated) context.
+    Resume,
+    /// The event cannot be recovered from; print the fatal report and halt.
+    Fatal,
+}
+
+/// An interrupt handler.
+pub type InterruptHandler = fn(&mut InterruptContext) -> Disposition;
+
+/// Errors returned by [`register`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegisterError {
+    /// Another handler already owns the vector.
+    AlreadyRegistered {
+        /// The contested vector.
+        vector: u8,
+    },
+}
+
+// ---------------------------------------------------------------------------
+// Registry
+// ---------------------------------------------------------------------------
+
+/// Handler table: a function pointer as `usize`, or 0 for "none".
+static HANDLERS: [AtomicUsize; 256] = [const { AtomicUsize::new(0) }; 256];
+
+/// Install `handler` for `vector`.
+///
+/// # Errors
+///
+/// [`RegisterError::AlreadyRegistered`] if the vector already has a
+/// handler; use [`unregister`] first to replace it.
+pub fn register(vector: u8, handler: InterruptHandler) -> Result<(), RegisterError> {
+    HANDLERS[vector as usize]
+        .compare_exchange(0, handler as usize, Ordering::AcqRel, Ordering::Acquire)
+        .map(|_| ())
+        .map_err(|_| RegisterError::AlreadyRegistered { vector })
+}
+
+/// Remove the handler for `vector`, returning it if there was one.
+pub fn unregister(vector: u8) -> Option<InterruptHandler> {
+    let raw = HANDLERS[vector as usize].swap(0, Ordering::AcqRel);
+    handler_from_raw(raw)
+}
+
+/// True if `vector` has a handler.
+pub fn is_registered(vector: u8) -> bool {
+    HANDLERS[vector as usize].load(Ordering::Acquire) != 0
+}
+
+fn handler_from_raw(raw: usize) -> Option<InterruptHandler> {
+    if raw == 0 {
+        return None;
+    }
+    // SAFETY: non-zero entries are only ever written by `register`, from a
+    // valid `InterruptHandler`; function pointers and `usize` have the same
+    // size on x86-64.
+    Some(unsafe { core::mem::transmute::<usize, InterruptHandler>(raw) })
+}
+
+// ---------------------------------------------------------------------------
+// Dispatch
+// ---------------------------------------------------------------------------
+
+/// Called by `__interrupt_common` for every vector.
+///
+/// Returns to the stub (which `IRETQ`s) only if a handler chose
+/// [`Disposition::Resume`].
+///
+/// # Safety (caller — the asm stub)
+///
+/// - `ctx` points to the [`InterruptContext`] the stub just built on the
+///   current stack; it is valid and exclusive for this call.
+/// - Must be `#[no_mangle]` so the linker name matches the `call` in asm.
+#[no_mangle]
+extern "C" fn interrupt_dispatch(ctx: &mut InterruptContext) {
+    let handler = handler_from_raw(HANDLERS[ctx.vector as usize & 0xFF].load(Ordering::Acquire));
+    if let Some(handler) = handler {
+        if handler(ctx) == Disposition::Resume {
+            return;
+        }
+    }
+    exceptions::fatal(ctx)
+}
+
+// ---------------------------------------------------------------------------
+// Built-in handlers
+// ---------------------------------------------------------------------------
+
+/// #BP: log and continue.
+///
+/// `int3` is a trap, so the saved RIP already points past the 1-byte
+/// instruction and resuming steps over it.
+fn breakpoint(ctx: &mut InterruptContext) -> Disposition {
+    serial_println!("[INFO] Breakpoint (int3) at {:#x}", ctx.frame.rip - 1);
+    Disposition::Resume
+}
+
+/// Install the kernel's built-in handlers (currently #BP).
+///
+/// # Panics
+///
+/// If a built-in vector is already taken — `init` runs once, before any
+/// subsystem registers handlers.
+pub fn init() {
+    register(BREAKPOINT_VECTOR, breakpoint).expect("#BP handler already registered");
+}
@@ -1,6 +1,42 @@
 //! x86-64 architecture support.
 
+pub mod cpu;
+pub mod cpuid;
 pub mod entry;
+pub mod error_code;
+pub mod exceptions;
+pub mod fpu;
 pub mod gdt;
 pub mod idt;
+pub mod interrupts;
+pub mod protection;
+pub mod registers;
 pub mod stack;
+pub mod usercopy;
+
+/// Halt the CPU permanently.
+///
+/// With interrupts disabled `hlt` never wakes; if an NMI — or, once
+/// interrupts are enabled, any handled interrupt — does wake it, the loop
+/// puts the CPU straight back to sleep.
+pub fn halt() -> ! {
+    loop {
+        // SAFETY: `hlt` suspends the CPU until the next interrupt. It has no
+        // memory effects and is valid at CPL=0.
+        unsafe { core::arch::asm!("hlt", options(nomem, nostack)) };
+    }
+}
+
+/// Run `f` with maskable interrupts disabled, restoring the previous state
+/// of RFLAGS.IF afterwards.
+pub fn without_interrupts<R>(f: impl FnOnce() -> R) -> R {
+    let rflags: u64;
+    // SAFETY: `pushfq; pop` reads RFLAGS; `cli` only masks interrupts.
+    unsafe { core::arch::asm!("pushfq", "pop {}", "cli", out(reg) rflags, options(nomem)) };
+    let result = f();
+    if rflags & (1 << 9) != 0 {
+        // SAFETY: interrupts were enabled on entry; re-enabling restores that.
+        unsafe { core::arch::asm!("sti", options(nomem, nostack)) };
+    }
+    result
+}
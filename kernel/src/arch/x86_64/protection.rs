@@ -0,0 +1,361 @@
+//! Kernel memory protection (W^X) and the CPU features that enforce it.
+//!
+//! [`init`] turns on every protection the CPU offers:
+//!
+//! | Feature | Register bit | CPUID | Effect |
+//! |---------|--------------|-------|--------|
+//! | WP   | CR0.16   | always            | supervisor writes honour read-only pages |
+//! | NXE  | EFER.11  | 8000_0001h EDX.20 | `NO_EXECUTE` pages cannot be fetched from |
+//! | SMEP | CR4.20   | 07h EBX.7         | no supervisor execution from user pages |
+//! | SMAP | CR4.21   | 07h EBX.20        | no supervisor data access to user pages unless RFLAGS.AC is set (see [`usercopy`](super::usercopy)) |
+//! | UMIP | CR4.11   | 07h ECX.2         | SGDT, SIDT, SLDT, SMSW and STR fault outside ring 0 |
+//!
+//! [`protect_kernel_image`] then applies W^X to the kernel image using the
+//! section bounds from the linker script: `.text` read-execute, `.rodata`
+//! read-only, `.data` and `.bss` read-write; the last two and the direct map
+//! also become non-executable. Only `WRITABLE` and `NO_EXECUTE` change;
+//! `GLOBAL`, the caching bits and the rest of each entry are kept. Heap
+//! pages are mapped with [`paging::kernel_data_flags`], which includes
+//! `NO_EXECUTE` from then on.
+//!
+//! The direct map aliases the image's frames too. The huge pages covering
+//! `.text` and `.rodata` there are split, and their 4 KiB aliases made
+//! read-only, so the image cannot be patched through the direct map either.
+//! [`self_test`] writes to `.text` through both mappings and expects a #PF
+//! each time.
+
+use core::fmt;
+use core::ops::Range;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use super::cpu::{self, Feature};
+use super::entry;
+use super::error_code::{PF_PRESENT, PF_WRITE};
+use super::interrupts::{self, Disposition, InterruptContext, PAGE_FAULT_VECTOR};
+use super::registers::{self, IA32_EFER};
+use crate::memory::paging::{self, MapError, PageTableFlags, UnmapError, VirtAddr, PAGE_SIZE};
+use crate::memory::{phys_to_virt, DIRECT_MAP_BASE};
+
+/// CR0.WP — supervisor writes honour read-only pages.
+const CR0_WP: u64 = 1 << 16;
+/// EFER.NXE — enables the `NO_EXECUTE` page table bit.
+const EFER_NXE: u64 = 1 << 11;
+/// CR4.UMIP — user-mode instruction prevention.
+const CR4_UMIP: u64 = 1 << 11;
+/// CR4.SMEP — supervisor-mode execution prevention.
+const CR4_SMEP: u64 = 1 << 20;
+/// CR4.SMAP — supervisor-mode access prevention.
+const CR4_SMAP: u64 = 1 << 21;
+
+/// The bits [`protect_kernel_image`] sets or clears in a leaf entry.
+const PERMISSION_BITS: PageTableFlags = PageTableFlags::from_bits_truncate(
+    PageTableFlags::WRITABLE.bits() | PageTableFlags::NO_EXECUTE.bits(),
+);
+
+/// Set by [`init`] once EFER.NXE is on.
+static NX_ENABLED: AtomicBool = AtomicBool::new(false);
+/// Set by [`init`] once CR4.SMAP is on; `stac`/`clac` are #UD without it.
+static SMAP_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Protection features enabled by [`init`]; CR0.WP is always on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Protection {
+    /// EFER.NXE.
+    pub nx: bool,
+    /// CR4.SMEP.
+    pub smep: bool,
+    /// CR4.SMAP.
+    pub smap: bool,
+    /// CR4.UMIP.
+    pub umip: bool,
+}
+
+/// Pages whose permissions [`protect_kernel_image`] changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageProtection {
+    /// 4 KiB `.text` pages, now read-execute.
+    pub text_pages: usize,
+    /// 4 KiB `.rodata` pages, now read-only.
+    pub rodata_pages: usize,
+    /// 4 KiB `.data`/`.bss` pages, now read-write (and NX).
+    pub data_pages: usize,
+    /// Direct-map pages made non-executable; 0 without NX.
+    pub direct_map_pages: usize,
+    /// 4 KiB direct-map aliases of `.text` and `.rodata`, now read-only.
+    pub alias_pages: usize,
+}
+
+/// Errors returned by [`protect_kernel_image`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProtectError {
+    /// A page's flags could not be changed.
+    Update(UnmapError),
+    /// A direct-map huge page over the image could not be split.
+    Split(MapError),
+}
+
+impl From<UnmapError> for ProtectError {
+    fn from(e: UnmapError) -> Self {
+        ProtectError::Update(e)
+    }
+}
+
+impl From<MapError> for ProtectError {
+    fn from(e: MapError) -> Self {
+        ProtectError::Split(e)
+    }
+}
+
+impl fmt::Display for ProtectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtectError::Update(e) => write!(f, "cannot update page flags: {:?}", e),
+            ProtectError::Split(e) => write!(f, "cannot split direct-map page: {:?}", e),
+        }
+    }
+}
+
+/// Enable CR0.WP and whichever of NXE, SMEP, SMAP and UMIP the CPU
+/// supports, as reported by [`cpu`]. Before [`cpu::init`] only CR0.WP is
+/// turned on.
+///
+/// # Safety
+///
+/// - Interrupts must be disabled.
+/// - No page the kernel writes to may be mapped read-only, no page it
+///   executes may be user-accessible, and no page it accesses outside
+///   [`usercopy`](super::usercopy) may be user-accessible.
+pub unsafe fn init() -> Protection {
+    let protection = Protection {
+        nx: cpu::has(Feature::NX),
+        smep: cpu::has(Feature::SMEP),
+        smap: cpu::has(Feature::SMAP),
+        umip: cpu::has(Feature::UMIP),
+    };
+
+    let mut cr4 = registers::read_cr4();
+    for (enabled, bit) in [
+        (protection.smep, CR4_SMEP),
+        (protection.smap, CR4_SMAP),
+        (protection.umip, CR4_UMIP),
+    ] {
+        if enabled {
+            cr4 |= bit;
+        }
+    }
+    // SAFETY: only bits for features CPUID reports are set; the caller
+    // guarantees the mappings are compatible with them.
+    unsafe {
+        registers::write_cr0(registers::read_cr0() | CR0_WP);
+        registers::write_cr4(cr4);
+        if protection.nx {
+            registers::wrmsr(IA32_EFER, registers::read_efer() | EFER_NXE);
+        }
+    }
+    NX_ENABLED.store(protection.nx, Ordering::Relaxed);
+    SMAP_ENABLED.store(protection.smap, Ordering::Relaxed);
+    protection
+}
+
+/// True once [`init`] has enabled EFER.NXE.
+pub fn nx_enabled() -> bool {
+    NX_ENABLED.load(Ordering::Relaxed)
+}
+
+/// True once [`init`] has enabled CR4.SMAP.
+pub fn smap_enabled() -> bool {
+    SMAP_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Apply W^X to the kernel image and make the direct map non-executable,
+/// with read-only aliases of `.text` and `.rodata`.
+///
+/// # Errors
+///
+/// The first page whose flags could not be changed or whose direct-map
+/// huge page could not be split; pages before it keep their new
+/// permissions.
+///
+/// # Safety
+///
+/// - Interrupts must be disabled; [`init`] and the frame allocator must
+///   have run, and the identity map must be gone (it shares tables with
+///   the direct map).
+/// - Nothing may write to `.text` or `.rodata`, through either mapping, or
+///   execute from `.data`, `.bss` or the direct map, afterwards.
+pub unsafe fn protect_kernel_image() -> Result<ImageProtection, ProtectError> {
+    let sections = entry::kernel_sections();
+    let nx = if nx_enabled() {
+        PageTableFlags::NO_EXECUTE
+    } else {
+        PageTableFlags::empty()
+    };
+    // `.text` and `.rodata` are adjacent (linker.ld).
+    let read_only = sections.text.start..sections.rodata.end;
+    // SAFETY: forwarded to the caller; the sections are mapped with 4 KiB
+    // pages by the bootloader. The direct map is made NX before the aliases
+    // are split, so the 4 KiB aliases inherit `NO_EXECUTE`.
+    let protection = unsafe {
+        ImageProtection {
+            text_pages: update_range(sections.text, PageTableFlags::empty())?,
+            rodata_pages: update_range(sections.rodata, nx)?,
+            data_pages: update_range(sections.data, paging::kernel_data_flags())?,
+            direct_map_pages: if nx_enabled() {
+                protect_direct_map()?
+            } else {
+                0
+            },
+            alias_pages: protect_aliases(read_only, nx)?,
+        }
+    };
+    Ok(protection)
+}
+
+/// Set the permission bits of every 4 KiB page overlapping `range` to
+/// `flags`; returns the count.
+///
+/// # Safety
+///
+/// See [`paging::update_flags`].
+unsafe fn update_range(range: Range<u64>, flags: PageTableFlags) -> Result<usize, UnmapError> {
+    let end = range.end.next_multiple_of(PAGE_SIZE);
+    let mut pages = 0;
+    for addr in (range.start..end).step_by(PAGE_SIZE as usize) {
+        // SAFETY: forwarded to the caller.
+        unsafe { set_permissions(VirtAddr::new_truncate(addr), flags) }?;
+        pages += 1;
+    }
+    Ok(pages)
+}
+
+/// Replace the [`PERMISSION_BITS`] of the page starting at `virt` with
+/// those in `flags`, keeping every other bit of the entry.
+///
+/// # Safety
+///
+/// See [`paging::update_flags`].
+unsafe fn set_permissions(virt: VirtAddr, flags: PageTableFlags) -> Result<(), UnmapError> {
+    let t = paging::translate(virt).ok_or(UnmapError::NotMapped)?;
+    let flags = (t.flags & !PERMISSION_BITS) | (flags & PERMISSION_BITS);
+    // SAFETY: forwarded to the caller.
+    unsafe { paging::update_flags(virt, flags) }?;
+    Ok(())
+}
+
+/// Split the direct-map pages aliasing the kernel image pages in `range`
+/// down to 4 KiB and make each alias read-only, plus `nx`; returns the
+/// number of aliases.
+///
+/// # Safety
+///
+/// See [`paging::update_flags`]; nothing may write to the aliased frames
+/// through the direct map.
+unsafe fn protect_aliases(range: Range<u64>, nx: PageTableFlags) -> Result<usize, ProtectError> {
+    let end = range.end.next_multiple_of(PAGE_SIZE);
+    let mut pages = 0;
+    for addr in (range.start..end).step_by(PAGE_SIZE as usize) {
+        let phys =
+            paging::translate_addr(VirtAddr::new_truncate(addr)).ok_or(UnmapError::NotMapped)?;
+        let alias = VirtAddr::new_truncate(phys_to_virt(phys.as_u64()));
+        // SAFETY: forwarded to the caller; the split keeps translations.
+        unsafe {
+            paging::split_huge_page(alias)?;
+            set_permissions(alias, nx)?;
+        }
+        pages += 1;
+    }
+    Ok(pages)
+}
+
+/// Add `NO_EXECUTE` to every page of the direct map, which the bootloader
+/// maps contiguously from [`DIRECT_MAP_BASE`].
+///
+/// # Safety
+///
+/// See [`paging::update_flags`]; EFER.NXE must be on.
+unsafe fn protect_direct_map() -> Result<usize, UnmapError> {
+    let mut virt = DIRECT_MAP_BASE;
+    let mut pages = 0;
+    while let Some(t) = paging::translate(VirtAddr::new_truncate(virt)) {
+        // SAFETY: forwarded to the caller.
+        unsafe {
+            paging::update_flags(
+                VirtAddr::new_truncate(virt),
+                t.flags | PageTableFlags::NO_EXECUTE,
+            )
+        }?;
+        pages += 1;
+        virt += t.size.bytes();
+    }
+    Ok(pages)
+}
+
+// ---------------------------------------------------------------------------
+// Self-test
+// ---------------------------------------------------------------------------
+
+/// Address [`self_test`] expects to fault on; 0 when no probe is running.
+static PROBE_ADDR: AtomicU64 = AtomicU64::new(0);
+/// Error code of the probe's #PF with [`PROBE_FAULTED`] set; 0 if none.
+static PROBE_RESULT: AtomicU64 = AtomicU64::new(0);
+/// Marks [`PROBE_RESULT`] as written (error codes may be 0).
+const PROBE_FAULTED: u64 = 1 << 63;
+
+/// #PF handler for the probe: resume at the address the probe left in RAX.
+fn probe_fault(ctx: &mut InterruptContext) -> Disposition {
+    let target = PROBE_ADDR.load(Ordering::Relaxed);
+    if target == 0 || registers::read_cr2() != target {
+        return Disposition::Fatal;
+    }
+    PROBE_RESULT.store(ctx.error_code | PROBE_FAULTED, Ordering::Relaxed);
+    ctx.frame.rip = ctx.rax;
+    Disposition::Resume
+}
+
+/// Write to the first byte of `.text`, through the kernel mapping and
+/// through its direct-map alias, and check that each write raises a #PF
+/// for a write to a present page.
+///
+/// The byte written is the one already there, so a regression leaves the
+/// code intact. Returns a short description of the failure.
+pub fn self_test() -> Result<(), &'static str> {
+    let text = entry::kernel_sections().text.start;
+    let alias = paging::translate_addr(VirtAddr::new_truncate(text))
+        .map(|phys| phys_to_virt(phys.as_u64()))
+        .ok_or(".text is not mapped")?;
+    interrupts::register(PAGE_FAULT_VECTOR, probe_fault).map_err(|_| "#PF vector in use")?;
+    let result = probe_write(text, ".text is writable")
+        .and_then(|()| probe_write(alias, ".text is writable through the direct map"));
+    interrupts::unregister(PAGE_FAULT_VECTOR);
+    result
+}
+
+/// Rewrite the byte at `target` with [`probe_fault`] installed; fails
+/// with `writable` if the write does not fault.
+fn probe_write(target: u64, writable: &'static str) -> Result<(), &'static str> {
+    PROBE_RESULT.store(0, Ordering::Relaxed);
+    PROBE_ADDR.store(target, Ordering::Relaxed);
+
+    // SAFETY: reads a byte of `.text` and writes the same value back. If
+    // the write faults, `probe_fault` resumes at label 2, whose address is
+    // in RAX; RAX and RCX are declared clobbered.
+    unsafe {
+        core::arch::asm!(
+            "lea rax, [rip + 2f]",
+            "mov cl, byte ptr [{addr}]",
+            "mov byte ptr [{addr}], cl",
+            "2:",
+            addr = in(reg) target,
+            out("rax") _,
+            out("rcx") _,
+            options(nostack)
+        )
+    };
+
+    PROBE_ADDR.store(0, Ordering::Relaxed);
+    match PROBE_RESULT.swap(0, Ordering::Relaxed) {
+        0 => Err(writable),
+        code if code & (PF_PRESENT | PF_WRITE) == PF_PRESENT | PF_WRITE => Ok(()),
+        _ => Err("unexpected #PF error code"),
+    }
+}
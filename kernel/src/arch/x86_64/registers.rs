@@ -0,0 +1,185 @@
+//! Control registers and model-specific registers.
+//!
+//! Raw readers for the system registers the kernel inspects: CR0, CR2, CR3,
+//! CR4, MSRs such as IA32_EFER and the DS/ES/FS/GS selectors. All of them
+//! are side-effect free at CPL=0. The writers are `unsafe`; only the
+//! modules that own the corresponding feature bits call them (CR0.WP,
+//! CR4.SMEP and EFER.NXE in `protection`, CR0.EM/TS and CR4.OSFXSR/OSXSAVE
+//! in `fpu`).
+
+/// IA32_EFER — Extended Feature Enable Register (SCE, LME, LMA, NXE).
+pub const IA32_EFER: u32 = 0xC000_0080;
+
+/// Read CR0 (PE, MP, EM, TS, ET, NE, WP, AM, NW, CD, PG).
+#[inline]
+pub fn read_cr0() -> u64 {
+    let value: u64;
+    // SAFETY: reading CR0 has no side effects at CPL=0.
+    unsafe { core::arch::asm!("mov {}, cr0", out(reg) value, options(nomem, nostack)) };
+    value
+}
+
+/// Read CR2 — the linear address of the most recent page fault.
+#[inline]
+pub fn read_cr2() -> u64 {
+    let value: u64;
+    // SAFETY: reading CR2 has no side effects at CPL=0.
+    unsafe { core::arch::asm!("mov {}, cr2", out(reg) value, options(nomem, nostack)) };
+    value
+}
+
+/// Read CR3 — PML4 physical address plus PCID / PWT / PCD bits.
+#[inline]
+pub fn read_cr3() -> u64 {
+    let value: u64;
+    // SAFETY: reading CR3 has no side effects at CPL=0.
+    unsafe { core::arch::asm!("mov {}, cr3", out(reg) value, options(nomem, nostack)) };
+    value
+}
+
+/// Read CR4 (PAE, PGE, OSFXSR, UMIP, SMEP, SMAP, ...).
+#[inline]
+pub fn read_cr4() -> u64 {
+    let value: u64;
+    // SAFETY: reading CR4 has no side effects at CPL=0.
+    unsafe { core::arch::asm!("mov {}, cr4", out(reg) value, options(nomem, nostack)) };
+    value
+}
+
+/// Write CR0.
+///
+/// # Safety
+///
+/// `value` must be a valid CR0 for the current mappings and CPU state.
+#[inline]
+pub unsafe fn write_cr0(value: u64) {
+    // SAFETY: forwarded to the caller.
+    unsafe { core::arch::asm!("mov cr0, {}", in(reg) value, options(nostack)) };
+}
+
+/// Write CR4.
+///
+/// # Safety
+///
+/// `value` must only set bits the CPU supports and be valid for the
+/// current mappings and CPU state.
+#[inline]
+pub unsafe fn write_cr4(value: u64) {
+    // SAFETY: forwarded to the caller.
+    unsafe { core::arch::asm!("mov cr4, {}", in(reg) value, options(nostack)) };
+}
+
+/// Read the model-specific register `msr`.
+///
+/// # Safety
+///
+/// `msr` must exist on this CPU; `rdmsr` of an unimplemented MSR raises #GP.
+#[inline]
+pub unsafe fn rdmsr(msr: u32) -> u64 {
+    let (low, high): (u32, u32);
+    // SAFETY: forwarded to the caller; `rdmsr` only reads.
+    unsafe {
+        core::arch::asm!(
+            "rdmsr",
+            in("ecx") msr,
+            out("eax") low,
+            out("edx") high,
+            options(nomem, nostack, preserves_flags)
+        )
+    };
+    (u64::from(high) << 32) | u64::from(low)
+}
+
+/// Write the model-specific register `msr`.
+///
+/// # Safety
+///
+/// `msr` must exist and `value` must be valid for it.
+#[inline]
+pub unsafe fn wrmsr(msr: u32, value: u64) {
+    // SAFETY: forwarded to the caller.
+    unsafe {
+        core::arch::asm!(
+            "wrmsr",
+            in("ecx") msr,
+            in("eax") value as u32,
+            in("edx") (value >> 32) as u32,
+            options(nostack, preserves_flags)
+        )
+    };
+}
+
+/// Read IA32_EFER.
+#[inline]
+pub fn read_efer() -> u64 {
+    // SAFETY: IA32_EFER exists on every x86-64 CPU (LMA is set while in
+    // long mode).
+    unsafe { rdmsr(IA32_EFER) }
+}
+
+/// Snapshot of the control registers, taken together for diagnostics.
+#[derive(Debug, Clone, Copy)]
+pub struct ControlRegisters {
+    /// CR0.
+    pub cr0: u64,
+    /// CR2.
+    pub cr2: u64,
+    /// CR3.
+    pub cr3: u64,
+    /// CR4.
+    pub cr4: u64,
+    /// IA32_EFER.
+    pub efer: u64,
+}
+
+impl ControlRegisters {
+    /// Read all of them now.
+    pub fn read() -> Self {
+        Self {
+            cr0: read_cr0(),
+            cr2: read_cr2(),
+            cr3: read_cr3(),
+            cr4: read_cr4(),
+            efer: read_efer(),
+        }
+    }
+}
+
+/// Data segment selectors, read together for diagnostics.
+///
+/// Interrupt delivery and the entry stubs leave DS, ES, FS and GS alone, so
+/// inside a handler these are the interrupted code's selectors (CS and SS
+/// are in the CPU-pushed frame instead).
+#[derive(Debug, Clone, Copy)]
+pub struct DataSegments {
+    /// DS.
+    pub ds: u16,
+    /// ES.
+    pub es: u16,
+    /// FS.
+    pub fs: u16,
+    /// GS.
+    pub gs: u16,
+}
+
+impl DataSegments {
+    /// Read all of them now.
+    pub fn read() -> Self {
+        let (ds, es, fs, gs): (u16, u16, u16, u16);
+        // SAFETY: reading a segment selector has no side effects.
+        unsafe {
+            core::arch::asm!(
+                "mov {ds:x}, ds",
+                "mov {es:x}, es",
+                "mov {fs:x}, fs",
+                "mov {gs:x}, gs",
+                ds = out(reg) ds,
+                es = out(reg) es,
+                fs = out(reg) fs,
+                gs = out(reg) gs,
+                options(nomem, nostack, preserves_flags)
+            )
+        };
+        Self { ds, es, fs, gs }
+    }
+}
@@ -13,20 +13,36 @@
 //!              │        usable stack space (60 KiB)      │
 //!              │                                          │
 //!              ├──────────────────────────────────────────┤
-//!              │      soft guard region (4 KiB)           │  ← future: unmapped page
+//!              │        guard page (4 KiB)                │  ← unmapped
 //! Low address  ──────────────────────────────────────────── bottom()
 //! ```
 //!
-//! The bottom 4 KiB is reserved as a soft guard region. It cannot be
-//! enforced without page-table control (Task 1.3.3), but is zeroed at
-//! startup and documented here so the invariant is clear.
+//! The bottom 4 KiB is a guard page. Once paging is available,
+//! [`unmap_guard_page`] removes its mapping, so running off the end of the
+//! stack raises #PF instead of silently corrupting the statics below it.
+//! The #PF cannot be delivered on the overflowed stack and escalates to
+//! #DF, which runs on [`DOUBLE_FAULT_STACK`] (IST1, see `gdt`) and reports
+//! "kernel stack overflow".
 //!
-//! # Phase notes
+//! # Interrupt stacks
 //!
-//! During Phase 1 the `KERNEL_STACK` static lives in `boot/src/main.rs`
-//! because the bootloader and kernel run in the same address space. When
-//! the kernel becomes a separate ELF binary, that static moves here and
-//! the linker script exports `__stack_top` / `__stack_bottom`.
+//! Three further stacks back the TSS Interrupt Stack Table. The CPU switches
+//! to them unconditionally for the vectors that can arrive while the current
+//! stack is unusable:
+//!
+//! | IST | Static                    | Vector |
+//! |-----|---------------------------|--------|
+//! | 1   | [`DOUBLE_FAULT_STACK`]    | #DF    |
+//! | 2   | [`NMI_STACK`]             | NMI    |
+//! | 3   | [`MACHINE_CHECK_STACK`]   | #MC    |
+//!
+//! # Placement
+//!
+//! The [`KERNEL_STACK`] static below lives in the kernel's `.bss`, so it is
+//! zero-filled by the ELF loader and again by `kernel_entry`. Its bounds are
+//! derived from the static's address rather than from linker symbols.
+
+use crate::memory::paging::{self, MapError, UnmapError, VirtAddr};
 
 // ---------------------------------------------------------------------------
 // Constants
@@ -36,26 +52,119 @@
 ///
 /// Breakdown:
 /// - 60 KiB usable stack depth
-/// - 4 KiB soft guard region at the bottom (enforced by page tables in Phase 1.3)
+/// - 4 KiB guard page at the bottom (unmapped by [`unmap_guard_page`])
 pub const KERNEL_STACK_SIZE: usize = 64 * 1024;
 
-/// Size of the soft guard region at the bottom of the stack (4 KiB = one page).
+/// Size of the guard region at the bottom of the stack (4 KiB = one page).
 ///
-/// This region is left zeroed. Once page table management is in place
-/// (Task 1.3.3) it will be mapped as non-present to catch stack overflows.
+/// Zeroed with the rest of `.bss`, then made non-present by
+/// [`unmap_guard_page`] to catch stack overflows.
 pub const KERNEL_STACK_GUARD_SIZE: usize = 4 * 1024;
 
 /// Usable stack depth = total size minus the guard region.
 pub const KERNEL_STACK_USABLE_SIZE: usize = KERNEL_STACK_SIZE - KERNEL_STACK_GUARD_SIZE;
 
+/// Size of each Interrupt Stack Table stack in bytes (16 KiB).
+pub const IST_STACK_SIZE: usize = 16 * 1024;
+
+// ---------------------------------------------------------------------------
+// Primary stack static
+// ---------------------------------------------------------------------------
+
+/// The kernel's primary execution stack.
+///
+/// `kernel_entry` switches RSP to the top of this buffer before calling
+/// `kernel_main`, leaving the bootloader's bootstrap stack behind.
+///
+/// Declared `static mut` because the CPU writes to it through RSP; an
+/// immutable static could be placed in read-only memory.
+///
+/// SAFETY: switched to exactly once from `kernel_entry`, single-core,
+/// interrupts disabled. Rust code never takes a reference to the contents.
+pub static mut KERNEL_STACK: KernelStack<KERNEL_STACK_SIZE> = KernelStack::new();
+
+// ---------------------------------------------------------------------------
+// Interrupt Stack Table stacks
+//
+// SAFETY (all three): only the CPU writes to them (through RSP); Rust code
+// takes their address, never a reference to the contents.
+// ---------------------------------------------------------------------------
+
+/// Stack for the #DF handler (IST1).
+///
+/// Lets the handler report even when [`KERNEL_STACK`] is exhausted.
+pub static mut DOUBLE_FAULT_STACK: KernelStack<IST_STACK_SIZE> = KernelStack::new();
+
+/// Stack for the NMI handler (IST2).
+///
+/// An NMI can arrive between any two instructions, including in the middle
+/// of a stack switch.
+pub static mut NMI_STACK: KernelStack<IST_STACK_SIZE> = KernelStack::new();
+
+/// Stack for the #MC handler (IST3).
+///
+/// A machine check may be caused by the memory backing the current stack.
+pub static mut MACHINE_CHECK_STACK: KernelStack<IST_STACK_SIZE> = KernelStack::new();
+
+// ---------------------------------------------------------------------------
+// Guard page
+// ---------------------------------------------------------------------------
+
+/// Address range `[start, end)` of the [`KERNEL_STACK`] guard page.
+pub fn guard_page_range() -> (u64, u64) {
+    let bottom = core::ptr::addr_of!(KERNEL_STACK) as u64;
+    (bottom, bottom + KERNEL_STACK_GUARD_SIZE as u64)
+}
+
+/// True if `addr` lies in the [`KERNEL_STACK`] guard page.
+pub fn is_guard_page_address(addr: u64) -> bool {
+    let (start, end) = guard_page_range();
+    (start..end).contains(&addr)
+}
+
+/// Why [`unmap_guard_page`] failed.
+#[derive(Debug)]
+pub enum GuardPageError {
+    /// The huge page containing the guard could not be split.
+    Split(MapError),
+    /// The guard page could not be unmapped.
+    Unmap(UnmapError),
+}
+
+/// Make the [`KERNEL_STACK`] guard page non-present.
+///
+/// Splits the surrounding huge page first if the firmware mapped the kernel
+/// with 2 MiB / 1 GiB pages. Returns the guard page address.
+///
+/// # Errors
+///
+/// See [`GuardPageError`].
+///
+/// # Safety
+///
+/// - Interrupts must be disabled; the frame allocator must be initialised.
+/// - The current stack must not be using the guard page (always true unless
+///   the stack has already overflowed).
+pub unsafe fn unmap_guard_page() -> Result<u64, GuardPageError> {
+    let (start, _) = guard_page_range();
+    let virt = VirtAddr::new_truncate(start);
+    // SAFETY: forwarded to the caller; the guard page holds no live data —
+    // KERNEL_STACK is page-aligned, so the page belongs to it alone.
+    unsafe {
+        paging::split_huge_page(virt).map_err(GuardPageError::Split)?;
+        paging::unmap(virt).map_err(GuardPageError::Unmap)?;
+    }
+    Ok(start)
+}
+
 // ---------------------------------------------------------------------------
 // KernelStack type
 // ---------------------------------------------------------------------------
 
-/// A statically allocated, 16-byte-aligned kernel stack.
+/// A statically allocated, page-aligned kernel stack.
 ///
 /// The size parameter `SIZE` is the total allocation in bytes, including the
-/// soft guard region at the bottom.
+/// guard region at the bottom.
 ///
 /// # Usage
 ///
@@ -71,10 +180,12 @@ pub const KERNEL_STACK_USABLE_SIZE: usize = KERNEL_STACK_SIZE - KERNEL_STACK_GUA
 ///
 /// # Alignment
 ///
-/// The struct carries `#[repr(C, align(16))]` so the compiler places it on a
-/// 16-byte boundary. The `top()` pointer is therefore also 16-byte aligned,
-/// satisfying the x86-64 ABI requirement before the first `call` instruction.
-#[repr(C, align(16))]
+/// The struct carries `#[repr(C, align(4096))]` so the guard page at the
+/// bottom is a whole page of its own and can be unmapped without touching
+/// neighbouring statics. With `SIZE` a multiple of 4 KiB the `top()` pointer
+/// is page-aligned too, which satisfies the x86-64 ABI's 16-byte alignment
+/// requirement before the first `call` instruction.
+#[repr(C, align(4096))]
 pub struct KernelStack<const SIZE: usize> {
     data: [u8; SIZE],
 }
@@ -104,19 +215,19 @@ impl<const SIZE: usize> KernelStack<SIZE> {
 
     /// Return a pointer to the bottom of the stack (lowest address).
     ///
-    /// The bottom `KERNEL_STACK_GUARD_SIZE` bytes are the soft guard region.
-    /// Once page-table management is implemented, this page will be marked
-    /// non-present to catch stack overflows.
+    /// For [`KERNEL_STACK`] the bottom `KERNEL_STACK_GUARD_SIZE` bytes are
+    /// the guard page (see [`unmap_guard_page`]).
     pub fn bottom(&self) -> *const u8 {
         self.data.as_ptr()
     }
 
-    /// Physical address of the stack top, suitable for loading into RSP.
+    /// Virtual (higher-half) address of the stack top, suitable for loading
+    /// into RSP.
     pub fn top_addr(&self) -> usize {
         self.top() as usize
     }
 
-    /// Physical address of the stack bottom.
+    /// Virtual (higher-half) address of the stack bottom.
     pub fn bottom_addr(&self) -> usize {
         self.bottom() as usize
     }
@@ -0,0 +1,125 @@
+//! Copies between kernel memory and user space.
+//!
+//! With CR4.SMAP on, any supervisor access to a user page faults unless
+//! RFLAGS.AC is set. [`copy_from_user`] and [`copy_to_user`] are the only
+//! places that set it: they check that the user range lies below
+//! [`USER_SPACE_END`](ferrous_boot_info::layout::USER_SPACE_END), then copy
+//! inside a [`UserAccessGuard`] (`stac` … `clac`).
+//!
+//! There are no user mappings yet, and a fault inside a copy is still
+//! fatal; callers must only pass ranges they know to be mapped.
+
+use core::fmt;
+
+use ferrous_boot_info::layout::is_user_range;
+
+use super::protection;
+
+/// Errors returned by the copy helpers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserCopyError {
+    /// The range is not entirely in user space.
+    NotUserRange {
+        /// Start of the rejected range.
+        addr: u64,
+        /// Length of the rejected range.
+        len: usize,
+    },
+}
+
+impl fmt::Display for UserCopyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UserCopyError::NotUserRange { addr, len } => {
+                write!(f, "{} bytes at {:#x} are not in user space", len, addr)
+            }
+        }
+    }
+}
+
+/// Allows supervisor access to user pages while alive: `stac` on creation,
+/// `clac` on drop. Does nothing unless SMAP is enabled, since both
+/// instructions are #UD on CPUs without it.
+pub struct UserAccessGuard {
+    smap: bool,
+}
+
+impl UserAccessGuard {
+    /// Open a user access window.
+    pub fn new() -> Self {
+        let smap = protection::smap_enabled();
+        if smap {
+            // SAFETY: SMAP is supported; `stac` only sets RFLAGS.AC. Not
+            // `nomem`, so user accesses cannot be moved before it.
+            unsafe { core::arch::asm!("stac", options(nostack)) };
+        }
+        Self { smap }
+    }
+}
+
+impl Default for UserAccessGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for UserAccessGuard {
+    fn drop(&mut self) {
+        if self.smap {
+            // SAFETY: SMAP is supported; `clac` only clears RFLAGS.AC. Not
+            // `nomem`, so user accesses cannot be moved after it.
+            unsafe { core::arch::asm!("clac", options(nostack)) };
+        }
+    }
+}
+
+/// Check that `len` bytes at `addr` are in user space.
+fn check_range(addr: u64, len: usize) -> Result<(), UserCopyError> {
+    if is_user_range(addr, len as u64) {
+        Ok(())
+    } else {
+        Err(UserCopyError::NotUserRange { addr, len })
+    }
+}
+
+/// Copy `dst.len()` bytes from user address `src` into `dst`.
+///
+/// # Errors
+///
+/// [`UserCopyError::NotUserRange`] if the source is not entirely in user
+/// space; nothing is copied.
+///
+/// # Safety
+///
+/// The source range must be mapped and readable.
+pub unsafe fn copy_from_user(dst: &mut [u8], src: u64) -> Result<(), UserCopyError> {
+    check_range(src, dst.len())?;
+    let _guard = UserAccessGuard::new();
+    // SAFETY: the caller guarantees `src` is mapped; it lies in user space,
+    // so it cannot overlap `dst`, which is kernel memory.
+    unsafe {
+        core::ptr::copy_nonoverlapping(src as *const u8, dst.as_mut_ptr(), dst.len());
+    }
+    Ok(())
+}
+
+/// Copy `src` to user address `dst`.
+///
+/// # Errors
+///
+/// [`UserCopyError::NotUserRange`] if the destination is not entirely in
+/// user space; nothing is copied.
+///
+/// # Safety
+///
+/// The destination range must be mapped and writable.
+pub unsafe fn copy_to_user(dst: u64, src: &[u8]) -> Result<(), UserCopyError> {
+    check_range(dst, src.len())?;
+    let _guard = UserAccessGuard::new();
+    // SAFETY: the caller guarantees `dst` is mapped and writable; it lies in
+    // user space, so it cannot overlap `src`, which is kernel memory.
+    unsafe {
+        core::ptr::copy_nonoverlapping(src.as_ptr(), dst as *mut u8, src.len());
+    }
+    Ok(())
+}
@@ -0,0 +1,199 @@
+//! Kernel command line.
+//!
+//! The bootloader passes the command line as a [`CMDLINE`] boot info tag:
+//! whitespace-separated `key` or `key=value` words, e.g.
+//!
+//! ```text
+//! log=debug serial=com2 mem=512M noreclaim nokaslr test=paging
+//! ```
+//!
+//! [`parse`] turns it into [`KernelOptions`]. Nothing here panics: unknown
+//! keys and bad values are reported through the `warn` callback and the
+//! option keeps its default.
+//!
+//! Everything here depends only on `core`, so `tests/boot_tests.rs`
+//! compiles this file directly and checks the parser on the host.
+//!
+//! [`CMDLINE`]: ferrous_boot_info::tag_type::CMDLINE
+
+use core::fmt;
+
+/// Serial output verbosity (`log=`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    fn parse(value: &str) -> Option<Self> {
+        Some(match value {
+            "error" => Self::Error,
+            "warn" => Self::Warn,
+            "info" => Self::Info,
+            "debug" => Self::Debug,
+            "trace" => Self::Trace,
+            _ => return None,
+        })
+    }
+}
+
+/// Legacy PC serial port used as the kernel console (`serial=`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SerialPortId {
+    Com1,
+    Com2,
+    Com3,
+    Com4,
+}
+
+impl SerialPortId {
+    fn parse(value: &str) -> Option<Self> {
+        Some(match value {
+            "com1" => Self::Com1,
+            "com2" => Self::Com2,
+            "com3" => Self::Com3,
+            "com4" => Self::Com4,
+            _ => return None,
+        })
+    }
+
+    /// Conventional I/O base address of the port.
+    pub const fn io_base(self) -> u16 {
+        match self {
+            Self::Com1 => 0x3F8,
+            Self::Com2 => 0x2F8,
+            Self::Com3 => 0x3E8,
+            Self::Com4 => 0x2E8,
+        }
+    }
+}
+
+/// Options selected on the command line; [`Default`] when absent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KernelOptions<'a> {
+    /// `log=error|warn|info|debug|trace` (default `info`).
+    pub log: LogLevel,
+    /// `serial=com1..com4` (default `com1`).
+    pub serial: SerialPortId,
+    /// `mem=<size>[K|M|G]`: ignore physical memory at and above this
+    /// address. Rounded down to a page; `None` uses all memory.
+    pub mem_limit: Option<u64>,
+    /// `noreclaim`: keep boot-services and loader memory reserved.
+    pub noreclaim: bool,
+    /// `nokaslr`: the bootloader loaded the kernel at its link address.
+    /// Acted on by the bootloader; recorded here for completeness.
+    pub nokaslr: bool,
+    /// `test=<name>`: boot self-test to select.
+    pub test: Option<&'a str>,
+}
+
+impl Default for KernelOptions<'_> {
+    fn default() -> Self {
+        Self {
+            log: LogLevel::Info,
+            serial: SerialPortId::Com1,
+            mem_limit: None,
+            noreclaim: false,
+            nokaslr: false,
+            test: None,
+        }
+    }
+}
+
+/// A problem with one command line word; the word is otherwise ignored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Warning<'a> {
+    /// The key is not a known option.
+    UnknownKey(&'a str),
+    /// The option needs a `=value`.
+    MissingValue(&'a str),
+    /// The option is a flag and takes no value.
+    UnexpectedValue { key: &'a str, value: &'a str },
+    /// The value is not valid for the option.
+    InvalidValue { key: &'a str, value: &'a str },
+}
+
+impl fmt::Display for Warning<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Warning::UnknownKey(key) => write!(f, "unknown option `{}`", key),
+            Warning::MissingValue(key) => write!(f, "`{}` needs a value", key),
+            Warning::UnexpectedValue { key, value } => {
+                write!(f, "`{}` takes no value (got `{}`)", key, value)
+            }
+            Warning::InvalidValue { key, value } => {
+                write!(f, "invalid value `{}` for `{}`", value, key)
+            }
+        }
+    }
+}
+
+/// Parse `cmdline` into options, calling `warn` for every word that could
+/// not be applied. Later words override earlier ones.
+pub fn parse<'a>(cmdline: &'a str, mut warn: impl FnMut(Warning<'a>)) -> KernelOptions<'a> {
+    let mut options = KernelOptions::default();
+    for word in cmdline.split_ascii_whitespace() {
+        let (key, value) = match word.split_once('=') {
+            Some((key, value)) => (key, Some(value)),
+            None => (word, None),
+        };
+        if let Err(warning) = apply(&mut options, key, value) {
+            warn(warning);
+        }
+    }
+    options
+}
+
+/// Apply one `key[=value]` word.
+fn apply<'a>(
+    options: &mut KernelOptions<'a>,
+    key: &'a str,
+    value: Option<&'a str>,
+) -> Result<(), Warning<'a>> {
+    let invalid = |value| Warning::InvalidValue { key, value };
+    match key {
+        "noreclaim" | "nokaslr" => {
+            if let Some(value) = value {
+                return Err(Warning::UnexpectedValue { key, value });
+            }
+            match key {
+                "noreclaim" => options.noreclaim = true,
+                _ => options.nokaslr = true,
+            }
+        }
+        "log" | "serial" | "mem" | "test" => {
+            let value = value
+                .filter(|v| !v.is_empty())
+                .ok_or(Warning::MissingValue(key))?;
+            match key {
+                "log" => options.log = LogLevel::parse(value).ok_or(invalid(value))?,
+                "serial" => options.serial = SerialPortId::parse(value).ok_or(invalid(value))?,
+                "mem" => options.mem_limit = Some(parse_size(value).ok_or(invalid(value))?),
+                _ => options.test = Some(value),
+            }
+        }
+        _ => return Err(Warning::UnknownKey(key)),
+    }
+    Ok(())
+}
+
+/// Parse `<digits>[K|M|G]` (binary units, either case) into a byte count
+/// rounded down to 4 KiB. Zero, overflow and sub-page sizes are rejected.
+pub fn parse_size(value: &str) -> Option<u64> {
+    let (digits, shift) = match value.as_bytes().last()? {
+        b'k' | b'K' => (&value[..value.len() - 1], 10),
+        b'm' | b'M' => (&value[..value.len() - 1], 20),
+        b'g' | b'G' => (&value[..value.len() - 1], 30),
+        _ => (value, 0),
+    };
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let number: u64 = digits.parse().ok()?;
+    let bytes = number.checked_mul(1 << shift)? & !0xFFF;
+    (bytes != 0).then_some(bytes)
+}
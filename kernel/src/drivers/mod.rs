@@ -5,4 +5,6 @@
 //! public APIs are safe to call from kernel code (given the invariants
 //! documented on each type's constructor / initialiser).
 
+pub mod pic8259;
+pub mod pic8259_layout;
 pub mod serial;
@@ -0,0 +1,277 @@
+//! Legacy 8259A programmable interrupt controller pair.
+//!
+//! After firmware, the master PIC delivers IRQ 0–7 at vectors 8–15 — on top
+//! of the CPU exceptions, so the timer (IRQ 0) would arrive as #DF. [`init`]
+//! reprograms both chips so that their sixteen lines use vectors 32–47,
+//! directly after the exceptions, and masks every line. Drivers then
+//! [`unmask`] the lines they handle and acknowledge each interrupt with
+//! [`end_of_interrupt`].
+//!
+//! | Chip   | Command | Data | IRQs | Vectors |
+//! |--------|---------|------|------|---------|
+//! | Master | 0x20    | 0x21 | 0–7  | 32–39   |
+//! | Slave  | 0xA0    | 0xA1 | 8–15 | 40–47   |
+//!
+//! The slave is cascaded into master IRQ 2, which [`init`] leaves unmasked
+//! so that slave lines work once unmasked themselves.
+//!
+//! # Spurious interrupts
+//!
+//! When a request disappears before the CPU acknowledges it, the PIC still
+//! delivers its lowest-priority line — IRQ 7 on the master, IRQ 15 on the
+//! slave — without setting the line's in-service bit. [`init`] installs
+//! handlers for both vectors that check the in-service register
+//! ([`is_spurious`]) and drop spurious ones: no EOI for a spurious IRQ 7,
+//! an EOI to the master only (for the cascade) for a spurious IRQ 15. A
+//! driver that takes over IRQ 7 or 15 must do the same check first.
+//!
+//! The command words, the vector layout and the in-service check live in
+//! [`pic8259_layout`](super::pic8259_layout), which the host tests compile.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::arch::x86_64::interrupts::{self, Disposition, InterruptContext};
+use crate::arch::x86_64::without_interrupts;
+use crate::serial_println;
+use crate::sync::SpinLock;
+
+pub use super::pic8259_layout::{
+    irq_vector, vector_irq, IRQ_COUNT, PIC1_VECTOR_BASE, PIC2_VECTOR_BASE,
+};
+use super::pic8259_layout::{
+    is_lowest_priority, is_spurious_delivery, CASCADE_IRQ, ICW1_INIT_ICW4,
+    ICW3_MASTER_SLAVE_ON_IRQ2, ICW3_SLAVE_ID, ICW4_8086, OCW2_EOI, OCW3_READ_ISR, SPURIOUS_LINE,
+};
+
+// ---------------------------------------------------------------------------
+// Ports
+// ---------------------------------------------------------------------------
+
+const PIC1_COMMAND: u16 = 0x20;
+const PIC1_DATA: u16 = 0x21;
+const PIC2_COMMAND: u16 = 0xA0;
+const PIC2_DATA: u16 = 0xA1;
+
+/// Unused port whose write gives the PIC time to settle between
+/// initialisation words (POST diagnostic port).
+const IO_WAIT_PORT: u16 = 0x80;
+
+// ---------------------------------------------------------------------------
+// State
+// ---------------------------------------------------------------------------
+
+/// Interrupt mask of both chips (bit n = IRQ n masked), mirrored so that
+/// [`mask`] and [`unmask`] need no port read. Only touched with interrupts
+/// disabled.
+static MASK: SpinLock<u16> = SpinLock::new(u16::MAX);
+
+/// Spurious IRQ 7 and IRQ 15 deliveries dropped so far.
+static SPURIOUS: AtomicU64 = AtomicU64::new(0);
+
+// ---------------------------------------------------------------------------
+// Public API
+// ---------------------------------------------------------------------------
+
+/// Remap both PICs to vectors 32–47, mask every line except the cascade
+/// and install the spurious IRQ 7/15 handlers.
+///
+/// # Safety
+///
+/// - Ring 0, interrupts disabled, called once before `sti`.
+/// - Nothing else may program the PICs concurrently.
+///
+/// # Panics
+///
+/// If vector 39 or 47 already has a handler.
+pub unsafe fn init() {
+    let mask = !(1u16 << CASCADE_IRQ);
+    // SAFETY: ring 0, interrupts disabled and exclusive access per the
+    // contract; the sequence is the standard ICW1–ICW4 initialisation.
+    unsafe {
+        outb(PIC1_COMMAND, ICW1_INIT_ICW4);
+        io_wait();
+        outb(PIC2_COMMAND, ICW1_INIT_ICW4);
+        io_wait();
+        outb(PIC1_DATA, PIC1_VECTOR_BASE);
+        io_wait();
+        outb(PIC2_DATA, PIC2_VECTOR_BASE);
+        io_wait();
+        outb(PIC1_DATA, ICW3_MASTER_SLAVE_ON_IRQ2);
+        io_wait();
+        outb(PIC2_DATA, ICW3_SLAVE_ID);
+        io_wait();
+        outb(PIC1_DATA, ICW4_8086);
+        io_wait();
+        outb(PIC2_DATA, ICW4_8086);
+        io_wait();
+        write_mask(mask);
+    }
+    *MASK.lock() = mask;
+
+    interrupts::register(irq_vector(SPURIOUS_LINE), spurious_master)
+        .expect("IRQ 7 handler already registered");
+    interrupts::register(irq_vector(8 + SPURIOUS_LINE), spurious_slave)
+        .expect("IRQ 15 handler already registered");
+}
+
+/// Stop `irq` (0–15) from being delivered.
+pub fn mask(irq: u8) {
+    update_mask(|mask| mask | line_bit(irq));
+}
+
+/// Allow `irq` (0–15) to be delivered. Slave lines also need the cascade
+/// (IRQ 2), which [`init`] leaves unmasked.
+pub fn unmask(irq: u8) {
+    update_mask(|mask| mask & !line_bit(irq));
+}
+
+/// Mask every line, e.g. once the I/O APIC takes over.
+pub fn mask_all() {
+    update_mask(|_| u16::MAX);
+}
+
+/// True if `irq` is currently masked.
+pub fn is_masked(irq: u8) -> bool {
+    without_interrupts(|| *MASK.lock() & line_bit(irq) != 0)
+}
+
+/// Acknowledge `irq` (0–15): EOI to the slave for IRQ 8–15, then to the
+/// master. Call at the end of every non-spurious IRQ handler.
+pub fn end_of_interrupt(irq: u8) {
+    // SAFETY: an EOI only clears the highest in-service bit; ring 0 is
+    // implied by running kernel code.
+    unsafe {
+        if irq >= 8 {
+            outb(PIC2_COMMAND, OCW2_EOI);
+        }
+        outb(PIC1_COMMAND, OCW2_EOI);
+    }
+}
+
+/// True if a delivery of `irq` is spurious: it is a chip's lowest-priority
+/// line (7 or 15) and that line's in-service bit is clear. Always false
+/// for other lines.
+pub fn is_spurious(irq: u8) -> bool {
+    if !is_lowest_priority(irq) {
+        return false;
+    }
+    let command = if irq >= 8 { PIC2_COMMAND } else { PIC1_COMMAND };
+    // SAFETY: OCW3 only selects which register the next command-port read
+    // returns; reading the ISR has no side effects.
+    let isr = unsafe {
+        outb(command, OCW3_READ_ISR);
+        inb(command)
+    };
+    is_spurious_delivery(irq, isr)
+}
+
+/// Spurious IRQ 7/15 deliveries dropped since [`init`].
+pub fn spurious_count() -> u64 {
+    SPURIOUS.load(Ordering::Relaxed)
+}
+
+// ---------------------------------------------------------------------------
+// Handlers
+// ---------------------------------------------------------------------------
+
+/// IRQ 7: drop spurious deliveries without an EOI.
+fn spurious_master(_ctx: &mut InterruptContext) -> Disposition {
+    handle_lowest_priority(SPURIOUS_LINE)
+}
+
+/// IRQ 15: drop spurious deliveries; the master still saw a real IRQ 2
+/// from the cascade and needs its EOI.
+fn spurious_slave(_ctx: &mut InterruptContext) -> Disposition {
+    handle_lowest_priority(8 + SPURIOUS_LINE)
+}
+
+fn handle_lowest_priority(irq: u8) -> Disposition {
+    if is_spurious(irq) {
+        SPURIOUS.fetch_add(1, Ordering::Relaxed);
+        if irq >= 8 {
+            // SAFETY: as in `end_of_interrupt`.
+            unsafe { outb(PIC1_COMMAND, OCW2_EOI) };
+        }
+        return Disposition::Resume;
+    }
+    // A real interrupt on a line no driver owns: it must have been
+    // unmasked by mistake.
+    serial_println!("[WARN] Unhandled IRQ {}; masking it", irq);
+    mask(irq);
+    end_of_interrupt(irq);
+    Disposition::Resume
+}
+
+// ---------------------------------------------------------------------------
+// Private helpers
+// ---------------------------------------------------------------------------
+
+fn line_bit(irq: u8) -> u16 {
+    assert!(irq < IRQ_COUNT, "IRQ {} out of range", irq);
+    1 << irq
+}
+
+/// Apply `f` to the cached mask and write the result to both chips.
+fn update_mask(f: impl FnOnce(u16) -> u16) {
+    without_interrupts(|| {
+        let mut mask = MASK.lock();
+        *mask = f(*mask);
+        // SAFETY: writing the IMR only changes which lines are delivered.
+        unsafe { write_mask(*mask) };
+    });
+}
+
+/// Write `mask` to the interrupt mask registers (OCW1) of both chips.
+///
+/// # Safety
+///
+/// Ring 0; the PICs must be initialised.
+unsafe fn write_mask(mask: u16) {
+    // SAFETY: forwarded to the caller.
+    unsafe {
+        outb(PIC1_DATA, mask as u8);
+        outb(PIC2_DATA, (mask >> 8) as u8);
+    }
+}
+
+/// Give the PIC time to process the previous word.
+///
+/// # Safety
+///
+/// Ring 0.
+unsafe fn io_wait() {
+    // SAFETY: nothing listens on the POST diagnostic port.
+    unsafe { outb(IO_WAIT_PORT, 0) };
+}
+
+/// # Safety
+///
+/// Ring 0; `port` must be one of the PIC ports or the POST port.
+unsafe fn outb(port: u16, value: u8) {
+    // SAFETY: forwarded to the caller.
+    unsafe {
+        core::arch::asm!(
+            "out dx, al",
+            in("dx") port,
+            in("al") value,
+            options(nomem, nostack, preserves_flags)
+        )
+    };
+}
+
+/// # Safety
+///
+/// Ring 0; `port` must be a PIC command port.
+unsafe fn inb(port: u16) -> u8 {
+    let value: u8;
+    // SAFETY: forwarded to the caller.
+    unsafe {
+        core::arch::asm!(
+            "in al, dx",
+            in("dx") port,
+            out("al") value,
+            options(nomem, nostack, preserves_flags)
+        )
+    };
+    value
+}
@@ -0,0 +1,71 @@
+//! 8259A command words and vector layout.
+//!
+//! The values [`pic8259`](super::pic8259) programs into the chips, the
+//! IRQ ↔ vector mapping that results, and the in-service check for
+//! spurious interrupts. Everything here is a constant or a pure function
+//! and depends only on `core`, so `tests/boot_tests.rs` compiles this file
+//! directly and checks the layout on the host.
+
+// ---------------------------------------------------------------------------
+// Command words
+// ---------------------------------------------------------------------------
+
+/// ICW1: initialisation, edge-triggered, cascade mode, ICW4 follows.
+pub const ICW1_INIT_ICW4: u8 = 0x11;
+/// ICW3 (master): a slave is attached to IRQ 2.
+pub const ICW3_MASTER_SLAVE_ON_IRQ2: u8 = 1 << CASCADE_IRQ;
+/// ICW3 (slave): cascade identity 2.
+pub const ICW3_SLAVE_ID: u8 = CASCADE_IRQ;
+/// ICW4: 8086/88 mode, normal (not automatic) EOI.
+pub const ICW4_8086: u8 = 0x01;
+/// OCW2: non-specific end of interrupt.
+pub const OCW2_EOI: u8 = 0x20;
+/// OCW3: the next command-port read returns the in-service register.
+pub const OCW3_READ_ISR: u8 = 0x0B;
+
+/// Master line the slave is cascaded into.
+pub const CASCADE_IRQ: u8 = 2;
+/// The lowest-priority line of each chip, used for spurious interrupts.
+pub const SPURIOUS_LINE: u8 = 7;
+
+// ---------------------------------------------------------------------------
+// Vector layout
+// ---------------------------------------------------------------------------
+
+/// Vector of master IRQ 0; the first vector after the CPU exceptions.
+pub const PIC1_VECTOR_BASE: u8 = 32;
+/// Vector of slave IRQ 8.
+pub const PIC2_VECTOR_BASE: u8 = PIC1_VECTOR_BASE + 8;
+/// Number of IRQ lines on the pair.
+pub const IRQ_COUNT: u8 = 16;
+
+/// Vector on which `irq` (0–15) is delivered after `pic8259::init`.
+pub const fn irq_vector(irq: u8) -> u8 {
+    PIC1_VECTOR_BASE + irq
+}
+
+/// IRQ line delivered on `vector`, if it is one of the PIC's.
+pub const fn vector_irq(vector: u8) -> Option<u8> {
+    if vector >= PIC1_VECTOR_BASE && vector < PIC1_VECTOR_BASE + IRQ_COUNT {
+        Some(vector - PIC1_VECTOR_BASE)
+    } else {
+        None
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Spurious interrupts
+// ---------------------------------------------------------------------------
+
+/// True if `irq` is its chip's lowest-priority line (7 or 15), the only
+/// lines a PIC delivers spuriously.
+pub const fn is_lowest_priority(irq: u8) -> bool {
+    irq & 7 == SPURIOUS_LINE
+}
+
+/// True if a delivery of `irq` is spurious, given the in-service register
+/// `isr` of its chip: it is a lowest-priority line whose in-service bit is
+/// clear.
+pub const fn is_spurious_delivery(irq: u8, isr: u8) -> bool {
+    is_lowest_priority(irq) && isr & (1 << SPURIOUS_LINE) == 0
+}
@@ -14,6 +14,17 @@
 //!
 //! Raw I/O port access requires CPL=0 (ring 0). All `unsafe` is confined to
 //! the two `inb`/`outb` helpers; everything above them is safe.
+//!
+//! # Formatted output
+//!
+//! [`SerialPort`] implements [`core::fmt::Write`], and the
+//! [`serial_print!`](crate::serial_print) / [`serial_println!`](crate::serial_println)
+//! macros format straight to the console port without any heap allocation.
+//! The console is COM1 unless `serial=com2..com4` on the kernel command
+//! line moves it ([`SerialPort::set_console`]).
+
+use core::fmt;
+use core::sync::atomic::{AtomicU16, Ordering};
 
 // ---------------------------------------------------------------------------
 // Register map (offsets from COM1 base 0x3F8)
@@ -22,6 +33,10 @@
 /// I/O base address for COM1.
 const COM1_BASE: u16 = 0x3F8;
 
+/// I/O base of the port used by [`SerialPort::console`]; COM1 until the
+/// command line selects another one.
+static CONSOLE_BASE: AtomicU16 = AtomicU16::new(COM1_BASE);
+
 /// Data register: Transmit Holding (write) / Receive Buffer (read), DLAB=0.
 const REG_DATA: u16 = 0;
 /// Interrupt Enable Register, DLAB=0.
@@ -97,6 +112,21 @@ impl SerialPort {
         Self { base: COM1_BASE }
     }
 
+    /// Create a `SerialPort` at an arbitrary I/O base (COM2 is 0x2F8, …).
+    pub const fn with_base(base: u16) -> Self {
+        Self { base }
+    }
+
+    /// The console port: where [`serial_print!`](crate::serial_print) writes.
+    pub fn console() -> Self {
+        Self::with_base(CONSOLE_BASE.load(Ordering::Relaxed))
+    }
+
+    /// Make this port the console. It must already be initialised.
+    pub fn set_console(&self) {
+        CONSOLE_BASE.store(self.base, Ordering::Relaxed);
+    }
+
     /// Initialise the UART: 115200 baud, 8 data bits, no parity, 1 stop bit.
     ///
     /// Sequence:
@@ -206,3 +236,39 @@ impl SerialPort {
         value
     }
 }
+
+impl fmt::Write for SerialPort {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        SerialPort::write_str(self, s);
+        Ok(())
+    }
+}
+
+/// Implementation detail of [`serial_print!`](crate::serial_print).
+///
+/// `SerialPort` is a stateless handle to the console port, so a fresh one is
+/// created per call. The UART must already be initialised (done in
+/// `kernel_main`).
+#[doc(hidden)]
+pub fn _print(args: fmt::Arguments) {
+    let _ = fmt::Write::write_fmt(&mut SerialPort::console(), args);
+}
+
+/// Print formatted text to the console port (COM1 by default).
+#[macro_export]
+macro_rules! serial_print {
+    ($($arg:tt)*) => {
+        $crate::drivers::serial::_print(format_args!($($arg)*))
+    };
+}
+
+/// Print formatted text to the console port, followed by a newline.
+#[macro_export]
+macro_rules! serial_println {
+    () => {
+        $crate::serial_print!("\n")
+    };
+    ($($arg:tt)*) => {
+        $crate::serial_print!("{}\n", format_args!($($arg)*))
+    };
+}
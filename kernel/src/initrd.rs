@@ -0,0 +1,64 @@
+//! Initial ramdisk.
+//!
+//! The bootloader loads `\EFI\ferrous\initrd.tar` into LOADER_DATA memory
+//! and passes its physical range as an [`INITRD`] boot info tag. The kernel
+//! carves that range out of the memory map before anything else allocates
+//! (so step 8 does not reclaim it), then [`init`] validates the archive and
+//! keeps it for [`get`].
+//!
+//! The archive is read in place through the direct map; see
+//! [`ferrous_initrd`] for the formats and path rules.
+//!
+//! [`INITRD`]: ferrous_boot_info::tag_type::INITRD
+
+use ferrous_alloc::PhysRange;
+use ferrous_boot_info::{tag_type, TagList};
+use ferrous_initrd::{Archive, InitrdError};
+
+use crate::memory::phys_to_virt;
+use crate::sync::SpinLock;
+
+/// The validated archive, once [`init`] succeeds.
+static INITRD: SpinLock<Option<Archive<'static>>> = SpinLock::new(None);
+
+/// Physical range of the initrd described by `tags`, if there is one.
+///
+/// A malformed tag (short payload, range wrapping past `u64::MAX`) is
+/// treated as absent.
+pub fn range(tags: &TagList<'_>) -> Option<PhysRange> {
+    let tag = tags.find(tag_type::INITRD)?;
+    let base = tag.u64_at(0)?;
+    let size = tag.u64_at(1)?;
+    base.checked_add(size)?;
+    Some(PhysRange::from_base_len(base, size))
+}
+
+/// Validate the archive in `range` and make it available through [`get`].
+///
+/// # Errors
+///
+/// Whatever [`Archive::parse`] rejects; [`get`] then keeps returning
+/// `None`.
+///
+/// # Safety
+///
+/// `range` must be covered by the direct map, hold the bootloader's copy of
+/// the initrd, and stay reserved and unmodified for the lifetime of the
+/// kernel.
+pub unsafe fn init(range: PhysRange) -> Result<Archive<'static>, InitrdError> {
+    // SAFETY: guaranteed by the caller.
+    let bytes = unsafe {
+        core::slice::from_raw_parts(
+            phys_to_virt(range.start) as usize as *const u8,
+            (range.end - range.start) as usize,
+        )
+    };
+    let archive = Archive::parse(bytes)?;
+    *INITRD.lock() = Some(archive);
+    Ok(archive)
+}
+
+/// The initrd archive, or `None` if none was loaded or it was invalid.
+pub fn get() -> Option<Archive<'static>> {
+    *INITRD.lock()
+}
@@ -0,0 +1,52 @@
+//! Kernel placement (KASLR).
+//!
+//! The kernel is a static PIE: the bootloader loads it at a randomised base,
+//! relocates it before the jump and reports the result as a [`KASLR`] boot
+//! info tag: the slide (virtual load address minus link address) and the
+//! physical base the image was copied to. [`init`] records the slide so that
+//! fatal reports can print it; subtracting it from a reported address gives
+//! the address in the ELF file, which is what `addr2line` and `objdump`
+//! expect.
+//!
+//! Booting with `nokaslr` makes the bootloader load the kernel at its link
+//! address, i.e. with a slide of 0.
+//!
+//! [`KASLR`]: ferrous_boot_info::tag_type::KASLR
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use ferrous_boot_info::{tag_type, TagList};
+
+/// Slide recorded by [`init`]; 0 before that or without a tag.
+static SLIDE: AtomicU64 = AtomicU64::new(0);
+
+/// Kernel placement reported by the bootloader.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Placement {
+    /// Virtual load address minus link address (two's complement).
+    pub slide: u64,
+    /// Physical address the lowest `PT_LOAD` page was copied to.
+    pub phys_base: u64,
+}
+
+/// Record the placement described by `tags`.
+///
+/// Returns `None` (and leaves the slide at 0) if there is no [`KASLR`] tag
+/// or its payload is short — an older bootloader that always loads the
+/// kernel at its link address.
+///
+/// [`KASLR`]: ferrous_boot_info::tag_type::KASLR
+pub fn init(tags: &TagList<'_>) -> Option<Placement> {
+    let tag = tags.find(tag_type::KASLR)?;
+    let placement = Placement {
+        slide: tag.u64_at(0)?,
+        phys_base: tag.u64_at(1)?,
+    };
+    SLIDE.store(placement.slide, Ordering::Relaxed);
+    Some(placement)
+}
+
+/// The kernel's slide; 0 when it runs at its link address.
+pub fn slide() -> u64 {
+    SLIDE.load(Ordering::Relaxed)
+}
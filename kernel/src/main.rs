@@ -2,32 +2,657 @@
 //!
 //! This file is the crate root for the kernel binary. It declares the
 //! architecture-specific entry module, the device driver collection, and
-//! provides the global panic handler.
+//! provides `kernel_main` and the global panic handler.
 //!
-//! The actual entry point (`kernel_entry`) is defined in the bootloader for
-//! Phase 1. When ELF loading is implemented the entry point will move here.
+//! The bootloader loads this binary as an ELF image and jumps to
+//! `arch::x86_64::entry::kernel_entry`, which validates the boot info,
+//! zeroes BSS, switches to the kernel stack and calls [`kernel_main`].
 
 #![no_std]
 #![no_main]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
 pub mod arch;
+pub mod cmdline;
 pub mod drivers;
+pub mod initrd;
+pub mod kaslr;
 pub mod memory;
+pub mod modules;
+pub mod sync;
 
+use arch::x86_64::stack::{KERNEL_STACK, KERNEL_STACK_GUARD_SIZE, KERNEL_STACK_SIZE};
+use arch::x86_64::{cpu, entry, fpu, gdt, halt, idt, interrupts, protection};
+use cmdline::{KernelOptions, LogLevel, SerialPortId};
+use drivers::pic8259;
 use drivers::serial::SerialPort;
+use ferrous_alloc::PhysRange;
+use ferrous_boot_info::{tag_type, KernelBootInfo, TagList, MAX_MODULES};
+
+/// Ranges carved out in step 4 besides the boot modules: kernel image,
+/// boot info, kernel stack and initrd. With the modules and the `mem=`
+/// limit this stays within `ferrous_alloc::frame::MAX_RESERVED_RANGES`.
+const FIXED_RESERVED: usize = 4;
+
+/// First Rust function executing on the kernel's own stack.
+///
+/// At this point:
+/// - Boot services have exited.
+/// - We are on [`KERNEL_STACK`] with interrupts disabled.
+/// - `boot_info` has been validated by `kernel_entry` and points into the
+///   bootloader's `KERNEL_BOOT_INFO` static through the direct map — valid
+///   for the lifetime of the kernel.
+/// - We run on the bootloader-built page tables, which still identity-map
+///   low memory until step 3b.
+extern "C" fn kernel_main(boot_info: &'static KernelBootInfo) -> ! {
+    // -----------------------------------------------------------------------
+    // Step 1: UART init — kernel now owns COM1 configuration.
+    //
+    // SAFETY: ring 0, single-threaded, no other code is touching COM1.
+    unsafe { SerialPort::new().init() };
+
+    serial_println!();
+    serial_println!("=== Ferrous Kernel ===");
+    serial_println!("[OK] kernel_entry: BootInfo validated");
+
+    // SAFETY: a version 3 tag list sits in the same bootloader static right
+    // behind `boot_info` and is carved out together with it in step 4.
+    let tags = match unsafe { boot_info.tags() } {
+        Ok(tags) => tags,
+        Err(e) => {
+            serial_println!("[WARN] Boot info tags ignored: {:?}", e);
+            TagList::empty()
+        }
+    };
+    serial_println!(
+        "[INFO] Boot info v{}: {} tags ({} bytes)",
+        boot_info.version,
+        tags.len(),
+        tags.size()
+    );
+    let options = parse_cmdline(&tags);
+    if options.log >= LogLevel::Debug {
+        for tag in &tags {
+            serial_println!(
+                "  tag {:#x} ({}): {} bytes",
+                tag.ty,
+                tag_type::name(tag.ty),
+                tag.payload.len()
+            );
+        }
+    }
+
+    // `serial=`: move the console. COM1 stays initialised for fatal reports
+    // issued before this point.
+    if options.serial != SerialPortId::Com1 {
+        let port = SerialPort::with_base(options.serial.io_base());
+        serial_println!(
+            "[INFO] Console moving to {:?} ({:#x})",
+            options.serial,
+            options.serial.io_base()
+        );
+        // SAFETY: ring 0, single-threaded, nothing else touches that port.
+        unsafe { port.init() };
+        port.set_console();
+        serial_println!("=== Ferrous Kernel ({:?}) ===", options.serial);
+    }
+    serial_println!("[OK] Kernel stack active");
+
+    let (image_start, image_end) = entry::kernel_image_range();
+    // The bootloader maps the image onto physically contiguous pages.
+    let Some(image_phys) = memory::virt_to_phys(image_start) else {
+        serial_println!("[FAIL] Kernel image at {:#x} not mapped?", image_start);
+        halt();
+    };
+    serial_println!(
+        "[INFO] Kernel image: {:#x} - {:#x} ({} KiB) at physical {:#x}",
+        image_start,
+        image_end,
+        (image_end - image_start) / 1024,
+        image_phys
+    );
+    match kaslr::init(&tags) {
+        Some(p) if p.slide != 0 => serial_println!(
+            "[INFO] KASLR: slide {:#x}, physical base {:#x}",
+            p.slide,
+            p.phys_base
+        ),
+        Some(p) => serial_println!(
+            "[INFO] KASLR: off, running at link address (physical base {:#x})",
+            p.phys_base
+        ),
+        None => serial_println!("[INFO] KASLR: no placement reported; assuming slide 0"),
+    }
+
+    // Stack bounds computed from the static address.
+    let stack_bottom = core::ptr::addr_of!(KERNEL_STACK) as usize;
+    serial_println!(
+        "[INFO] Kernel stack: {:#x} - {:#x} ({} KiB, guard={} KiB)",
+        stack_bottom,
+        stack_bottom + KERNEL_STACK_SIZE,
+        KERNEL_STACK_SIZE / 1024,
+        KERNEL_STACK_GUARD_SIZE / 1024
+    );
+
+    // -----------------------------------------------------------------------
+    // Step 1b: Identify the CPU. Everything that depends on optional
+    // hardware (NX, SMEP, XSAVE, ...) asks `cpu::has` from here on.
+    //
+    // SAFETY: single-threaded, interrupts disabled, called exactly once.
+    let cpu_features = unsafe { cpu::init() };
+    serial_println!("[OK] CPU: {}", cpu_features);
+    serial_println!("[INFO] CPU features: {}", cpu_features.feature_names());
+    if options.log >= LogLevel::Debug {
+        for cache in cpu_features.caches() {
+            serial_println!("  {}", cache);
+        }
+    }
+
+    // -----------------------------------------------------------------------
+    // Step 2: Load GDT — set up kernel code/data segments and the TSS.
+    //
+    // The UEFI firmware may have installed its own GDT, which is no longer
+    // mapped or valid after exit_boot_services(). We install a minimal GDT
+    // with exactly the segments needed for kernel operation.
+    //
+    // SAFETY: CPL=0, interrupts disabled since the bootloader's `cli`, GDT is
+    // a static in the permanently loaded kernel image.
+    unsafe { gdt::init() };
+    serial_println!("[OK] GDT loaded (null / kernel-code 0x08 / kernel-data 0x10 / TSS 0x18)");
+
+    // -----------------------------------------------------------------------
+    // Step 3: Load IDT — install exception stubs, load IDTR.
+    //
+    // After this call, any CPU exception will be caught by our stub handlers
+    // instead of triple-faulting; unhandled vectors print the fatal report.
+    // Interrupts remain disabled (no STI).
+    //
+    // SAFETY: CPL=0, interrupts disabled, called exactly once.
+    unsafe { idt::init() };
+    serial_println!("[OK] IDT loaded (256 vectors -> interrupt dispatch, interrupts disabled)");
+    serial_println!("[OK] IST stacks: #DF=IST1 NMI=IST2 #MC=IST3");
+
+    // Built-in handlers; every other vector still ends in the fatal report.
+    // `int3` checks that a handled exception returns through IRETQ.
+    interrupts::init();
+    // SAFETY: #BP is handled by the breakpoint handler registered above,
+    // which resumes after the instruction. The stubs restore every register
+    // Rust code relies on; the CPU pushes the frame below RSP (the kernel
+    // target has no red zone).
+    unsafe { core::arch::asm!("int3") };
+    serial_println!("[OK] Interrupt dispatch: int3 handled and resumed");
+
+    // -----------------------------------------------------------------------
+    // Step 3b: Drop the bootloader's identity mapping.
+    //
+    // Everything the kernel uses is higher-half from here on: the image
+    // (code, stacks, GDT, IDT, TSS) at KERNEL_VIRT_BASE and the boot info,
+    // tags and all other physical memory through the direct map. A stray
+    // physical pointer now faults instead of silently working.
+    //
+    // SAFETY: interrupts disabled; the GDT and IDT just loaded live in the
+    // kernel image, and no lower-half pointer is held.
+    let cleared = unsafe { memory::paging::remove_identity_map() };
+    serial_println!(
+        "[OK] Identity mapping removed ({} PML4 entries); direct map at {:#x}",
+        cleared,
+        memory::DIRECT_MAP_BASE
+    );
+
+    // -----------------------------------------------------------------------
+    // Step 3c: x87/SSE/AVX — enable the FPU state per CPUID and install the
+    // #NM, #MF and #XM handlers. The kernel itself is soft-float; this is
+    // for the code it will run.
+    //
+    // SAFETY: interrupts disabled, `cpu::init` ran in step 1b, no x87/SSE
+    // state is live.
+    let fpu_config = unsafe { fpu::init() };
+    if fpu_config.xsave {
+        serial_println!(
+            "[OK] FPU: x87/SSE{}{} via XSAVE (XCR0={:#x}, {} bytes per task)",
+            if fpu_config.avx() { "/AVX" } else { "" },
+            if fpu_config.avx512() { "/AVX-512" } else { "" },
+            fpu_config.xcr0,
+            fpu_config.state_size
+        );
+    } else {
+        serial_println!(
+            "[OK] FPU: x87/SSE via FXSAVE ({} bytes per task)",
+            fpu_config.state_size
+        );
+    }
+    match fpu::self_test() {
+        Ok(()) => serial_println!("[OK] FPU self-test: save/restore, #NM"),
+        Err(e) => serial_println!("[FAIL] FPU self-test: {}", e),
+    }
+
+    // -----------------------------------------------------------------------
+    // Step 3d: Legacy PICs — move IRQ 0–15 off the exception vectors to
+    // 32–47 and mask them all, so that `sti` cannot deliver a timer tick as
+    // #DF.
+    //
+    // SAFETY: ring 0, interrupts disabled, nothing else programs the PICs.
+    unsafe { pic8259::init() };
+    serial_println!(
+        "[OK] PIC: IRQ 0-15 remapped to vectors {}-{}, all lines masked",
+        pic8259::irq_vector(0),
+        pic8259::irq_vector(pic8259::IRQ_COUNT - 1)
+    );
+
+    serial_println!("[OK] Kernel entered successfully!");
+    serial_println!("Hello from Ferrous!");
+    serial_println!();
+
+    // -----------------------------------------------------------------------
+    // Step 4: Parse and report the physical memory map.
+    //
+    // Carve out everything that is still live: the kernel image (which also
+    // holds KERNEL_STACK and the frame bitmap in .bss) and the boot info
+    // structure plus its tags inside the bootloader image, the initrd and
+    // the boot modules. All normally sit in LOADER_* memory; as
+    // KERNEL_RESERVED they are safe from the allocator and from the
+    // reclamation pass in step 8.
+    // The stack lives in the image's .bss and the boot info in the direct
+    // map; the carve-outs need their physical addresses.
+    let stack_phys = image_phys + (stack_bottom as u64 - image_start);
+    let stack_range = PhysRange::from_base_len(stack_phys, KERNEL_STACK_SIZE as u64);
+    // `kernel_entry` checked that `boot_info` is a direct-map address.
+    let boot_info_phys = boot_info as *const KernelBootInfo as u64 - memory::DIRECT_MAP_BASE;
+    let initrd_range = initrd::range(&tags);
+    let mut reserved = [PhysRange::new(0, 0); FIXED_RESERVED + MAX_MODULES];
+    reserved[..FIXED_RESERVED].copy_from_slice(&[
+        PhysRange::from_base_len(image_phys, image_end - image_start),
+        PhysRange::from_base_len(boot_info_phys, boot_info.total_size() as u64),
+        stack_range,
+        initrd_range.unwrap_or(PhysRange::new(0, 0)),
+    ]);
+    // The bootloader loads at most MAX_MODULES; any extra tags are ignored
+    // here and by `modules::iter` users alike.
+    for (slot, module) in reserved[FIXED_RESERVED..].iter_mut().zip(tags.modules()) {
+        *slot = modules::range(&module);
+    }
+    // SAFETY: called once, single-threaded, interrupts disabled; loader
+    // memory (which holds a version 2 extended map) is untouched until step 8.
+    let map = match unsafe { memory::init(boot_info, &reserved) } {
+        Ok(map) => map,
+        Err(e) => {
+            serial_println!("[FAIL] Memory map parse failed: {:?}", e);
+            halt();
+        }
+    };
+    // A map too long for the kernel's copy loses its highest regions rather
+    // than stopping the boot; say so whatever the log level.
+    let dropped = map.stats().dropped_bytes;
+    if dropped != 0 {
+        serial_println!(
+            "[WARN] Memory map exceeds {} regions; ignoring the top {} KiB",
+            memory::MAX_MEMORY_REGIONS,
+            dropped / 1024
+        );
+    }
+    if options.log >= LogLevel::Info {
+        print_memory_map(map);
+    }
+
+    // -----------------------------------------------------------------------
+    // Step 5: Physical frame allocator.
+    //
+    // The map already excludes the carve-outs; passing the same ranges as
+    // reserved ranges keeps them withheld should a later map edit drop one.
+    // `mem=` withholds everything from the limit up in the same way.
+    let mut frame_reserved = [PhysRange::new(0, 0); FIXED_RESERVED + MAX_MODULES + 1];
+    frame_reserved[..reserved.len()].copy_from_slice(&reserved);
+    if let Some(limit) = options.mem_limit {
+        frame_reserved[reserved.len()] = PhysRange::new(limit, u64::MAX);
+        serial_println!(
+            "[INFO] mem={} MiB: ignoring memory above {:#x}",
+            limit >> 20,
+            limit
+        );
+    }
+    // SAFETY: called once, after memory::init(), interrupts disabled.
+    match unsafe { memory::frame::init(map, &frame_reserved) } {
+        Ok(stats) => serial_println!(
+            "[OK] Frame allocator: {} free frames ({} MiB), {} reserved, {} untracked{}",
+            stats.free_frames,
+            stats.free_bytes() / 1024 / 1024,
+            stats.reserved_frames,
+            stats.untracked_frames,
+            if stats.is_consistent() {
+                ""
+            } else {
+                " [WARN: counters disagree with memory map]"
+            }
+        ),
+        Err(e) => {
+            serial_println!("[FAIL] Frame allocator init failed: {:?}", e);
+            halt();
+        }
+    }
+
+    // -----------------------------------------------------------------------
+    // Step 5b: Initial ramdisk, reserved in step 4.
+    if let Some(range) = initrd_range {
+        open_initrd(range, options.log);
+    }
+
+    // -----------------------------------------------------------------------
+    // Step 5c: Boot modules, reserved in step 4.
+    //
+    // SAFETY: the module ranges were carved out in step 4 and are never
+    // written by the kernel.
+    unsafe { modules::init(tags) };
+    for module in modules::iter() {
+        serial_println!(
+            "[OK] Module `{}`: {:#x} - {:#x} ({} bytes)",
+            module.name,
+            module.phys_addr,
+            module.phys_end(),
+            module.size
+        );
+    }
+
+    // -----------------------------------------------------------------------
+    // Step 6: Paging — inspect the active page tables and exercise
+    // map/translate/unmap on a scratch page.
+    let root = memory::paging::active_root();
+    let entry_virt = memory::paging::VirtAddr::new_truncate(kernel_main as *const () as u64);
+    match memory::paging::translate(entry_virt) {
+        Some(t) => serial_println!(
+            "[INFO] Paging: CR3={:#x}, kernel_main {:#x} -> {:#x} ({:?}, {:?})",
+            root.as_u64(),
+            entry_virt.as_u64(),
+            t.phys.as_u64(),
+            t.size,
+            t.flags
+        ),
+        None => serial_println!(
+            "[WARN] Paging: CR3={:#x}, kernel_main {:#x} not mapped?",
+            root.as_u64(),
+            entry_virt.as_u64()
+        ),
+    }
+    // SAFETY: interrupts disabled, frame allocator initialised above.
+    match unsafe { memory::paging::self_test() } {
+        Ok(()) => serial_println!("[OK] Paging self-test: map/translate/unmap/invlpg"),
+        Err(e) => serial_println!("[FAIL] Paging self-test: {}", e),
+    }
+
+    // -----------------------------------------------------------------------
+    // Step 6b: Memory protection — CR0.WP, NX, SMEP, SMAP and UMIP, then W^X
+    // for the kernel image. Before the heap, so that heap pages are mapped
+    // NX, and before step 7, which unmaps a page inside `.bss`.
+    //
+    // SAFETY: interrupts disabled; the kernel only writes to `.data`, `.bss`,
+    // the direct map and the heap, and there are no user mappings.
+    let protection = unsafe { protection::init() };
+    serial_println!(
+        "[OK] CPU protection: WP NX={} SMEP={} SMAP={} UMIP={}",
+        protection.nx,
+        protection.smep,
+        protection.smap,
+        protection.umip
+    );
+    // SAFETY: as above; nothing writes to `.text`/`.rodata` or executes
+    // from data after this point.
+    match unsafe { protection::protect_kernel_image() } {
+        Ok(p) => serial_println!(
+            "[OK] W^X: {} text, {} rodata, {} data pages; {} direct-map pages NX, {} aliases read-only",
+            p.text_pages,
+            p.rodata_pages,
+            p.data_pages,
+            p.direct_map_pages,
+            p.alias_pages
+        ),
+        Err(e) => serial_println!("[WARN] W^X not fully enforced: {}", e),
+    }
+    match protection::self_test() {
+        Ok(()) => serial_println!("[OK] W^X self-test: writes to .text and its alias raised #PF"),
+        Err(e) => serial_println!("[FAIL] W^X self-test: {}", e),
+    }
+
+    // -----------------------------------------------------------------------
+    // Step 6c: Slab caches — straight from the frame allocator.
+    match memory::slab::self_test() {
+        Ok(()) => serial_println!("[OK] Slab cache self-test: alloc/free/shrink"),
+        Err(e) => serial_println!("[FAIL] Slab cache self-test: {}", e),
+    }
+    memory::slab::dump();
+
+    // -----------------------------------------------------------------------
+    // Step 6d: Kernel heap (`alloc` feature).
+    #[cfg(feature = "alloc")]
+    init_heap();
+
+    // -----------------------------------------------------------------------
+    // Step 7: Enforce the kernel stack guard page.
+    //
+    // SAFETY: interrupts disabled, frame allocator initialised; RSP is far
+    // above the guard page at this point.
+    match unsafe { arch::x86_64::stack::unmap_guard_page() } {
+        Ok(guard) => serial_println!("[OK] Stack guard page unmapped at {:#x}", guard),
+        Err(e) => serial_println!("[WARN] Stack guard page not enforced: {:?}", e),
+    }
+
+    // -----------------------------------------------------------------------
+    // Step 8: Reclaim boot-services and loader memory.
+    //
+    // Nothing reads bootloader or firmware data any more except the boot
+    // info, the initrd and the modules, which stay reserved along with the
+    // kernel image and stacks. `noreclaim` leaves it all reserved, for
+    // debugging the handoff.
+    if options.noreclaim {
+        serial_println!("[INFO] Boot memory reclamation skipped (noreclaim)");
+    } else {
+        reclaim_boot_memory();
+    }
+
+    if let Some(test) = options.test {
+        serial_println!("[INFO] test={}: no boot test runner yet, ignored", test);
+    }
+
+    if boot_info.acpi_rsdp != 0 {
+        serial_println!("[INFO] ACPI RSDP: {:#x}", boot_info.acpi_rsdp);
+    }
+
+    if boot_info.has_framebuffer {
+        serial_println!(
+            "[INFO] Framebuffer: {}x{} @ {:#x}",
+            boot_info.framebuffer.width,
+            boot_info.framebuffer.height,
+            boot_info.framebuffer.base
+        );
+    }
+
+    // -----------------------------------------------------------------------
+    // Step 9: Enable interrupts. Every PIC line is masked, so only NMIs and
+    // spurious IRQ 7/15 (dropped by `pic8259`) can arrive until drivers
+    // unmask their lines.
+    //
+    // SAFETY: the IDT covers every vector and the PICs no longer alias the
+    // exception vectors.
+    unsafe { core::arch::asm!("sti", options(nomem, nostack)) };
+    serial_println!("[OK] Interrupts enabled (IRQ lines masked)");
+
+    serial_println!();
+    serial_println!(
+        "Kernel halting. Exception handlers active — any CPU exception will be caught."
+    );
+
+    halt()
+}
+
+/// Give boot-services and loader memory to the frame allocator and report
+/// the result.
+fn reclaim_boot_memory() {
+    match memory::frame::reclaim_boot_memory() {
+        Ok(r) => serial_println!(
+            "[OK] Reclaimed {} MiB of boot memory ({} regions, {} page-table frames kept, {} reserved, {} untracked)",
+            r.reclaimed_bytes() / 1024 / 1024,
+            r.regions,
+            r.in_use_frames,
+            r.reserved_frames,
+            r.untracked_frames
+        ),
+        Err(e) => serial_println!("[WARN] Boot memory not reclaimed: {:?}", e),
+    }
+    if let Some(stats) = memory::frame::stats() {
+        serial_println!(
+            "[INFO] Frame allocator: {} free frames ({} MiB){}",
+            stats.free_frames,
+            stats.free_bytes() / 1024 / 1024,
+            if stats.is_consistent() {
+                ""
+            } else {
+                " [WARN: counters disagree with memory map]"
+            }
+        );
+    }
+}
+
+/// Validate the initrd in `range` and list its contents.
+fn open_initrd(range: PhysRange, log: LogLevel) {
+    // SAFETY: the bootloader copied the archive to `range`, which is
+    // direct-mapped and was carved out of the memory map in step 4.
+    match unsafe { initrd::init(range) } {
+        Ok(archive) => {
+            serial_println!(
+                "[OK] Initrd: {:#x} - {:#x}, {:?}, {} entries",
+                range.start,
+                range.end,
+                archive.format(),
+                archive.len()
+            );
+            if log >= LogLevel::Debug {
+                for entry in archive.entries() {
+                    serial_println!(
+                        "  {:?} {:o} {} ({} bytes)",
+                        entry.kind(),
+                        entry.mode(),
+                        entry.path(),
+                        entry.data().len()
+                    );
+                }
+            }
+        }
+        Err(e) => serial_println!("[WARN] Initrd at {:#x} ignored: {:?}", range.start, e),
+    }
+}
+
+/// Parse the `CMDLINE` boot info tag; defaults if there is none.
+///
+/// Problems are reported as warnings and never stop the boot.
+fn parse_cmdline(tags: &TagList<'static>) -> KernelOptions<'static> {
+    let Some(tag) = tags.find(tag_type::CMDLINE) else {
+        return KernelOptions::default();
+    };
+    let Ok(text) = core::str::from_utf8(tag.payload) else {
+        serial_println!("[WARN] Command line is not UTF-8; ignored");
+        return KernelOptions::default();
+    };
+    serial_println!("[INFO] Command line: {}", text);
+    cmdline::parse(text, |warning| {
+        serial_println!("[WARN] Command line: {}", warning)
+    })
+}
+
+/// Attach the kernel heap, exercise it and report per-size-class usage.
+#[cfg(feature = "alloc")]
+fn init_heap() {
+    if let Err(e) = memory::heap::init() {
+        serial_println!("[FAIL] Kernel heap init failed: {:?}", e);
+        halt();
+    }
+    match memory::heap::self_test() {
+        Ok(()) => serial_println!("[OK] Kernel heap: Box/Vec/BTreeMap self-test passed"),
+        Err(e) => serial_println!("[FAIL] Kernel heap self-test: {}", e),
+    }
+    if let Some(stats) = memory::heap::stats() {
+        serial_println!(
+            "[INFO] Heap @ {:#x}: {} KiB mapped, {} KiB free pages, {} large allocations live",
+            memory::heap::HEAP_START,
+            stats.pool_bytes() / 1024,
+            stats.free_pages * 4,
+            stats.large_live
+        );
+        for class in stats.classes.iter().filter(|c| c.pages != 0) {
+            serial_println!(
+                "[INFO]   {:>4} B: {} page(s), {} live, {} free, {} allocations",
+                class.size,
+                class.pages,
+                class.live,
+                class.free,
+                class.total_allocations
+            );
+        }
+    }
+}
+
+/// Print the parsed physical memory map and summary statistics over serial.
+fn print_memory_map(map: &memory::MemoryMap) {
+    let stats = map.stats();
+    serial_println!(
+        "[INFO] Physical memory map ({} entries{}):",
+        stats.region_count,
+        if stats.is_truncated {
+            ", TRUNCATED"
+        } else {
+            ""
+        }
+    );
+
+    for (i, desc) in map.regions().iter().enumerate() {
+        let size = desc.size_bytes();
+        let kib = size / 1024;
+        let (amount, unit) = if kib >= 1024 {
+            (kib / 1024, "MiB")
+        } else {
+            (kib, "KiB")
+        };
+        // Format: "  [0] 0x1000 - 0x100000  1020 KiB  Conventional"
+        serial_println!(
+            "  [{}] {:#x} - {:#x}  {} {}  {}",
+            i,
+            desc.phys_start,
+            desc.phys_start.saturating_add(size),
+            amount,
+            unit,
+            memory::MemoryRegionKind::from(desc.ty).name()
+        );
+    }
+
+    if stats.is_truncated {
+        serial_println!("[WARN] Memory map was truncated — some regions are missing!");
+    }
+    if stats.overlapping_bytes != 0 {
+        serial_println!(
+            "[WARN] Firmware map had {} KiB of overlapping descriptors (most restrictive type kept)",
+            stats.overlapping_bytes / 1024
+        );
+    }
+
+    serial_println!(
+        "[INFO] RAM: {} MiB total | {} MiB usable | {} MiB reclaimable",
+        stats.total_bytes / 1024 / 1024,
+        stats.usable_bytes / 1024 / 1024,
+        stats.reclaimable_bytes / 1024 / 1024
+    );
+}
 
 /// Kernel panic handler.
 ///
-/// Writes "KERNEL PANIC" to COM1 and halts. The `SerialPort` driver is used
-/// here without calling `init()` first — we rely on the UART having been
-/// initialised during `kernel_main`. If a panic occurs before that point the
-/// output may be garbled, but the alternative (silently looping) is worse.
+/// Writes "KERNEL PANIC" and the panic message to COM1 and halts. The UART
+/// is normally initialised at the top of `kernel_main`; a panic before that
+/// point may produce garbled output, but the alternative (silently looping)
+/// is worse.
 #[panic_handler]
-fn panic(_info: &core::panic::PanicInfo) -> ! {
-    let serial = SerialPort::new();
-    serial.write_str("\r\nKERNEL PANIC\r\n");
-    loop {
-        // SAFETY: `hlt` safely suspends the CPU until the next interrupt.
-        unsafe { core::arch::asm!("hlt") };
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    serial_println!();
+    serial_println!("KERNEL PANIC: {}", info);
+    if kaslr::slide() != 0 {
+        serial_println!("Kernel slide: {:#x}", kaslr::slide());
     }
+    halt()
 }
@@ -0,0 +1,183 @@
+//! Global physical frame allocator.
+//!
+//! Wraps [`ferrous_alloc::PhysicalFrameAllocator`] in a [`SpinLock`] and
+//! gives it a `.bss` bitmap sized for [`MAX_PHYS_MEMORY`] of physical
+//! address space (128 KiB of bitmap for 4 GiB). Usable memory above that
+//! limit is not handed out and is reported as untracked.
+//!
+//! # Usage
+//!
+//! ```ignore
+//! // SAFETY: called once, after memory::init(), interrupts disabled.
+//! unsafe { memory::frame::init(map, &reserved) }?;
+//! let frame = memory::frame::allocate_frame().expect("out of memory");
+//! memory::frame::deallocate_frame(frame)?;
+//! ```
+//!
+//! # Boot memory reclamation
+//!
+//! Boot-services and loader memory stays off-limits until
+//! [`reclaim_boot_memory`] runs. The reserved ranges passed to [`init`]
+//! (kernel image, boot info, stacks) remain withheld, and the frames of the
+//! active page tables — which OVMF allocates as boot-services data — are
+//! kept as allocated.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use ferrous_alloc::{
+    FrameAllocError, FrameStats, PhysFrame, PhysRange, PhysicalFrameAllocator, ReclaimStats,
+};
+
+use super::{paging, MemoryMap};
+use crate::sync::SpinLock;
+
+/// Physical address space covered by the frame bitmap (4 GiB).
+pub const MAX_PHYS_MEMORY: u64 = 4 * 1024 * 1024 * 1024;
+
+const BITMAP_WORDS: usize = PhysicalFrameAllocator::bitmap_words_for(MAX_PHYS_MEMORY);
+
+/// Most page tables [`reclaim_boot_memory`] can protect.
+const MAX_PAGE_TABLES: usize = 1024;
+
+/// Bitmap storage handed to the allocator in [`init`].
+///
+/// # SAFETY invariant
+///
+/// Only [`init`] creates a reference to this static, exactly once (guarded
+/// by [`INITIALIZED`]); from then on the allocator owns it.
+static mut FRAME_BITMAP: [u64; BITMAP_WORDS] = [0; BITMAP_WORDS];
+
+/// Set by the first call to [`init`].
+static INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+/// The global allocator; `None` until [`init`] succeeds.
+static FRAME_ALLOCATOR: SpinLock<Option<PhysicalFrameAllocator<'static>>> = SpinLock::new(None);
+
+/// Initialise the global frame allocator from `map`.
+///
+/// Every frame overlapping a range in `reserved` is withheld. Returns the
+/// initial counters.
+///
+/// # Errors
+///
+/// Propagates [`FrameAllocError`] from [`PhysicalFrameAllocator::new`].
+///
+/// # Safety
+///
+/// - Must be called **exactly once**; a second call panics.
+/// - `reserved` must cover every range of usable memory that is still in
+///   use (kernel image, boot info, stacks), otherwise those frames will be
+///   handed out and overwritten.
+pub unsafe fn init(
+    map: &'static MemoryMap,
+    reserved: &[PhysRange],
+) -> Result<FrameStats, FrameAllocError> {
+    assert!(
+        !INITIALIZED.swap(true, Ordering::AcqRel),
+        "memory::frame::init() called more than once"
+    );
+
+    // SAFETY: INITIALIZED guarantees this is the only reference ever taken.
+    #[allow(static_mut_refs)]
+    let storage: &'static mut [u64] = &mut *core::ptr::addr_of_mut!(FRAME_BITMAP);
+
+    let allocator = PhysicalFrameAllocator::new(map, storage, reserved)?;
+    let stats = allocator.stats();
+    *FRAME_ALLOCATOR.lock() = Some(allocator);
+    Ok(stats)
+}
+
+/// Allocate a single 4 KiB frame.
+///
+/// Returns `None` when memory is exhausted or [`init`] has not run.
+pub fn allocate_frame() -> Option<PhysFrame> {
+    FRAME_ALLOCATOR.lock().as_mut()?.allocate_frame()
+}
+
+/// Allocate `count` physically contiguous frames.
+///
+/// Returns `None` when no run is free or [`init`] has not run.
+pub fn allocate_contiguous(count: u64) -> Option<PhysFrame> {
+    FRAME_ALLOCATOR.lock().as_mut()?.allocate_contiguous(count)
+}
+
+/// Return a frame to the allocator.
+///
+/// # Errors
+///
+/// See [`PhysicalFrameAllocator::deallocate_frame`]. Before [`init`] every
+/// frame is reported as [`FrameAllocError::NotManaged`].
+pub fn deallocate_frame(frame: PhysFrame) -> Result<(), FrameAllocError> {
+    match FRAME_ALLOCATOR.lock().as_mut() {
+        Some(fa) => fa.deallocate_frame(frame),
+        None => Err(FrameAllocError::NotManaged {
+            addr: frame.start_address(),
+        }),
+    }
+}
+
+/// Return `count` contiguous frames starting at `first`.
+///
+/// # Errors
+///
+/// As for [`deallocate_frame`].
+pub fn deallocate_contiguous(first: PhysFrame, count: u64) -> Result<(), FrameAllocError> {
+    match FRAME_ALLOCATOR.lock().as_mut() {
+        Some(fa) => fa.deallocate_contiguous(first, count),
+        None => Err(FrameAllocError::NotManaged {
+            addr: first.start_address(),
+        }),
+    }
+}
+
+/// Current allocator counters, or `None` before [`init`].
+pub fn stats() -> Option<FrameStats> {
+    FRAME_ALLOCATOR.lock().as_ref().map(|fa| fa.stats())
+}
+
+/// Errors returned by [`reclaim_boot_memory`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReclaimError {
+    /// [`init`] has not run.
+    NotInitialized,
+    /// The active hierarchy has more than [`MAX_PAGE_TABLES`] tables, so
+    /// some of them could not be protected. Nothing was reclaimed.
+    TooManyPageTables,
+    /// The allocator refused (already reclaimed).
+    Allocator(FrameAllocError),
+}
+
+/// Hand boot-services and loader memory to the allocator.
+///
+/// Call once the kernel no longer reads anything the bootloader or firmware
+/// left behind, other than the ranges reserved at [`init`]. Frames holding
+/// the active page tables are kept and counted as allocated.
+///
+/// # Errors
+///
+/// See [`ReclaimError`].
+pub fn reclaim_boot_memory() -> Result<ReclaimStats, ReclaimError> {
+    // Collect the table frames first: the paging lock must not be taken
+    // while the allocator lock is held (table allocation nests them the
+    // other way round).
+    let mut tables = [0u64; MAX_PAGE_TABLES];
+    let mut count = 0;
+    let mut overflow = false;
+    paging::for_each_table(|table| match tables.get_mut(count) {
+        Some(slot) => {
+            *slot = table.as_u64();
+            count += 1;
+        }
+        None => overflow = true,
+    });
+    if overflow {
+        return Err(ReclaimError::TooManyPageTables);
+    }
+    let tables = &mut tables[..count];
+    tables.sort_unstable();
+
+    let mut guard = FRAME_ALLOCATOR.lock();
+    let fa = guard.as_mut().ok_or(ReclaimError::NotInitialized)?;
+    fa.reclaim_bootloader_memory(|f| tables.binary_search(&f.start_address()).is_ok())
+        .map_err(ReclaimError::Allocator)
+}
@@ -0,0 +1,139 @@
+//! Kernel heap (`alloc` feature).
+//!
+//! Installs a [`ferrous_alloc::LockedHeap`] as the `#[global_allocator]`.
+//! Its backend maps fresh frames from the physical frame allocator into the
+//! dedicated virtual range [`HEAP_START`]..[`HEAP_START`] + [`HEAP_MAX_SIZE`],
+//! one growth step at a time, so the heap starts empty and grows on demand.
+//!
+//! # Usage
+//!
+//! ```ignore
+//! memory::heap::init()?;
+//! let v: Vec<u64> = (0..16).collect();
+//! serial_println!("{:?}", memory::heap::stats());
+//! ```
+//!
+//! Allocating before [`init`] fails and ends in the `alloc` error handler
+//! (a panic). The heap lock does not disable interrupts: interrupt handlers
+//! must not allocate.
+
+use core::ptr::NonNull;
+
+use ferrous_alloc::{HeapBackend, HeapError, HeapStats, LockedHeap, PhysFrame};
+
+use super::frame;
+use super::paging::{self, PhysAddr, VirtAddr, PAGE_SIZE};
+use crate::arch::x86_64::without_interrupts;
+
+/// First virtual address of the heap range (PML4 slot 384).
+pub const HEAP_START: u64 = 0xFFFF_C000_0000_0000;
+
+/// Size of the heap's virtual range (256 MiB).
+pub const HEAP_MAX_SIZE: u64 = 256 * 1024 * 1024;
+
+/// The global allocator; allocations fail until [`init`] has run.
+#[global_allocator]
+static HEAP: LockedHeap<KernelHeapBackend> = LockedHeap::empty();
+
+/// [`HeapBackend`] that maps frames at the next free address of the heap
+/// range.
+pub struct KernelHeapBackend {
+    /// End of the mapped part of the heap range.
+    next: u64,
+}
+
+impl KernelHeapBackend {
+    /// Undo a partially completed [`grow`](HeapBackend::grow): unmap the
+    /// pages in `[self.next, end)` and free their frames.
+    fn rollback(&self, end: u64) {
+        for addr in (self.next..end).step_by(PAGE_SIZE as usize) {
+            // SAFETY: the page was mapped by `grow` moments ago and has not
+            // been handed to the heap.
+            let unmapped =
+                without_interrupts(|| unsafe { paging::unmap(VirtAddr::new_truncate(addr)) });
+            if let Ok(t) = unmapped {
+                let _ = frame::deallocate_frame(PhysFrame::containing_address(t.frame.as_u64()));
+            }
+        }
+    }
+}
+
+impl HeapBackend for KernelHeapBackend {
+    fn grow(&mut self, pages: usize) -> Option<NonNull<u8>> {
+        let start = self.next;
+        let end = start.checked_add(pages as u64 * PAGE_SIZE)?;
+        if end > HEAP_START + HEAP_MAX_SIZE {
+            return None;
+        }
+        let flags = paging::kernel_data_flags();
+        for addr in (start..end).step_by(PAGE_SIZE as usize) {
+            let mapped = frame::allocate_frame().and_then(|f| {
+                let phys = PhysAddr::new_truncate(f.start_address());
+                // SAFETY: `addr` lies in the heap range above `self.next`,
+                // which nothing else maps; the frame is freshly allocated.
+                let result = without_interrupts(|| unsafe {
+                    paging::map(VirtAddr::new_truncate(addr), phys, flags)
+                });
+                match result {
+                    Ok(()) => Some(()),
+                    Err(_) => {
+                        let _ = frame::deallocate_frame(f);
+                        None
+                    }
+                }
+            });
+            if mapped.is_none() {
+                self.rollback(addr);
+                return None;
+            }
+        }
+        self.next = end;
+        NonNull::new(start as *mut u8)
+    }
+}
+
+/// Attach the backend; allocations succeed from here on.
+///
+/// Requires [`frame::init`] to have run. No memory is mapped until the
+/// first allocation.
+///
+/// # Errors
+///
+/// [`HeapError::AlreadyInitialized`] on a second call.
+pub fn init() -> Result<(), HeapError> {
+    HEAP.init(KernelHeapBackend { next: HEAP_START })
+}
+
+/// Current heap counters, or `None` before [`init`].
+pub fn stats() -> Option<HeapStats> {
+    HEAP.stats()
+}
+
+/// Exercise `Box`, `Vec` and `BTreeMap`, checking their contents.
+///
+/// Returns a short description of the first failure.
+pub fn self_test() -> Result<(), &'static str> {
+    use alloc::boxed::Box;
+    use alloc::collections::BTreeMap;
+    use alloc::vec::Vec;
+
+    let boxed = Box::new(0xFE44_u64);
+    if *boxed != 0xFE44 {
+        return Err("Box contents wrong");
+    }
+
+    // 64 KiB: forces a large, multi-page allocation and heap growth.
+    let v: Vec<u64> = (0..8192).collect();
+    if v.iter().sum::<u64>() != 8191 * 8192 / 2 {
+        return Err("Vec contents wrong");
+    }
+
+    let mut map = BTreeMap::new();
+    for i in 0..256u32 {
+        map.insert(i, i * i);
+    }
+    if map.get(&15) != Some(&225) || map.len() != 256 {
+        return Err("BTreeMap contents wrong");
+    }
+    Ok(())
+}
@@ -6,14 +6,25 @@
 //! # Usage
 //!
 //! During early kernel initialisation (before the allocator runs), call
-//! [`init`] exactly once with the memory map from [`KernelBootInfo`]:
+//! [`init`] exactly once with the [`KernelBootInfo`] and the physical ranges
+//! the kernel still needs:
 //!
 //! ```ignore
-//! // SAFETY: called once, single-threaded, interrupts disabled.
-//! let map = unsafe { memory::init(&boot_info.memory_map) }
+//! // SAFETY: called once, single-threaded, interrupts disabled, loader
+//! // memory not yet reclaimed.
+//! let map = unsafe { memory::init(boot_info, &[image, boot_info_range]) }
 //!     .expect("memory map parse failed");
 //! ```
 //!
+//! Version 2 boot info carries the complete firmware map out of line
+//! ([`KernelBootInfo::memory_map_ext`]); [`init`] prefers it over the
+//! 256-entry inline copy, which is all a version 1 bootloader provides.
+//!
+//! The stored map is normalised (sorted, overlaps resolved, neighbours
+//! merged) and the given ranges are carved out as
+//! [`memory_type::KERNEL_RESERVED`], so no allocator built from it — nor
+//! the bootloader reclamation pass — can hand them out.
+//!
 //! Thereafter any kernel subsystem can call [`get`] to borrow the map:
 //!
 //! ```ignore
@@ -21,6 +32,39 @@
 //! for region in map.usable_regions() { ... }
 //! ```
 //!
+//! # Physical frames
+//!
+//! Once the map is initialised, [`frame::init`] seeds the global physical
+//! frame allocator from its usable regions; [`frame::allocate_frame`] and
+//! friends then hand out 4 KiB frames.
+//!
+//! # Address space
+//!
+//! The kernel runs in the higher half ([`ferrous_boot_info::layout`]): its
+//! image at [`KERNEL_VIRT_BASE`] and all physical memory in the direct map
+//! at [`DIRECT_MAP_BASE`]. Physical memory is only ever accessed through
+//! [`phys_to_virt`]; [`virt_to_phys`] goes the other way. The bootloader's
+//! identity mapping is removed by [`paging::remove_identity_map`].
+//!
+//! # Paging
+//!
+//! [`paging`] maps, unmaps and translates pages in the active (CR3)
+//! hierarchy; table-walking logic lives in [`ferrous_paging`] and takes its
+//! intermediate tables from the frame allocator.
+//!
+//! # Slab caches
+//!
+//! [`slab`] provides typed caches for fixed-size kernel objects, fed
+//! directly by the frame allocator (no heap required), and a registry that
+//! [`slab::dump`] prints over serial.
+//!
+//! # Heap
+//!
+//! With the `alloc` feature, [`heap`] installs the `#[global_allocator]`:
+//! a size-class heap ([`ferrous_alloc::heap`]) that maps frames into its own
+//! virtual range as it grows. `Box`, `Vec` and `BTreeMap` work after
+//! [`heap::init`].
+//!
 //! # Re-exports
 //!
 //! The parsing types ([`MemoryMap`], [`MemoryRegionKind`], [`MemoryStats`],
@@ -28,12 +72,21 @@
 //! host without targeting `x86_64-unknown-none`. They are re-exported here
 //! for ergonomic access within the kernel.
 
-use core::mem::MaybeUninit;
+pub mod frame;
+#[cfg(feature = "alloc")]
+pub mod heap;
+pub mod paging;
+pub mod slab;
+
 use core::sync::atomic::{AtomicBool, Ordering};
 
-use ferrous_boot_info::KernelMemoryMap;
+use ferrous_alloc::PhysRange;
+use ferrous_boot_info::{memory_type, KernelBootInfo};
 
-pub use ferrous_boot_info::{MemoryMap, MemoryRegionKind, MemoryStats, ParseError};
+pub use ferrous_boot_info::layout::{phys_to_virt, DIRECT_MAP_BASE, KERNEL_VIRT_BASE};
+pub use ferrous_boot_info::{
+    MemoryMap, MemoryRegionKind, MemoryStats, ParseError, MAX_MEMORY_REGIONS,
+};
 
 // ---------------------------------------------------------------------------
 // Global memory map
@@ -58,9 +111,12 @@ static INITIALIZED: AtomicBool = AtomicBool::new(false);
 ///    safe to take shared references via [`get`].
 ///
 /// No mutable reference is ever taken after [`init`] sets `INITIALIZED`.
+///
+/// The map is 32 KiB, so it is filled in place ([`MemoryMap::load`]) rather
+/// than built on the kernel stack and moved here.
 // SAFETY: written once in `init()` before INITIALIZED is set to true.
 #[allow(static_mut_refs)]
-static mut MEMORY_MAP: MaybeUninit<MemoryMap> = MaybeUninit::uninit();
+static mut MEMORY_MAP: MemoryMap = MemoryMap::empty();
 
 // ---------------------------------------------------------------------------
 // Public API
@@ -68,12 +124,17 @@ static mut MEMORY_MAP: MaybeUninit<MemoryMap> = MaybeUninit::uninit();
 
 /// Initialise the global kernel memory map.
 ///
-/// Parses `source`, stores the result in a `'static` slot, and returns a
-/// reference to it.  This reference is valid for the lifetime of the kernel.
+/// Loads the memory map of `boot_info` (the extended map of a version 2
+/// struct if present, otherwise the inline one), carves every non-empty
+/// range in `reserved` out of it as
+/// [`memory_type::KERNEL_RESERVED`], stores the result in a `'static` slot,
+/// and returns a reference to it.  This reference is valid for the lifetime
+/// of the kernel.
 ///
 /// # Errors
 ///
-/// Propagates any [`ParseError`] from [`MemoryMap::parse`].
+/// Propagates any [`ParseError`] from [`MemoryMap::load`] or
+/// [`MemoryMap::carve_out`].
 ///
 /// # Safety
 ///
@@ -81,27 +142,47 @@ static mut MEMORY_MAP: MaybeUninit<MemoryMap> = MaybeUninit::uninit();
 /// - Must be called **before** any call to [`get`].
 /// - Must be called from a **single-threaded context** with interrupts
 ///   disabled (the standard early-boot environment).
+/// - The extended map of a version 2 `boot_info` must still be intact
+///   (loader memory not yet reclaimed).
 ///
 /// Violating any of these invariants is undefined behaviour.
-pub unsafe fn init(source: &KernelMemoryMap) -> Result<&'static MemoryMap, ParseError> {
+pub unsafe fn init(
+    boot_info: &KernelBootInfo,
+    reserved: &[PhysRange],
+) -> Result<&'static MemoryMap, ParseError> {
     debug_assert!(
         !INITIALIZED.load(Ordering::Relaxed),
         "memory::init() called more than once"
     );
 
-    let map = MemoryMap::parse(source)?;
-
     // SAFETY: single-threaded, interrupts disabled, INITIALIZED is still
     // false so no concurrent reader exists.
     #[allow(static_mut_refs)]
-    MEMORY_MAP.write(map);
+    let map = &mut *core::ptr::addr_of_mut!(MEMORY_MAP);
+
+    // SAFETY: the extended map is intact per this function's contract.
+    let (descriptors, truncated) = boot_info.memory_descriptors(DIRECT_MAP_BASE);
+    map.load(descriptors, truncated)?;
+    for range in reserved.iter().filter(|r| !r.is_empty()) {
+        map.carve_out(range.start, range.end, memory_type::KERNEL_RESERVED)?;
+    }
 
     // Release store: all writes to MEMORY_MAP are visible after this.
     INITIALIZED.store(true, Ordering::Release);
 
-    // SAFETY: MEMORY_MAP was fully written above.
-    #[allow(static_mut_refs)]
-    Ok(MEMORY_MAP.assume_init_ref())
+    // The mutable borrow ends here; only shared references exist from now.
+    Ok(map)
+}
+
+/// Physical address behind the kernel virtual address `virt`.
+///
+/// Direct-map addresses are converted arithmetically; anything else (the
+/// kernel image, the heap) is looked up in the active page tables. `None`
+/// if `virt` is not mapped.
+pub fn virt_to_phys(virt: u64) -> Option<u64> {
+    ferrous_boot_info::layout::direct_map_to_phys(virt).or_else(|| {
+        paging::translate_addr(paging::VirtAddr::new(virt)?).map(paging::PhysAddr::as_u64)
+    })
 }
 
 /// Returns a shared reference to the global memory map.
@@ -112,8 +193,7 @@ pub fn get() -> Option<&'static MemoryMap> {
     if INITIALIZED.load(Ordering::Acquire) {
         // SAFETY: MEMORY_MAP is fully initialised when INITIALIZED is true
         // and is never written again after that point.
-        #[allow(static_mut_refs)]
-        Some(unsafe { MEMORY_MAP.assume_init_ref() })
+        Some(unsafe { &*core::ptr::addr_of!(MEMORY_MAP) })
     } else {
         None
     }
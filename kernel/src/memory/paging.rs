@@ -0,0 +1,305 @@
+//! Kernel page table management.
+//!
+//! Thin layer over [`ferrous_paging`] that binds the generic table walker to
+//! the running CPU:
+//!
+//! - the active hierarchy is found through CR3;
+//! - tables are reached through the direct map
+//!   ([`OffsetTableAccess::new`] with [`DIRECT_MAP_BASE`]);
+//! - intermediate tables come from the global frame allocator
+//!   ([`KernelFrameSource`]);
+//! - every change is followed by `invlpg` for the affected page.
+//!
+//! Edits are serialised by a [`SpinLock`]. The hierarchy is the one the
+//! bootloader built for the kernel; [`remove_identity_map`] drops its
+//! temporary identity mapping of the low half.
+//!
+//! # Usage
+//!
+//! ```ignore
+//! let frame = memory::frame::allocate_frame().expect("out of memory");
+//! // SAFETY: `virt` is unused and `frame` is exclusively ours.
+//! unsafe { paging::map(virt, PhysAddr::new_truncate(frame.start_address()), flags) }?;
+//! assert_eq!(paging::translate_addr(virt), Some(phys));
+//! ```
+
+use ferrous_paging::PageTableMapper;
+
+use super::{frame, phys_to_virt, DIRECT_MAP_BASE};
+use crate::arch::x86_64::protection;
+use crate::sync::SpinLock;
+
+pub use ferrous_paging::{
+    FrameSource, MapError, OffsetTableAccess, PageSize, PageTable, PageTableEntry, PageTableFlags,
+    PhysAddr, Translation, UnmapError, VirtAddr, PAGE_SIZE,
+};
+
+/// CR3 bits 12–51 hold the PML4 address; the rest are PCID/PWT/PCD.
+const CR3_ADDR_MASK: u64 = 0x000F_FFFF_FFFF_F000;
+
+/// PML4 entries mapping the lower (user) half of the address space.
+const LOWER_HALF_ENTRIES: usize = 256;
+
+/// Serialises edits to the active hierarchy.
+static PAGING_LOCK: SpinLock<()> = SpinLock::new(());
+
+// ---------------------------------------------------------------------------
+// Frame source
+// ---------------------------------------------------------------------------
+
+/// [`FrameSource`] backed by the global physical frame allocator.
+pub struct KernelFrameSource;
+
+impl FrameSource for KernelFrameSource {
+    fn allocate_frame(&mut self) -> Option<PhysAddr> {
+        frame::allocate_frame().map(|f| PhysAddr::new_truncate(f.start_address()))
+    }
+}
+
+// ---------------------------------------------------------------------------
+// TLB and CR3
+// ---------------------------------------------------------------------------
+
+/// Invalidate the TLB entry for the page containing `virt`.
+#[inline]
+pub fn invlpg(virt: VirtAddr) {
+    // SAFETY: `invlpg` only drops a cached translation; the next access
+    // re-walks the tables. Valid at CPL=0 for any address.
+    unsafe {
+        core::arch::asm!("invlpg [{}]", in(reg) virt.as_u64(), options(nostack, preserves_flags))
+    };
+}
+
+/// Flush every non-global TLB entry by reloading CR3.
+#[inline]
+pub fn flush_all() {
+    // SAFETY: writing back the current CR3 keeps the same hierarchy and only
+    // discards cached translations.
+    unsafe {
+        core::arch::asm!(
+            "mov {tmp}, cr3",
+            "mov cr3, {tmp}",
+            tmp = out(reg) _,
+            options(nostack, preserves_flags)
+        )
+    };
+}
+
+/// Physical address of the active PML4 (from CR3).
+#[inline]
+pub fn active_root() -> PhysAddr {
+    let cr3: u64;
+    // SAFETY: reading CR3 has no side effects at CPL=0.
+    unsafe { core::arch::asm!("mov {}, cr3", out(reg) cr3, options(nomem, nostack)) };
+    PhysAddr::new_truncate(cr3 & CR3_ADDR_MASK)
+}
+
+/// A mapper for the active hierarchy through the direct map.
+///
+/// # Safety
+///
+/// - All page tables reachable from CR3, and all frames handed out by the
+///   frame allocator, must be covered by the direct map.
+/// - The caller must hold [`PAGING_LOCK`] (or otherwise guarantee no
+///   concurrent edits) for as long as the mapper is used to write.
+unsafe fn active_mapper() -> PageTableMapper<OffsetTableAccess> {
+    // SAFETY: forwarded to the caller.
+    unsafe { PageTableMapper::new(active_root(), OffsetTableAccess::new(DIRECT_MAP_BASE)) }
+}
+
+// ---------------------------------------------------------------------------
+// Public API
+// ---------------------------------------------------------------------------
+
+/// Flags for kernel data pages: writable, and non-executable once
+/// [`protection::init`] has enabled NX.
+pub fn kernel_data_flags() -> PageTableFlags {
+    if protection::nx_enabled() {
+        PageTableFlags::WRITABLE | PageTableFlags::NO_EXECUTE
+    } else {
+        PageTableFlags::WRITABLE
+    }
+}
+
+/// Walk the active page tables for `virt`.
+pub fn translate(virt: VirtAddr) -> Option<Translation> {
+    let _guard = PAGING_LOCK.lock();
+    // SAFETY: the bootloader direct-maps all of physical memory, including
+    // the page tables; the lock keeps the hierarchy stable during the walk.
+    unsafe { active_mapper() }.translate(virt)
+}
+
+/// Physical address `virt` maps to in the active page tables, if any.
+pub fn translate_addr(virt: VirtAddr) -> Option<PhysAddr> {
+    translate(virt).map(|t| t.phys)
+}
+
+/// Call `f` with the physical address of every table in the active
+/// hierarchy (see [`PageTableMapper::for_each_table`]).
+///
+/// `f` runs with the paging lock held and must not map or unmap pages.
+pub fn for_each_table(f: impl FnMut(PhysAddr)) {
+    let _guard = PAGING_LOCK.lock();
+    // SAFETY: as for `translate`.
+    unsafe { active_mapper() }.for_each_table(f)
+}
+
+/// Map the 4 KiB page at `virt` to `phys` in the active page tables.
+///
+/// Intermediate tables are allocated from the frame allocator.
+///
+/// # Errors
+///
+/// See [`MapError`].
+///
+/// # Safety
+///
+/// - Interrupts must be disabled.
+/// - `phys` must not be memory that Rust code elsewhere assumes is
+///   exclusively owned, and `virt` must not alias live references.
+pub unsafe fn map(virt: VirtAddr, phys: PhysAddr, flags: PageTableFlags) -> Result<(), MapError> {
+    let _guard = PAGING_LOCK.lock();
+    // SAFETY: direct map as in `translate`; lock held; interrupts are
+    // disabled per this function's contract.
+    let result = unsafe { active_mapper() }.map(virt, phys, flags, &mut KernelFrameSource);
+    invlpg(virt);
+    result
+}
+
+/// Remove the mapping of the page starting at `virt` from the active page
+/// tables and return what was mapped.
+///
+/// The backing frame is not freed. Intermediate tables are kept.
+///
+/// # Errors
+///
+/// See [`UnmapError`].
+///
+/// # Safety
+///
+/// - Interrupts must be disabled.
+/// - Nothing may still reference memory through `virt`.
+pub unsafe fn unmap(virt: VirtAddr) -> Result<Translation, UnmapError> {
+    let _guard = PAGING_LOCK.lock();
+    // SAFETY: as for `map`.
+    let result = unsafe { active_mapper() }.unmap(virt);
+    invlpg(virt);
+    result
+}
+
+/// Replace the flags of the page starting at `virt` in the active page
+/// tables.
+///
+/// # Errors
+///
+/// See [`UnmapError`].
+///
+/// # Safety
+///
+/// - Interrupts must be disabled.
+/// - Removing permissions must not invalidate live references (for
+///   example making a page under a `&mut` read-only).
+pub unsafe fn update_flags(
+    virt: VirtAddr,
+    flags: PageTableFlags,
+) -> Result<Translation, UnmapError> {
+    let _guard = PAGING_LOCK.lock();
+    // SAFETY: as for `map`.
+    let result = unsafe { active_mapper() }.update_flags(virt, flags);
+    invlpg(virt);
+    result
+}
+
+/// Break the huge page containing `virt` into 4 KiB pages in the active
+/// page tables, so that a single page inside it can be unmapped or
+/// re-protected. A no-op if `virt` is already mapped by a 4 KiB page.
+///
+/// # Errors
+///
+/// See [`PageTableMapper::split_huge_page`].
+///
+/// # Safety
+///
+/// Interrupts must be disabled.
+pub unsafe fn split_huge_page(virt: VirtAddr) -> Result<(), MapError> {
+    let _guard = PAGING_LOCK.lock();
+    // SAFETY: as for `map`; translations are unchanged by the split.
+    let result = unsafe { active_mapper() }.split_huge_page(virt, &mut KernelFrameSource);
+    // One `invlpg` drops the cached huge-page translation as a whole.
+    invlpg(virt);
+    result
+}
+
+/// Remove the bootloader's identity mapping: clear every lower-half PML4
+/// entry and flush the TLB. Returns the number of entries cleared.
+///
+/// The tables behind those entries are shared with the direct map and stay
+/// in use.
+///
+/// # Safety
+///
+/// - Interrupts must be disabled.
+/// - Nothing may still use a lower-half address: the stack, the code, the
+///   GDT, IDT and TSS and every pointer must be higher-half.
+pub unsafe fn remove_identity_map() -> usize {
+    let _guard = PAGING_LOCK.lock();
+    let pml4 = phys_to_virt(active_root().as_u64()) as *mut PageTable;
+    // SAFETY: the PML4 is direct-mapped and the lock is held; the caller
+    // guarantees nothing still runs through the lower half.
+    let pml4 = unsafe { &mut *pml4 };
+    let mut cleared = 0;
+    for entry in 0..LOWER_HALF_ENTRIES {
+        if !pml4[entry].is_unused() {
+            pml4[entry].set_unused();
+            cleared += 1;
+        }
+    }
+    flush_all();
+    cleared
+}
+
+/// Map a fresh frame at an otherwise unused address, write through the new
+/// mapping, read the value back through the direct map, then unmap it and
+/// return the frame.
+///
+/// Exercises table allocation, `map`, `translate`, `unmap` and `invlpg` on
+/// the live hierarchy. Returns a short description of the first failure.
+///
+/// # Safety
+///
+/// Interrupts must be disabled; [`frame::init`] must have run.
+pub unsafe fn self_test() -> Result<(), &'static str> {
+    /// Start of PML4 slot 510, between the heap and the kernel image.
+    const TEST_VIRT: u64 = 0xFFFF_FF00_0000_0000;
+    const PATTERN: u64 = 0xFE44_0005_C0DE_CAFE;
+
+    let virt = VirtAddr::new(TEST_VIRT).ok_or("test address not canonical")?;
+    if translate(virt).is_some() {
+        return Err("test address already mapped");
+    }
+    let frame = frame::allocate_frame().ok_or("no frame for test page")?;
+    let phys = PhysAddr::new_truncate(frame.start_address());
+    let flags = kernel_data_flags();
+
+    // SAFETY: `virt` is unmapped (checked above) and `frame` is ours.
+    unsafe { map(virt, phys, flags) }.map_err(|_| "map failed")?;
+    if translate_addr(virt) != Some(phys) {
+        return Err("translate disagrees with map");
+    }
+
+    // SAFETY: `virt` now maps `phys` writable; `phys` is direct-mapped.
+    let readback = unsafe {
+        core::ptr::write_volatile(TEST_VIRT as *mut u64, PATTERN);
+        core::ptr::read_volatile(phys_to_virt(phys.as_u64()) as *const u64)
+    };
+    if readback != PATTERN {
+        return Err("write through new mapping not visible");
+    }
+
+    // SAFETY: no references to `virt` remain.
+    unsafe { unmap(virt) }.map_err(|_| "unmap failed")?;
+    if translate(virt).is_some() {
+        return Err("page still mapped after unmap");
+    }
+    frame::deallocate_frame(frame).map_err(|_| "frame release failed")
+}
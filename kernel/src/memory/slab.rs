@@ -0,0 +1,149 @@
+//! Kernel slab caches.
+//!
+//! Binds [`ferrous_alloc::slab`] to the kernel: [`KernelSlabSource`] feeds
+//! slabs straight from the physical frame allocator (reached through the
+//! direct map), so caches work without the global heap, and a
+//! global [`SlabRegistry`] collects every cache for [`dump`].
+//!
+//! # Usage
+//!
+//! ```ignore
+//! static TASKS: SpinLock<SlabCache<Task, KernelSlabSource>> =
+//!     SpinLock::new(SlabCache::new("task", KernelSlabSource));
+//!
+//! slab::register(|| TASKS.lock().stats())?;
+//! let task = TASKS.lock().alloc().expect("out of memory");
+//! slab::dump(); // one line per cache over serial
+//! ```
+
+use core::ptr::NonNull;
+
+use ferrous_alloc::{PhysFrame, SlabCacheStats, SlabPageSource, SlabRegistry, SlabRegistryError};
+
+use super::{frame, phys_to_virt, DIRECT_MAP_BASE};
+use crate::drivers::serial::SerialPort;
+use crate::sync::SpinLock;
+
+pub use ferrous_alloc::slab::{MAX_SLAB_CACHES, SLAB_PAGE_SIZE};
+pub use ferrous_alloc::{SlabCache, SlabHooks};
+
+/// Every registered cache.
+static REGISTRY: SpinLock<SlabRegistry> = SpinLock::new(SlabRegistry::new());
+
+// ---------------------------------------------------------------------------
+// Page source
+// ---------------------------------------------------------------------------
+
+/// [`SlabPageSource`] backed by the global frame allocator.
+///
+/// Slabs are used through the direct map, so a slab's virtual address is
+/// [`phys_to_virt`] of its frame.
+pub struct KernelSlabSource;
+
+impl SlabPageSource for KernelSlabSource {
+    fn alloc_pages(&mut self, pages: usize) -> Option<NonNull<u8>> {
+        if pages == 1 {
+            let frame = frame::allocate_frame()?;
+            return NonNull::new(phys_to_virt(frame.start_address()) as *mut u8);
+        }
+
+        // Over-allocate so that an aligned run lies inside, then give back
+        // the frames on either side of it.
+        let count = pages as u64;
+        let span = 2 * count - 1;
+        let first = frame::allocate_contiguous(span)?;
+        let align = count * SLAB_PAGE_SIZE as u64;
+        let start = first.start_address();
+        let aligned = (start + align - 1) & !(align - 1);
+        let head = (aligned - start) / SLAB_PAGE_SIZE as u64;
+        let tail = span - head - count;
+        if head != 0 {
+            let _ = frame::deallocate_contiguous(first, head);
+        }
+        if tail != 0 {
+            let after = PhysFrame::containing_address(aligned + align);
+            let _ = frame::deallocate_contiguous(after, tail);
+        }
+        NonNull::new(phys_to_virt(aligned) as *mut u8)
+    }
+
+    unsafe fn free_pages(&mut self, ptr: NonNull<u8>, pages: usize) {
+        let first = PhysFrame::containing_address(ptr.as_ptr() as u64 - DIRECT_MAP_BASE);
+        let _ = frame::deallocate_contiguous(first, pages as u64);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Registry
+// ---------------------------------------------------------------------------
+
+/// Add a cache to the registry; `stats` is called on every [`dump`].
+///
+/// # Errors
+///
+/// [`SlabRegistryError::Full`] once [`MAX_SLAB_CACHES`] caches exist.
+pub fn register(stats: fn() -> SlabCacheStats) -> Result<(), SlabRegistryError> {
+    REGISTRY.lock().register(stats)
+}
+
+/// Print every registered cache to the serial console.
+pub fn dump() {
+    let _ = REGISTRY.lock().dump(&mut SerialPort::console());
+}
+
+// ---------------------------------------------------------------------------
+// Self-test
+// ---------------------------------------------------------------------------
+
+/// Object type of the boot-time self-test cache (one cache line).
+#[repr(align(64))]
+struct SelfTestObject {
+    words: [u64; 8],
+}
+
+/// Cache exercised by [`self_test`]; stays registered afterwards.
+static SELF_TEST_CACHE: SpinLock<SlabCache<SelfTestObject, KernelSlabSource>> =
+    SpinLock::new(SlabCache::with_hooks(
+        "slab-selftest",
+        KernelSlabSource,
+        SlabHooks {
+            constructor: || SelfTestObject { words: [0x5AB; 8] },
+            destructor: None,
+        },
+    ));
+
+/// Fill more than one slab, check the objects, free them and shrink the
+/// cache back to zero pages. Registers the cache so that [`dump`] shows it.
+///
+/// Returns a short description of the first failure.
+pub fn self_test() -> Result<(), &'static str> {
+    register(|| SELF_TEST_CACHE.lock().stats()).map_err(|_| "registry full")?;
+
+    let mut cache = SELF_TEST_CACHE.lock();
+    let count = cache.stats().objects_per_slab + 1;
+    let mut objects = [None; 128];
+    for slot in objects.iter_mut().take(count) {
+        let object = cache.alloc().ok_or("out of slab memory")?;
+        if object.as_ptr() as usize & 63 != 0 {
+            return Err("object misaligned");
+        }
+        // SAFETY: freshly constructed by the cache.
+        if unsafe { object.as_ref() }.words != [0x5AB; 8] {
+            return Err("constructor hook not applied");
+        }
+        *slot = Some(object);
+    }
+    if cache.stats().slabs != 2 {
+        return Err("expected a second slab");
+    }
+    for object in objects.iter_mut().filter_map(Option::take) {
+        // SAFETY: allocated from this cache above and not used again.
+        unsafe { cache.free(object) };
+    }
+    cache.shrink();
+    let stats = cache.stats();
+    if stats.live != 0 || stats.pages != 0 {
+        return Err("cache did not return its pages");
+    }
+    Ok(())
+}
@@ -0,0 +1,66 @@
+//! Boot modules.
+//!
+//! The bootloader loads the files named by the selected `boot.cfg` entry's
+//! `module` lines — or, without a `boot.cfg`, those listed in
+//! `\EFI\ferrous\modules.txt` — into LOADER_DATA memory and describes
+//! each with a [`MODULE`] boot info tag: a name and a physical range. Step
+//! 4 of `kernel_main` carves every range out of the memory map, so neither
+//! the frame allocator nor reclamation hands the pages out; [`init`] then
+//! records the tag list so later code can look modules up by name:
+//!
+//! ```ignore
+//! if let Some(image) = modules::data("init") {
+//!     // ...
+//! }
+//! ```
+//!
+//! [`MODULE`]: ferrous_boot_info::tag_type::MODULE
+
+use ferrous_alloc::PhysRange;
+use ferrous_boot_info::{Module, ModuleIter, TagList};
+
+use crate::memory::phys_to_virt;
+use crate::sync::SpinLock;
+
+/// The boot info tags the modules were read from; empty before [`init`].
+static TAGS: SpinLock<TagList<'static>> = SpinLock::new(TagList::empty());
+
+/// Physical range occupied by `module`.
+pub fn range(module: &Module<'_>) -> PhysRange {
+    PhysRange::from_base_len(module.phys_addr, module.size)
+}
+
+/// Make the modules described by `tags` available through [`iter`],
+/// [`find`] and [`data`].
+///
+/// # Safety
+///
+/// Every module range in `tags` must be covered by the direct map, hold the
+/// bootloader's copy of the module, and stay reserved and unmodified for
+/// the lifetime of the kernel.
+pub unsafe fn init(tags: TagList<'static>) {
+    *TAGS.lock() = tags;
+}
+
+/// The boot modules, in load order.
+pub fn iter() -> ModuleIter<'static> {
+    TAGS.lock().modules()
+}
+
+/// The first module named `name`.
+pub fn find(name: &str) -> Option<Module<'static>> {
+    iter().find(|m| m.name == name)
+}
+
+/// Contents of the first module named `name`.
+pub fn data(name: &str) -> Option<&'static [u8]> {
+    let module = find(name)?;
+    // SAFETY: modules only exist after `init`, whose contract keeps their
+    // ranges mapped, reserved and unmodified forever.
+    Some(unsafe {
+        core::slice::from_raw_parts(
+            phys_to_virt(module.phys_addr) as usize as *const u8,
+            module.size as usize,
+        )
+    })
+}
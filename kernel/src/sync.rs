@@ -0,0 +1,7 @@
+//! Kernel synchronisation primitives.
+//!
+//! [`SpinLock`] is defined in `ferrous_core::sync` so that the allocator
+//! library locks with the same primitive; see there for its interrupt
+//! caveat.
+
+pub use ferrous_core::sync::{SpinLock, SpinLockGuard};
@@ -0,0 +1,917 @@
+//! Buddy allocator for power-of-two contiguous physical blocks.
+//!
+//! [`BuddyAllocator`] hands out naturally aligned blocks of `2^order`
+//! frames, for `order` in `0..=MAX_ORDER` (4 KiB up to 4 MiB). It is meant
+//! for callers that need physically contiguous, aligned memory — DMA
+//! buffers, 2 MiB huge pages (order 9), page-table pools — where the
+//! single-frame [`PhysicalFrameAllocator`] would have to search.
+//!
+//! # Design
+//!
+//! The allocator never touches the memory it manages: all metadata lives in
+//! caller-supplied bitmap storage, so it works before paging is set up and
+//! can be exercised on the host with a simulated memory map.
+//!
+//! - One *free bitmap per order*: bit `i` of order `k` is set when the block
+//!   of frames `[i << k, (i + 1) << k)` is free and not part of a larger
+//!   free block.
+//! - One *managed bitmap* with a bit per frame: set for frames that were
+//!   added to the allocator. It lets [`free_order`] reject blocks that were
+//!   never part of the pool.
+//!
+//! Allocation takes the lowest-addressed free block of the smallest order
+//! that fits and splits it down, returning the upper halves to the lower
+//! orders. Freeing merges a block with its buddy (`index ^ 1`) for as long
+//! as the buddy is free, then records the result.
+//!
+//! Storage needed is about three bits per frame of physical address space;
+//! see [`BuddyAllocator::storage_words_for`].
+//!
+//! Blocks handed out by a buddy allocator must not also be handed out by
+//! another allocator: seed each allocator with disjoint memory.
+//!
+//! [`PhysicalFrameAllocator`]: crate::PhysicalFrameAllocator
+//! [`free_order`]: BuddyAllocator::free_order
+
+use ferrous_boot_info::{KernelMemoryDescriptor, MemoryMap};
+
+use crate::frame::{PhysRange, FRAME_SIZE, MAX_RESERVED_RANGES};
+
+/// Highest supported order: blocks of `2^10` frames = 4 MiB.
+pub const MAX_ORDER: usize = 10;
+
+/// Number of orders (`0..=MAX_ORDER`).
+pub const ORDER_COUNT: usize = MAX_ORDER + 1;
+
+/// Frames in a block of [`MAX_ORDER`].
+const MAX_BLOCK_FRAMES: u64 = 1 << MAX_ORDER;
+
+/// Bits per bitmap word.
+const BITS: u64 = u64::BITS as u64;
+
+/// Which memory map regions seed a [`BuddyAllocator`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionSelection {
+    /// [`MemoryMap::usable_regions()`] — conventional memory only.
+    Usable,
+    /// [`MemoryMap::reclaimable_regions()`] — conventional, bootloader and
+    /// ACPI-reclaimable memory. Only valid once the bootloader and ACPI data
+    /// are no longer needed (or are listed as reserved ranges).
+    Reclaimable,
+}
+
+/// Errors returned by [`BuddyAllocator`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuddyError {
+    /// The supplied storage is smaller than
+    /// [`BuddyAllocator::storage_words_for`] requires.
+    StorageTooSmall {
+        /// Words required.
+        needed: usize,
+        /// Words supplied.
+        provided: usize,
+    },
+    /// More than [`MAX_RESERVED_RANGES`] reserved ranges were supplied.
+    TooManyReservedRanges,
+    /// `order` exceeds [`MAX_ORDER`].
+    OrderTooLarge {
+        /// The offending order.
+        order: usize,
+    },
+    /// `addr` is not aligned to the block size of `order`.
+    Unaligned {
+        /// The offending address.
+        addr: u64,
+        /// Order of the block being freed.
+        order: usize,
+    },
+    /// Part of the block lies outside the managed pool (beyond the limit,
+    /// in a hole, or in a reserved range).
+    NotManaged {
+        /// Start address of the block.
+        addr: u64,
+        /// Order of the block.
+        order: usize,
+    },
+    /// Part of the block is already free.
+    DoubleFree {
+        /// Start address of the block.
+        addr: u64,
+        /// Order of the block.
+        order: usize,
+    },
+}
+
+/// Allocator counters and fragmentation statistics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuddyStats {
+    /// Number of free blocks at each order.
+    pub free_blocks: [u64; ORDER_COUNT],
+    /// Frames managed by the allocator.
+    pub total_frames: u64,
+    /// Frames currently free (sum of `free_blocks[k] << k`).
+    pub free_frames: u64,
+    /// Frames currently allocated.
+    pub allocated_frames: u64,
+}
+
+impl BuddyStats {
+    /// Highest order with at least one free block, or `None` if the pool is
+    /// exhausted.
+    pub fn largest_free_order(&self) -> Option<usize> {
+        (0..ORDER_COUNT).rev().find(|&k| self.free_blocks[k] != 0)
+    }
+
+    /// Free frames held in blocks of `order` or larger — the memory that
+    /// can satisfy an allocation of `order` without merging.
+    pub fn free_frames_at_or_above(&self, order: usize) -> u64 {
+        (order.min(ORDER_COUNT)..ORDER_COUNT)
+            .map(|k| self.free_blocks[k] << k)
+            .sum()
+    }
+
+    /// Unusable free space index for `order`, in per-mille.
+    ///
+    /// The fraction of free memory that sits in blocks too small to satisfy
+    /// an allocation of `order`: 0 means every free frame is usable for such
+    /// a request, 1000 means none is. Returns 0 when nothing is free.
+    pub fn fragmentation_permille(&self, order: usize) -> u32 {
+        if self.free_frames == 0 {
+            return 0;
+        }
+        let unusable = self.free_frames - self.free_frames_at_or_above(order);
+        (unusable * 1000 / self.free_frames) as u32
+    }
+}
+
+/// Binary buddy allocator over physical frames.
+///
+/// See the [module documentation](self) for the design.
+pub struct BuddyAllocator<'a> {
+    storage: &'a mut [u64],
+    /// Word offset of each order's free bitmap in `storage`.
+    order_offset: [usize; ORDER_COUNT],
+    /// Word offset of the managed bitmap in `storage`.
+    managed_offset: usize,
+    /// Frames covered: `0..frame_limit`, a multiple of `MAX_BLOCK_FRAMES`.
+    frame_limit: u64,
+    stats: BuddyStats,
+}
+
+impl<'a> BuddyAllocator<'a> {
+    /// Number of `u64` storage words needed to manage physical addresses
+    /// `[0, limit)`.
+    pub const fn storage_words_for(limit: u64) -> usize {
+        let frames = Self::frames_for(limit);
+        let mut words = frames.div_ceil(BITS); // managed bitmap
+        let mut k = 0;
+        while k < ORDER_COUNT {
+            words += (frames >> k).div_ceil(BITS);
+            k += 1;
+        }
+        words as usize
+    }
+
+    /// Frame count covered for `limit`, rounded up to a whole number of
+    /// maximum-order blocks.
+    const fn frames_for(limit: u64) -> u64 {
+        limit.div_ceil(FRAME_SIZE).div_ceil(MAX_BLOCK_FRAMES) * MAX_BLOCK_FRAMES
+    }
+
+    /// Create an empty allocator able to manage physical addresses
+    /// `[0, limit)`. Memory is added with [`add_range`](Self::add_range).
+    ///
+    /// # Errors
+    ///
+    /// [`BuddyError::StorageTooSmall`] if `storage` has fewer than
+    /// [`storage_words_for(limit)`](Self::storage_words_for) words.
+    pub fn new(storage: &'a mut [u64], limit: u64) -> Result<Self, BuddyError> {
+        let needed = Self::storage_words_for(limit);
+        if storage.len() < needed {
+            return Err(BuddyError::StorageTooSmall {
+                needed,
+                provided: storage.len(),
+            });
+        }
+        storage[..needed].fill(0);
+
+        let frame_limit = Self::frames_for(limit);
+        let mut order_offset = [0; ORDER_COUNT];
+        let mut offset = 0;
+        for (k, slot) in order_offset.iter_mut().enumerate() {
+            *slot = offset;
+            offset += (frame_limit >> k).div_ceil(BITS) as usize;
+        }
+
+        Ok(Self {
+            storage,
+            order_offset,
+            managed_offset: offset,
+            frame_limit,
+            stats: BuddyStats {
+                free_blocks: [0; ORDER_COUNT],
+                total_frames: 0,
+                free_frames: 0,
+                allocated_frames: 0,
+            },
+        })
+    }
+
+    /// Build an allocator seeded from the regions of `map` chosen by
+    /// `selection`, minus every frame overlapping a range in `reserved`.
+    ///
+    /// The managed limit is the end of the highest selected region, capped
+    /// at `limit`; memory above the cap is ignored. Physical frame 0 is
+    /// always withheld.
+    ///
+    /// # Errors
+    ///
+    /// - [`BuddyError::TooManyReservedRanges`]: more than
+    ///   [`MAX_RESERVED_RANGES`] entries in `reserved`.
+    /// - [`BuddyError::StorageTooSmall`]: as for [`new`](Self::new).
+    pub fn from_memory_map(
+        map: &MemoryMap,
+        selection: RegionSelection,
+        storage: &'a mut [u64],
+        limit: u64,
+        reserved: &[PhysRange],
+    ) -> Result<Self, BuddyError> {
+        if reserved.len() > MAX_RESERVED_RANGES {
+            return Err(BuddyError::TooManyReservedRanges);
+        }
+        let regions = || selected_regions(map, selection);
+        let highest = regions()
+            .map(|d| d.phys_start.saturating_add(d.size_bytes()))
+            .max()
+            .unwrap_or(0);
+
+        let mut this = Self::new(storage, highest.min(limit))?;
+        for d in regions() {
+            this.add_range(PhysRange::from_base_len(d.phys_start, d.size_bytes()));
+        }
+        this.withhold(PhysRange::new(0, FRAME_SIZE));
+        for range in reserved {
+            this.withhold(*range);
+        }
+        Ok(this)
+    }
+
+    /// Add the frames fully inside `range` to the pool.
+    ///
+    /// The range is rounded inward to frame boundaries and clipped to the
+    /// managed limit. Frames already managed are skipped. Returns the number
+    /// of frames added.
+    pub fn add_range(&mut self, range: PhysRange) -> u64 {
+        let first = range.start.div_ceil(FRAME_SIZE);
+        let end = (range.end / FRAME_SIZE).min(self.frame_limit);
+        let mut added = 0;
+        let mut n = first;
+        while n < end {
+            if self.is_managed(n) {
+                n += 1;
+                continue;
+            }
+            // Largest naturally aligned block at `n` that fits below `end`
+            // and contains no managed frame.
+            let mut order = 0;
+            while order < MAX_ORDER {
+                let next = order + 1;
+                let size = 1u64 << next;
+                if n & (size - 1) != 0 || n + size > end || self.any_managed(n, n + size) {
+                    break;
+                }
+                order = next;
+            }
+            let size = 1u64 << order;
+            self.set_managed(n, n + size);
+            self.stats.total_frames += size;
+            self.release(n >> order, order);
+            added += size;
+            n += size;
+        }
+        added
+    }
+
+    /// Current counters.
+    #[inline]
+    pub fn stats(&self) -> BuddyStats {
+        self.stats
+    }
+
+    /// Number of free blocks of exactly `order` (0 if `order` is too large).
+    #[inline]
+    pub fn free_blocks(&self, order: usize) -> u64 {
+        self.stats.free_blocks.get(order).copied().unwrap_or(0)
+    }
+
+    /// Smallest order whose block holds at least `bytes`, or `None` if that
+    /// exceeds [`MAX_ORDER`].
+    pub fn order_for(bytes: u64) -> Option<usize> {
+        let frames = bytes.div_ceil(FRAME_SIZE).max(1);
+        let order = frames.next_power_of_two().trailing_zeros() as usize;
+        (order <= MAX_ORDER).then_some(order)
+    }
+
+    /// Allocate a naturally aligned block of `2^order` frames.
+    ///
+    /// Returns the physical start address, or `None` if no block of that
+    /// order can be produced. The lowest-addressed suitable block is used.
+    ///
+    /// # Errors
+    ///
+    /// [`BuddyError::OrderTooLarge`] if `order > MAX_ORDER`.
+    pub fn alloc_order(&mut self, order: usize) -> Result<Option<u64>, BuddyError> {
+        if order > MAX_ORDER {
+            return Err(BuddyError::OrderTooLarge { order });
+        }
+        let Some(mut k) = (order..ORDER_COUNT).find(|&k| self.stats.free_blocks[k] != 0) else {
+            return Ok(None);
+        };
+        let mut index = self
+            .first_free(k)
+            .expect("free_blocks counter out of sync with bitmap");
+        self.take(index, k);
+
+        // Split down, returning the upper half at each level.
+        while k > order {
+            k -= 1;
+            index <<= 1;
+            self.put(index | 1, k);
+        }
+
+        self.stats.allocated_frames += 1 << order;
+        Ok(Some((index << order) * FRAME_SIZE))
+    }
+
+    /// Return a block obtained from [`alloc_order`](Self::alloc_order).
+    ///
+    /// The block is merged with its buddy for as long as the buddy is free.
+    ///
+    /// # Errors
+    ///
+    /// - [`BuddyError::OrderTooLarge`]: `order > MAX_ORDER`.
+    /// - [`BuddyError::Unaligned`]: `addr` is not aligned to the block size.
+    /// - [`BuddyError::NotManaged`]: part of the block is outside the pool.
+    /// - [`BuddyError::DoubleFree`]: part of the block is already free.
+    pub fn free_order(&mut self, addr: u64, order: usize) -> Result<(), BuddyError> {
+        if order > MAX_ORDER {
+            return Err(BuddyError::OrderTooLarge { order });
+        }
+        let size = 1u64 << order;
+        if addr & (size * FRAME_SIZE - 1) != 0 {
+            return Err(BuddyError::Unaligned { addr, order });
+        }
+        let first = addr / FRAME_SIZE;
+        if first + size > self.frame_limit || !self.all_managed(first, first + size) {
+            return Err(BuddyError::NotManaged { addr, order });
+        }
+        if self.any_free(first, order) {
+            return Err(BuddyError::DoubleFree { addr, order });
+        }
+
+        self.stats.allocated_frames -= size;
+        self.release(first >> order, order);
+        Ok(())
+    }
+
+    // -----------------------------------------------------------------------
+    // Internals
+    // -----------------------------------------------------------------------
+
+    /// Mark block `index` of `order` free, merging with free buddies.
+    fn release(&mut self, mut index: u64, mut order: usize) {
+        while order < MAX_ORDER && self.is_free(index ^ 1, order) {
+            self.take(index ^ 1, order);
+            index >>= 1;
+            order += 1;
+        }
+        self.put(index, order);
+    }
+
+    /// Remove every frame overlapping `range` from the pool: free blocks
+    /// containing them are split and the frames are unmarked as managed.
+    fn withhold(&mut self, range: PhysRange) {
+        if range.is_empty() {
+            return;
+        }
+        let first = range.start / FRAME_SIZE;
+        let end = range.end.div_ceil(FRAME_SIZE).min(self.frame_limit);
+        for n in first..end {
+            if !self.is_managed(n) {
+                continue;
+            }
+            let Some(mut k) = (0..ORDER_COUNT).find(|&k| self.is_free(n >> k, k)) else {
+                // Managed but allocated — cannot withhold a live frame.
+                continue;
+            };
+            self.take(n >> k, k);
+            while k > 0 {
+                k -= 1;
+                // Return the half that does not contain `n`.
+                self.put((n >> k) ^ 1, k);
+            }
+            self.clear_managed(n);
+            self.stats.total_frames -= 1;
+        }
+    }
+
+    /// True if block `index` of `order`, any block containing it, or any
+    /// block inside it is free.
+    fn any_free(&self, first: u64, order: usize) -> bool {
+        // Containing blocks (including the block itself).
+        if (order..ORDER_COUNT).any(|k| self.is_free(first >> k, k)) {
+            return true;
+        }
+        // Sub-blocks.
+        (0..order).any(|k| {
+            let lo = first >> k;
+            let hi = lo + (1 << (order - k));
+            (lo..hi).any(|i| self.is_free(i, k))
+        })
+    }
+
+    fn first_free(&self, order: usize) -> Option<u64> {
+        let off = self.order_offset[order];
+        let words = (self.frame_limit >> order).div_ceil(BITS) as usize;
+        self.storage[off..off + words]
+            .iter()
+            .position(|&w| w != 0)
+            .map(|w| w as u64 * BITS + u64::from(self.storage[off + w].trailing_zeros()))
+    }
+
+    #[inline]
+    fn bit(&self, word_offset: usize, index: u64) -> (usize, u64) {
+        (word_offset + (index / BITS) as usize, 1 << (index % BITS))
+    }
+
+    #[inline]
+    fn is_free(&self, index: u64, order: usize) -> bool {
+        if index >= self.frame_limit >> order {
+            return false;
+        }
+        let (w, m) = self.bit(self.order_offset[order], index);
+        self.storage[w] & m != 0
+    }
+
+    fn put(&mut self, index: u64, order: usize) {
+        let (w, m) = self.bit(self.order_offset[order], index);
+        self.storage[w] |= m;
+        self.stats.free_blocks[order] += 1;
+        self.stats.free_frames += 1 << order;
+    }
+
+    fn take(&mut self, index: u64, order: usize) {
+        let (w, m) = self.bit(self.order_offset[order], index);
+        self.storage[w] &= !m;
+        self.stats.free_blocks[order] -= 1;
+        self.stats.free_frames -= 1 << order;
+    }
+
+    #[inline]
+    fn is_managed(&self, n: u64) -> bool {
+        let (w, m) = self.bit(self.managed_offset, n);
+        self.storage[w] & m != 0
+    }
+
+    fn any_managed(&self, first: u64, end: u64) -> bool {
+        (first..end).any(|n| self.is_managed(n))
+    }
+
+    fn all_managed(&self, first: u64, end: u64) -> bool {
+        (first..end).all(|n| self.is_managed(n))
+    }
+
+    fn set_managed(&mut self, first: u64, end: u64) {
+        for n in first..end {
+            let (w, m) = self.bit(self.managed_offset, n);
+            self.storage[w] |= m;
+        }
+    }
+
+    fn clear_managed(&mut self, n: u64) {
+        let (w, m) = self.bit(self.managed_offset, n);
+        self.storage[w] &= !m;
+    }
+}
+
+/// The regions of `map` chosen by `selection`.
+fn selected_regions(
+    map: &MemoryMap,
+    selection: RegionSelection,
+) -> impl Iterator<Item = &KernelMemoryDescriptor> {
+    let usable = map
+        .usable_regions()
+        .filter(move |_| selection == RegionSelection::Usable);
+    let reclaimable = map
+        .reclaimable_regions()
+        .filter(move |_| selection == RegionSelection::Reclaimable);
+    usable.chain(reclaimable)
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{desc, make_map, MIB};
+    use ferrous_boot_info::memory_type;
+    use std::vec;
+    use std::vec::Vec;
+
+    /// Simulated machine with 16 MiB of address space:
+    /// - 0x0000_0000 .. 0x000A_0000 conventional (640 KiB, low memory)
+    /// - 0x000A_0000 .. 0x0010_0000 reserved hole
+    /// - 0x0010_0000 .. 0x0020_0000 boot services data (reclaimable)
+    /// - 0x0020_0000 .. 0x0040_0000 loader code (kernel image)
+    /// - 0x0040_0000 .. 0x0100_0000 conventional (12 MiB)
+    fn simulated_map() -> MemoryMap {
+        make_map(&[
+            desc(memory_type::CONVENTIONAL, 0, 160),
+            desc(memory_type::RESERVED, 0xA_0000, 96),
+            desc(memory_type::BOOT_SERVICES_DATA, 0x10_0000, 256),
+            desc(memory_type::LOADER_CODE, 0x20_0000, 512),
+            desc(memory_type::CONVENTIONAL, 0x40_0000, 3072),
+        ])
+    }
+
+    fn storage(limit: u64) -> Vec<u64> {
+        vec![0; BuddyAllocator::storage_words_for(limit)]
+    }
+
+    fn usable(storage: &mut [u64]) -> BuddyAllocator<'_> {
+        let map = simulated_map();
+        BuddyAllocator::from_memory_map(&map, RegionSelection::Usable, storage, u64::MAX, &[])
+            .unwrap()
+    }
+
+    // -----------------------------------------------------------------------
+    // Construction and seeding
+    // -----------------------------------------------------------------------
+
+    #[test]
+    fn storage_too_small_is_rejected() {
+        let mut s = vec![0u64; 1];
+        assert!(matches!(
+            BuddyAllocator::new(&mut s, 16 * MIB),
+            Err(BuddyError::StorageTooSmall { provided: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn usable_seed_counts_conventional_frames_minus_frame_zero() {
+        let mut s = storage(16 * MIB);
+        let b = usable(&mut s);
+        let st = b.stats();
+        assert_eq!(st.total_frames, 160 - 1 + 3072);
+        assert_eq!(st.free_frames, st.total_frames);
+        assert_eq!(st.allocated_frames, 0);
+    }
+
+    #[test]
+    fn seed_produces_maximal_blocks() {
+        let mut s = storage(16 * MIB);
+        let b = usable(&mut s);
+        // 12 MiB at 4 MiB alignment → three order-10 blocks.
+        assert_eq!(b.free_blocks(MAX_ORDER), 3);
+        // 640 KiB minus frame 0: 1+2+4+8+16+32 frames from frame 1 up to 64,
+        // then 64 (order 6) at 64 and 32 (order 5) at 128.
+        assert_eq!(b.free_blocks(6), 1);
+        assert_eq!(b.free_blocks(5), 2);
+        assert_eq!(b.free_blocks(0), 1);
+    }
+
+    #[test]
+    fn reclaimable_seed_includes_bootloader_memory() {
+        let map = simulated_map();
+        let mut s = storage(16 * MIB);
+        let b = BuddyAllocator::from_memory_map(
+            &map,
+            RegionSelection::Reclaimable,
+            &mut s,
+            u64::MAX,
+            &[],
+        )
+        .unwrap();
+        assert_eq!(b.stats().total_frames, 160 - 1 + 256 + 512 + 3072);
+        // 1–4 MiB is now contiguous with 4–16 MiB.
+        assert_eq!(b.free_blocks(MAX_ORDER), 3);
+        assert_eq!(b.free_blocks(9), 1); // 2–4 MiB
+        assert_eq!(b.free_blocks(8), 1); // 1–2 MiB
+    }
+
+    #[test]
+    fn reserved_ranges_are_withheld() {
+        let map = simulated_map();
+        let mut s = storage(16 * MIB);
+        let reserved = [PhysRange::new(0x20_0000, 0x40_0000)];
+        let b = BuddyAllocator::from_memory_map(
+            &map,
+            RegionSelection::Reclaimable,
+            &mut s,
+            u64::MAX,
+            &reserved,
+        )
+        .unwrap();
+        assert_eq!(b.stats().total_frames, 160 - 1 + 256 + 3072);
+        assert_eq!(b.free_blocks(9), 0);
+    }
+
+    #[test]
+    fn reserved_frame_splits_max_block() {
+        let map = simulated_map();
+        let mut s = storage(16 * MIB);
+        let reserved = [PhysRange::from_base_len(0x40_0000, FRAME_SIZE)];
+        let b = BuddyAllocator::from_memory_map(
+            &map,
+            RegionSelection::Usable,
+            &mut s,
+            u64::MAX,
+            &reserved,
+        )
+        .unwrap();
+        // One order-10 block broke into one block of each order 0..=9.
+        assert_eq!(b.free_blocks(MAX_ORDER), 2);
+        for k in 1..MAX_ORDER {
+            assert!(b.free_blocks(k) >= 1, "order {k}");
+        }
+        assert_eq!(b.stats().total_frames, 160 - 1 + 3072 - 1);
+    }
+
+    #[test]
+    fn too_many_reserved_ranges_is_rejected() {
+        let map = simulated_map();
+        let mut s = storage(16 * MIB);
+        let reserved = [PhysRange::new(0, FRAME_SIZE); MAX_RESERVED_RANGES + 1];
+        assert!(matches!(
+            BuddyAllocator::from_memory_map(
+                &map,
+                RegionSelection::Usable,
+                &mut s,
+                u64::MAX,
+                &reserved
+            ),
+            Err(BuddyError::TooManyReservedRanges)
+        ));
+    }
+
+    #[test]
+    fn limit_caps_managed_memory() {
+        let map = simulated_map();
+        let mut s = storage(8 * MIB);
+        let b =
+            BuddyAllocator::from_memory_map(&map, RegionSelection::Usable, &mut s, 8 * MIB, &[])
+                .unwrap();
+        assert_eq!(b.stats().total_frames, 160 - 1 + 1024);
+    }
+
+    #[test]
+    fn add_range_rounds_inward_and_skips_managed_frames() {
+        let mut s = storage(4 * MIB);
+        let mut b = BuddyAllocator::new(&mut s, 4 * MIB).unwrap();
+        // Frames 2, 3, 4 lie fully inside; 1 and 5 are partial.
+        assert_eq!(b.add_range(PhysRange::new(0x1001, 0x5FFF)), 3);
+        // Frames 1..8 minus the three already managed.
+        assert_eq!(b.add_range(PhysRange::new(0x1000, 0x8000)), 4);
+        assert_eq!(b.stats().total_frames, 7);
+    }
+
+    #[test]
+    fn adjacent_ranges_coalesce() {
+        let mut s = storage(4 * MIB);
+        let mut b = BuddyAllocator::new(&mut s, 4 * MIB).unwrap();
+        b.add_range(PhysRange::new(0, 0x20_0000));
+        b.add_range(PhysRange::new(0x20_0000, 0x40_0000));
+        assert_eq!(b.free_blocks(MAX_ORDER), 1);
+        assert_eq!(b.stats().free_blocks.iter().sum::<u64>(), 1);
+    }
+
+    // -----------------------------------------------------------------------
+    // Allocation and splitting
+    // -----------------------------------------------------------------------
+
+    #[test]
+    fn alloc_returns_naturally_aligned_blocks() {
+        let mut s = storage(16 * MIB);
+        let mut b = usable(&mut s);
+        for order in 0..=MAX_ORDER {
+            let addr = b.alloc_order(order).unwrap().unwrap();
+            assert_eq!(addr % ((1 << order) * FRAME_SIZE), 0, "order {order}");
+        }
+    }
+
+    #[test]
+    fn alloc_splits_larger_block() {
+        let mut s = storage(4 * MIB);
+        let mut b = BuddyAllocator::new(&mut s, 4 * MIB).unwrap();
+        b.add_range(PhysRange::new(0, 4 * MIB));
+        let addr = b.alloc_order(0).unwrap().unwrap();
+        assert_eq!(addr, 0);
+        // One free block at each order 0..MAX_ORDER, none at MAX_ORDER.
+        for k in 0..MAX_ORDER {
+            assert_eq!(b.free_blocks(k), 1, "order {k}");
+        }
+        assert_eq!(b.free_blocks(MAX_ORDER), 0);
+        assert_eq!(b.stats().free_frames, 1023);
+    }
+
+    #[test]
+    fn alloc_prefers_smallest_fitting_order() {
+        let mut s = storage(16 * MIB);
+        let mut b = usable(&mut s);
+        let before = b.free_blocks(MAX_ORDER);
+        // Order 5 blocks exist in low memory; the 4 MiB blocks stay intact.
+        let addr = b.alloc_order(5).unwrap().unwrap();
+        assert!(addr < 0xA_0000);
+        assert_eq!(b.free_blocks(MAX_ORDER), before);
+    }
+
+    #[test]
+    fn alloc_exhausts_and_returns_none() {
+        let mut s = storage(4 * MIB);
+        let mut b = BuddyAllocator::new(&mut s, 4 * MIB).unwrap();
+        b.add_range(PhysRange::new(0, 4 * MIB));
+        assert!(b.alloc_order(MAX_ORDER).unwrap().is_some());
+        assert_eq!(b.alloc_order(0).unwrap(), None);
+        assert_eq!(b.stats().allocated_frames, 1024);
+    }
+
+    #[test]
+    fn alloc_order_too_large_is_rejected() {
+        let mut s = storage(16 * MIB);
+        let mut b = usable(&mut s);
+        assert_eq!(
+            b.alloc_order(MAX_ORDER + 1),
+            Err(BuddyError::OrderTooLarge {
+                order: MAX_ORDER + 1
+            })
+        );
+    }
+
+    #[test]
+    fn allocated_blocks_do_not_overlap() {
+        let mut s = storage(16 * MIB);
+        let mut b = usable(&mut s);
+        let mut blocks = Vec::new();
+        for order in [0, 3, 1, 9, 2, 0, 5, 10, 4] {
+            if let Some(a) = b.alloc_order(order).unwrap() {
+                blocks.push((a, a + (1 << order) * FRAME_SIZE));
+            }
+        }
+        for (i, x) in blocks.iter().enumerate() {
+            for y in &blocks[i + 1..] {
+                assert!(x.1 <= y.0 || y.1 <= x.0, "{x:x?} overlaps {y:x?}");
+            }
+        }
+    }
+
+    // -----------------------------------------------------------------------
+    // Freeing and coalescing
+    // -----------------------------------------------------------------------
+
+    #[test]
+    fn free_coalesces_back_to_max_order() {
+        let mut s = storage(4 * MIB);
+        let mut b = BuddyAllocator::new(&mut s, 4 * MIB).unwrap();
+        b.add_range(PhysRange::new(0, 4 * MIB));
+        let blocks: Vec<_> = (0..8).map(|_| b.alloc_order(7).unwrap().unwrap()).collect();
+        assert_eq!(b.stats().free_frames, 0);
+        for a in blocks.into_iter().rev() {
+            b.free_order(a, 7).unwrap();
+        }
+        assert_eq!(b.free_blocks(MAX_ORDER), 1);
+        assert_eq!(b.stats().free_blocks.iter().sum::<u64>(), 1);
+        assert_eq!(b.stats().allocated_frames, 0);
+    }
+
+    #[test]
+    fn free_does_not_merge_with_allocated_buddy() {
+        let mut s = storage(4 * MIB);
+        let mut b = BuddyAllocator::new(&mut s, 4 * MIB).unwrap();
+        b.add_range(PhysRange::new(0, 4 * MIB));
+        let a = b.alloc_order(0).unwrap().unwrap();
+        let c = b.alloc_order(0).unwrap().unwrap();
+        assert_eq!(c, a + FRAME_SIZE);
+        b.free_order(a, 0).unwrap();
+        assert_eq!(b.free_blocks(0), 1);
+        b.free_order(c, 0).unwrap();
+        assert_eq!(b.free_blocks(0), 0);
+        assert_eq!(b.free_blocks(MAX_ORDER), 1);
+    }
+
+    #[test]
+    fn double_free_is_detected() {
+        let mut s = storage(16 * MIB);
+        let mut b = usable(&mut s);
+        let a = b.alloc_order(3).unwrap().unwrap();
+        b.free_order(a, 3).unwrap();
+        assert_eq!(
+            b.free_order(a, 3),
+            Err(BuddyError::DoubleFree { addr: a, order: 3 })
+        );
+        // A sub-block of a free block is also a double free.
+        assert_eq!(
+            b.free_order(a, 0),
+            Err(BuddyError::DoubleFree { addr: a, order: 0 })
+        );
+    }
+
+    #[test]
+    fn freeing_larger_order_than_allocated_is_detected() {
+        let mut s = storage(4 * MIB);
+        let mut b = BuddyAllocator::new(&mut s, 4 * MIB).unwrap();
+        b.add_range(PhysRange::new(0, 4 * MIB));
+        let a = b.alloc_order(0).unwrap().unwrap();
+        // The order-1 block at `a` contains a's free buddy.
+        assert_eq!(
+            b.free_order(a, 1),
+            Err(BuddyError::DoubleFree { addr: a, order: 1 })
+        );
+    }
+
+    #[test]
+    fn freeing_unmanaged_block_is_rejected() {
+        let mut s = storage(16 * MIB);
+        let mut b = usable(&mut s);
+        for (addr, order) in [(0, 0), (0xA_0000, 0), (0x20_0000, 9), (0x100_0000, 0)] {
+            assert_eq!(
+                b.free_order(addr, order),
+                Err(BuddyError::NotManaged { addr, order }),
+                "{addr:#x}"
+            );
+        }
+    }
+
+    #[test]
+    fn freeing_unaligned_block_is_rejected() {
+        let mut s = storage(16 * MIB);
+        let mut b = usable(&mut s);
+        assert_eq!(
+            b.free_order(0x40_1000, 1),
+            Err(BuddyError::Unaligned {
+                addr: 0x40_1000,
+                order: 1
+            })
+        );
+    }
+
+    // -----------------------------------------------------------------------
+    // Statistics
+    // -----------------------------------------------------------------------
+
+    #[test]
+    fn free_frames_match_per_order_counts() {
+        let mut s = storage(16 * MIB);
+        let mut b = usable(&mut s);
+        for order in [2, 0, 7, 1] {
+            b.alloc_order(order).unwrap();
+        }
+        let st = b.stats();
+        let sum: u64 = (0..ORDER_COUNT).map(|k| st.free_blocks[k] << k).sum();
+        assert_eq!(sum, st.free_frames);
+        assert_eq!(st.free_frames + st.allocated_frames, st.total_frames);
+    }
+
+    #[test]
+    fn fragmentation_index_reflects_block_sizes() {
+        let mut s = storage(4 * MIB);
+        let mut b = BuddyAllocator::new(&mut s, 4 * MIB).unwrap();
+        b.add_range(PhysRange::new(0, 4 * MIB));
+        assert_eq!(b.stats().fragmentation_permille(MAX_ORDER), 0);
+
+        // Allocate every frame, then free every other one: 512 isolated
+        // order-0 blocks, nothing larger.
+        let frames: Vec<_> = (0..1024)
+            .map(|_| b.alloc_order(0).unwrap().unwrap())
+            .collect();
+        for a in frames.iter().step_by(2) {
+            b.free_order(*a, 0).unwrap();
+        }
+        let st = b.stats();
+        assert_eq!(st.free_blocks[0], 512);
+        assert_eq!(st.largest_free_order(), Some(0));
+        assert_eq!(st.fragmentation_permille(0), 0);
+        assert_eq!(st.fragmentation_permille(1), 1000);
+    }
+
+    #[test]
+    fn fragmentation_index_is_zero_when_exhausted() {
+        let mut s = storage(4 * MIB);
+        let mut b = BuddyAllocator::new(&mut s, 4 * MIB).unwrap();
+        b.add_range(PhysRange::new(0, 4 * MIB));
+        b.alloc_order(MAX_ORDER).unwrap();
+        assert_eq!(b.stats().largest_free_order(), None);
+        assert_eq!(b.stats().fragmentation_permille(3), 0);
+    }
+
+    #[test]
+    fn order_for_rounds_up_to_power_of_two() {
+        assert_eq!(BuddyAllocator::order_for(0), Some(0));
+        assert_eq!(BuddyAllocator::order_for(FRAME_SIZE), Some(0));
+        assert_eq!(BuddyAllocator::order_for(FRAME_SIZE + 1), Some(1));
+        assert_eq!(BuddyAllocator::order_for(2 * MIB), Some(9));
+        assert_eq!(BuddyAllocator::order_for(4 * MIB), Some(MAX_ORDER));
+        assert_eq!(BuddyAllocator::order_for(4 * MIB + 1), None);
+    }
+}
@@ -0,0 +1,1136 @@
+//! Physical frame allocator.
+//!
+//! [`PhysicalFrameAllocator`] hands out 4 KiB physical frames (and contiguous
+//! runs of frames) from the conventional memory regions reported by
+//! [`MemoryMap::usable_regions()`].
+//!
+//! # Design
+//!
+//! The allocator is a bitmap with one bit per 4 KiB frame, indexed by
+//! physical frame number. A set bit means "not available" — either allocated
+//! or never usable (firmware, MMIO, holes, reserved ranges). The bitmap
+//! storage is supplied by the caller so the allocator needs no heap: the
+//! kernel passes a `.bss` array, host tests pass a `Vec`.
+//!
+//! Frames covered by a *reserved range* — the kernel image, the boot info
+//! structure, stacks — are withheld even if they lie inside a usable region.
+//! Physical frame 0 is always withheld so that a zero physical address never
+//! escapes the allocator.
+//!
+//! Frames above the bitmap's capacity are ignored and reported as
+//! [`FrameStats::untracked_frames`].
+//!
+//! # Bootloader reclamation
+//!
+//! Boot-services and loader regions ([`MemoryRegionKind::BootloaderReclaimable`])
+//! are left alone at construction. Once the kernel no longer needs the
+//! bootloader's data,
+//! [`reclaim_bootloader_memory`](PhysicalFrameAllocator::reclaim_bootloader_memory)
+//! folds them into the managed pool, still honouring the reserved ranges.
+//!
+//! # Counters
+//!
+//! [`FrameStats`] tracks live free/allocated counts and records the
+//! [`MemoryStats::usable_bytes`] figure the map reported, so the two can be
+//! cross-checked with [`FrameStats::is_consistent`].
+//!
+//! [`MemoryStats::usable_bytes`]: ferrous_boot_info::MemoryStats::usable_bytes
+
+use ferrous_boot_info::{KernelMemoryDescriptor, MemoryMap, MemoryRegionKind};
+
+/// Size of a physical frame in bytes.
+pub const FRAME_SIZE: u64 = 4096;
+
+/// Maximum number of reserved ranges accepted by
+/// [`PhysicalFrameAllocator::new`].
+pub const MAX_RESERVED_RANGES: usize = 16;
+
+/// Bits per bitmap word.
+const BITS: u64 = u64::BITS as u64;
+
+// ---------------------------------------------------------------------------
+// Address types
+// ---------------------------------------------------------------------------
+
+/// A 4 KiB-aligned physical frame, identified by its start address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PhysFrame(u64);
+
+impl PhysFrame {
+    /// The frame with the given start address, or `None` if `addr` is not
+    /// 4 KiB aligned.
+    #[inline]
+    pub const fn from_start_address(addr: u64) -> Option<Self> {
+        if addr & (FRAME_SIZE - 1) == 0 {
+            Some(Self(addr))
+        } else {
+            None
+        }
+    }
+
+    /// The frame containing `addr`.
+    #[inline]
+    pub const fn containing_address(addr: u64) -> Self {
+        Self(addr & !(FRAME_SIZE - 1))
+    }
+
+    /// The frame with physical frame number `number`.
+    #[inline]
+    pub const fn from_number(number: u64) -> Self {
+        Self(number * FRAME_SIZE)
+    }
+
+    /// Physical start address of the frame.
+    #[inline]
+    pub const fn start_address(self) -> u64 {
+        self.0
+    }
+
+    /// Physical frame number (`start_address / 4096`).
+    #[inline]
+    pub const fn number(self) -> u64 {
+        self.0 / FRAME_SIZE
+    }
+}
+
+/// A half-open physical address range `[start, end)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PhysRange {
+    /// First byte of the range.
+    pub start: u64,
+    /// One past the last byte of the range.
+    pub end: u64,
+}
+
+impl PhysRange {
+    /// The range `[start, end)`.
+    #[inline]
+    pub const fn new(start: u64, end: u64) -> Self {
+        Self { start, end }
+    }
+
+    /// The range `[base, base + len)`, saturating at `u64::MAX`.
+    #[inline]
+    pub const fn from_base_len(base: u64, len: u64) -> Self {
+        Self {
+            start: base,
+            end: base.saturating_add(len),
+        }
+    }
+
+    /// True if the range contains no bytes.
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        self.end <= self.start
+    }
+
+    /// True if `addr` lies inside the range.
+    #[inline]
+    pub const fn contains(&self, addr: u64) -> bool {
+        self.start <= addr && addr < self.end
+    }
+
+    /// Frame numbers `[first, last)` touched by this range — rounded
+    /// outward to frame boundaries.
+    #[inline]
+    fn frames_outward(&self) -> (u64, u64) {
+        (self.start / FRAME_SIZE, self.end.div_ceil(FRAME_SIZE))
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Errors and statistics
+// ---------------------------------------------------------------------------
+
+/// Errors returned by [`PhysicalFrameAllocator`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameAllocError {
+    /// No usable frame remains after applying reserved ranges and the
+    /// bitmap capacity.
+    NoUsableMemory,
+    /// More than [`MAX_RESERVED_RANGES`] reserved ranges were supplied.
+    TooManyReservedRanges,
+    /// The address passed to a free call is not 4 KiB aligned.
+    Unaligned {
+        /// The offending address.
+        addr: u64,
+    },
+    /// The frame was never handed out by this allocator: it lies outside
+    /// usable memory, inside a reserved range, or beyond the bitmap.
+    NotManaged {
+        /// Start address of the offending frame.
+        addr: u64,
+    },
+    /// The frame is already free.
+    DoubleFree {
+        /// Start address of the offending frame.
+        addr: u64,
+    },
+    /// Bootloader memory has already been reclaimed.
+    AlreadyReclaimed,
+}
+
+/// Live allocator counters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameStats {
+    /// Frames managed by the allocator (free + allocated).
+    pub total_frames: u64,
+    /// Frames currently available.
+    pub free_frames: u64,
+    /// Frames currently handed out.
+    pub allocated_frames: u64,
+    /// Highest value `allocated_frames` has reached.
+    pub peak_allocated_frames: u64,
+    /// Usable frames withheld by reserved ranges (and frame 0).
+    pub reserved_frames: u64,
+    /// Usable frames above the bitmap's capacity.
+    pub untracked_frames: u64,
+    /// `MemoryStats::usable_bytes` of the map the allocator was built from,
+    /// plus the size of the bootloader regions once they are reclaimed.
+    pub usable_bytes: u64,
+}
+
+impl FrameStats {
+    /// Bytes currently available.
+    #[inline]
+    pub fn free_bytes(&self) -> u64 {
+        self.free_frames * FRAME_SIZE
+    }
+
+    /// Bytes currently handed out.
+    #[inline]
+    pub fn allocated_bytes(&self) -> u64 {
+        self.allocated_frames * FRAME_SIZE
+    }
+
+    /// True if every usable byte reported by the memory map is accounted for
+    /// as managed, reserved, or untracked, and free + allocated == managed.
+    pub fn is_consistent(&self) -> bool {
+        self.free_frames + self.allocated_frames == self.total_frames
+            && (self.total_frames + self.reserved_frames + self.untracked_frames) * FRAME_SIZE
+                == self.usable_bytes
+    }
+}
+
+/// Outcome of [`PhysicalFrameAllocator::reclaim_bootloader_memory`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReclaimStats {
+    /// Bootloader-reclaimable regions in the memory map.
+    pub regions: usize,
+    /// Frames that became free.
+    pub reclaimed_frames: u64,
+    /// Frames reported as still in use; counted as allocated.
+    pub in_use_frames: u64,
+    /// Frames withheld by reserved ranges (and frame 0).
+    pub reserved_frames: u64,
+    /// Frames above the bitmap's capacity.
+    pub untracked_frames: u64,
+}
+
+impl ReclaimStats {
+    /// Bytes that became free.
+    #[inline]
+    pub fn reclaimed_bytes(&self) -> u64 {
+        self.reclaimed_frames * FRAME_SIZE
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Allocator
+// ---------------------------------------------------------------------------
+
+/// Bitmap allocator for 4 KiB physical frames.
+///
+/// See the [module documentation](self) for the design.
+pub struct PhysicalFrameAllocator<'a> {
+    map: &'a MemoryMap,
+    /// One bit per frame; set = unavailable.
+    bitmap: &'a mut [u64],
+    /// Number of frames tracked by `bitmap` (frame numbers `0..frame_limit`).
+    frame_limit: u64,
+    reserved: [PhysRange; MAX_RESERVED_RANGES],
+    reserved_count: usize,
+    /// Word index at which the next single-frame search starts.
+    next_word: usize,
+    /// Set once bootloader regions have joined the managed pool.
+    bootloader_reclaimed: bool,
+    stats: FrameStats,
+}
+
+impl<'a> PhysicalFrameAllocator<'a> {
+    /// Number of `u64` bitmap words needed to track `bytes` of physical
+    /// address space.
+    pub const fn bitmap_words_for(bytes: u64) -> usize {
+        bytes.div_ceil(FRAME_SIZE).div_ceil(BITS) as usize
+    }
+
+    /// Build an allocator over `map.usable_regions()`.
+    ///
+    /// `storage` holds the bitmap; its previous contents are overwritten.
+    /// Every frame overlapping a range in `reserved` is withheld — ranges
+    /// are rounded outward to frame boundaries.
+    ///
+    /// # Errors
+    ///
+    /// - [`FrameAllocError::TooManyReservedRanges`]: `reserved` has more
+    ///   than [`MAX_RESERVED_RANGES`] entries.
+    /// - [`FrameAllocError::NoUsableMemory`]: no frame is left to manage.
+    pub fn new(
+        map: &'a MemoryMap,
+        storage: &'a mut [u64],
+        reserved: &[PhysRange],
+    ) -> Result<Self, FrameAllocError> {
+        if reserved.len() > MAX_RESERVED_RANGES {
+            return Err(FrameAllocError::TooManyReservedRanges);
+        }
+
+        storage.fill(u64::MAX);
+        let capacity = storage.len() as u64 * BITS;
+        let highest = map
+            .usable_regions()
+            .map(|d| d.phys_start / FRAME_SIZE + d.page_count)
+            .max()
+            .unwrap_or(0);
+
+        let mut this = Self {
+            map,
+            bitmap: storage,
+            frame_limit: highest.min(capacity),
+            reserved: [PhysRange::new(0, 0); MAX_RESERVED_RANGES],
+            reserved_count: reserved.len(),
+            next_word: 0,
+            bootloader_reclaimed: false,
+            stats: FrameStats {
+                total_frames: 0,
+                free_frames: 0,
+                allocated_frames: 0,
+                peak_allocated_frames: 0,
+                reserved_frames: 0,
+                untracked_frames: 0,
+                usable_bytes: map.stats().usable_bytes,
+            },
+        };
+        this.reserved[..reserved.len()].copy_from_slice(reserved);
+
+        // Release every usable frame the bitmap can track.
+        let mut released = 0;
+        for desc in map.usable_regions() {
+            let first = desc.phys_start / FRAME_SIZE;
+            let end = first + desc.page_count;
+            let tracked_end = end.min(this.frame_limit);
+            if tracked_end > first {
+                released += this.clear_range(first, tracked_end);
+            }
+            this.stats.untracked_frames += end - tracked_end.max(first);
+        }
+
+        // Withhold frame 0 and the reserved ranges.
+        let mut withheld = this.set_range(0, 1.min(this.frame_limit));
+        for range in reserved {
+            if range.is_empty() {
+                continue;
+            }
+            let (first, end) = range.frames_outward();
+            let end = end.min(this.frame_limit);
+            if end > first {
+                withheld += this.set_range(first, end);
+            }
+        }
+        // `set_range` returns the number of bits that were clear — each of
+        // those is a usable frame that is now withheld.
+        let managed = released - withheld;
+        if managed == 0 {
+            return Err(FrameAllocError::NoUsableMemory);
+        }
+
+        this.stats.reserved_frames = withheld;
+        this.stats.total_frames = managed;
+        this.stats.free_frames = managed;
+        Ok(this)
+    }
+
+    /// Current allocator counters.
+    #[inline]
+    pub fn stats(&self) -> FrameStats {
+        self.stats
+    }
+
+    /// The memory map this allocator was built from.
+    #[inline]
+    pub fn memory_map(&self) -> &'a MemoryMap {
+        self.map
+    }
+
+    /// True if `frame` is managed by this allocator and currently free.
+    pub fn is_free(&self, frame: PhysFrame) -> bool {
+        self.is_managed(frame) && !self.test(frame.number())
+    }
+
+    /// Allocate a single 4 KiB frame.
+    ///
+    /// Returns `None` when no frame is free.
+    pub fn allocate_frame(&mut self) -> Option<PhysFrame> {
+        if self.stats.free_frames == 0 {
+            return None;
+        }
+        let words = self.bitmap.len();
+        for i in 0..words {
+            let w = (self.next_word + i) % words;
+            let word = self.bitmap[w];
+            if word == u64::MAX {
+                continue;
+            }
+            let number = w as u64 * BITS + u64::from(word.trailing_ones());
+            if number >= self.frame_limit {
+                continue;
+            }
+            self.next_word = w;
+            self.set(number);
+            self.record_alloc(1);
+            return Some(PhysFrame::from_number(number));
+        }
+        None
+    }
+
+    /// Allocate `count` physically contiguous frames.
+    ///
+    /// Returns the first frame of the run, or `None` if `count` is zero or
+    /// no sufficiently long run is free. The search is first-fit from the
+    /// lowest address.
+    pub fn allocate_contiguous(&mut self, count: u64) -> Option<PhysFrame> {
+        if count == 0 || count > self.stats.free_frames {
+            return None;
+        }
+        let mut run_start = 0;
+        let mut run_len = 0;
+        let mut n = 0;
+        while n < self.frame_limit {
+            // Skip fully used words quickly.
+            if n % BITS == 0 && self.bitmap[(n / BITS) as usize] == u64::MAX {
+                run_len = 0;
+                n += BITS;
+                continue;
+            }
+            if self.test(n) {
+                run_len = 0;
+            } else {
+                if run_len == 0 {
+                    run_start = n;
+                }
+                run_len += 1;
+                if run_len == count {
+                    self.set_range(run_start, run_start + count);
+                    self.record_alloc(count);
+                    return Some(PhysFrame::from_number(run_start));
+                }
+            }
+            n += 1;
+        }
+        None
+    }
+
+    /// Return a frame obtained from [`allocate_frame`](Self::allocate_frame)
+    /// or [`allocate_contiguous`](Self::allocate_contiguous).
+    ///
+    /// # Errors
+    ///
+    /// - [`FrameAllocError::NotManaged`]: the frame was never handed out by
+    ///   this allocator.
+    /// - [`FrameAllocError::DoubleFree`]: the frame is already free.
+    pub fn deallocate_frame(&mut self, frame: PhysFrame) -> Result<(), FrameAllocError> {
+        self.deallocate_contiguous(frame, 1)
+    }
+
+    /// Return `count` contiguous frames starting at `first`.
+    ///
+    /// Either every frame is freed or, on error, none is.
+    ///
+    /// # Errors
+    ///
+    /// As for [`deallocate_frame`](Self::deallocate_frame), reported for the
+    /// first offending frame of the run.
+    pub fn deallocate_contiguous(
+        &mut self,
+        first: PhysFrame,
+        count: u64,
+    ) -> Result<(), FrameAllocError> {
+        let start = first.number();
+        for n in start..start + count {
+            let frame = PhysFrame::from_number(n);
+            if !self.is_managed(frame) {
+                return Err(FrameAllocError::NotManaged {
+                    addr: frame.start_address(),
+                });
+            }
+            if !self.test(n) {
+                return Err(FrameAllocError::DoubleFree {
+                    addr: frame.start_address(),
+                });
+            }
+        }
+        self.clear_range(start, start + count);
+        self.stats.allocated_frames -= count;
+        self.stats.free_frames += count;
+        self.next_word = self.next_word.min((start / BITS) as usize);
+        Ok(())
+    }
+
+    /// Free the frame starting at `addr`.
+    ///
+    /// Convenience wrapper over [`deallocate_frame`](Self::deallocate_frame)
+    /// for callers holding a raw address.
+    ///
+    /// # Errors
+    ///
+    /// [`FrameAllocError::Unaligned`] if `addr` is not 4 KiB aligned, else as
+    /// for [`deallocate_frame`](Self::deallocate_frame).
+    pub fn deallocate_address(&mut self, addr: u64) -> Result<(), FrameAllocError> {
+        let frame =
+            PhysFrame::from_start_address(addr).ok_or(FrameAllocError::Unaligned { addr })?;
+        self.deallocate_frame(frame)
+    }
+
+    /// Add the memory map's boot-services and loader regions to the managed
+    /// pool.
+    ///
+    /// Call once the bootloader's code and data are no longer needed. Frames
+    /// in a reserved range (and frame 0) stay withheld. `in_use` is asked
+    /// about every other frame; those it claims stay unavailable and are
+    /// counted as allocated, so their owner may later return them with
+    /// [`deallocate_frame`](Self::deallocate_frame). The rest become free.
+    ///
+    /// Bootloader regions are assumed not to overlap usable regions, as the
+    /// UEFI specification guarantees.
+    ///
+    /// # Errors
+    ///
+    /// [`FrameAllocError::AlreadyReclaimed`] on a second call.
+    pub fn reclaim_bootloader_memory(
+        &mut self,
+        mut in_use: impl FnMut(PhysFrame) -> bool,
+    ) -> Result<ReclaimStats, FrameAllocError> {
+        if self.bootloader_reclaimed {
+            return Err(FrameAllocError::AlreadyReclaimed);
+        }
+        self.bootloader_reclaimed = true;
+
+        // Bits above the old limit are still set from construction, so the
+        // limit can grow without touching them.
+        let map = self.map;
+        let capacity = self.bitmap.len() as u64 * BITS;
+        let highest = bootloader_regions(map)
+            .map(|d| d.phys_start / FRAME_SIZE + d.page_count)
+            .max()
+            .unwrap_or(0);
+        self.frame_limit = self.frame_limit.max(highest.min(capacity));
+
+        let mut result = ReclaimStats::default();
+        for desc in bootloader_regions(map) {
+            result.regions += 1;
+            let first = desc.phys_start / FRAME_SIZE;
+            let end = first + desc.page_count;
+            let tracked_end = end.min(self.frame_limit).max(first);
+            for n in first..tracked_end {
+                let frame = PhysFrame::from_number(n);
+                if n == 0 || self.in_reserved(n) {
+                    result.reserved_frames += 1;
+                } else if in_use(frame) {
+                    result.in_use_frames += 1;
+                } else {
+                    self.bitmap[(n / BITS) as usize] &= !(1 << (n % BITS));
+                    result.reclaimed_frames += 1;
+                }
+            }
+            result.untracked_frames += end - tracked_end;
+            self.next_word = self.next_word.min((first / BITS) as usize);
+        }
+
+        let managed = result.reclaimed_frames + result.in_use_frames;
+        let stats = &mut self.stats;
+        stats.total_frames += managed;
+        stats.free_frames += result.reclaimed_frames;
+        stats.allocated_frames += result.in_use_frames;
+        stats.peak_allocated_frames = stats.peak_allocated_frames.max(stats.allocated_frames);
+        stats.reserved_frames += result.reserved_frames;
+        stats.untracked_frames += result.untracked_frames;
+        stats.usable_bytes +=
+            (managed + result.reserved_frames + result.untracked_frames) * FRAME_SIZE;
+        Ok(result)
+    }
+
+    // -----------------------------------------------------------------------
+    // Internals
+    // -----------------------------------------------------------------------
+
+    /// True if `frame` lies inside a usable region (or a reclaimed
+    /// bootloader region), below the bitmap limit, and outside frame 0 and
+    /// every reserved range.
+    fn is_managed(&self, frame: PhysFrame) -> bool {
+        let n = frame.number();
+        if n == 0 || n >= self.frame_limit {
+            return false;
+        }
+        let contains = |d: &KernelMemoryDescriptor| {
+            let first = d.phys_start / FRAME_SIZE;
+            first <= n && n < first + d.page_count
+        };
+        let in_pool = self.map.usable_regions().any(contains)
+            || (self.bootloader_reclaimed && bootloader_regions(self.map).any(contains));
+        in_pool && !self.in_reserved(n)
+    }
+
+    /// True if frame `n` overlaps a reserved range.
+    fn in_reserved(&self, n: u64) -> bool {
+        self.reserved[..self.reserved_count].iter().any(|r| {
+            let (first, end) = r.frames_outward();
+            !r.is_empty() && first <= n && n < end
+        })
+    }
+
+    fn record_alloc(&mut self, count: u64) {
+        self.stats.free_frames -= count;
+        self.stats.allocated_frames += count;
+        self.stats.peak_allocated_frames = self
+            .stats
+            .peak_allocated_frames
+            .max(self.stats.allocated_frames);
+    }
+
+    #[inline]
+    fn test(&self, n: u64) -> bool {
+        self.bitmap[(n / BITS) as usize] & (1 << (n % BITS)) != 0
+    }
+
+    #[inline]
+    fn set(&mut self, n: u64) {
+        self.bitmap[(n / BITS) as usize] |= 1 << (n % BITS);
+    }
+
+    /// Set bits `[first, end)`; returns how many were previously clear.
+    fn set_range(&mut self, first: u64, end: u64) -> u64 {
+        let mut changed = 0;
+        self.for_each_word(first, end, |word, mask| {
+            changed += u64::from((!*word & mask).count_ones());
+            *word |= mask;
+        });
+        changed
+    }
+
+    /// Clear bits `[first, end)`; returns how many were previously set.
+    fn clear_range(&mut self, first: u64, end: u64) -> u64 {
+        let mut changed = 0;
+        self.for_each_word(first, end, |word, mask| {
+            changed += u64::from((*word & mask).count_ones());
+            *word &= !mask;
+        });
+        changed
+    }
+
+    /// Call `f(word, mask)` for every bitmap word overlapping bits
+    /// `[first, end)`, with `mask` selecting the overlapping bits.
+    fn for_each_word(&mut self, first: u64, end: u64, mut f: impl FnMut(&mut u64, u64)) {
+        let mut n = first;
+        while n < end {
+            let bit = n % BITS;
+            let span = (BITS - bit).min(end - n);
+            let mask = if span == BITS {
+                u64::MAX
+            } else {
+                ((1u64 << span) - 1) << bit
+            };
+            f(&mut self.bitmap[(n / BITS) as usize], mask);
+            n += span;
+        }
+    }
+}
+
+/// Boot-services and loader regions of `map`.
+fn bootloader_regions(map: &MemoryMap) -> impl Iterator<Item = &KernelMemoryDescriptor> {
+    map.regions()
+        .iter()
+        .filter(|d| MemoryRegionKind::from(d.ty) == MemoryRegionKind::BootloaderReclaimable)
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{desc, make_map, MIB};
+    use ferrous_boot_info::memory_type;
+    use std::vec;
+    use std::vec::Vec;
+
+    /// A small QEMU-like layout:
+    /// - 0x0000_0000 .. 0x000A_0000 conventional (160 pages, frame 0 included)
+    /// - 0x000A_0000 .. 0x0010_0000 reserved (VGA / BIOS hole)
+    /// - 0x0010_0000 .. 0x0020_0000 conventional (256 pages)
+    /// - 0x0020_0000 .. 0x0030_0000 loader code (kernel image)
+    /// - 0x0030_0000 .. 0x0080_0000 conventional (1280 pages)
+    fn qemu_like_map() -> MemoryMap {
+        make_map(&[
+            desc(memory_type::CONVENTIONAL, 0, 160),
+            desc(memory_type::RESERVED, 0xA_0000, 96),
+            desc(memory_type::CONVENTIONAL, 0x10_0000, 256),
+            desc(memory_type::LOADER_CODE, 0x20_0000, 256),
+            desc(memory_type::CONVENTIONAL, 0x30_0000, 1280),
+        ])
+    }
+
+    fn storage_for(bytes: u64) -> Vec<u64> {
+        vec![0; PhysicalFrameAllocator::bitmap_words_for(bytes)]
+    }
+
+    // -----------------------------------------------------------------------
+    // Construction
+    // -----------------------------------------------------------------------
+
+    #[test]
+    fn new_counts_usable_frames_minus_frame_zero() {
+        let map = qemu_like_map();
+        let mut storage = storage_for(8 * MIB);
+        let fa = PhysicalFrameAllocator::new(&map, &mut storage, &[]).unwrap();
+        let s = fa.stats();
+        assert_eq!(s.total_frames, 160 + 256 + 1280 - 1);
+        assert_eq!(s.free_frames, s.total_frames);
+        assert_eq!(s.allocated_frames, 0);
+        assert_eq!(s.reserved_frames, 1);
+        assert_eq!(s.untracked_frames, 0);
+        assert!(s.is_consistent());
+    }
+
+    #[test]
+    fn new_withholds_reserved_ranges() {
+        let map = qemu_like_map();
+        let mut storage = storage_for(8 * MIB);
+        // 16 KiB "boot info" inside conventional memory, 64 KiB "stack".
+        let reserved = [
+            PhysRange::from_base_len(0x10_0000, 0x4000),
+            PhysRange::from_base_len(0x40_0000, 0x1_0000),
+        ];
+        let fa = PhysicalFrameAllocator::new(&map, &mut storage, &reserved).unwrap();
+        let s = fa.stats();
+        assert_eq!(s.reserved_frames, 1 + 4 + 16);
+        assert_eq!(s.total_frames, 1696 - 1 - 4 - 16);
+        assert!(s.is_consistent());
+        assert!(!fa.is_free(PhysFrame::containing_address(0x10_0000)));
+        assert!(!fa.is_free(PhysFrame::containing_address(0x40_F000)));
+        assert!(fa.is_free(PhysFrame::containing_address(0x41_0000)));
+    }
+
+    #[test]
+    fn reserved_range_is_rounded_outward() {
+        let map = qemu_like_map();
+        let mut storage = storage_for(8 * MIB);
+        // Two bytes straddling a frame boundary withhold both frames.
+        let reserved = [PhysRange::new(0x30_0FFF, 0x30_1001)];
+        let fa = PhysicalFrameAllocator::new(&map, &mut storage, &reserved).unwrap();
+        assert_eq!(fa.stats().reserved_frames, 1 + 2);
+        assert!(!fa.is_free(PhysFrame::from_start_address(0x30_0000).unwrap()));
+        assert!(!fa.is_free(PhysFrame::from_start_address(0x30_1000).unwrap()));
+        assert!(fa.is_free(PhysFrame::from_start_address(0x30_2000).unwrap()));
+    }
+
+    #[test]
+    fn reserved_range_outside_usable_memory_withholds_nothing() {
+        let map = qemu_like_map();
+        let mut storage = storage_for(8 * MIB);
+        // The kernel image lives in LOADER_CODE, which is never usable.
+        let reserved = [PhysRange::new(0x20_0000, 0x30_0000)];
+        let fa = PhysicalFrameAllocator::new(&map, &mut storage, &reserved).unwrap();
+        assert_eq!(fa.stats().reserved_frames, 1);
+        assert!(fa.stats().is_consistent());
+    }
+
+    #[test]
+    fn overlapping_reserved_ranges_count_once() {
+        let map = qemu_like_map();
+        let mut storage = storage_for(8 * MIB);
+        let reserved = [
+            PhysRange::new(0x30_0000, 0x30_4000),
+            PhysRange::new(0x30_2000, 0x30_6000),
+        ];
+        let fa = PhysicalFrameAllocator::new(&map, &mut storage, &reserved).unwrap();
+        assert_eq!(fa.stats().reserved_frames, 1 + 6);
+        assert!(fa.stats().is_consistent());
+    }
+
+    #[test]
+    fn too_many_reserved_ranges_is_rejected() {
+        let map = qemu_like_map();
+        let mut storage = storage_for(8 * MIB);
+        let reserved = [PhysRange::new(0x30_0000, 0x30_1000); MAX_RESERVED_RANGES + 1];
+        assert_eq!(
+            PhysicalFrameAllocator::new(&map, &mut storage, &reserved).err(),
+            Some(FrameAllocError::TooManyReservedRanges)
+        );
+    }
+
+    #[test]
+    fn fully_reserved_map_is_rejected() {
+        let map = make_map(&[desc(memory_type::CONVENTIONAL, 0x10_0000, 4)]);
+        let mut storage = storage_for(2 * MIB);
+        let reserved = [PhysRange::new(0x10_0000, 0x10_4000)];
+        assert_eq!(
+            PhysicalFrameAllocator::new(&map, &mut storage, &reserved).err(),
+            Some(FrameAllocError::NoUsableMemory)
+        );
+    }
+
+    #[test]
+    fn map_without_conventional_memory_is_rejected() {
+        let map = make_map(&[desc(memory_type::RESERVED, 0x10_0000, 4)]);
+        let mut storage = storage_for(2 * MIB);
+        assert_eq!(
+            PhysicalFrameAllocator::new(&map, &mut storage, &[]).err(),
+            Some(FrameAllocError::NoUsableMemory)
+        );
+    }
+
+    #[test]
+    fn frames_beyond_bitmap_capacity_are_untracked() {
+        let map = qemu_like_map();
+        // One word tracks 64 frames = 256 KiB.
+        let mut storage = vec![0u64; 1];
+        let fa = PhysicalFrameAllocator::new(&map, &mut storage, &[]).unwrap();
+        let s = fa.stats();
+        assert_eq!(s.total_frames, 63);
+        assert_eq!(s.untracked_frames, 1696 - 64);
+        assert!(s.is_consistent());
+    }
+
+    #[test]
+    fn storage_contents_are_overwritten() {
+        let map = qemu_like_map();
+        let mut storage =
+            vec![0x5555_5555_5555_5555u64; PhysicalFrameAllocator::bitmap_words_for(8 * MIB)];
+        let fa = PhysicalFrameAllocator::new(&map, &mut storage, &[]).unwrap();
+        assert_eq!(fa.stats().free_frames, 1695);
+    }
+
+    // -----------------------------------------------------------------------
+    // Single-frame allocation
+    // -----------------------------------------------------------------------
+
+    #[test]
+    fn allocate_frame_never_returns_frame_zero_or_holes() {
+        let map = qemu_like_map();
+        let mut storage = storage_for(8 * MIB);
+        let mut fa = PhysicalFrameAllocator::new(&map, &mut storage, &[]).unwrap();
+        while let Some(frame) = fa.allocate_frame() {
+            let a = frame.start_address();
+            assert_ne!(a, 0);
+            assert!(!(0xA_0000..0x10_0000).contains(&a), "hole frame {a:#x}");
+            assert!(!(0x20_0000..0x30_0000).contains(&a), "kernel frame {a:#x}");
+        }
+        assert_eq!(fa.stats().free_frames, 0);
+    }
+
+    #[test]
+    fn allocate_frame_exhausts_exactly_total_frames() {
+        let map = qemu_like_map();
+        let mut storage = storage_for(8 * MIB);
+        let mut fa = PhysicalFrameAllocator::new(&map, &mut storage, &[]).unwrap();
+        let total = fa.stats().total_frames;
+        let mut seen = std::collections::BTreeSet::new();
+        while let Some(frame) = fa.allocate_frame() {
+            assert!(seen.insert(frame), "frame handed out twice");
+        }
+        assert_eq!(seen.len() as u64, total);
+        assert_eq!(fa.allocate_frame(), None);
+        assert_eq!(fa.stats().allocated_frames, total);
+        assert_eq!(fa.stats().peak_allocated_frames, total);
+    }
+
+    #[test]
+    fn allocated_frames_are_aligned() {
+        let map = qemu_like_map();
+        let mut storage = storage_for(8 * MIB);
+        let mut fa = PhysicalFrameAllocator::new(&map, &mut storage, &[]).unwrap();
+        for _ in 0..100 {
+            let f = fa.allocate_frame().unwrap();
+            assert_eq!(f.start_address() % FRAME_SIZE, 0);
+        }
+    }
+
+    #[test]
+    fn freed_frame_is_reused() {
+        let map = qemu_like_map();
+        let mut storage = storage_for(8 * MIB);
+        let mut fa = PhysicalFrameAllocator::new(&map, &mut storage, &[]).unwrap();
+        let a = fa.allocate_frame().unwrap();
+        let _b = fa.allocate_frame().unwrap();
+        fa.deallocate_frame(a).unwrap();
+        assert_eq!(fa.allocate_frame(), Some(a));
+    }
+
+    // -----------------------------------------------------------------------
+    // Contiguous allocation
+    // -----------------------------------------------------------------------
+
+    #[test]
+    fn allocate_contiguous_returns_free_run() {
+        let map = qemu_like_map();
+        let mut storage = storage_for(8 * MIB);
+        let mut fa = PhysicalFrameAllocator::new(&map, &mut storage, &[]).unwrap();
+        let first = fa.allocate_contiguous(16).unwrap();
+        for i in 0..16 {
+            assert!(!fa.is_free(PhysFrame::from_number(first.number() + i)));
+        }
+        assert_eq!(fa.stats().allocated_frames, 16);
+    }
+
+    #[test]
+    fn allocate_contiguous_does_not_span_holes() {
+        let map = qemu_like_map();
+        let mut storage = storage_for(8 * MIB);
+        let mut fa = PhysicalFrameAllocator::new(&map, &mut storage, &[]).unwrap();
+        // 100 frames come from the 159-frame low region; 200 no longer fit
+        // there and come from the 256-frame region at 1 MiB; 300 fit only in
+        // the 1280-frame region at 3 MiB.
+        let low = fa.allocate_contiguous(100).unwrap();
+        let mid = fa.allocate_contiguous(200).unwrap();
+        let run = fa.allocate_contiguous(300).unwrap();
+        assert_eq!(low.start_address(), 0x1000);
+        assert_eq!(mid.start_address(), 0x10_0000);
+        assert!(run.start_address() >= 0x30_0000);
+        assert!(run.start_address() + 300 * FRAME_SIZE <= 0x80_0000);
+    }
+
+    #[test]
+    fn allocate_contiguous_too_large_fails_without_side_effects() {
+        let map = qemu_like_map();
+        let mut storage = storage_for(8 * MIB);
+        let mut fa = PhysicalFrameAllocator::new(&map, &mut storage, &[]).unwrap();
+        let before = fa.stats();
+        assert_eq!(fa.allocate_contiguous(1281), None);
+        assert_eq!(fa.allocate_contiguous(0), None);
+        assert_eq!(fa.stats(), before);
+    }
+
+    #[test]
+    fn contiguous_run_can_be_freed_and_reallocated() {
+        let map = qemu_like_map();
+        let mut storage = storage_for(8 * MIB);
+        let mut fa = PhysicalFrameAllocator::new(&map, &mut storage, &[]).unwrap();
+        let run = fa.allocate_contiguous(1280).unwrap();
+        assert_eq!(run.start_address(), 0x30_0000);
+        fa.deallocate_contiguous(run, 1280).unwrap();
+        assert_eq!(fa.allocate_contiguous(1280), Some(run));
+    }
+
+    // -----------------------------------------------------------------------
+    // Deallocation errors
+    // -----------------------------------------------------------------------
+
+    #[test]
+    fn double_free_is_detected() {
+        let map = qemu_like_map();
+        let mut storage = storage_for(8 * MIB);
+        let mut fa = PhysicalFrameAllocator::new(&map, &mut storage, &[]).unwrap();
+        let f = fa.allocate_frame().unwrap();
+        fa.deallocate_frame(f).unwrap();
+        assert_eq!(
+            fa.deallocate_frame(f),
+            Err(FrameAllocError::DoubleFree {
+                addr: f.start_address()
+            })
+        );
+    }
+
+    #[test]
+    fn freeing_unmanaged_frames_is_rejected() {
+        let map = qemu_like_map();
+        let mut storage = storage_for(8 * MIB);
+        let reserved = [PhysRange::from_base_len(0x40_0000, 0x1000)];
+        let mut fa = PhysicalFrameAllocator::new(&map, &mut storage, &reserved).unwrap();
+        for addr in [0, 0xA_0000, 0x20_0000, 0x40_0000, 0x80_0000, 0x1_0000_0000] {
+            assert_eq!(
+                fa.deallocate_address(addr),
+                Err(FrameAllocError::NotManaged { addr }),
+                "addr {addr:#x}"
+            );
+        }
+    }
+
+    #[test]
+    fn freeing_unaligned_address_is_rejected() {
+        let map = qemu_like_map();
+        let mut storage = storage_for(8 * MIB);
+        let mut fa = PhysicalFrameAllocator::new(&map, &mut storage, &[]).unwrap();
+        assert_eq!(
+            fa.deallocate_address(0x30_0010),
+            Err(FrameAllocError::Unaligned { addr: 0x30_0010 })
+        );
+    }
+
+    #[test]
+    fn failed_contiguous_free_changes_nothing() {
+        let map = qemu_like_map();
+        let mut storage = storage_for(8 * MIB);
+        let mut fa = PhysicalFrameAllocator::new(&map, &mut storage, &[]).unwrap();
+        let run = fa.allocate_contiguous(4).unwrap();
+        // Last frame of a 5-frame run was never allocated.
+        let before = fa.stats();
+        assert!(matches!(
+            fa.deallocate_contiguous(run, 5),
+            Err(FrameAllocError::DoubleFree { .. })
+        ));
+        assert_eq!(fa.stats(), before);
+        fa.deallocate_contiguous(run, 4).unwrap();
+    }
+
+    // -----------------------------------------------------------------------
+    // Bootloader reclamation
+    // -----------------------------------------------------------------------
+
+    #[test]
+    fn reclaim_frees_loader_region_except_reserved_and_in_use() {
+        let map = qemu_like_map();
+        let mut storage = storage_for(8 * MIB);
+        let reserved = [PhysRange::new(0x20_0000, 0x21_0000)];
+        let mut fa = PhysicalFrameAllocator::new(&map, &mut storage, &reserved).unwrap();
+        let before = fa.stats();
+        let table = PhysFrame::containing_address(0x25_0000);
+        assert_eq!(
+            fa.deallocate_frame(table),
+            Err(FrameAllocError::NotManaged { addr: 0x25_0000 })
+        );
+
+        let r = fa.reclaim_bootloader_memory(|f| f == table).unwrap();
+        assert_eq!(r.regions, 1);
+        assert_eq!(r.reserved_frames, 16);
+        assert_eq!(r.in_use_frames, 1);
+        assert_eq!(r.reclaimed_frames, 239);
+        assert_eq!(r.untracked_frames, 0);
+        assert_eq!(r.reclaimed_bytes(), 239 * FRAME_SIZE);
+
+        let s = fa.stats();
+        assert_eq!(s.free_frames, before.free_frames + 239);
+        assert_eq!(s.allocated_frames, 1);
+        assert_eq!(s.usable_bytes, before.usable_bytes + 256 * FRAME_SIZE);
+        assert!(s.is_consistent());
+
+        assert!(fa.is_free(PhysFrame::containing_address(0x21_0000)));
+        assert!(!fa.is_free(table));
+        // The in-use frame can be handed back later; reserved ones cannot.
+        fa.deallocate_frame(table).unwrap();
+        assert_eq!(
+            fa.deallocate_address(0x20_0000),
+            Err(FrameAllocError::NotManaged { addr: 0x20_0000 })
+        );
+        assert!(fa.stats().is_consistent());
+    }
+
+    #[test]
+    fn reclaim_twice_is_an_error() {
+        let map = qemu_like_map();
+        let mut storage = storage_for(8 * MIB);
+        let mut fa = PhysicalFrameAllocator::new(&map, &mut storage, &[]).unwrap();
+        fa.reclaim_bootloader_memory(|_| false).unwrap();
+        assert_eq!(
+            fa.reclaim_bootloader_memory(|_| false),
+            Err(FrameAllocError::AlreadyReclaimed)
+        );
+    }
+
+    #[test]
+    fn reclaim_extends_the_tracked_range() {
+        // Boot services data above the highest conventional frame.
+        let map = make_map(&[
+            desc(memory_type::CONVENTIONAL, 0x10_0000, 256),
+            desc(memory_type::BOOT_SERVICES_DATA, 0x20_0000, 512),
+        ]);
+        let mut storage = storage_for(4 * MIB);
+        let mut fa = PhysicalFrameAllocator::new(&map, &mut storage, &[]).unwrap();
+        let r = fa.reclaim_bootloader_memory(|_| false).unwrap();
+        assert_eq!(r.reclaimed_frames, 512);
+        assert!(fa.stats().is_consistent());
+
+        // A run can now span the conventional and the reclaimed region.
+        let run = fa.allocate_contiguous(700).unwrap();
+        assert_eq!(run.start_address(), 0x10_0000);
+        fa.deallocate_contiguous(run, 700).unwrap();
+    }
+
+    #[test]
+    fn reclaim_counts_frames_beyond_capacity_as_untracked() {
+        let map = make_map(&[
+            desc(memory_type::CONVENTIONAL, 0x10_0000, 256),
+            desc(memory_type::LOADER_DATA, 0x20_0000, 512),
+        ]);
+        // Covers 0 .. 3 MiB: half of the loader region is out of reach.
+        let mut storage = storage_for(3 * MIB);
+        let mut fa = PhysicalFrameAllocator::new(&map, &mut storage, &[]).unwrap();
+        let r = fa.reclaim_bootloader_memory(|_| false).unwrap();
+        assert_eq!(r.reclaimed_frames, 256);
+        assert_eq!(r.untracked_frames, 256);
+        assert!(fa.stats().is_consistent());
+        assert_eq!(
+            fa.deallocate_address(0x30_0000),
+            Err(FrameAllocError::NotManaged { addr: 0x30_0000 })
+        );
+    }
+
+    // -----------------------------------------------------------------------
+    // Statistics
+    // -----------------------------------------------------------------------
+
+    #[test]
+    fn stats_track_live_allocations_and_peak() {
+        let map = qemu_like_map();
+        let mut storage = storage_for(8 * MIB);
+        let mut fa = PhysicalFrameAllocator::new(&map, &mut storage, &[]).unwrap();
+        let frames: Vec<_> = (0..10).map(|_| fa.allocate_frame().unwrap()).collect();
+        assert_eq!(fa.stats().allocated_frames, 10);
+        assert_eq!(fa.stats().allocated_bytes(), 10 * FRAME_SIZE);
+        for f in &frames[..4] {
+            fa.deallocate_frame(*f).unwrap();
+        }
+        let s = fa.stats();
+        assert_eq!(s.allocated_frames, 6);
+        assert_eq!(s.peak_allocated_frames, 10);
+        assert_eq!(s.free_bytes(), (s.total_frames - 6) * FRAME_SIZE);
+        assert!(s.is_consistent());
+    }
+
+    #[test]
+    fn stats_report_memory_map_usable_bytes() {
+        let map = qemu_like_map();
+        let mut storage = storage_for(8 * MIB);
+        let fa = PhysicalFrameAllocator::new(&map, &mut storage, &[]).unwrap();
+        assert_eq!(fa.stats().usable_bytes, map.stats().usable_bytes);
+        assert_eq!(fa.stats().usable_bytes, 1696 * FRAME_SIZE);
+    }
+
+    // -----------------------------------------------------------------------
+    // Address types
+    // -----------------------------------------------------------------------
+
+    #[test]
+    fn phys_frame_alignment() {
+        assert_eq!(PhysFrame::from_start_address(0x1000).unwrap().number(), 1);
+        assert_eq!(PhysFrame::from_start_address(0x1001), None);
+        assert_eq!(
+            PhysFrame::containing_address(0x1FFF).start_address(),
+            0x1000
+        );
+    }
+
+    #[test]
+    fn bitmap_words_for_rounds_up() {
+        assert_eq!(PhysicalFrameAllocator::bitmap_words_for(0), 0);
+        assert_eq!(PhysicalFrameAllocator::bitmap_words_for(1), 1);
+        assert_eq!(PhysicalFrameAllocator::bitmap_words_for(64 * FRAME_SIZE), 1);
+        assert_eq!(
+            PhysicalFrameAllocator::bitmap_words_for(64 * FRAME_SIZE + 1),
+            2
+        );
+        assert_eq!(
+            PhysicalFrameAllocator::bitmap_words_for(4 * 1024 * MIB),
+            16384
+        );
+    }
+}
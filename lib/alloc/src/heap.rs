@@ -0,0 +1,690 @@
+//! Kernel heap: size-class slabs over a grow-on-demand page pool.
+//!
+//! [`Heap`] serves `Layout`-based allocations from virtual memory that a
+//! [`HeapBackend`] supplies in whole pages. [`LockedHeap`] wraps it in a
+//! spin lock and implements [`GlobalAlloc`], so a kernel can install it with
+//! `#[global_allocator]` and use `Box`, `Vec`, `BTreeMap` and friends.
+//!
+//! # Design
+//!
+//! - **Small allocations** (up to [`MAX_SMALL_SIZE`] bytes after rounding
+//!   size and alignment up to a power of two) come from one of the
+//!   [`SIZE_CLASSES`]. Each class keeps an intrusive free list of objects;
+//!   an empty list is refilled by carving a whole page into objects. Since
+//!   pages are page-aligned and objects are power-of-two sized, every object
+//!   is naturally aligned to its size. Pages given to a class stay with it.
+//! - **Large allocations** take whole pages from the page pool: an
+//!   address-ordered list of free page runs, first fit, with neighbouring
+//!   runs merged on free.
+//! - When the pool cannot satisfy a request the heap asks the backend for
+//!   at least [`GROW_PAGES`] more pages and retries once.
+//!
+//! All metadata lives inside the free memory itself, so the heap needs no
+//! storage of its own beyond the [`Heap`] struct.
+//!
+//! # Safety
+//!
+//! This module and [`slab`](crate::slab) are the only ones in the crate
+//! that dereference the memory they manage, and the only ones where
+//! `unsafe_code` is allowed; every block states its invariant.
+#![allow(unsafe_code)]
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::ptr::{self, NonNull};
+
+use ferrous_core::sync::SpinLock;
+
+/// Granularity of the page pool and of [`HeapBackend::grow`].
+pub const HEAP_PAGE_SIZE: usize = 4096;
+
+/// Object sizes of the small-allocation classes.
+pub const SIZE_CLASSES: [usize; SIZE_CLASS_COUNT] = [8, 16, 32, 64, 128, 256, 512, 1024, 2048];
+
+/// Number of entries in [`SIZE_CLASSES`].
+pub const SIZE_CLASS_COUNT: usize = 9;
+
+/// Largest request (after rounding) served by a size class.
+pub const MAX_SMALL_SIZE: usize = SIZE_CLASSES[SIZE_CLASS_COUNT - 1];
+
+/// Minimum number of pages requested from the backend per growth step.
+pub const GROW_PAGES: usize = 16;
+
+/// Source of fresh heap pages.
+pub trait HeapBackend {
+    /// Make `pages` new pages available and return the address of the first.
+    ///
+    /// The pages must be page-aligned, contiguous, readable and writable,
+    /// and owned by the heap from then on. Returns `None` when out of memory
+    /// or address space.
+    fn grow(&mut self, pages: usize) -> Option<NonNull<u8>>;
+}
+
+/// Errors returned by [`LockedHeap::init`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeapError {
+    /// [`LockedHeap::init`] was called more than once.
+    AlreadyInitialized,
+}
+
+/// Usage of one size class.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SizeClassStats {
+    /// Object size in bytes.
+    pub size: usize,
+    /// Pages carved into objects of this class.
+    pub pages: u64,
+    /// Objects currently allocated.
+    pub live: u64,
+    /// Objects on the free list.
+    pub free: u64,
+    /// Allocations served since the heap was created.
+    pub total_allocations: u64,
+}
+
+/// Heap counters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeapStats {
+    /// Per-class usage, in [`SIZE_CLASSES`] order.
+    pub classes: [SizeClassStats; SIZE_CLASS_COUNT],
+    /// Large (page-granular) allocations currently live.
+    pub large_live: u64,
+    /// Pages held by live large allocations.
+    pub large_pages: u64,
+    /// Pages obtained from the backend so far.
+    pub pool_pages: u64,
+    /// Pages in the free page pool.
+    pub free_pages: u64,
+}
+
+impl HeapStats {
+    /// Bytes handed out to callers (rounded to class size / whole pages).
+    pub fn live_bytes(&self) -> u64 {
+        let small: u64 = self.classes.iter().map(|c| c.live * c.size as u64).sum();
+        small + self.large_pages * HEAP_PAGE_SIZE as u64
+    }
+
+    /// Bytes obtained from the backend.
+    pub fn pool_bytes(&self) -> u64 {
+        self.pool_pages * HEAP_PAGE_SIZE as u64
+    }
+}
+
+/// Index into [`SIZE_CLASSES`] for `layout`, or `None` for a large request.
+pub fn size_class_for(layout: Layout) -> Option<usize> {
+    let size = layout.size().max(layout.align()).max(SIZE_CLASSES[0]);
+    let rounded = size.checked_next_power_of_two()?;
+    if rounded > MAX_SMALL_SIZE {
+        return None;
+    }
+    Some((rounded.trailing_zeros() - SIZE_CLASSES[0].trailing_zeros()) as usize)
+}
+
+/// Pages needed for a large allocation of `layout`.
+fn large_pages(layout: Layout) -> usize {
+    layout.size().div_ceil(HEAP_PAGE_SIZE).max(1)
+}
+
+// ---------------------------------------------------------------------------
+// Heap
+// ---------------------------------------------------------------------------
+
+/// Free-list link stored in the first word of a free object.
+struct FreeObject {
+    next: *mut FreeObject,
+}
+
+/// Header stored at the start of a free page run.
+struct FreeRun {
+    pages: usize,
+    next: *mut FreeRun,
+}
+
+struct SizeClass {
+    free: *mut FreeObject,
+    stats: SizeClassStats,
+}
+
+/// An unsynchronised heap; see the [module docs](self).
+pub struct Heap<B> {
+    backend: B,
+    classes: [SizeClass; SIZE_CLASS_COUNT],
+    /// Free page runs, sorted by address, never adjacent.
+    runs: *mut FreeRun,
+    large_live: u64,
+    large_pages: u64,
+    pool_pages: u64,
+    free_pages: u64,
+}
+
+// SAFETY: the raw pointers refer to memory owned by the heap itself; moving
+// the heap to another thread moves that ownership with it.
+unsafe impl<B: Send> Send for Heap<B> {}
+
+impl<B: HeapBackend> Heap<B> {
+    /// Create an empty heap; no memory is requested until the first
+    /// allocation.
+    pub const fn new(backend: B) -> Self {
+        const EMPTY: SizeClass = SizeClass {
+            free: ptr::null_mut(),
+            stats: SizeClassStats {
+                size: 0,
+                pages: 0,
+                live: 0,
+                free: 0,
+                total_allocations: 0,
+            },
+        };
+        let mut classes = [EMPTY; SIZE_CLASS_COUNT];
+        let mut i = 0;
+        while i < SIZE_CLASS_COUNT {
+            classes[i].stats.size = SIZE_CLASSES[i];
+            i += 1;
+        }
+        Self {
+            backend,
+            classes,
+            runs: ptr::null_mut(),
+            large_live: 0,
+            large_pages: 0,
+            pool_pages: 0,
+            free_pages: 0,
+        }
+    }
+
+    /// The backend supplying pages.
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    /// Current counters.
+    pub fn stats(&self) -> HeapStats {
+        let mut classes = [SizeClassStats::default(); SIZE_CLASS_COUNT];
+        for (out, class) in classes.iter_mut().zip(&self.classes) {
+            *out = class.stats;
+        }
+        HeapStats {
+            classes,
+            large_live: self.large_live,
+            large_pages: self.large_pages,
+            pool_pages: self.pool_pages,
+            free_pages: self.free_pages,
+        }
+    }
+
+    /// Allocate memory for `layout`.
+    ///
+    /// Returns `None` when the backend cannot supply more pages.
+    pub fn allocate(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        match size_class_for(layout) {
+            Some(index) => self.allocate_small(index),
+            None => {
+                let pages = large_pages(layout);
+                let addr = self.allocate_pages(pages, layout.align().max(HEAP_PAGE_SIZE))?;
+                self.large_live += 1;
+                self.large_pages += pages as u64;
+                NonNull::new(addr as *mut u8)
+            }
+        }
+    }
+
+    /// Return memory obtained from [`allocate`](Self::allocate).
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been returned by `allocate` on this heap with the
+    /// same `layout`, and must not be used afterwards.
+    pub unsafe fn deallocate(&mut self, ptr: NonNull<u8>, layout: Layout) {
+        match size_class_for(layout) {
+            Some(index) => {
+                let object = ptr.as_ptr().cast::<FreeObject>();
+                let class = &mut self.classes[index];
+                // SAFETY: the object is at least 8 bytes, aligned to its
+                // class size and no longer in use (caller contract).
+                unsafe { object.write(FreeObject { next: class.free }) };
+                class.free = object;
+                class.stats.live -= 1;
+                class.stats.free += 1;
+            }
+            None => {
+                let pages = large_pages(layout);
+                // SAFETY: whole pages from the pool, now unused.
+                unsafe { self.release_pages(ptr.as_ptr() as usize, pages) };
+                self.large_live -= 1;
+                self.large_pages -= pages as u64;
+            }
+        }
+    }
+
+    fn allocate_small(&mut self, index: usize) -> Option<NonNull<u8>> {
+        if self.classes[index].free.is_null() {
+            self.refill(index)?;
+        }
+        let class = &mut self.classes[index];
+        let object = class.free;
+        // SAFETY: non-null entries on a free list are free objects whose
+        // first word holds the link written by `refill` or `deallocate`.
+        class.free = unsafe { (*object).next };
+        class.stats.free -= 1;
+        class.stats.live += 1;
+        class.stats.total_allocations += 1;
+        NonNull::new(object.cast::<u8>())
+    }
+
+    /// Carve a fresh page into objects for class `index`.
+    fn refill(&mut self, index: usize) -> Option<()> {
+        let page = self.allocate_pages(1, HEAP_PAGE_SIZE)?;
+        let size = SIZE_CLASSES[index];
+        let count = HEAP_PAGE_SIZE / size;
+        let class = &mut self.classes[index];
+        // Link back to front so the list hands out ascending addresses.
+        for i in (0..count).rev() {
+            let object = (page + i * size) as *mut FreeObject;
+            // SAFETY: the page belongs to the heap and is unused; objects
+            // are in bounds and aligned to `size` (>= 8).
+            unsafe { object.write(FreeObject { next: class.free }) };
+            class.free = object;
+        }
+        class.stats.pages += 1;
+        class.stats.free += count as u64;
+        Some(())
+    }
+
+    // -----------------------------------------------------------------------
+    // Page pool
+    // -----------------------------------------------------------------------
+
+    /// Take `pages` pages aligned to `align` from the pool, growing it if
+    /// needed.
+    fn allocate_pages(&mut self, pages: usize, align: usize) -> Option<usize> {
+        if let Some(addr) = self.take_run(pages, align) {
+            return Some(addr);
+        }
+        // Enough for the request even if the new pages start misaligned.
+        let slack = align / HEAP_PAGE_SIZE - 1;
+        let grow = pages.checked_add(slack)?.max(GROW_PAGES);
+        let base = self.backend.grow(grow)?.as_ptr() as usize;
+        self.pool_pages += grow as u64;
+        // SAFETY: the backend just handed these pages to the heap.
+        unsafe { self.release_pages(base, grow) };
+        self.take_run(pages, align)
+    }
+
+    /// First-fit search of the run list.
+    fn take_run(&mut self, pages: usize, align: usize) -> Option<usize> {
+        let len = pages.checked_mul(HEAP_PAGE_SIZE)?;
+        let mut prev: *mut FreeRun = ptr::null_mut();
+        let mut cur = self.runs;
+        while !cur.is_null() {
+            let start = cur as usize;
+            // SAFETY: every node on the list is a valid `FreeRun` header.
+            let (run_pages, next) = unsafe { ((*cur).pages, (*cur).next) };
+            let end = start + run_pages * HEAP_PAGE_SIZE;
+            let aligned = start.checked_next_multiple_of(align)?;
+            if aligned.checked_add(len).is_some_and(|e| e <= end) {
+                if prev.is_null() {
+                    self.runs = next;
+                } else {
+                    // SAFETY: `prev` is a valid node.
+                    unsafe { (*prev).next = next };
+                }
+                // SAFETY: the head and tail pieces are free pages that were
+                // part of the run just unlinked.
+                unsafe {
+                    if aligned > start {
+                        self.insert_run(start, (aligned - start) / HEAP_PAGE_SIZE);
+                    }
+                    if aligned + len < end {
+                        self.insert_run(aligned + len, (end - aligned - len) / HEAP_PAGE_SIZE);
+                    }
+                }
+                self.free_pages -= pages as u64;
+                return Some(aligned);
+            }
+            prev = cur;
+            cur = next;
+        }
+        None
+    }
+
+    /// Put `pages` pages starting at `addr` into the pool.
+    ///
+    /// # Safety
+    ///
+    /// The pages must belong to the heap and be unused.
+    unsafe fn release_pages(&mut self, addr: usize, pages: usize) {
+        // SAFETY: forwarded to the caller.
+        unsafe { self.insert_run(addr, pages) };
+        self.free_pages += pages as u64;
+    }
+
+    /// Link a run into the address-ordered list, merging with neighbours.
+    /// Does not touch the counters.
+    ///
+    /// # Safety
+    ///
+    /// As for [`release_pages`](Self::release_pages).
+    unsafe fn insert_run(&mut self, addr: usize, pages: usize) {
+        let mut prev: *mut FreeRun = ptr::null_mut();
+        let mut cur = self.runs;
+        // SAFETY (whole body): list nodes are valid headers at the start of
+        // free runs; `addr` is the start of `pages` free, writable pages.
+        unsafe {
+            while !cur.is_null() && (cur as usize) < addr {
+                prev = cur;
+                cur = (*cur).next;
+            }
+            let node = addr as *mut FreeRun;
+            node.write(FreeRun { pages, next: cur });
+            if !cur.is_null() && addr + pages * HEAP_PAGE_SIZE == cur as usize {
+                (*node).pages += (*cur).pages;
+                (*node).next = (*cur).next;
+            }
+            if prev.is_null() {
+                self.runs = node;
+            } else if prev as usize + (*prev).pages * HEAP_PAGE_SIZE == addr {
+                (*prev).pages += (*node).pages;
+                (*prev).next = (*node).next;
+            } else {
+                (*prev).next = node;
+            }
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// LockedHeap
+// ---------------------------------------------------------------------------
+
+/// A [`Heap`] behind a [`SpinLock`], usable as the `#[global_allocator]`.
+///
+/// Allocation fails (returns null) until [`init`](Self::init) has run.
+///
+/// The lock does not disable interrupts: allocating from an interrupt
+/// handler that may have interrupted an allocation deadlocks.
+pub struct LockedHeap<B> {
+    heap: SpinLock<Option<Heap<B>>>,
+}
+
+impl<B: HeapBackend> LockedHeap<B> {
+    /// An uninitialised heap, for use in a `static`.
+    pub const fn empty() -> Self {
+        Self {
+            heap: SpinLock::new(None),
+        }
+    }
+
+    /// Attach `backend`; allocations succeed from here on.
+    ///
+    /// # Errors
+    ///
+    /// [`HeapError::AlreadyInitialized`] on a second call.
+    pub fn init(&self, backend: B) -> Result<(), HeapError> {
+        let mut heap = self.heap.lock();
+        if heap.is_some() {
+            return Err(HeapError::AlreadyInitialized);
+        }
+        *heap = Some(Heap::new(backend));
+        Ok(())
+    }
+
+    /// Current counters, or `None` before [`init`](Self::init).
+    pub fn stats(&self) -> Option<HeapStats> {
+        self.heap.lock().as_ref().map(Heap::stats)
+    }
+}
+
+// SAFETY: `allocate` returns blocks of at least `layout.size()` bytes aligned
+// to `layout.align()` that are not handed out again until deallocated.
+unsafe impl<B: HeapBackend + Send> GlobalAlloc for LockedHeap<B> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.heap
+            .lock()
+            .as_mut()
+            .and_then(|heap| heap.allocate(layout))
+            .map_or(ptr::null_mut(), NonNull::as_ptr)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if let (Some(heap), Some(ptr)) = (self.heap.lock().as_mut(), NonNull::new(ptr)) {
+            // SAFETY: `GlobalAlloc` contract — `ptr` came from `alloc` with
+            // this `layout`.
+            unsafe { heap.deallocate(ptr, layout) };
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::alloc::{alloc, dealloc};
+    use std::vec::Vec;
+
+    /// Hands out consecutive pages from one host allocation.
+    struct ArenaBackend {
+        base: *mut u8,
+        next: usize,
+        capacity: usize,
+        grows: usize,
+    }
+
+    // SAFETY: test-only; the arena is owned by the backend.
+    unsafe impl Send for ArenaBackend {}
+
+    impl ArenaBackend {
+        fn layout(pages: usize) -> Layout {
+            Layout::from_size_align(pages * HEAP_PAGE_SIZE, HEAP_PAGE_SIZE).unwrap()
+        }
+
+        fn new(pages: usize) -> Self {
+            // SAFETY: non-zero size.
+            let base = unsafe { alloc(Self::layout(pages)) };
+            assert!(!base.is_null());
+            Self {
+                base,
+                next: 0,
+                capacity: pages,
+                grows: 0,
+            }
+        }
+
+        fn contains(&self, ptr: NonNull<u8>) -> bool {
+            let addr = ptr.as_ptr() as usize;
+            let base = self.base as usize;
+            addr >= base && addr < base + self.capacity * HEAP_PAGE_SIZE
+        }
+    }
+
+    impl Drop for ArenaBackend {
+        fn drop(&mut self) {
+            // SAFETY: allocated in `new` with the same layout.
+            unsafe { dealloc(self.base, Self::layout(self.capacity)) };
+        }
+    }
+
+    impl HeapBackend for ArenaBackend {
+        fn grow(&mut self, pages: usize) -> Option<NonNull<u8>> {
+            if self.next + pages > self.capacity {
+                return None;
+            }
+            // SAFETY: in bounds of the arena.
+            let ptr = unsafe { self.base.add(self.next * HEAP_PAGE_SIZE) };
+            self.next += pages;
+            self.grows += 1;
+            NonNull::new(ptr)
+        }
+    }
+
+    fn layout(size: usize, align: usize) -> Layout {
+        Layout::from_size_align(size, align).unwrap()
+    }
+
+    #[test]
+    fn size_classes_are_consistent() {
+        assert_eq!(SIZE_CLASSES.len(), SIZE_CLASS_COUNT);
+        for (i, &size) in SIZE_CLASSES.iter().enumerate() {
+            assert!(size.is_power_of_two());
+            assert_eq!(size_class_for(layout(size, 1)), Some(i));
+        }
+    }
+
+    #[test]
+    fn size_class_rounds_size_and_alignment() {
+        assert_eq!(size_class_for(layout(1, 1)), Some(0));
+        assert_eq!(size_class_for(layout(9, 8)), Some(1));
+        assert_eq!(size_class_for(layout(8, 64)), Some(3));
+        assert_eq!(size_class_for(layout(2049, 8)), None);
+        assert_eq!(size_class_for(layout(16, 4096)), None);
+    }
+
+    #[test]
+    fn small_allocations_are_aligned_and_distinct() {
+        let mut heap = Heap::new(ArenaBackend::new(64));
+        let mut seen = Vec::new();
+        for size in [1, 8, 24, 100, 700, 2048] {
+            for _ in 0..10 {
+                let l = layout(size, 8);
+                let p = heap.allocate(l).unwrap();
+                let class = SIZE_CLASSES[size_class_for(l).unwrap()];
+                assert_eq!(p.as_ptr() as usize % class, 0);
+                assert!(heap.backend().contains(p));
+                seen.push(p.as_ptr() as usize);
+            }
+        }
+        let total = seen.len();
+        seen.sort_unstable();
+        seen.dedup();
+        assert_eq!(seen.len(), total);
+    }
+
+    #[test]
+    fn small_free_reuses_object() {
+        let mut heap = Heap::new(ArenaBackend::new(32));
+        let l = layout(32, 8);
+        let a = heap.allocate(l).unwrap();
+        // SAFETY: `a` came from this heap with `l`.
+        unsafe { heap.deallocate(a, l) };
+        assert_eq!(heap.allocate(l), Some(a));
+    }
+
+    #[test]
+    fn per_class_stats_track_usage() {
+        let mut heap = Heap::new(ArenaBackend::new(32));
+        let l = layout(64, 8);
+        let ptrs: Vec<_> = (0..3).map(|_| heap.allocate(l).unwrap()).collect();
+        let stats = heap.stats();
+        let class = stats.classes[3];
+        assert_eq!(class.size, 64);
+        assert_eq!(class.pages, 1);
+        assert_eq!(class.live, 3);
+        assert_eq!(class.free, (HEAP_PAGE_SIZE / 64 - 3) as u64);
+        assert_eq!(class.total_allocations, 3);
+        assert_eq!(stats.live_bytes(), 3 * 64);
+
+        for p in ptrs {
+            // SAFETY: allocated above with `l`.
+            unsafe { heap.deallocate(p, l) };
+        }
+        let class = heap.stats().classes[3];
+        assert_eq!(class.live, 0);
+        assert_eq!(class.free, (HEAP_PAGE_SIZE / 64) as u64);
+        assert_eq!(class.total_allocations, 3);
+    }
+
+    #[test]
+    fn class_refills_with_a_new_page_when_exhausted() {
+        let mut heap = Heap::new(ArenaBackend::new(32));
+        let l = layout(2048, 8);
+        for _ in 0..3 {
+            heap.allocate(l).unwrap();
+        }
+        assert_eq!(heap.stats().classes[8].pages, 2);
+    }
+
+    #[test]
+    fn heap_grows_on_demand() {
+        let mut heap = Heap::new(ArenaBackend::new(64));
+        assert_eq!(heap.stats().pool_pages, 0);
+        heap.allocate(layout(8, 8)).unwrap();
+        let stats = heap.stats();
+        assert_eq!(stats.pool_pages, GROW_PAGES as u64);
+        assert_eq!(stats.free_pages, GROW_PAGES as u64 - 1);
+
+        // 20 pages exceed what is left; the heap asks for exactly that.
+        heap.allocate(layout(20 * HEAP_PAGE_SIZE, 8)).unwrap();
+        assert_eq!(heap.backend().grows, 2);
+        assert_eq!(heap.stats().pool_pages, (GROW_PAGES + 20) as u64);
+    }
+
+    #[test]
+    fn large_allocations_return_pages_and_coalesce() {
+        let mut heap = Heap::new(ArenaBackend::new(64));
+        let l = layout(3 * HEAP_PAGE_SIZE, 8);
+        let a = heap.allocate(l).unwrap();
+        let b = heap.allocate(l).unwrap();
+        let c = heap.allocate(l).unwrap();
+        assert_eq!(a.as_ptr() as usize % HEAP_PAGE_SIZE, 0);
+        let stats = heap.stats();
+        assert_eq!((stats.large_live, stats.large_pages), (3, 9));
+
+        // SAFETY: each came from this heap with `l`.
+        unsafe {
+            heap.deallocate(b, l);
+            heap.deallocate(a, l);
+            heap.deallocate(c, l);
+        }
+        let stats = heap.stats();
+        assert_eq!((stats.large_live, stats.large_pages), (0, 0));
+        assert_eq!(stats.free_pages, GROW_PAGES as u64);
+
+        // Merged back into one run: the whole pool fits without growing.
+        heap.allocate(layout(GROW_PAGES * HEAP_PAGE_SIZE, 8))
+            .unwrap();
+        assert_eq!(heap.backend().grows, 1);
+    }
+
+    #[test]
+    fn large_allocation_honours_alignment() {
+        let mut heap = Heap::new(ArenaBackend::new(128));
+        heap.allocate(layout(HEAP_PAGE_SIZE, 8)).unwrap();
+        let p = heap.allocate(layout(HEAP_PAGE_SIZE, 64 * 1024)).unwrap();
+        assert_eq!(p.as_ptr() as usize % (64 * 1024), 0);
+        let stats = heap.stats();
+        assert_eq!(stats.pool_pages - stats.free_pages, 2);
+    }
+
+    #[test]
+    fn allocation_fails_when_backend_is_exhausted() {
+        let mut heap = Heap::new(ArenaBackend::new(GROW_PAGES));
+        assert!(heap
+            .allocate(layout(GROW_PAGES * HEAP_PAGE_SIZE, 8))
+            .is_some());
+        assert!(heap.allocate(layout(8, 8)).is_none());
+    }
+
+    #[test]
+    fn locked_heap_requires_init() {
+        let heap = LockedHeap::<ArenaBackend>::empty();
+        let l = layout(16, 8);
+        // SAFETY: non-zero size.
+        assert!(unsafe { heap.alloc(l) }.is_null());
+        assert_eq!(heap.stats(), None);
+
+        heap.init(ArenaBackend::new(32)).unwrap();
+        assert_eq!(
+            heap.init(ArenaBackend::new(1)),
+            Err(HeapError::AlreadyInitialized)
+        );
+        // SAFETY: non-zero size; freed with the same layout.
+        unsafe {
+            let p = heap.alloc(l);
+            assert!(!p.is_null());
+            p.write_bytes(0xAB, 16);
+            heap.dealloc(p, l);
+        }
+        assert_eq!(heap.stats().unwrap().classes[1].total_allocations, 1);
+    }
+}
@@ -2,13 +2,52 @@
 //!
 //! Memory allocation primitives for Ferrous Kernel.
 //!
-//! This library provides allocator implementations for kernel use.
+//! This library provides allocator implementations for kernel use. Every
+//! allocator here is `no_std`, takes its backing storage from the caller,
+//! and is unit-tested on the host against simulated memory maps.
+//!
+//! # Modules
+//!
+//! - [`frame`] — bitmap allocator for 4 KiB physical frames, seeded from
+//!   [`ferrous_boot_info::MemoryMap::usable_regions()`].
+//! - [`buddy`] — binary buddy allocator for naturally aligned power-of-two
+//!   blocks (4 KiB – 4 MiB) with splitting, coalescing and fragmentation
+//!   statistics.
+//! - [`heap`] — kernel heap with power-of-two size classes over a
+//!   grow-on-demand page pool; [`LockedHeap`] implements
+//!   [`GlobalAlloc`](core::alloc::GlobalAlloc).
+//! - [`slab`] — typed [`SlabCache`]s for fixed-size objects with
+//!   constructor/destructor hooks, fed directly with pages (no heap needed),
+//!   and a [`SlabRegistry`] that dumps per-cache statistics.
 
 #![no_std]
-#![feature(allocator_api)]
 #![deny(unsafe_code)]
 #![warn(missing_docs)]
 
-extern crate alloc;
+pub mod buddy;
+pub mod frame;
+pub mod heap;
+pub mod slab;
+
+pub use buddy::{BuddyAllocator, BuddyError, BuddyStats, RegionSelection};
+pub use frame::{
+    FrameAllocError, FrameStats, PhysFrame, PhysRange, PhysicalFrameAllocator, ReclaimStats,
+};
+pub use heap::{Heap, HeapBackend, HeapError, HeapStats, LockedHeap, SizeClassStats};
+pub use slab::{
+    SlabCache, SlabCacheStats, SlabHooks, SlabPageSource, SlabRegistry, SlabRegistryError,
+};
+
+// ---------------------------------------------------------------------------
+// Tests
+//
+// `cargo test` links std for the test binary; `extern crate std` makes it
+// available to the per-module test suites, and `test_support` holds the
+// memory-map helpers they share.
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+extern crate std;
 
-// Allocator implementation will be added in Phase 1
+#[cfg(test)]
+mod test_support;
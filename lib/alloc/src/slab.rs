@@ -0,0 +1,834 @@
+//! Typed slab caches for fixed-size kernel objects.
+//!
+//! A [`SlabCache<T, S>`] hands out `T`-sized slots carved from *slabs*:
+//! naturally aligned runs of pages obtained from a [`SlabPageSource`] —
+//! in the kernel, straight from the physical frame allocator, so caches
+//! work without (and before) the global heap.
+//!
+//! # Layout
+//!
+//! ```text
+//! slab (pages_per_slab pages, aligned to its own size)
+//! ┌────────────┬──────────┬──────────┬─────┬──────────┬───────┐
+//! │ SlabHeader │ object 0 │ object 1 │ ... │ object n │ slack │
+//! └────────────┴──────────┴──────────┴─────┴──────────┴───────┘
+//! ```
+//!
+//! Because a slab is aligned to its size, [`SlabCache::free`] finds the
+//! header of an object by masking its address. Each slab sits on one of
+//! three doubly linked lists — *partial*, *full*, *empty* — and allocation
+//! prefers partial slabs so that memory stays packed. One empty slab is
+//! kept as a reserve; any further empty slab goes back to the source.
+//!
+//! # Hooks
+//!
+//! [`SlabHooks::constructor`] builds the value for [`SlabCache::alloc`];
+//! [`SlabHooks::destructor`] runs on an object just before
+//! [`SlabCache::free`] drops it. Both are plain function pointers so caches
+//! can be built in `const` context.
+//!
+//! # Registry
+//!
+//! [`SlabRegistry`] is a fixed-capacity list of per-cache statistics
+//! callbacks that can be written to any [`fmt::Write`] sink (the serial
+//! port in the kernel) with [`SlabRegistry::dump`].
+#![allow(unsafe_code)]
+
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::{align_of, size_of};
+use core::ptr::{self, NonNull};
+
+use crate::frame::FRAME_SIZE;
+
+/// Size of one slab page.
+pub const SLAB_PAGE_SIZE: usize = FRAME_SIZE as usize;
+
+/// A slab grows (in powers of two pages) until it holds at least this many
+/// objects.
+pub const MIN_OBJECTS_PER_SLAB: usize = 8;
+
+/// Largest slab, in pages.
+pub const MAX_PAGES_PER_SLAB: usize = 16;
+
+/// Maximum number of caches in a [`SlabRegistry`].
+pub const MAX_SLAB_CACHES: usize = 32;
+
+/// Supplier of slab memory.
+pub trait SlabPageSource {
+    /// Allocate `pages` contiguous, readable and writable pages, aligned to
+    /// `pages * SLAB_PAGE_SIZE`. `pages` is a power of two.
+    fn alloc_pages(&mut self, pages: usize) -> Option<NonNull<u8>>;
+
+    /// Return pages obtained from [`alloc_pages`](Self::alloc_pages).
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must come from `alloc_pages(pages)` on this source and must no
+    /// longer be used.
+    unsafe fn free_pages(&mut self, ptr: NonNull<u8>, pages: usize);
+}
+
+/// Object lifecycle hooks for a [`SlabCache`].
+pub struct SlabHooks<T> {
+    /// Produces the value stored by [`SlabCache::alloc`].
+    pub constructor: fn() -> T,
+    /// Called on an object just before [`SlabCache::free`] drops it.
+    pub destructor: Option<fn(&mut T)>,
+}
+
+/// Counters for one cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlabCacheStats {
+    /// Cache name.
+    pub name: &'static str,
+    /// Bytes per slot (object size rounded up to its alignment, at least 8).
+    pub object_size: usize,
+    /// Slots per slab.
+    pub objects_per_slab: usize,
+    /// Pages per slab.
+    pub pages_per_slab: usize,
+    /// Objects currently allocated.
+    pub live: u64,
+    /// Slabs currently held.
+    pub slabs: u64,
+    /// Pages currently held (`slabs * pages_per_slab`).
+    pub pages: u64,
+    /// Allocations served since the cache was created.
+    pub total_allocations: u64,
+}
+
+impl SlabCacheStats {
+    /// Slots allocated but not in use, across all slabs.
+    pub fn free_slots(&self) -> u64 {
+        self.slabs * self.objects_per_slab as u64 - self.live
+    }
+}
+
+impl fmt::Display for SlabCacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:<16} {:>5} B  live {:>6}  free {:>6}  slabs {:>4}  pages {:>5}  allocs {}",
+            self.name,
+            self.object_size,
+            self.live,
+            self.free_slots(),
+            self.slabs,
+            self.pages,
+            self.total_allocations
+        )
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Geometry
+// ---------------------------------------------------------------------------
+
+/// Placement of objects inside a slab.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Geometry {
+    stride: usize,
+    first_offset: usize,
+    capacity: usize,
+    pages: usize,
+}
+
+const fn round_up(value: usize, align: usize) -> usize {
+    (value + align - 1) & !(align - 1)
+}
+
+/// Compute the layout of a slab for objects of `size` bytes aligned to
+/// `align`. Panics if the object cannot fit [`MIN_OBJECTS_PER_SLAB`] times
+/// into [`MAX_PAGES_PER_SLAB`] pages.
+const fn geometry(size: usize, align: usize) -> Geometry {
+    let align = if align < 8 { 8 } else { align };
+    assert!(
+        align <= SLAB_PAGE_SIZE,
+        "slab object alignment exceeds a page"
+    );
+    let stride = round_up(if size < 8 { 8 } else { size }, align);
+    let first_offset = round_up(size_of::<SlabHeader>(), align);
+    let mut pages = 1;
+    while pages <= MAX_PAGES_PER_SLAB {
+        let capacity = (pages * SLAB_PAGE_SIZE - first_offset) / stride;
+        if capacity >= MIN_OBJECTS_PER_SLAB {
+            return Geometry {
+                stride,
+                first_offset,
+                capacity,
+                pages,
+            };
+        }
+        pages *= 2;
+    }
+    panic!("slab object too large");
+}
+
+// ---------------------------------------------------------------------------
+// Slabs
+// ---------------------------------------------------------------------------
+
+/// Free-slot link stored in the first word of an unused slot.
+struct FreeSlot {
+    next: *mut FreeSlot,
+}
+
+/// Header at the start of every slab.
+struct SlabHeader {
+    prev: *mut SlabHeader,
+    next: *mut SlabHeader,
+    free: *mut FreeSlot,
+    in_use: usize,
+}
+
+/// Head of an intrusive doubly linked slab list.
+struct SlabList {
+    head: *mut SlabHeader,
+}
+
+impl SlabList {
+    const fn new() -> Self {
+        Self {
+            head: ptr::null_mut(),
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `slab` is a valid header not on any list.
+    unsafe fn push(&mut self, slab: *mut SlabHeader) {
+        // SAFETY: `slab` and the current head are valid headers.
+        unsafe {
+            (*slab).prev = ptr::null_mut();
+            (*slab).next = self.head;
+            if !self.head.is_null() {
+                (*self.head).prev = slab;
+            }
+        }
+        self.head = slab;
+    }
+
+    /// # Safety
+    ///
+    /// `slab` is a valid header on this list.
+    unsafe fn remove(&mut self, slab: *mut SlabHeader) {
+        // SAFETY: `slab` and its neighbours are valid headers.
+        unsafe {
+            let (prev, next) = ((*slab).prev, (*slab).next);
+            if prev.is_null() {
+                self.head = next;
+            } else {
+                (*prev).next = next;
+            }
+            if !next.is_null() {
+                (*next).prev = prev;
+            }
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// SlabCache
+// ---------------------------------------------------------------------------
+
+/// A cache of `T` objects; see the [module docs](self).
+pub struct SlabCache<T, S: SlabPageSource> {
+    name: &'static str,
+    source: S,
+    hooks: SlabHooks<T>,
+    geometry: Geometry,
+    partial: SlabList,
+    full: SlabList,
+    empty: SlabList,
+    empty_slabs: u64,
+    slabs: u64,
+    live: u64,
+    total_allocations: u64,
+    _marker: PhantomData<T>,
+}
+
+// SAFETY: the cache owns its slabs and the `T`s in them; sending the cache
+// sends those objects.
+unsafe impl<T: Send, S: SlabPageSource + Send> Send for SlabCache<T, S> {}
+
+impl<T: Default, S: SlabPageSource> SlabCache<T, S> {
+    /// A cache whose constructor is `T::default` and which has no
+    /// destructor hook.
+    pub const fn new(name: &'static str, source: S) -> Self {
+        Self::with_hooks(
+            name,
+            source,
+            SlabHooks {
+                constructor: T::default,
+                destructor: None,
+            },
+        )
+    }
+}
+
+impl<T, S: SlabPageSource> SlabCache<T, S> {
+    /// A cache with explicit hooks. No memory is requested until the first
+    /// allocation.
+    ///
+    /// # Panics
+    ///
+    /// If `T` is aligned to more than a page, or too large for
+    /// [`MIN_OBJECTS_PER_SLAB`] objects to fit in [`MAX_PAGES_PER_SLAB`]
+    /// pages (in `const` context this is a compile error).
+    pub const fn with_hooks(name: &'static str, source: S, hooks: SlabHooks<T>) -> Self {
+        Self {
+            name,
+            source,
+            hooks,
+            geometry: geometry(size_of::<T>(), align_of::<T>()),
+            partial: SlabList::new(),
+            full: SlabList::new(),
+            empty: SlabList::new(),
+            empty_slabs: 0,
+            slabs: 0,
+            live: 0,
+            total_allocations: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Cache name.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Current counters.
+    pub fn stats(&self) -> SlabCacheStats {
+        SlabCacheStats {
+            name: self.name,
+            object_size: self.geometry.stride,
+            objects_per_slab: self.geometry.capacity,
+            pages_per_slab: self.geometry.pages,
+            live: self.live,
+            slabs: self.slabs,
+            pages: self.slabs * self.geometry.pages as u64,
+            total_allocations: self.total_allocations,
+        }
+    }
+
+    /// Allocate an object built by the constructor hook.
+    ///
+    /// Returns `None` when the page source is exhausted.
+    pub fn alloc(&mut self) -> Option<NonNull<T>> {
+        let value = (self.hooks.constructor)();
+        self.alloc_with(value)
+    }
+
+    /// Allocate an object holding `value`, bypassing the constructor hook.
+    ///
+    /// Returns `None` (dropping `value`) when the page source is exhausted.
+    pub fn alloc_with(&mut self, value: T) -> Option<NonNull<T>> {
+        let slot = self.alloc_slot()?.cast::<T>();
+        // SAFETY: the slot is unused, in bounds and aligned for `T`.
+        unsafe { slot.as_ptr().write(value) };
+        Some(slot)
+    }
+
+    /// Run the destructor hook, drop the object and return its slot.
+    ///
+    /// # Safety
+    ///
+    /// `object` must come from [`alloc`](Self::alloc) or
+    /// [`alloc_with`](Self::alloc_with) on this cache and must not be used
+    /// afterwards.
+    pub unsafe fn free(&mut self, object: NonNull<T>) {
+        // SAFETY: caller contract — a live, initialised object of this cache.
+        unsafe {
+            if let Some(destructor) = self.hooks.destructor {
+                destructor(&mut *object.as_ptr());
+            }
+            ptr::drop_in_place(object.as_ptr());
+            self.free_slot(object.cast::<u8>());
+        }
+    }
+
+    /// Return every empty slab, including the reserve, to the page source.
+    /// Returns the number of pages released.
+    pub fn shrink(&mut self) -> usize {
+        let mut released = 0;
+        while !self.empty.head.is_null() {
+            let slab = self.empty.head;
+            // SAFETY: `slab` heads the empty list and holds no objects.
+            unsafe { self.release_slab(slab) };
+            released += self.geometry.pages;
+        }
+        released
+    }
+
+    fn slab_bytes(&self) -> usize {
+        self.geometry.pages * SLAB_PAGE_SIZE
+    }
+
+    fn alloc_slot(&mut self) -> Option<NonNull<u8>> {
+        let slab = if !self.partial.head.is_null() {
+            self.partial.head
+        } else {
+            if self.empty.head.is_null() {
+                self.grow()?;
+            }
+            let slab = self.empty.head;
+            // SAFETY: `slab` heads the empty list.
+            unsafe {
+                self.empty.remove(slab);
+                self.partial.push(slab);
+            }
+            self.empty_slabs -= 1;
+            slab
+        };
+
+        // SAFETY: `slab` is a valid partial slab, so its free list is
+        // non-empty and every link points at an unused slot.
+        let slot = unsafe {
+            let slot = (*slab).free;
+            (*slab).free = (*slot).next;
+            (*slab).in_use += 1;
+            if (*slab).free.is_null() {
+                self.partial.remove(slab);
+                self.full.push(slab);
+            }
+            slot
+        };
+        self.live += 1;
+        self.total_allocations += 1;
+        NonNull::new(slot.cast::<u8>())
+    }
+
+    /// # Safety
+    ///
+    /// `slot` is an allocated, now unused slot of this cache.
+    unsafe fn free_slot(&mut self, slot: NonNull<u8>) {
+        let addr = slot.as_ptr() as usize;
+        let slab = (addr & !(self.slab_bytes() - 1)) as *mut SlabHeader;
+        debug_assert_eq!(
+            (addr - slab as usize - self.geometry.first_offset) % self.geometry.stride,
+            0,
+            "pointer is not a slot start"
+        );
+        // SAFETY: slabs are aligned to their size, so masking yields the
+        // header of the slab holding `slot`.
+        unsafe {
+            let was_full = (*slab).free.is_null();
+            let link = slot.as_ptr().cast::<FreeSlot>();
+            link.write(FreeSlot { next: (*slab).free });
+            (*slab).free = link;
+            (*slab).in_use -= 1;
+
+            if (*slab).in_use == 0 {
+                if was_full {
+                    // Only possible with one object per slab.
+                    self.full.remove(slab);
+                } else {
+                    self.partial.remove(slab);
+                }
+                if self.empty_slabs >= 1 {
+                    self.release_slab_unlinked(slab);
+                } else {
+                    self.empty.push(slab);
+                    self.empty_slabs += 1;
+                }
+            } else if was_full {
+                self.full.remove(slab);
+                self.partial.push(slab);
+            }
+        }
+        self.live -= 1;
+    }
+
+    /// Obtain a new slab, thread its slots and put it on the empty list.
+    fn grow(&mut self) -> Option<()> {
+        let base = self.source.alloc_pages(self.geometry.pages)?.as_ptr();
+        debug_assert_eq!(base as usize & (self.slab_bytes() - 1), 0);
+        let slab = base.cast::<SlabHeader>();
+        let mut free: *mut FreeSlot = ptr::null_mut();
+        // SAFETY: the source handed us `slab_bytes` fresh, aligned bytes;
+        // the header and every slot lie within them.
+        unsafe {
+            for i in (0..self.geometry.capacity).rev() {
+                let slot = base
+                    .add(self.geometry.first_offset + i * self.geometry.stride)
+                    .cast::<FreeSlot>();
+                slot.write(FreeSlot { next: free });
+                free = slot;
+            }
+            slab.write(SlabHeader {
+                prev: ptr::null_mut(),
+                next: ptr::null_mut(),
+                free,
+                in_use: 0,
+            });
+            self.empty.push(slab);
+        }
+        self.empty_slabs += 1;
+        self.slabs += 1;
+        Some(())
+    }
+
+    /// # Safety
+    ///
+    /// `slab` is on the empty list.
+    unsafe fn release_slab(&mut self, slab: *mut SlabHeader) {
+        // SAFETY: forwarded to the caller.
+        unsafe {
+            self.empty.remove(slab);
+            self.release_slab_unlinked(slab);
+        }
+        self.empty_slabs -= 1;
+    }
+
+    /// # Safety
+    ///
+    /// `slab` holds no objects and is on no list.
+    unsafe fn release_slab_unlinked(&mut self, slab: *mut SlabHeader) {
+        if let Some(ptr) = NonNull::new(slab.cast::<u8>()) {
+            // SAFETY: the slab came from `alloc_pages(geometry.pages)`.
+            unsafe { self.source.free_pages(ptr, self.geometry.pages) };
+        }
+        self.slabs -= 1;
+    }
+}
+
+impl<T, S: SlabPageSource> Drop for SlabCache<T, S> {
+    /// Releases empty slabs. Slabs with live objects are leaked: the objects
+    /// may still be referenced.
+    fn drop(&mut self) {
+        self.shrink();
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Registry
+// ---------------------------------------------------------------------------
+
+/// Callback returning the current statistics of one cache.
+pub type SlabStatsFn = fn() -> SlabCacheStats;
+
+/// Errors returned by [`SlabRegistry::register`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlabRegistryError {
+    /// [`MAX_SLAB_CACHES`] caches are already registered.
+    Full,
+}
+
+/// Fixed-capacity list of caches whose statistics can be dumped.
+pub struct SlabRegistry {
+    entries: [Option<SlabStatsFn>; MAX_SLAB_CACHES],
+    len: usize,
+}
+
+impl SlabRegistry {
+    /// An empty registry.
+    pub const fn new() -> Self {
+        Self {
+            entries: [None; MAX_SLAB_CACHES],
+            len: 0,
+        }
+    }
+
+    /// Add a cache.
+    ///
+    /// # Errors
+    ///
+    /// [`SlabRegistryError::Full`] when no slot is left.
+    pub fn register(&mut self, stats: SlabStatsFn) -> Result<(), SlabRegistryError> {
+        let slot = self
+            .entries
+            .get_mut(self.len)
+            .ok_or(SlabRegistryError::Full)?;
+        *slot = Some(stats);
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Number of registered caches.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// True if no cache is registered.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Current statistics of every registered cache, in registration order.
+    pub fn stats(&self) -> impl Iterator<Item = SlabCacheStats> + '_ {
+        self.entries[..self.len].iter().flatten().map(|f| f())
+    }
+
+    /// Write a table of all caches to `out`, one line per cache.
+    ///
+    /// # Errors
+    ///
+    /// Propagates errors from `out`.
+    pub fn dump(&self, out: &mut impl fmt::Write) -> fmt::Result {
+        writeln!(out, "slab caches ({}):", self.len)?;
+        for stats in self.stats() {
+            writeln!(out, "  {stats}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for SlabRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::alloc::Layout;
+    use core::cell::Cell;
+    use std::alloc::{alloc, dealloc};
+    use std::string::String;
+    use std::vec::Vec;
+
+    /// Host page source counting outstanding pages.
+    #[derive(Default)]
+    struct HostPages {
+        outstanding: usize,
+    }
+
+    impl HostPages {
+        fn layout(pages: usize) -> Layout {
+            let bytes = pages * SLAB_PAGE_SIZE;
+            Layout::from_size_align(bytes, bytes).unwrap()
+        }
+    }
+
+    impl SlabPageSource for HostPages {
+        fn alloc_pages(&mut self, pages: usize) -> Option<NonNull<u8>> {
+            self.outstanding += pages;
+            // SAFETY: non-zero size.
+            NonNull::new(unsafe { alloc(Self::layout(pages)) })
+        }
+
+        unsafe fn free_pages(&mut self, ptr: NonNull<u8>, pages: usize) {
+            self.outstanding -= pages;
+            // SAFETY: allocated in `alloc_pages` with the same layout.
+            unsafe { dealloc(ptr.as_ptr(), Self::layout(pages)) };
+        }
+    }
+
+    #[derive(Default)]
+    struct Small {
+        a: u32,
+    }
+
+    #[repr(align(64))]
+    struct Aligned {
+        _line: [u8; 64],
+    }
+
+    #[test]
+    fn geometry_small_objects_fit_one_page() {
+        let g = geometry(4, 4);
+        assert_eq!(g.stride, 8);
+        assert_eq!(g.pages, 1);
+        assert_eq!(g.first_offset, round_up(size_of::<SlabHeader>(), 8));
+        assert_eq!(g.capacity, (SLAB_PAGE_SIZE - g.first_offset) / 8);
+    }
+
+    #[test]
+    fn geometry_large_objects_use_more_pages() {
+        let g = geometry(1024, 8);
+        assert!(g.capacity >= MIN_OBJECTS_PER_SLAB);
+        assert_eq!(g.pages, 4);
+        let g = geometry(64, 64);
+        assert_eq!(g.first_offset, 64);
+        assert_eq!(g.stride, 64);
+    }
+
+    #[test]
+    fn alloc_runs_constructor_and_aligns() {
+        let mut cache: SlabCache<Aligned, _> = SlabCache::with_hooks(
+            "aligned",
+            HostPages::default(),
+            SlabHooks {
+                constructor: || Aligned { _line: [7; 64] },
+                destructor: None,
+            },
+        );
+        let p = cache.alloc().unwrap();
+        assert_eq!(p.as_ptr() as usize % 64, 0);
+        // SAFETY: freshly allocated and initialised.
+        assert_eq!(unsafe { p.as_ref() }._line[63], 7);
+        // SAFETY: from this cache.
+        unsafe { cache.free(p) };
+    }
+
+    #[test]
+    fn default_constructor_and_alloc_with() {
+        let mut cache: SlabCache<Small, _> = SlabCache::new("small", HostPages::default());
+        let a = cache.alloc().unwrap();
+        let b = cache.alloc_with(Small { a: 42 }).unwrap();
+        // SAFETY: both live.
+        unsafe {
+            assert_eq!(a.as_ref().a, 0);
+            assert_eq!(b.as_ref().a, 42);
+            cache.free(a);
+            cache.free(b);
+        }
+    }
+
+    std::thread_local! {
+        static DESTROYED: Cell<u32> = const { Cell::new(0) };
+    }
+
+    #[test]
+    fn destructor_hook_and_drop_run_on_free() {
+        DESTROYED.with(|d| d.set(0));
+        let mut cache: SlabCache<Vec<u8>, _> = SlabCache::with_hooks(
+            "vecs",
+            HostPages::default(),
+            SlabHooks {
+                constructor: || Vec::with_capacity(16),
+                destructor: Some(|v| {
+                    assert_eq!(v.capacity(), 16);
+                    DESTROYED.with(|d| d.set(d.get() + 1));
+                }),
+            },
+        );
+        let p = cache.alloc().unwrap();
+        // SAFETY: from this cache; the Vec's buffer is freed by drop_in_place.
+        unsafe { cache.free(p) };
+        assert_eq!(DESTROYED.with(Cell::get), 1);
+    }
+
+    #[test]
+    fn free_slot_is_reused() {
+        let mut cache: SlabCache<u64, _> = SlabCache::new("u64", HostPages::default());
+        let a = cache.alloc().unwrap();
+        // SAFETY: from this cache.
+        unsafe { cache.free(a) };
+        assert_eq!(cache.alloc(), Some(a));
+    }
+
+    #[test]
+    fn stats_track_live_objects_slabs_and_pages() {
+        let mut cache: SlabCache<[u8; 1024], _> = SlabCache::with_hooks(
+            "kib",
+            HostPages::default(),
+            SlabHooks {
+                constructor: || [0; 1024],
+                destructor: None,
+            },
+        );
+        let per_slab = cache.stats().objects_per_slab;
+        let objects: Vec<_> = (0..per_slab + 1).map(|_| cache.alloc().unwrap()).collect();
+        let stats = cache.stats();
+        assert_eq!(stats.live, per_slab as u64 + 1);
+        assert_eq!(stats.slabs, 2);
+        assert_eq!(stats.pages, 2 * stats.pages_per_slab as u64);
+        assert_eq!(stats.free_slots(), per_slab as u64 - 1);
+        assert_eq!(stats.total_allocations, per_slab as u64 + 1);
+
+        for p in objects {
+            // SAFETY: from this cache.
+            unsafe { cache.free(p) };
+        }
+        let stats = cache.stats();
+        assert_eq!(stats.live, 0);
+        // One empty slab is kept in reserve, the other went back.
+        assert_eq!(stats.slabs, 1);
+        assert_eq!(cache.source.outstanding, stats.pages_per_slab);
+    }
+
+    #[test]
+    fn shrink_and_drop_return_all_pages() {
+        let mut cache: SlabCache<u64, _> = SlabCache::new("u64", HostPages::default());
+        let p = cache.alloc().unwrap();
+        // SAFETY: from this cache.
+        unsafe { cache.free(p) };
+        assert_eq!(cache.source.outstanding, 1);
+        assert_eq!(cache.shrink(), 1);
+        assert_eq!(cache.source.outstanding, 0);
+        assert_eq!(cache.stats().slabs, 0);
+
+        // Allocation still works after shrinking.
+        let p = cache.alloc().unwrap();
+        // SAFETY: from this cache.
+        unsafe { cache.free(p) };
+    }
+
+    #[test]
+    fn allocations_are_distinct_across_slabs() {
+        let mut cache: SlabCache<u64, _> = SlabCache::new("u64", HostPages::default());
+        let n = cache.stats().objects_per_slab * 3;
+        let mut addrs: Vec<_> = (0..n)
+            .map(|i| {
+                let p = cache.alloc_with(i as u64).unwrap();
+                p.as_ptr() as usize
+            })
+            .collect();
+        addrs.sort_unstable();
+        addrs.dedup();
+        assert_eq!(addrs.len(), n);
+        assert_eq!(cache.stats().slabs, 3);
+    }
+
+    #[test]
+    fn exhausted_source_fails_allocation() {
+        struct NoPages;
+        impl SlabPageSource for NoPages {
+            fn alloc_pages(&mut self, _: usize) -> Option<NonNull<u8>> {
+                None
+            }
+            unsafe fn free_pages(&mut self, _: NonNull<u8>, _: usize) {}
+        }
+        let mut cache: SlabCache<u64, _> = SlabCache::new("none", NoPages);
+        assert_eq!(cache.alloc(), None);
+        assert_eq!(cache.stats().slabs, 0);
+    }
+
+    fn fake_stats() -> SlabCacheStats {
+        SlabCacheStats {
+            name: "task",
+            object_size: 256,
+            objects_per_slab: 15,
+            pages_per_slab: 1,
+            live: 3,
+            slabs: 1,
+            pages: 1,
+            total_allocations: 9,
+        }
+    }
+
+    #[test]
+    fn registry_dump_lists_every_cache() {
+        let mut registry = SlabRegistry::new();
+        assert!(registry.is_empty());
+        registry.register(fake_stats).unwrap();
+        registry.register(fake_stats).unwrap();
+        assert_eq!(registry.len(), 2);
+
+        let mut out = String::new();
+        registry.dump(&mut out).unwrap();
+        let lines: Vec<_> = out.lines().collect();
+        assert_eq!(lines[0], "slab caches (2):");
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].trim_start().starts_with("task"));
+        assert!(lines[1].contains("256 B"));
+        assert!(lines[1].contains("live      3"));
+        assert!(lines[1].contains("free     12"));
+    }
+
+    #[test]
+    fn registry_rejects_overflow() {
+        let mut registry = SlabRegistry::new();
+        for _ in 0..MAX_SLAB_CACHES {
+            registry.register(fake_stats).unwrap();
+        }
+        assert_eq!(registry.register(fake_stats), Err(SlabRegistryError::Full));
+    }
+}
@@ -0,0 +1,23 @@
+//! Helpers shared by the host test suites: building a [`MemoryMap`] from
+//! a list of descriptors.
+
+use ferrous_boot_info::{KernelMemoryDescriptor, KernelMemoryMap, MemoryMap};
+
+pub const MIB: u64 = 1024 * 1024;
+
+pub fn desc(ty: u32, phys_start: u64, page_count: u64) -> KernelMemoryDescriptor {
+    KernelMemoryDescriptor {
+        ty,
+        _pad: 0,
+        phys_start,
+        page_count,
+        attribute: 0,
+    }
+}
+
+pub fn make_map(descs: &[KernelMemoryDescriptor]) -> MemoryMap {
+    let mut raw = KernelMemoryMap::new();
+    raw.descriptors[..descs.len()].copy_from_slice(descs);
+    raw.count = descs.len();
+    MemoryMap::parse(&raw).expect("test map must parse")
+}
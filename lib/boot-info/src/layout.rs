@@ -0,0 +1,111 @@
+//! Kernel virtual address space layout.
+//!
+//! The bootloader builds the page tables the kernel starts on, so the
+//! layout is part of the handoff contract:
+//!
+//! | Range | PML4 slots | Contents |
+//! |-------|------------|----------|
+//! | `0` – [`USER_SPACE_END`] | 0–255 | Identity map of physical memory during the handoff, removed by the kernel; later user space |
+//! | [`DIRECT_MAP_BASE`] – `+ DIRECT_MAP_MAX_SIZE` | 256–383 | All physical memory, linearly ([`phys_to_virt`]) |
+//! | `0xFFFF_C000_0000_0000` – | 384–510 | Kernel heap and other on-demand mappings |
+//! | [`KERNEL_VIRT_BASE`] – `0xFFFF_FFFF_FFFF_FFFF` | 511 | Kernel image (top 2 GiB, code model `kernel`) |
+//!
+//! The kernel is linked at [`KERNEL_VIRT_BASE`] plus its physical load
+//! address (`kernel/linker.ld`).
+
+/// End of the lower canonical half, which belongs to user space once the
+/// identity mapping is gone.
+pub const USER_SPACE_END: u64 = 0x0000_8000_0000_0000;
+
+/// Start of the direct map: physical address `p` is mapped at
+/// `DIRECT_MAP_BASE + p`. First address of the upper canonical half.
+pub const DIRECT_MAP_BASE: u64 = 0xFFFF_8000_0000_0000;
+
+/// Most physical memory the direct map can cover (64 TiB, PML4 slots
+/// 256–383).
+pub const DIRECT_MAP_MAX_SIZE: u64 = 1 << 46;
+
+/// Base of the kernel image region: the top 2 GiB of the address space,
+/// reachable with 32-bit sign-extended displacements.
+pub const KERNEL_VIRT_BASE: u64 = 0xFFFF_FFFF_8000_0000;
+
+/// Virtual address of physical address `phys` in the direct map.
+///
+/// Only meaningful for `phys < DIRECT_MAP_MAX_SIZE`, and only mapped for
+/// the physical range the bootloader covered.
+pub const fn phys_to_virt(phys: u64) -> u64 {
+    DIRECT_MAP_BASE + phys
+}
+
+/// Physical address behind `virt` if it lies in the direct map.
+pub const fn direct_map_to_phys(virt: u64) -> Option<u64> {
+    if virt >= DIRECT_MAP_BASE && virt - DIRECT_MAP_BASE < DIRECT_MAP_MAX_SIZE {
+        Some(virt - DIRECT_MAP_BASE)
+    } else {
+        None
+    }
+}
+
+/// True if `virt` is in the upper canonical half (kernel space).
+pub const fn is_higher_half(virt: u64) -> bool {
+    virt >= DIRECT_MAP_BASE
+}
+
+/// True if the `len` bytes at `addr` lie entirely in user space. An empty
+/// range is accepted anywhere below [`USER_SPACE_END`].
+pub const fn is_user_range(addr: u64, len: u64) -> bool {
+    match addr.checked_add(len) {
+        Some(end) => addr < USER_SPACE_END && end <= USER_SPACE_END,
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bytes covered by one PML4 entry.
+    const PML4_SLOT: u64 = 1 << 39;
+
+    #[test]
+    fn regions_are_slot_aligned_and_disjoint() {
+        assert_eq!(DIRECT_MAP_BASE % PML4_SLOT, 0);
+        assert_eq!(DIRECT_MAP_MAX_SIZE % PML4_SLOT, 0);
+        const { assert!(DIRECT_MAP_BASE + DIRECT_MAP_MAX_SIZE <= 0xFFFF_C000_0000_0000) };
+        assert_eq!(KERNEL_VIRT_BASE / PML4_SLOT % 512, 511);
+        assert_eq!(KERNEL_VIRT_BASE.wrapping_neg(), 2 << 30);
+    }
+
+    #[test]
+    fn direct_map_round_trips() {
+        assert_eq!(phys_to_virt(0x20_0000), 0xFFFF_8000_0020_0000);
+        assert_eq!(
+            direct_map_to_phys(phys_to_virt(0x1234_5678)),
+            Some(0x1234_5678)
+        );
+        assert_eq!(direct_map_to_phys(0x20_0000), None);
+        assert_eq!(direct_map_to_phys(KERNEL_VIRT_BASE), None);
+        assert_eq!(
+            direct_map_to_phys(DIRECT_MAP_BASE + DIRECT_MAP_MAX_SIZE - 1),
+            Some(DIRECT_MAP_MAX_SIZE - 1)
+        );
+    }
+
+    #[test]
+    fn user_range_stays_in_lower_half() {
+        assert!(is_user_range(0x40_0000, 0x1000));
+        assert!(is_user_range(USER_SPACE_END - 8, 8));
+        assert!(is_user_range(0, 0));
+        assert!(!is_user_range(USER_SPACE_END - 8, 9));
+        assert!(!is_user_range(USER_SPACE_END, 0));
+        assert!(!is_user_range(DIRECT_MAP_BASE, 8));
+        assert!(!is_user_range(u64::MAX, 2));
+    }
+
+    #[test]
+    fn higher_half() {
+        assert!(is_higher_half(KERNEL_VIRT_BASE));
+        assert!(is_higher_half(DIRECT_MAP_BASE));
+        assert!(!is_higher_half(0x0000_7FFF_FFFF_F000));
+    }
+}
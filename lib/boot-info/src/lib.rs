@@ -7,24 +7,72 @@
 //! All types are `#[repr(C)]` with fixed-size arrays — no heap, no Vec,
 //! no UEFI dependency. This ensures the layout is stable across separately
 //! compiled crates and remains valid after boot services have exited.
+//!
+//! # Versions
+//!
+//! | Version | Layout |
+//! |---------|--------|
+//! | 1 | Memory map limited to [`MAX_MEMORY_DESCRIPTORS`] inline entries. |
+//! | 2 | Appends [`KernelBootInfo::memory_map_ext`]: the complete map in a bootloader-allocated LOADER_DATA buffer. |
+//! | 3 | Appends [`KernelBootInfo::tags_size`]: a [`tag`] list directly follows the struct. |
+//!
+//! Fields are only ever appended, so a v2 struct starts with a valid v1
+//! struct. The kernel accepts every version from [`BOOT_INFO_VERSION_MIN`]
+//! to [`BOOT_INFO_VERSION`] and must not read a field newer than the
+//! version it was handed.
+//!
+//! Version 3 freezes the header: new handoff data is added as a new tag
+//! type, not as a field.
 
 #![no_std]
 
+pub mod layout;
+pub mod tag;
+
+pub use tag::{tag_type, Module, ModuleIter, Tag, TagBuilder, TagError, TagIter, TagList};
+
 /// Magic sentinel stored in `KernelBootInfo.magic`.
 ///
 /// The kernel checks this at entry to detect stale or corrupt pointers.
 pub const BOOT_INFO_MAGIC: u64 = 0xFE220B00_CAFE0001;
 
 /// ABI version. Increment when the layout of `KernelBootInfo` changes.
-pub const BOOT_INFO_VERSION: u32 = 1;
+pub const BOOT_INFO_VERSION: u32 = 3;
+
+/// Oldest `KernelBootInfo` version the kernel still accepts.
+pub const BOOT_INFO_VERSION_MIN: u32 = 1;
+
+/// Size in bytes of a version 1 `KernelBootInfo`.
+pub const BOOT_INFO_SIZE_V1: usize = core::mem::offset_of!(KernelBootInfo, memory_map_ext);
+
+/// Size in bytes of a version 2 `KernelBootInfo`.
+pub const BOOT_INFO_SIZE_V2: usize = core::mem::offset_of!(KernelBootInfo, tags_size);
 
 /// Maximum number of UEFI memory descriptors stored in `KernelMemoryMap`.
 ///
 /// OVMF (QEMU) typically produces 20–40 entries. Real hardware rarely
-/// exceeds 128. 256 is a safe upper bound; if firmware exceeds this the
-/// kernel will detect truncation and halt.
+/// exceeds 128, but large servers can. Since version 2 the complete map
+/// travels in [`KernelBootInfo::memory_map_ext`]; the inline copy keeps the
+/// first 256 entries for version 1 consumers.
 pub const MAX_MEMORY_DESCRIPTORS: usize = 256;
 
+/// Capacity of a normalised [`MemoryMap`].
+///
+/// Larger than [`MAX_MEMORY_DESCRIPTORS`] so that an extended (version 2)
+/// map and the splits made by overlap resolution and carve-outs fit. A map
+/// that still needs more keeps its lowest regions and reports the rest in
+/// [`MemoryStats::dropped_bytes`] rather than failing.
+pub const MAX_MEMORY_REGIONS: usize = 1024;
+
+/// Longest kernel command line the bootloader passes on, in bytes.
+pub const MAX_CMDLINE_LEN: usize = 4096;
+
+/// Most boot modules the bootloader loads; further entries are skipped.
+pub const MAX_MODULES: usize = 8;
+
+/// Longest boot module name, in bytes.
+pub const MAX_MODULE_NAME_LEN: usize = 64;
+
 /// Pixel format codes stored in `KernelFramebuffer.pixel_format`.
 pub mod pixel_format {
     pub const RGB: u32 = 0;
@@ -49,6 +97,9 @@ pub mod memory_type {
     pub const MMIO: u32 = 11;
     pub const MMIO_PORT_SPACE: u32 = 12;
     pub const PERSISTENT_MEMORY: u32 = 14;
+    /// Kernel-owned memory carved out with `MemoryMap::carve_out` (first
+    /// type of the range UEFI leaves to OS loaders, 0x8000_0000 and up).
+    pub const KERNEL_RESERVED: u32 = 0x8000_0000;
 }
 
 /// A single UEFI memory descriptor, mirrored for the kernel.
@@ -124,6 +175,36 @@ impl KernelMemoryMap {
     }
 }
 
+/// Location of the complete memory map outside `KernelBootInfo` (version 2).
+///
+/// The descriptors live in a LOADER_DATA buffer allocated by the bootloader;
+/// the kernel reads them through the direct map before reclaiming loader
+/// memory.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct ExtendedMemoryMap {
+    /// Physical address of the first [`KernelMemoryDescriptor`], or 0 if
+    /// the bootloader did not provide an extended map.
+    pub phys_addr: u64,
+    /// Number of descriptors at `phys_addr`.
+    pub count: u64,
+}
+
+impl ExtendedMemoryMap {
+    /// No extended map.
+    pub const fn none() -> Self {
+        Self {
+            phys_addr: 0,
+            count: 0,
+        }
+    }
+
+    /// True if a non-empty map is present.
+    pub const fn is_present(&self) -> bool {
+        self.phys_addr != 0 && self.count != 0
+    }
+}
+
 /// Framebuffer information from UEFI GOP, or zeroed if unavailable.
 #[derive(Debug, Clone, Copy)]
 #[repr(C)]
@@ -187,6 +268,19 @@ pub struct KernelBootInfo {
 
     /// Null-terminated bootloader name string (ASCII).
     pub bootloader_name: [u8; 32],
+
+    // --- version 2 ---
+    /// Complete memory map, not limited to [`MAX_MEMORY_DESCRIPTORS`]
+    /// entries. Only valid if `version >= 2`; use
+    /// [`memory_descriptors`](Self::memory_descriptors) rather than reading
+    /// it directly.
+    pub memory_map_ext: ExtendedMemoryMap,
+
+    // --- version 3 ---
+    /// Size in bytes of the tag list that directly follows this struct,
+    /// end tag included; 0 if there is none. Only valid if `version >= 3`;
+    /// use [`tags`](Self::tags) rather than reading it directly.
+    pub tags_size: u64,
 }
 
 impl KernelBootInfo {
@@ -205,14 +299,147 @@ impl KernelBootInfo {
             has_framebuffer: false,
             _pad2: [0; 7],
             bootloader_name: *b"ferrous-boot\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0",
+            memory_map_ext: ExtendedMemoryMap::none(),
+            tags_size: 0,
         }
     }
 
-    /// Returns true if this `KernelBootInfo` has a valid magic and version.
+    /// Returns true if this `KernelBootInfo` has a valid magic and a
+    /// supported version ([`BOOT_INFO_VERSION_MIN`]..=[`BOOT_INFO_VERSION`]).
     ///
     /// The kernel must call this before accessing any other field.
     pub fn is_valid(&self) -> bool {
-        self.magic == BOOT_INFO_MAGIC && self.version == BOOT_INFO_VERSION
+        self.magic == BOOT_INFO_MAGIC
+            && (BOOT_INFO_VERSION_MIN..=BOOT_INFO_VERSION).contains(&self.version)
+    }
+
+    /// Size in bytes of the struct the bootloader actually wrote, according
+    /// to `version`.
+    pub fn size_for_version(&self) -> usize {
+        match self.version {
+            0 | 1 => BOOT_INFO_SIZE_V1,
+            2 => BOOT_INFO_SIZE_V2,
+            _ => core::mem::size_of::<Self>(),
+        }
+    }
+
+    /// Size in bytes of the struct plus the tag list following it: the
+    /// whole range the bootloader wrote.
+    pub fn total_size(&self) -> usize {
+        self.size_for_version() + self.tags_size_for_version()
+    }
+
+    /// `tags_size` if this is a version 3 struct, otherwise 0.
+    fn tags_size_for_version(&self) -> usize {
+        if self.version >= 3 {
+            self.tags_size as usize
+        } else {
+            0
+        }
+    }
+
+    /// The tag list following the struct; empty before version 3.
+    ///
+    /// # Safety
+    ///
+    /// For a version 3 struct, `tags_size` bytes directly after it must be
+    /// readable and stay unmodified for the lifetime of `&self` — as is the
+    /// case for a [`BootInfoBuffer`] filled by the bootloader.
+    ///
+    /// # Errors
+    ///
+    /// Any [`TagError`] from [`TagList::parse`].
+    #[allow(unsafe_code)]
+    pub unsafe fn tags(&self) -> Result<TagList<'_>, TagError> {
+        let size = self.tags_size_for_version();
+        if size == 0 {
+            return Ok(TagList::empty());
+        }
+        let start = (self as *const Self as *const u8).wrapping_add(core::mem::size_of::<Self>());
+        // SAFETY: forwarded to the caller.
+        TagList::parse(unsafe { core::slice::from_raw_parts(start, size) })
+    }
+
+    /// The extended memory map, if this is a version 2 struct that has one.
+    pub fn extended_memory_map(&self) -> Option<ExtendedMemoryMap> {
+        // The field does not exist in a version 1 struct: check first.
+        if self.version < 2 {
+            return None;
+        }
+        Some(self.memory_map_ext).filter(ExtendedMemoryMap::is_present)
+    }
+
+    /// The complete memory map and whether it is truncated: the extended
+    /// map if present, otherwise the inline [`KernelMemoryMap`].
+    ///
+    /// # Safety
+    ///
+    /// If [`extended_memory_map`](Self::extended_memory_map) returns `Some`,
+    /// its `count` descriptors must be readable at `phys_offset + phys_addr`
+    /// and stay unmodified for `'a`. `phys_offset` is 0 under an identity
+    /// mapping and [`layout::DIRECT_MAP_BASE`] in the kernel.
+    #[allow(unsafe_code)]
+    pub unsafe fn memory_descriptors(&self, phys_offset: u64) -> (&[KernelMemoryDescriptor], bool) {
+        match self.extended_memory_map() {
+            // SAFETY: forwarded to the caller.
+            Some(ext) => (
+                unsafe {
+                    core::slice::from_raw_parts(
+                        (phys_offset + ext.phys_addr) as *const KernelMemoryDescriptor,
+                        ext.count as usize,
+                    )
+                },
+                false,
+            ),
+            None => (self.memory_map.entries(), self.memory_map.truncated),
+        }
+    }
+}
+
+/// A `KernelBootInfo` with room for `N` bytes of tags after it.
+///
+/// `#[repr(C)]` keeps `tags` directly behind `info` (the struct size is a
+/// multiple of its 8-byte alignment), which is where
+/// [`KernelBootInfo::tags`] looks for them.
+#[repr(C)]
+pub struct BootInfoBuffer<const N: usize> {
+    /// The fixed header.
+    pub info: KernelBootInfo,
+    /// Tag list storage; the first `info.tags_size` bytes are valid.
+    pub tags: [u8; N],
+}
+
+impl<const N: usize> Default for BootInfoBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> BootInfoBuffer<N> {
+    /// A fresh header and an empty tag area.
+    pub const fn new() -> Self {
+        Self {
+            info: KernelBootInfo::new(),
+            tags: [0; N],
+        }
+    }
+
+    /// Build the tag list with `build` and record its size in the header.
+    ///
+    /// # Errors
+    ///
+    /// The first [`TagError`] returned by `build` or by
+    /// [`TagBuilder::finish`]; `tags_size` is left at 0 then.
+    pub fn write_tags(
+        &mut self,
+        build: impl FnOnce(&mut TagBuilder<'_>) -> Result<(), TagError>,
+    ) -> Result<usize, TagError> {
+        self.info.tags_size = 0;
+        let mut builder = TagBuilder::new(&mut self.tags);
+        build(&mut builder)?;
+        let size = builder.finish()?;
+        self.info.tags_size = size as u64;
+        Ok(size)
     }
 }
 
@@ -249,6 +476,8 @@ pub enum MemoryRegionKind {
     PersistentMemory,
     /// Defective RAM reported by firmware.
     Unusable,
+    /// Carved out for the kernel (image, boot info, ...) — never allocate.
+    KernelReserved,
     /// Firmware-reserved or unrecognised type — do not touch.
     Reserved,
 }
@@ -271,6 +500,24 @@ impl MemoryRegionKind {
         self == Self::Usable
     }
 
+    /// Precedence when two descriptors claim the same page: the higher
+    /// value wins. Memory the kernel may allocate ranks lowest, memory that
+    /// is not RAM at all ranks highest.
+    fn restrictiveness(self) -> u8 {
+        match self {
+            Self::Usable => 0,
+            Self::BootloaderReclaimable => 1,
+            Self::AcpiReclaimable => 2,
+            Self::AcpiNonVolatile => 3,
+            Self::PersistentMemory => 4,
+            Self::KernelReserved => 5,
+            Self::Unusable => 6,
+            Self::FirmwareRuntime => 7,
+            Self::Reserved => 8,
+            Self::Mmio => 9,
+        }
+    }
+
     /// Short human-readable label for serial diagnostics.
     pub fn name(self) -> &'static str {
         match self {
@@ -282,6 +529,7 @@ impl MemoryRegionKind {
             Self::Mmio => "Mmio",
             Self::PersistentMemory => "PersistentMemory",
             Self::Unusable => "Unusable",
+            Self::KernelReserved => "KernelReserved",
             Self::Reserved => "Reserved",
         }
     }
@@ -303,6 +551,7 @@ impl From<u32> for MemoryRegionKind {
             memory_type::MMIO | memory_type::MMIO_PORT_SPACE => Self::Mmio,
             memory_type::PERSISTENT_MEMORY => Self::PersistentMemory,
             memory_type::UNUSABLE => Self::Unusable,
+            memory_type::KERNEL_RESERVED => Self::KernelReserved,
             _ => Self::Reserved, // RESERVED (0) and any unknown type
         }
     }
@@ -319,13 +568,20 @@ pub struct MemoryStats {
     pub usable_bytes: u64,
     /// Bytes reclaimable after boot (bootloader + ACPI reclaimable).
     pub reclaimable_bytes: u64,
-    /// Number of valid (non-zero-size) regions in the parsed map.
+    /// Number of regions in the normalised map.
     pub region_count: usize,
     /// Number of immediately-usable (conventional) regions.
     pub usable_region_count: usize,
     /// True if the bootloader truncated the map (source had more entries than
     /// [`MAX_MEMORY_DESCRIPTORS`]).
     pub is_truncated: bool,
+    /// Bytes claimed by more than one source descriptor; each such byte is
+    /// counted once, under the most restrictive claimant.
+    pub overlapping_bytes: u64,
+    /// Bytes at the top of the map left out because the normalised map
+    /// would have needed more than [`MAX_MEMORY_REGIONS`] entries. They are
+    /// treated like holes: no allocator built from the map uses them.
+    pub dropped_bytes: u64,
 }
 
 impl MemoryStats {
@@ -336,7 +592,7 @@ impl MemoryStats {
     }
 }
 
-/// Errors returned by [`MemoryMap::parse()`].
+/// Errors returned by [`MemoryMap::parse()`] and [`MemoryMap::carve_out()`].
 #[derive(Debug)]
 pub enum ParseError {
     /// The memory map contains zero valid (non-zero-size) entries.
@@ -348,6 +604,13 @@ pub enum ParseError {
         /// The misaligned physical address.
         phys_start: u64,
     },
+    /// A carve-out range is empty (`end <= start`).
+    EmptyRange {
+        /// Start of the range.
+        start: u64,
+        /// End of the range.
+        end: u64,
+    },
 }
 
 /// Zero-value descriptor used to initialise the fixed-size array.
@@ -369,40 +632,100 @@ const ZERO_DESC: KernelMemoryDescriptor = KernelMemoryDescriptor {
 /// serves as the authoritative physical memory layout for the allocator
 /// (Phase 1.3.2 and beyond).
 ///
+/// # Canonical form
+///
+/// Firmware maps may be unsorted, may overlap and may split one range into
+/// several adjacent descriptors. The parsed map is normalised:
+///
+/// - regions are sorted by address and never overlap;
+/// - a page claimed by several descriptors takes the most restrictive type
+///   (for example reserved beats conventional), and the overlap is counted
+///   in [`MemoryStats::overlapping_bytes`];
+/// - adjacent regions with the same type and attributes are merged.
+///
+/// [`carve_out`](Self::carve_out) keeps this form.
+///
 /// # Obtaining an instance
 ///
-/// In the kernel, use `kernel::memory::init(&boot_info.memory_map)` to
-/// initialise the global instance, then `kernel::memory::get()` to borrow it.
+/// In the kernel, use `kernel::memory::init(...)` to load the global instance
+/// from the boot info, then `kernel::memory::get()` to borrow it.
 pub struct MemoryMap {
     // Not derived — KernelMemoryDescriptor's large fixed array makes Debug
     // output impractical and KernelMemoryDescriptor doesn't derive Debug.
     // Use stats() for diagnostic output.
-    descriptors: [KernelMemoryDescriptor; MAX_MEMORY_DESCRIPTORS],
+    descriptors: [KernelMemoryDescriptor; MAX_MEMORY_REGIONS],
     count: usize,
     stats: MemoryStats,
 }
 
 impl MemoryMap {
+    /// An empty map with no regions.
+    ///
+    /// At [`MAX_MEMORY_REGIONS`] entries a map is 32 KiB: the kernel keeps
+    /// its instance in a static and fills it with [`load`](Self::load)
+    /// rather than moving a parsed map around on a small stack.
+    pub const fn empty() -> Self {
+        Self {
+            descriptors: [ZERO_DESC; MAX_MEMORY_REGIONS],
+            count: 0,
+            stats: MemoryStats {
+                total_bytes: 0,
+                usable_bytes: 0,
+                reclaimable_bytes: 0,
+                region_count: 0,
+                usable_region_count: 0,
+                is_truncated: false,
+                overlapping_bytes: 0,
+                dropped_bytes: 0,
+            },
+        }
+    }
+
     /// Parse and validate the bootloader-provided memory map.
     ///
+    /// Shorthand for [`load`](Self::load) on an [`empty`](Self::empty) map
+    /// with the inline descriptors of `source`.
+    ///
+    /// # Errors
+    ///
+    /// As for [`load`](Self::load).
+    pub fn parse(source: &KernelMemoryMap) -> Result<Self, ParseError> {
+        let mut map = Self::empty();
+        map.load(source.entries(), source.truncated)?;
+        Ok(map)
+    }
+
+    /// Replace the contents of this map with the normalised form of
+    /// `entries`, which may be any length (for example a version 2
+    /// extended map).
+    ///
     /// Entries with `page_count == 0` are silently skipped — some firmware
-    /// produces zero-size descriptors as padding.
+    /// produces zero-size descriptors as padding. `truncated` is reported
+    /// as [`MemoryStats::is_truncated`]. If the normalised map needs more
+    /// than [`MAX_MEMORY_REGIONS`] entries, the highest regions are left out
+    /// and counted in [`MemoryStats::dropped_bytes`]. On error the map is
+    /// left empty.
+    ///
+    /// Sorted, non-overlapping input — what UEFI produces and `ferrous-boot`
+    /// passes on — is normalised in a single pass; anything else takes a
+    /// slower sweep.
     ///
     /// # Errors
     ///
-    /// - [`ParseError::Empty`]: every entry has zero pages (or `count == 0`).
+    /// - [`ParseError::Empty`]: every entry has zero pages (or there are
+    ///   none).
     /// - [`ParseError::UnalignedBase`]: a descriptor's `phys_start` is not
     ///   4 KiB aligned (guaranteed by the UEFI spec; this catches corrupt maps).
-    pub fn parse(source: &KernelMemoryMap) -> Result<Self, ParseError> {
-        let mut descriptors = [ZERO_DESC; MAX_MEMORY_DESCRIPTORS];
-        let mut count = 0usize;
-
-        let mut total_bytes: u64 = 0;
-        let mut usable_bytes: u64 = 0;
-        let mut reclaimable_bytes: u64 = 0;
-        let mut usable_region_count: usize = 0;
-
-        for (i, desc) in source.entries().iter().enumerate() {
+    pub fn load(
+        &mut self,
+        entries: &[KernelMemoryDescriptor],
+        truncated: bool,
+    ) -> Result<(), ParseError> {
+        self.count = 0;
+        self.stats = Self::empty().stats;
+
+        let mut valid = 0usize;
+        for (i, desc) in entries.iter().enumerate() {
             // Skip zero-size entries emitted by some firmware.
             if desc.page_count == 0 {
                 continue;
@@ -415,47 +738,91 @@ impl MemoryMap {
                     phys_start: desc.phys_start,
                 });
             }
+            valid += 1;
+        }
 
-            let size = desc.size_bytes();
-            let kind = MemoryRegionKind::from(desc.ty);
-
-            // Accumulate RAM totals, excluding address-space holes (MMIO).
-            match kind {
-                MemoryRegionKind::Mmio | MemoryRegionKind::FirmwareRuntime => {}
-                _ => total_bytes = total_bytes.saturating_add(size),
-            }
+        if valid == 0 {
+            return Err(ParseError::Empty);
+        }
 
-            match kind {
-                MemoryRegionKind::Usable => {
-                    usable_bytes = usable_bytes.saturating_add(size);
-                    usable_region_count += 1;
-                }
-                MemoryRegionKind::BootloaderReclaimable | MemoryRegionKind::AcpiReclaimable => {
-                    reclaimable_bytes = reclaimable_bytes.saturating_add(size);
-                }
-                _ => {}
-            }
+        let overlapping_pages = self.normalise_from(entries);
+        self.stats.is_truncated = truncated;
+        self.stats.overlapping_bytes = overlapping_pages * PAGE_SIZE;
+        self.recompute_stats();
+        Ok(())
+    }
 
-            descriptors[count] = *desc;
-            count += 1;
+    /// Give every page of `[start, end)` that the map covers the type `ty`,
+    /// typically [`memory_type::KERNEL_RESERVED`].
+    ///
+    /// The range is rounded outward to page boundaries. Holes in the map stay
+    /// holes; attributes of the covered regions are kept. Use it to withhold
+    /// the kernel image, boot info and similar ranges before the map is handed
+    /// to an allocator.
+    ///
+    /// If the map is too full for the split, its highest regions are
+    /// dropped first (see [`MemoryStats::dropped_bytes`]).
+    ///
+    /// # Errors
+    ///
+    /// [`ParseError::EmptyRange`]: `end <= start`.
+    pub fn carve_out(&mut self, start: u64, end: u64, ty: u32) -> Result<(), ParseError> {
+        if end <= start {
+            return Err(ParseError::EmptyRange { start, end });
+        }
+        let first = start / PAGE_SIZE;
+        let end = end.div_ceil(PAGE_SIZE).min(PAGE_LIMIT);
+
+        // Each region straddling an edge of the range gains one entry.
+        let extra = |map: &Self| -> usize {
+            map.regions()
+                .iter()
+                .map(span)
+                .filter(|&(a, b)| a < end && first < b)
+                .map(|(a, b)| usize::from(a < first) + usize::from(b > end))
+                .sum()
+        };
+        while self.count + extra(self) > MAX_MEMORY_REGIONS {
+            self.count -= 1;
+            let dropped = self.descriptors[self.count].size_bytes();
+            self.stats.dropped_bytes = self.stats.dropped_bytes.saturating_add(dropped);
         }
 
-        if count == 0 {
-            return Err(ParseError::Empty);
+        // Walk backwards so that splitting a region only shifts entries
+        // that have already been handled.
+        for i in (0..self.count).rev() {
+            let desc = self.descriptors[i];
+            let (a, b) = span(&desc);
+            if b <= first || end <= a {
+                continue;
+            }
+            let piece = |ty: u32, from: u64, to: u64| KernelMemoryDescriptor {
+                ty,
+                _pad: 0,
+                phys_start: from * PAGE_SIZE,
+                page_count: to - from,
+                attribute: desc.attribute,
+            };
+            let mut pieces = [ZERO_DESC; 3];
+            let mut n = 0;
+            if a < first {
+                pieces[n] = piece(desc.ty, a, first);
+                n += 1;
+            }
+            pieces[n] = piece(ty, a.max(first), b.min(end));
+            n += 1;
+            if b > end {
+                pieces[n] = piece(desc.ty, end, b);
+                n += 1;
+            }
+            self.descriptors.copy_within(i + 1..self.count, i + n);
+            self.descriptors[i..i + n].copy_from_slice(&pieces[..n]);
+            self.count += n - 1;
         }
 
-        Ok(Self {
-            descriptors,
-            count,
-            stats: MemoryStats {
-                total_bytes,
-                usable_bytes,
-                reclaimable_bytes,
-                region_count: count,
-                usable_region_count,
-                is_truncated: source.truncated,
-            },
-        })
+        self.merge_adjacent();
+        self.recompute_stats();
+        Ok(())
     }
 
     /// Returns memory statistics computed during parsing.
@@ -493,6 +860,171 @@ impl MemoryMap {
     pub fn regions_of_type(&self, ty: u32) -> impl Iterator<Item = &KernelMemoryDescriptor> {
         self.regions().iter().filter(move |d| d.ty == ty)
     }
+
+    /// Rebuild `descriptors` as the canonical form of `source`. Returns the
+    /// number of pages claimed by more than one descriptor.
+    ///
+    /// Sorted, disjoint input is copied in one pass, merging neighbours.
+    /// Anything else sweeps the page boundaries of every descriptor in
+    /// ascending order (O(n²), no scratch buffers); each elementary span
+    /// takes the most restrictive type among the descriptors covering it.
+    fn normalise_from(&mut self, source: &[KernelMemoryDescriptor]) -> u64 {
+        self.count = 0;
+        let non_empty = || source.iter().filter(|d| d.page_count != 0);
+        let mut prev_end = 0;
+        if non_empty().map(span).all(|(first, end)| {
+            let ordered = first >= prev_end;
+            prev_end = end;
+            ordered
+        }) {
+            for desc in non_empty() {
+                let (first, end) = span(desc);
+                self.push(desc.ty, desc.attribute, first, end);
+            }
+            return 0;
+        }
+
+        let mut overlapping = 0;
+        let Some(mut pos) = source.iter().map(|d| span(d).0).min() else {
+            return 0;
+        };
+
+        loop {
+            // Next boundary above `pos`.
+            let next = source
+                .iter()
+                .map(span)
+                .flat_map(|(first, end)| [first, end])
+                .filter(|&b| b > pos)
+                .min();
+            let Some(next) = next else {
+                return overlapping;
+            };
+
+            // The most restrictive descriptor covering [pos, next).
+            let mut winner: Option<&KernelMemoryDescriptor> = None;
+            let mut claimants = 0u64;
+            for desc in source {
+                let (first, end) = span(desc);
+                if first <= pos && pos < end {
+                    claimants += 1;
+                    if winner.is_none_or(|w| precedes(w, desc)) {
+                        winner = Some(desc);
+                    }
+                }
+            }
+            if claimants > 1 {
+                overlapping += next - pos;
+            }
+
+            if let Some(w) = winner {
+                self.push(w.ty, w.attribute, pos, next);
+            }
+            pos = next;
+        }
+    }
+
+    /// Merge neighbouring regions that touch and share type and attributes.
+    fn merge_adjacent(&mut self) {
+        let mut kept = 0;
+        for i in 0..self.count {
+            let desc = self.descriptors[i];
+            if kept > 0 && mergeable(&self.descriptors[kept - 1], &desc) {
+                self.descriptors[kept - 1].page_count += desc.page_count;
+            } else {
+                self.descriptors[kept] = desc;
+                kept += 1;
+            }
+        }
+        self.count = kept;
+    }
+
+    /// Append pages `[first, end)`, merging with the last region if it is
+    /// adjacent with the same type and attributes. A full map counts them
+    /// in [`MemoryStats::dropped_bytes`] instead.
+    fn push(&mut self, ty: u32, attribute: u64, first: u64, end: u64) {
+        let desc = KernelMemoryDescriptor {
+            ty,
+            _pad: 0,
+            phys_start: first * PAGE_SIZE,
+            page_count: end - first,
+            attribute,
+        };
+        if let Some(last) = self.descriptors[..self.count].last_mut() {
+            if mergeable(last, &desc) {
+                last.page_count += desc.page_count;
+                return;
+            }
+        }
+        if self.count == MAX_MEMORY_REGIONS {
+            self.stats.dropped_bytes = self.stats.dropped_bytes.saturating_add(desc.size_bytes());
+            return;
+        }
+        self.descriptors[self.count] = desc;
+        self.count += 1;
+    }
+
+    /// Recompute everything in `stats` except `is_truncated`,
+    /// `overlapping_bytes` and `dropped_bytes` from the current regions.
+    fn recompute_stats(&mut self) {
+        let mut total_bytes: u64 = 0;
+        let mut usable_bytes: u64 = 0;
+        let mut reclaimable_bytes: u64 = 0;
+        let mut usable_region_count: usize = 0;
+
+        for desc in self.regions() {
+            let size = desc.size_bytes();
+            let kind = MemoryRegionKind::from(desc.ty);
+
+            // Accumulate RAM totals, excluding address-space holes (MMIO).
+            match kind {
+                MemoryRegionKind::Mmio | MemoryRegionKind::FirmwareRuntime => {}
+                _ => total_bytes = total_bytes.saturating_add(size),
+            }
+
+            match kind {
+                MemoryRegionKind::Usable => {
+                    usable_bytes = usable_bytes.saturating_add(size);
+                    usable_region_count += 1;
+                }
+                MemoryRegionKind::BootloaderReclaimable | MemoryRegionKind::AcpiReclaimable => {
+                    reclaimable_bytes = reclaimable_bytes.saturating_add(size);
+                }
+                _ => {}
+            }
+        }
+
+        self.stats.total_bytes = total_bytes;
+        self.stats.usable_bytes = usable_bytes;
+        self.stats.reclaimable_bytes = reclaimable_bytes;
+        self.stats.region_count = self.count;
+        self.stats.usable_region_count = usable_region_count;
+    }
+}
+
+/// Page size assumed by the memory map (UEFI pages are always 4 KiB).
+const PAGE_SIZE: u64 = 4096;
+
+/// Number of pages in the 64-bit physical address space.
+const PAGE_LIMIT: u64 = 1 << 52;
+
+/// Page numbers `[first, end)` covered by `desc`, clamped to the top of the
+/// address space.
+fn span(desc: &KernelMemoryDescriptor) -> (u64, u64) {
+    let first = desc.phys_start / PAGE_SIZE;
+    (first, first.saturating_add(desc.page_count).min(PAGE_LIMIT))
+}
+
+/// True if `b` directly follows `a` with the same type and attributes.
+fn mergeable(a: &KernelMemoryDescriptor, b: &KernelMemoryDescriptor) -> bool {
+    a.ty == b.ty && a.attribute == b.attribute && span(a).1 == span(b).0
+}
+
+/// True if `b` takes precedence over `a` where both claim a page: the more
+/// restrictive kind wins, ties go to the higher raw type.
+fn precedes(a: &KernelMemoryDescriptor, b: &KernelMemoryDescriptor) -> bool {
+    let rank = |d: &KernelMemoryDescriptor| (MemoryRegionKind::from(d.ty).restrictiveness(), d.ty);
+    rank(b) > rank(a)
 }
 
 // ---------------------------------------------------------------------------
@@ -521,8 +1053,22 @@ mod tests {
     }
 
     #[test]
-    fn boot_info_version_is_one() {
-        assert_eq!(BOOT_INFO_VERSION, 1);
+    fn boot_info_version_is_three_and_accepts_one() {
+        assert_eq!(BOOT_INFO_VERSION, 3);
+        assert_eq!(BOOT_INFO_VERSION_MIN, 1);
+    }
+
+    /// The v1 and v2 prefixes must never change: older bootloaders still
+    /// write them.
+    #[test]
+    fn boot_info_prefix_sizes_are_stable() {
+        // 8 (magic) + 8 (version, pad) + 8208 (memory map) + 8 (RSDP)
+        // + 32 (framebuffer) + 8 (flag, pad) + 32 (name) = 8304
+        assert_eq!(BOOT_INFO_SIZE_V1, 8304);
+        // + 16 (memory_map_ext)
+        assert_eq!(BOOT_INFO_SIZE_V2, 8320);
+        // + 8 (tags_size); frozen from version 3 on.
+        assert_eq!(core::mem::size_of::<KernelBootInfo>(), 8328);
     }
 
     #[test]
@@ -558,6 +1104,128 @@ mod tests {
         let mut info = KernelBootInfo::new();
         info.version = 0;
         assert!(!info.is_valid(), "wrong version must fail is_valid()");
+        info.version = BOOT_INFO_VERSION + 1;
+        assert!(!info.is_valid(), "future version must fail is_valid()");
+    }
+
+    #[test]
+    fn version_one_boot_info_is_valid() {
+        let mut info = KernelBootInfo::new();
+        info.version = 1;
+        assert!(info.is_valid());
+        assert_eq!(info.size_for_version(), BOOT_INFO_SIZE_V1);
+        assert_eq!(info.total_size(), BOOT_INFO_SIZE_V1);
+    }
+
+    // -----------------------------------------------------------------------
+    // Extended memory map (version 2)
+    // -----------------------------------------------------------------------
+
+    #[test]
+    fn new_boot_info_has_no_extended_map() {
+        let info = KernelBootInfo::new();
+        assert!(info.extended_memory_map().is_none());
+        assert_eq!(
+            info.size_for_version(),
+            core::mem::size_of::<KernelBootInfo>()
+        );
+    }
+
+    #[test]
+    #[allow(unsafe_code)]
+    fn memory_descriptors_prefers_extended_map() {
+        let ext: std::vec::Vec<_> = (0..300u64)
+            .map(|i| make_desc(memory_type::CONVENTIONAL, i * 0x2000, 1))
+            .collect();
+        let mut info = KernelBootInfo::new();
+        info.memory_map.count = 1;
+        info.memory_map.truncated = true;
+        info.memory_map_ext = ExtendedMemoryMap {
+            phys_addr: ext.as_ptr() as u64,
+            count: ext.len() as u64,
+        };
+        // SAFETY: `ext` outlives the returned slice.
+        let (descs, truncated) = unsafe { info.memory_descriptors(0) };
+        assert_eq!(descs.len(), 300);
+        assert_eq!(descs[299].phys_start, 299 * 0x2000);
+        assert!(!truncated);
+    }
+
+    #[test]
+    #[allow(unsafe_code)]
+    fn version_one_ignores_extended_map_field() {
+        let mut info = KernelBootInfo::new();
+        info.version = 1;
+        info.memory_map.count = 3;
+        info.memory_map.truncated = true;
+        // Garbage where a v1 struct has no field: must not be dereferenced.
+        info.memory_map_ext = ExtendedMemoryMap {
+            phys_addr: 0xDEAD_0000,
+            count: 7,
+        };
+        assert!(info.extended_memory_map().is_none());
+        // SAFETY: no extended map is reported, so nothing is dereferenced.
+        let (descs, truncated) = unsafe { info.memory_descriptors(0) };
+        assert_eq!(descs.len(), 3);
+        assert!(truncated);
+    }
+
+    // -----------------------------------------------------------------------
+    // Tags (version 3)
+    // -----------------------------------------------------------------------
+
+    #[test]
+    #[allow(unsafe_code)]
+    fn new_boot_info_has_no_tags() {
+        let info = KernelBootInfo::new();
+        assert_eq!(info.total_size(), core::mem::size_of::<KernelBootInfo>());
+        // SAFETY: tags_size is 0, so nothing after the struct is read.
+        assert!(unsafe { info.tags() }.unwrap().is_empty());
+    }
+
+    #[test]
+    #[allow(unsafe_code)]
+    fn boot_info_buffer_places_tags_after_the_header() {
+        let mut buffer = std::boxed::Box::new(BootInfoBuffer::<64>::new());
+        let size = buffer
+            .write_tags(|tags| tags.add(0x7000_0001, b"payload"))
+            .unwrap();
+        assert_eq!(size, 24);
+        assert_eq!(buffer.info.tags_size, 24);
+        assert_eq!(
+            core::mem::offset_of!(BootInfoBuffer<64>, tags),
+            core::mem::size_of::<KernelBootInfo>()
+        );
+        assert_eq!(
+            buffer.info.total_size(),
+            core::mem::size_of::<KernelBootInfo>() + 24
+        );
+
+        // SAFETY: the tags live in the same BootInfoBuffer.
+        let tags = unsafe { buffer.info.tags() }.unwrap();
+        assert_eq!(tags.len(), 1);
+        assert_eq!(tags.find(0x7000_0001).unwrap().payload, b"payload");
+    }
+
+    #[test]
+    #[allow(unsafe_code)]
+    fn older_versions_ignore_tags_size() {
+        let mut info = KernelBootInfo::new();
+        info.version = 2;
+        // Garbage where a v2 struct has no field: must not be used.
+        info.tags_size = 4096;
+        assert_eq!(info.size_for_version(), BOOT_INFO_SIZE_V2);
+        assert_eq!(info.total_size(), BOOT_INFO_SIZE_V2);
+        // SAFETY: version 2 has no tags, so nothing is dereferenced.
+        assert!(unsafe { info.tags() }.unwrap().is_empty());
+    }
+
+    #[test]
+    fn write_tags_failure_leaves_no_tags() {
+        let mut buffer = std::boxed::Box::new(BootInfoBuffer::<16>::new());
+        let err = buffer.write_tags(|tags| tags.add(0x7000_0001, &[0; 32]));
+        assert_eq!(err, Err(TagError::BufferTooSmall));
+        assert_eq!(buffer.info.tags_size, 0);
     }
 
     #[test]
@@ -830,6 +1498,7 @@ mod tests {
             MemoryRegionKind::Mmio,
             MemoryRegionKind::PersistentMemory,
             MemoryRegionKind::Unusable,
+            MemoryRegionKind::KernelReserved,
             MemoryRegionKind::Reserved,
         ];
         for kind in kinds {
@@ -906,7 +1575,7 @@ mod tests {
             make_desc(memory_type::CONVENTIONAL, 0x0000_1000, 100), // 400 KiB usable
             make_desc(memory_type::BOOT_SERVICES_DATA, 0x0010_0000, 50), // 200 KiB reclaimable
             make_desc(memory_type::MMIO, 0xFEC0_0000, 4),           // excluded from RAM
-            make_desc(memory_type::RESERVED, 0x0000_F000, 1),       // not usable
+            make_desc(memory_type::RESERVED, 0x0000_0000, 1),       // not usable
         ]);
         let parsed = MemoryMap::parse(&map).unwrap();
         let stats = parsed.stats();
@@ -1019,6 +1688,304 @@ mod tests {
         assert_eq!(parsed.regions().len(), 3);
     }
 
+    // -----------------------------------------------------------------------
+    // MemoryMap::parse — normalisation of adversarial maps
+    // -----------------------------------------------------------------------
+
+    /// `(type, phys_start, page_count)` of every parsed region.
+    fn layout(map: &MemoryMap) -> std::vec::Vec<(u32, u64, u64)> {
+        map.regions()
+            .iter()
+            .map(|d| (d.ty, d.phys_start, d.page_count))
+            .collect()
+    }
+
+    #[test]
+    fn parse_sorts_regions_by_address() {
+        let map = make_map(&[
+            make_desc(memory_type::MMIO, 0xFEC0_0000, 4),
+            make_desc(memory_type::CONVENTIONAL, 0x10_0000, 16),
+            make_desc(memory_type::RESERVED, 0x0, 1),
+        ]);
+        let parsed = MemoryMap::parse(&map).unwrap();
+        assert_eq!(
+            layout(&parsed),
+            [
+                (memory_type::RESERVED, 0x0, 1),
+                (memory_type::CONVENTIONAL, 0x10_0000, 16),
+                (memory_type::MMIO, 0xFEC0_0000, 4),
+            ]
+        );
+        assert_eq!(parsed.stats().overlapping_bytes, 0);
+    }
+
+    #[test]
+    fn parse_merges_adjacent_regions_of_same_type_and_attributes() {
+        let mut uncached = make_desc(memory_type::CONVENTIONAL, 0x5000, 1);
+        uncached.attribute = 1;
+        let map = make_map(&[
+            make_desc(memory_type::CONVENTIONAL, 0x3000, 2),
+            make_desc(memory_type::CONVENTIONAL, 0x1000, 2),
+            uncached,
+            make_desc(memory_type::LOADER_DATA, 0x6000, 1),
+        ]);
+        let parsed = MemoryMap::parse(&map).unwrap();
+        assert_eq!(
+            layout(&parsed),
+            [
+                (memory_type::CONVENTIONAL, 0x1000, 4),
+                (memory_type::CONVENTIONAL, 0x5000, 1),
+                (memory_type::LOADER_DATA, 0x6000, 1),
+            ]
+        );
+        assert_eq!(parsed.regions()[1].attribute, 1);
+        assert_eq!(parsed.stats().usable_region_count, 2);
+    }
+
+    #[test]
+    fn parse_splits_conventional_around_contained_reserved() {
+        let map = make_map(&[
+            make_desc(memory_type::CONVENTIONAL, 0x1000, 100),
+            make_desc(memory_type::RESERVED, 0xF000, 1),
+        ]);
+        let parsed = MemoryMap::parse(&map).unwrap();
+        assert_eq!(
+            layout(&parsed),
+            [
+                (memory_type::CONVENTIONAL, 0x1000, 14),
+                (memory_type::RESERVED, 0xF000, 1),
+                (memory_type::CONVENTIONAL, 0x1_0000, 85),
+            ]
+        );
+        let stats = parsed.stats();
+        assert_eq!(stats.usable_bytes, 99 * 4096);
+        assert_eq!(stats.total_bytes, 100 * 4096);
+        assert_eq!(stats.overlapping_bytes, 4096);
+    }
+
+    #[test]
+    fn parse_most_restrictive_type_wins() {
+        let map = make_map(&[
+            make_desc(memory_type::CONVENTIONAL, 0x0, 16),
+            make_desc(memory_type::MMIO, 0x8000, 16),
+            make_desc(memory_type::ACPI_RECLAIM, 0x4000, 8),
+        ]);
+        let parsed = MemoryMap::parse(&map).unwrap();
+        assert_eq!(
+            layout(&parsed),
+            [
+                (memory_type::CONVENTIONAL, 0x0, 4),
+                (memory_type::ACPI_RECLAIM, 0x4000, 4),
+                (memory_type::MMIO, 0x8000, 16),
+            ]
+        );
+        assert_eq!(parsed.stats().overlapping_bytes, 12 * 4096);
+    }
+
+    #[test]
+    fn parse_ties_within_a_kind_go_to_the_higher_type() {
+        let map = make_map(&[
+            make_desc(memory_type::BOOT_SERVICES_DATA, 0x1000, 4),
+            make_desc(memory_type::LOADER_CODE, 0x1000, 4),
+        ]);
+        let parsed = MemoryMap::parse(&map).unwrap();
+        assert_eq!(
+            layout(&parsed),
+            [(memory_type::BOOT_SERVICES_DATA, 0x1000, 4)]
+        );
+    }
+
+    #[test]
+    fn parse_duplicate_descriptors_collapse() {
+        let d = make_desc(memory_type::CONVENTIONAL, 0x1000, 10);
+        let parsed = MemoryMap::parse(&make_map(&[d, d, d])).unwrap();
+        assert_eq!(layout(&parsed), [(memory_type::CONVENTIONAL, 0x1000, 10)]);
+        assert_eq!(parsed.stats().usable_bytes, 10 * 4096);
+        assert_eq!(parsed.stats().overlapping_bytes, 10 * 4096);
+    }
+
+    #[test]
+    fn parse_saturates_huge_page_counts() {
+        let map = make_map(&[
+            make_desc(memory_type::CONVENTIONAL, 0x1000, 4),
+            make_desc(memory_type::RESERVED, 0xFFFF_FFFF_FFFF_0000, u64::MAX),
+        ]);
+        let parsed = MemoryMap::parse(&map).unwrap();
+        assert_eq!(parsed.stats().region_count, 2);
+        // Clamped to the 16 pages below 2^64.
+        assert_eq!(
+            layout(&parsed)[1],
+            (memory_type::RESERVED, 0xFFFF_FFFF_FFFF_0000, 16)
+        );
+    }
+
+    #[test]
+    fn parse_drops_regions_beyond_capacity() {
+        // One conventional region riddled with single reserved pages: each
+        // reserved page splits it again, to 1201 regions in total.
+        let mut descs = std::vec![make_desc(memory_type::CONVENTIONAL, 0, 4096)];
+        for i in 0..600u64 {
+            descs.push(make_desc(memory_type::RESERVED, (4 * i + 1) * 0x1000, 1));
+        }
+        let mut map = MemoryMap::empty();
+        map.load(&descs, false).unwrap();
+        let stats = *map.stats();
+        assert_eq!(stats.region_count, MAX_MEMORY_REGIONS);
+        // The lowest regions are kept; everything above is dropped.
+        assert_eq!(map.regions()[0].phys_start, 0);
+        let kept: u64 = map.regions().iter().map(|d| d.size_bytes()).sum();
+        assert_eq!(kept + stats.dropped_bytes, 4096 * 4096);
+        let last = map.regions().last().unwrap();
+        assert_eq!(
+            last.phys_start + last.size_bytes() + stats.dropped_bytes,
+            4096 * 4096
+        );
+    }
+
+    #[test]
+    fn load_merges_sorted_disjoint_input_in_one_pass() {
+        let mut map = MemoryMap::empty();
+        map.load(
+            &[
+                make_desc(memory_type::CONVENTIONAL, 0x1000, 2),
+                make_desc(memory_type::CONVENTIONAL, 0x3000, 2),
+                make_desc(memory_type::RESERVED, 0x8000, 0),
+                make_desc(memory_type::MMIO, 0x8000, 1),
+            ],
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            layout(&map),
+            [
+                (memory_type::CONVENTIONAL, 0x1000, 4),
+                (memory_type::MMIO, 0x8000, 1),
+            ]
+        );
+        assert_eq!(map.stats().overlapping_bytes, 0);
+        assert_eq!(map.stats().dropped_bytes, 0);
+    }
+
+    #[test]
+    fn load_accepts_maps_longer_than_the_inline_array() {
+        let descs: std::vec::Vec<_> = (0..3 * MAX_MEMORY_DESCRIPTORS as u64)
+            .map(|i| make_desc(memory_type::RUNTIME_SERVICES_DATA, 2 * i * 0x1000, 1))
+            .collect();
+        let mut map = MemoryMap::empty();
+        map.load(&descs, false).unwrap();
+        assert_eq!(map.stats().region_count, 3 * MAX_MEMORY_DESCRIPTORS);
+        assert!(!map.stats().is_truncated);
+    }
+
+    // -----------------------------------------------------------------------
+    // MemoryMap::carve_out
+    // -----------------------------------------------------------------------
+
+    #[test]
+    fn carve_out_splits_usable_region() {
+        let map = make_map(&[make_desc(memory_type::CONVENTIONAL, 0x10_0000, 256)]);
+        let mut parsed = MemoryMap::parse(&map).unwrap();
+        // Rounded outward to 0x14_0000 .. 0x14_2000.
+        parsed
+            .carve_out(0x14_0010, 0x14_1001, memory_type::KERNEL_RESERVED)
+            .unwrap();
+        assert_eq!(
+            layout(&parsed),
+            [
+                (memory_type::CONVENTIONAL, 0x10_0000, 64),
+                (memory_type::KERNEL_RESERVED, 0x14_0000, 2),
+                (memory_type::CONVENTIONAL, 0x14_2000, 190),
+            ]
+        );
+        let stats = parsed.stats();
+        assert_eq!(stats.usable_bytes, 254 * 4096);
+        assert_eq!(stats.region_count, 3);
+        assert_eq!(
+            MemoryRegionKind::from(parsed.regions()[1].ty),
+            MemoryRegionKind::KernelReserved
+        );
+    }
+
+    #[test]
+    fn carve_out_keeps_holes_and_attributes() {
+        let mut loader = make_desc(memory_type::LOADER_DATA, 0x4000, 4);
+        loader.attribute = 0xF;
+        let map = make_map(&[make_desc(memory_type::CONVENTIONAL, 0x0, 2), loader]);
+        let mut parsed = MemoryMap::parse(&map).unwrap();
+        parsed
+            .carve_out(0x1000, 0x6000, memory_type::KERNEL_RESERVED)
+            .unwrap();
+        assert_eq!(
+            layout(&parsed),
+            [
+                (memory_type::CONVENTIONAL, 0x0, 1),
+                (memory_type::KERNEL_RESERVED, 0x1000, 1),
+                (memory_type::KERNEL_RESERVED, 0x4000, 2),
+                (memory_type::LOADER_DATA, 0x6000, 2),
+            ]
+        );
+        assert_eq!(parsed.regions()[2].attribute, 0xF);
+        assert_eq!(parsed.stats().reclaimable_bytes, 2 * 4096);
+    }
+
+    #[test]
+    fn adjacent_carve_outs_merge() {
+        let map = make_map(&[make_desc(memory_type::LOADER_CODE, 0x20_0000, 64)]);
+        let mut parsed = MemoryMap::parse(&map).unwrap();
+        let ty = memory_type::KERNEL_RESERVED;
+        parsed.carve_out(0x20_0000, 0x21_0000, ty).unwrap();
+        parsed.carve_out(0x21_0000, 0x22_0000, ty).unwrap();
+        assert_eq!(
+            layout(&parsed),
+            [
+                (ty, 0x20_0000, 32),
+                (memory_type::LOADER_CODE, 0x22_0000, 32)
+            ]
+        );
+    }
+
+    #[test]
+    fn carve_out_rejects_empty_range() {
+        let map = make_map(&[make_desc(memory_type::CONVENTIONAL, 0x1000, 4)]);
+        let mut parsed = MemoryMap::parse(&map).unwrap();
+        assert!(matches!(
+            parsed.carve_out(0x2000, 0x2000, memory_type::KERNEL_RESERVED),
+            Err(ParseError::EmptyRange {
+                start: 0x2000,
+                end: 0x2000
+            })
+        ));
+    }
+
+    #[test]
+    fn carve_out_on_a_full_map_drops_the_highest_regions() {
+        // A full map with gaps between regions: any split overflows.
+        let descs: std::vec::Vec<_> = (0..MAX_MEMORY_REGIONS as u64)
+            .map(|i| make_desc(memory_type::CONVENTIONAL, 4 * i * 0x1000, 3))
+            .collect();
+        let mut parsed = MemoryMap::empty();
+        parsed.load(&descs, false).unwrap();
+        parsed
+            .carve_out(0x1000, 0x2000, memory_type::KERNEL_RESERVED)
+            .unwrap();
+        assert_eq!(parsed.stats().region_count, MAX_MEMORY_REGIONS);
+        assert_eq!(
+            layout(&parsed)[..4],
+            [
+                (memory_type::CONVENTIONAL, 0x0, 1),
+                (memory_type::KERNEL_RESERVED, 0x1000, 1),
+                (memory_type::CONVENTIONAL, 0x2000, 1),
+                (memory_type::CONVENTIONAL, 0x4000, 3),
+            ]
+        );
+        // The two splits cost the two highest regions.
+        assert_eq!(parsed.stats().dropped_bytes, 2 * 3 * 4096);
+        assert_eq!(
+            parsed.stats().usable_bytes,
+            (3 * (MAX_MEMORY_REGIONS as u64 - 2) - 1) * 4096
+        );
+    }
+
     // -----------------------------------------------------------------------
     // Large map (stress test at MAX_MEMORY_DESCRIPTORS)
     // -----------------------------------------------------------------------
@@ -1027,7 +1994,9 @@ mod tests {
     fn parse_max_descriptors() {
         let mut map = KernelMemoryMap::new();
         for i in 0..MAX_MEMORY_DESCRIPTORS {
-            map.descriptors[i] = make_desc(memory_type::CONVENTIONAL, (i as u64 + 1) * 0x1000, 1);
+            // One-page gaps keep the regions from merging.
+            map.descriptors[i] =
+                make_desc(memory_type::CONVENTIONAL, (2 * i as u64 + 1) * 0x1000, 1);
         }
         map.count = MAX_MEMORY_DESCRIPTORS;
         let parsed = MemoryMap::parse(&map).unwrap();
@@ -0,0 +1,650 @@
+//! Tagged boot information (version 3).
+//!
+//! Since version 3 the fixed [`KernelBootInfo`](crate::KernelBootInfo)
+//! header is followed by a list of typed, length-prefixed records, in the
+//! spirit of Multiboot2. New handoff data gets a new tag type instead of a
+//! new header field, so the header layout and its size tests stay frozen.
+//!
+//! # Wire format
+//!
+//! ```text
+//! +---------+-----------+-------------+---------+
+//! | ty: u32 | size: u32 | payload ... | padding |  next tag at align8(size)
+//! +---------+-----------+-------------+---------+
+//! ```
+//!
+//! `size` covers the 8-byte header and the payload but not the padding.
+//! The list ends with a tag of type [`tag_type::END`] and size 8. All
+//! integers are little-endian.
+//!
+//! # Usage
+//!
+//! ```ignore
+//! // Bootloader
+//! let mut builder = TagBuilder::new(&mut buffer);
+//! builder.add(tag_type::CMDLINE, b"log=debug")?;
+//! let size = builder.finish()?;
+//!
+//! // Kernel
+//! let tags = TagList::parse(&buffer[..size])?;
+//! if let Some(tag) = tags.find(tag_type::CMDLINE) { /* ... */ }
+//! ```
+//!
+//! Consumers match on the tag types they know; every other tag is skipped.
+
+/// Tag type codes.
+///
+/// Codes are never reused. Kernels ignore codes they do not know, so a newer
+/// bootloader may add tags without breaking an older kernel.
+pub mod tag_type {
+    /// Terminates the list.
+    pub const END: u32 = 0;
+    /// Kernel command line: UTF-8 text, no terminator, at most
+    /// [`MAX_CMDLINE_LEN`](crate::MAX_CMDLINE_LEN) bytes.
+    pub const CMDLINE: u32 = 1;
+    /// Initial ramdisk: physical address and size in bytes, two `u64`s.
+    /// The bytes are the archive exactly as read from the boot volume.
+    pub const INITRD: u32 = 2;
+    /// Boot module: physical address and size in bytes as two `u64`s,
+    /// followed by the module name (UTF-8, no terminator, at most
+    /// [`MAX_MODULE_NAME_LEN`](crate::MAX_MODULE_NAME_LEN) bytes). One tag
+    /// per module, in load order; see [`Module`](super::Module).
+    pub const MODULE: u32 = 3;
+    /// Kernel placement: the slide (virtual load address minus virtual
+    /// link address, two's complement) and the physical address the image
+    /// was loaded at, two `u64`s. The slide is 0 when the kernel runs at
+    /// its link address.
+    pub const KASLR: u32 = 4;
+
+    /// Human-readable name of a tag type, or `"unknown"`.
+    pub fn name(ty: u32) -> &'static str {
+        match ty {
+            END => "end",
+            CMDLINE => "cmdline",
+            INITRD => "initrd",
+            MODULE => "module",
+            KASLR => "kaslr",
+            _ => "unknown",
+        }
+    }
+}
+
+/// Size of a tag header (`ty` + `size`).
+pub const TAG_HEADER_SIZE: usize = 8;
+
+/// Alignment of every tag within the list.
+pub const TAG_ALIGN: usize = 8;
+
+/// Errors from [`TagBuilder`] and [`TagList::parse`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TagError {
+    /// The builder's buffer cannot hold the tag and the end marker.
+    BufferTooSmall,
+    /// A payload is too large for the 32-bit `size` field.
+    PayloadTooLarge,
+    /// A tag was added with type [`tag_type::END`].
+    ReservedType,
+    /// The tag at `offset` has a size below the header size or runs past
+    /// the end of the list.
+    Malformed {
+        /// Byte offset of the offending tag.
+        offset: usize,
+    },
+    /// The list ends without an [`tag_type::END`] tag.
+    MissingEnd,
+    /// A module name is longer than
+    /// [`MAX_MODULE_NAME_LEN`](crate::MAX_MODULE_NAME_LEN).
+    NameTooLong,
+}
+
+/// Round `n` up to the tag alignment.
+const fn align_up(n: usize) -> usize {
+    (n + TAG_ALIGN - 1) & !(TAG_ALIGN - 1)
+}
+
+/// Read the header at `offset`, if the bytes are there.
+fn read_header(bytes: &[u8], offset: usize) -> Option<(u32, u32)> {
+    let header = bytes.get(offset..offset.checked_add(TAG_HEADER_SIZE)?)?;
+    let ty = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+    let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+    Some((ty, size))
+}
+
+// ---------------------------------------------------------------------------
+// Builder
+// ---------------------------------------------------------------------------
+
+/// Writes a tag list into a caller-provided buffer.
+///
+/// Room for the end tag is kept free at all times, so [`finish`](Self::finish)
+/// only fails if the buffer could not even hold that.
+pub struct TagBuilder<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> TagBuilder<'a> {
+    /// Start an empty list at the beginning of `buf`.
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        Self { buf, len: 0 }
+    }
+
+    /// Bytes written so far, excluding the end tag.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// True if no tag has been added.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Append a tag with the given payload.
+    ///
+    /// # Errors
+    ///
+    /// - [`TagError::ReservedType`] for [`tag_type::END`].
+    /// - [`TagError::PayloadTooLarge`] if the size does not fit in a `u32`.
+    /// - [`TagError::BufferTooSmall`] if the tag plus the end tag do not fit;
+    ///   the list is unchanged.
+    pub fn add(&mut self, ty: u32, payload: &[u8]) -> Result<(), TagError> {
+        self.add_with(ty, payload.len(), |out| out.copy_from_slice(payload))
+    }
+
+    /// Append a tag whose `len`-byte payload is written in place by `fill`.
+    ///
+    /// The payload starts zeroed. Errors as for [`add`](Self::add).
+    pub fn add_with(
+        &mut self,
+        ty: u32,
+        len: usize,
+        fill: impl FnOnce(&mut [u8]),
+    ) -> Result<(), TagError> {
+        if ty == tag_type::END {
+            return Err(TagError::ReservedType);
+        }
+        let size = TAG_HEADER_SIZE
+            .checked_add(len)
+            .filter(|&s| s <= u32::MAX as usize)
+            .ok_or(TagError::PayloadTooLarge)?;
+        let next = align_up(self.len + size);
+        if next + TAG_HEADER_SIZE > self.buf.len() {
+            return Err(TagError::BufferTooSmall);
+        }
+
+        let tag = &mut self.buf[self.len..next];
+        tag.fill(0);
+        tag[0..4].copy_from_slice(&ty.to_le_bytes());
+        tag[4..8].copy_from_slice(&(size as u32).to_le_bytes());
+        fill(&mut tag[TAG_HEADER_SIZE..size]);
+        self.len = next;
+        Ok(())
+    }
+
+    /// Append a tag whose payload is `values` as little-endian `u64`s.
+    ///
+    /// Errors as for [`add`](Self::add).
+    pub fn add_u64s(&mut self, ty: u32, values: &[u64]) -> Result<(), TagError> {
+        self.add_with(ty, values.len() * 8, |out| {
+            for (chunk, value) in out.chunks_exact_mut(8).zip(values) {
+                chunk.copy_from_slice(&value.to_le_bytes());
+            }
+        })
+    }
+
+    /// Append a [`tag_type::MODULE`] tag.
+    ///
+    /// # Errors
+    ///
+    /// [`TagError::NameTooLong`] if `name` exceeds
+    /// [`MAX_MODULE_NAME_LEN`](crate::MAX_MODULE_NAME_LEN) bytes, otherwise
+    /// as for [`add`](Self::add).
+    pub fn add_module(&mut self, module: &Module<'_>) -> Result<(), TagError> {
+        if module.name.len() > crate::MAX_MODULE_NAME_LEN {
+            return Err(TagError::NameTooLong);
+        }
+        self.add_with(tag_type::MODULE, 16 + module.name.len(), |out| {
+            out[0..8].copy_from_slice(&module.phys_addr.to_le_bytes());
+            out[8..16].copy_from_slice(&module.size.to_le_bytes());
+            out[16..].copy_from_slice(module.name.as_bytes());
+        })
+    }
+
+    /// Write the end tag and return the total size of the list in bytes.
+    ///
+    /// # Errors
+    ///
+    /// [`TagError::BufferTooSmall`] if the buffer is shorter than one header.
+    pub fn finish(self) -> Result<usize, TagError> {
+        let end = self
+            .buf
+            .get_mut(self.len..self.len + TAG_HEADER_SIZE)
+            .ok_or(TagError::BufferTooSmall)?;
+        end[0..4].copy_from_slice(&tag_type::END.to_le_bytes());
+        end[4..8].copy_from_slice(&(TAG_HEADER_SIZE as u32).to_le_bytes());
+        Ok(self.len + TAG_HEADER_SIZE)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Parser
+// ---------------------------------------------------------------------------
+
+/// One record of a [`TagList`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tag<'a> {
+    /// Tag type (see [`tag_type`]).
+    pub ty: u32,
+    /// Payload bytes, without header or padding.
+    pub payload: &'a [u8],
+}
+
+impl Tag<'_> {
+    /// The `index`th little-endian `u64` of the payload, if present.
+    pub fn u64_at(&self, index: usize) -> Option<u64> {
+        let bytes = self.payload.get(index * 8..index * 8 + 8)?;
+        Some(u64::from_le_bytes(bytes.try_into().ok()?))
+    }
+}
+
+/// A validated tag list.
+///
+/// [`parse`](Self::parse) checks every header up front, so iteration cannot
+/// fail and never reads out of bounds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TagList<'a> {
+    /// The tags, up to but excluding the end tag.
+    bytes: &'a [u8],
+}
+
+impl<'a> TagList<'a> {
+    /// A list with no tags (boot info older than version 3).
+    pub const fn empty() -> Self {
+        Self { bytes: &[] }
+    }
+
+    /// Validate `bytes` as a tag list. Bytes after the end tag are ignored.
+    ///
+    /// # Errors
+    ///
+    /// [`TagError::Malformed`] for a bad size, [`TagError::MissingEnd`] if
+    /// the bytes run out before the end tag.
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, TagError> {
+        let mut offset = 0;
+        loop {
+            let (ty, size) = read_header(bytes, offset).ok_or(TagError::MissingEnd)?;
+            let size = size as usize;
+            if size < TAG_HEADER_SIZE || offset + size > bytes.len() {
+                return Err(TagError::Malformed { offset });
+            }
+            if ty == tag_type::END {
+                return Ok(Self {
+                    bytes: &bytes[..offset],
+                });
+            }
+            offset = align_up(offset + size);
+        }
+    }
+
+    /// Iterate over the tags in order, end tag excluded.
+    pub fn iter(&self) -> TagIter<'a> {
+        TagIter {
+            bytes: self.bytes,
+            offset: 0,
+        }
+    }
+
+    /// The first tag of type `ty`.
+    pub fn find(&self, ty: u32) -> Option<Tag<'a>> {
+        self.iter().find(|tag| tag.ty == ty)
+    }
+
+    /// The boot modules, in load order.
+    ///
+    /// Tags that do not decode as a [`Module`] are skipped.
+    pub fn modules(&self) -> ModuleIter<'a> {
+        ModuleIter { tags: self.iter() }
+    }
+
+    /// Number of tags, end tag excluded.
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    /// True if the list holds only the end tag (or nothing at all).
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Size of the list in bytes, excluding the end tag.
+    pub fn size(&self) -> usize {
+        self.bytes.len()
+    }
+}
+
+impl<'a> IntoIterator for &TagList<'a> {
+    type Item = Tag<'a>;
+    type IntoIter = TagIter<'a>;
+
+    fn into_iter(self) -> TagIter<'a> {
+        self.iter()
+    }
+}
+
+/// Iterator over a [`TagList`].
+pub struct TagIter<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Iterator for TagIter<'a> {
+    type Item = Tag<'a>;
+
+    fn next(&mut self) -> Option<Tag<'a>> {
+        // The list was validated by `TagList::parse`; the checks here only
+        // keep the iterator safe on its own.
+        let (ty, size) = read_header(self.bytes, self.offset)?;
+        let start = self.offset + TAG_HEADER_SIZE;
+        let end = self.offset + size as usize;
+        let payload = self.bytes.get(start..end)?;
+        self.offset = align_up(end);
+        Some(Tag { ty, payload })
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Modules
+// ---------------------------------------------------------------------------
+
+/// A named blob the bootloader loaded for the kernel ([`tag_type::MODULE`]).
+///
+/// The bytes sit in LOADER_DATA pages at `phys_addr`; the kernel must keep
+/// `[phys_addr, phys_addr + size)` out of the frame allocator for as long
+/// as it uses them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Module<'a> {
+    /// Name given in the bootloader configuration, e.g. `"init"`.
+    pub name: &'a str,
+    /// Physical address of the first byte (page-aligned).
+    pub phys_addr: u64,
+    /// Size in bytes.
+    pub size: u64,
+}
+
+impl<'a> Module<'a> {
+    /// Decode a [`tag_type::MODULE`] tag.
+    ///
+    /// Returns `None` for other tag types, a short payload, a name that is
+    /// not UTF-8 or too long, or a range that wraps past `u64::MAX`.
+    pub fn from_tag(tag: &Tag<'a>) -> Option<Self> {
+        if tag.ty != tag_type::MODULE {
+            return None;
+        }
+        let phys_addr = tag.u64_at(0)?;
+        let size = tag.u64_at(1)?;
+        phys_addr.checked_add(size)?;
+        let name = core::str::from_utf8(&tag.payload[16..]).ok()?;
+        (name.len() <= crate::MAX_MODULE_NAME_LEN).then_some(Self {
+            name,
+            phys_addr,
+            size,
+        })
+    }
+
+    /// First address past the module.
+    pub fn phys_end(&self) -> u64 {
+        self.phys_addr + self.size
+    }
+}
+
+/// Iterator over the modules of a [`TagList`].
+pub struct ModuleIter<'a> {
+    tags: TagIter<'a>,
+}
+
+impl<'a> Iterator for ModuleIter<'a> {
+    type Item = Module<'a>;
+
+    fn next(&mut self) -> Option<Module<'a>> {
+        self.tags.by_ref().find_map(|tag| Module::from_tag(&tag))
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unassigned codes standing in for tags a newer bootloader might add.
+    const TEST_A: u32 = 0x7000_0001;
+    const TEST_B: u32 = 0x7000_0002;
+
+    fn build(tags: &[(u32, &[u8])], buf: &mut [u8]) -> usize {
+        let mut builder = TagBuilder::new(buf);
+        for &(ty, payload) in tags {
+            builder.add(ty, payload).unwrap();
+        }
+        builder.finish().unwrap()
+    }
+
+    #[test]
+    fn empty_list_is_just_the_end_tag() {
+        let mut buf = [0xAAu8; 16];
+        let size = build(&[], &mut buf);
+        assert_eq!(size, TAG_HEADER_SIZE);
+        assert_eq!(&buf[..8], &[0, 0, 0, 0, 8, 0, 0, 0]);
+
+        let list = TagList::parse(&buf[..size]).unwrap();
+        assert!(list.is_empty());
+        assert_eq!(list.iter().count(), 0);
+    }
+
+    #[test]
+    fn round_trip_preserves_order_and_payloads() {
+        let mut buf = [0u8; 128];
+        let size = build(
+            &[(TEST_A, b"hello"), (TEST_B, &[]), (TEST_A, b"12345678")],
+            &mut buf,
+        );
+        // 8+5 -> 16, 8 -> 8, 8+8 -> 16, end 8
+        assert_eq!(size, 48);
+
+        let list = TagList::parse(&buf[..size]).unwrap();
+        let tags: std::vec::Vec<_> = list.iter().collect();
+        assert_eq!(tags.len(), 3);
+        assert_eq!(
+            tags[0],
+            Tag {
+                ty: TEST_A,
+                payload: b"hello"
+            }
+        );
+        assert_eq!(
+            tags[1],
+            Tag {
+                ty: TEST_B,
+                payload: &[]
+            }
+        );
+        assert_eq!(tags[2].payload, b"12345678");
+        assert_eq!(list.find(TEST_A).unwrap().payload, b"hello");
+    }
+
+    #[test]
+    fn tags_are_eight_byte_aligned_and_padding_is_zero() {
+        let mut buf = [0xFFu8; 64];
+        build(&[(TEST_A, b"abc"), (TEST_B, b"x")], &mut buf);
+        assert_eq!(&buf[11..16], &[0; 5]);
+        assert_eq!(u32::from_le_bytes(buf[16..20].try_into().unwrap()), TEST_B);
+    }
+
+    #[test]
+    fn unknown_tags_are_skipped_by_find() {
+        let mut buf = [0u8; 64];
+        let size = build(&[(0xDEAD_BEEF, b"future"), (TEST_B, b"known")], &mut buf);
+        let list = TagList::parse(&buf[..size]).unwrap();
+        assert_eq!(list.find(TEST_B).unwrap().payload, b"known");
+        assert_eq!(list.find(TEST_A), None);
+        assert_eq!(tag_type::name(0xDEAD_BEEF), "unknown");
+    }
+
+    #[test]
+    fn add_with_writes_in_place() {
+        let mut buf = [0u8; 32];
+        let mut builder = TagBuilder::new(&mut buf);
+        builder
+            .add_with(TEST_A, 16, |out| {
+                out[..8].copy_from_slice(&0x1000u64.to_le_bytes());
+                out[8..].copy_from_slice(&0x2000u64.to_le_bytes());
+            })
+            .unwrap();
+        let size = builder.finish().unwrap();
+        let tag = TagList::parse(&buf[..size]).unwrap().find(TEST_A).unwrap();
+        assert_eq!(tag.payload.len(), 16);
+        assert_eq!(
+            u64::from_le_bytes(tag.payload[8..].try_into().unwrap()),
+            0x2000
+        );
+    }
+
+    #[test]
+    fn u64_payloads_round_trip() {
+        let mut buf = [0u8; 64];
+        let mut builder = TagBuilder::new(&mut buf);
+        builder
+            .add_u64s(tag_type::INITRD, &[0x20_0000, 0x1800])
+            .unwrap();
+        let size = builder.finish().unwrap();
+        let tag = TagList::parse(&buf[..size])
+            .unwrap()
+            .find(tag_type::INITRD)
+            .unwrap();
+        assert_eq!(tag.payload.len(), 16);
+        assert_eq!(tag.u64_at(0), Some(0x20_0000));
+        assert_eq!(tag.u64_at(1), Some(0x1800));
+        assert_eq!(tag.u64_at(2), None);
+    }
+
+    #[test]
+    fn modules_round_trip_in_order() {
+        let mut buf = [0u8; 256];
+        let mut builder = TagBuilder::new(&mut buf);
+        let init = Module {
+            name: "init",
+            phys_addr: 0x40_0000,
+            size: 0x1234,
+        };
+        let config = Module {
+            name: "config",
+            phys_addr: 0x50_0000,
+            size: 0,
+        };
+        builder.add_module(&init).unwrap();
+        builder.add(TEST_A, b"in between").unwrap();
+        builder.add_module(&config).unwrap();
+        let size = builder.finish().unwrap();
+
+        let list = TagList::parse(&buf[..size]).unwrap();
+        let modules: std::vec::Vec<_> = list.modules().collect();
+        assert_eq!(modules, [init, config]);
+        assert_eq!(modules[0].phys_end(), 0x40_1234);
+        assert_eq!(tag_type::name(tag_type::MODULE), "module");
+    }
+
+    #[test]
+    fn malformed_modules_are_skipped() {
+        let long = [b'x'; crate::MAX_MODULE_NAME_LEN + 1];
+        let long = core::str::from_utf8(&long).unwrap();
+        let mut buf = [0u8; 256];
+        let mut builder = TagBuilder::new(&mut buf);
+        assert_eq!(
+            builder.add_module(&Module {
+                name: long,
+                phys_addr: 0,
+                size: 0
+            }),
+            Err(TagError::NameTooLong)
+        );
+        // Too short for the two u64s.
+        builder.add(tag_type::MODULE, &[0; 12]).unwrap();
+        // Wraps past u64::MAX.
+        builder
+            .add_u64s(tag_type::MODULE, &[u64::MAX - 1, 2])
+            .unwrap();
+        // Name is not UTF-8.
+        builder
+            .add_with(tag_type::MODULE, 17, |out| out[16] = 0xFF)
+            .unwrap();
+        builder
+            .add_module(&Module {
+                name: "",
+                phys_addr: 0x1000,
+                size: 1,
+            })
+            .unwrap();
+        let size = builder.finish().unwrap();
+
+        let list = TagList::parse(&buf[..size]).unwrap();
+        assert_eq!(list.len(), 4);
+        let modules: std::vec::Vec<_> = list.modules().collect();
+        assert_eq!(modules.len(), 1);
+        assert_eq!(modules[0].name, "");
+        assert_eq!(modules[0].phys_addr, 0x1000);
+    }
+
+    #[test]
+    fn builder_rejects_end_type_and_full_buffers() {
+        let mut buf = [0u8; 24];
+        let mut builder = TagBuilder::new(&mut buf);
+        assert_eq!(builder.add(tag_type::END, b""), Err(TagError::ReservedType));
+        // 16 bytes of tag + 8 bytes of end tag fit exactly.
+        builder.add(TEST_A, b"12345678").unwrap();
+        assert_eq!(builder.add(TEST_B, b""), Err(TagError::BufferTooSmall));
+        assert_eq!(builder.len(), 16);
+        assert_eq!(builder.finish(), Ok(24));
+
+        let mut tiny = [0u8; 4];
+        assert_eq!(
+            TagBuilder::new(&mut tiny).finish(),
+            Err(TagError::BufferTooSmall)
+        );
+    }
+
+    #[test]
+    fn parse_rejects_truncated_and_malformed_lists() {
+        let mut buf = [0u8; 64];
+        let size = build(&[(TEST_A, b"payload")], &mut buf);
+
+        // Cut before the end tag.
+        assert_eq!(TagList::parse(&buf[..size - 8]), Err(TagError::MissingEnd));
+        assert_eq!(TagList::parse(&[]), Err(TagError::MissingEnd));
+
+        // Size smaller than a header.
+        let mut bad = buf;
+        bad[4..8].copy_from_slice(&4u32.to_le_bytes());
+        assert_eq!(
+            TagList::parse(&bad[..size]),
+            Err(TagError::Malformed { offset: 0 })
+        );
+
+        // Size running past the end of the list.
+        let mut bad = buf;
+        bad[4..8].copy_from_slice(&1000u32.to_le_bytes());
+        assert_eq!(
+            TagList::parse(&bad[..size]),
+            Err(TagError::Malformed { offset: 0 })
+        );
+    }
+
+    #[test]
+    fn parse_ignores_bytes_after_the_end_tag() {
+        let mut buf = [0xEEu8; 64];
+        let size = build(&[(TEST_A, b"x")], &mut buf);
+        let list = TagList::parse(&buf).unwrap();
+        assert_eq!(list.size(), size - TAG_HEADER_SIZE);
+        assert_eq!(list.len(), 1);
+    }
+}
@@ -3,10 +3,18 @@
 //! Core utilities for Ferrous Kernel that work in `no_std` environments.
 //!
 //! This library provides essential utilities without dependencies on the standard library.
+//!
+//! # Modules
+//!
+//! - [`sync`] — [`SpinLock`](sync::SpinLock), the one lock primitive shared
+//!   by the kernel and the allocator library.
 
 #![no_std]
-#![no_main]
+#![cfg_attr(not(test), no_main)]
 #![deny(unsafe_code)]
 #![warn(missing_docs)]
 
-// Core library implementation will be added in Phase 1
+pub mod sync;
+
+#[cfg(test)]
+extern crate std;
@@ -0,0 +1,1002 @@
+//! Minimal ELF64 image parser.
+//!
+//! The bootloader uses this crate to validate `\EFI\ferrous\kernel.elf` and
+//! to enumerate its `PT_LOAD` segments before copying them into memory. Only
+//! the subset of the ELF specification needed to load an x86-64 kernel is
+//! implemented: the file header, program headers, and — for
+//! position-independent (`ET_DYN`) images — the `DT_RELA` table, of which
+//! only `R_X86_64_RELATIVE` entries can be applied ([`ElfFile::relocate`]).
+//!
+//! All fields are decoded from the raw byte buffer with explicit
+//! little-endian reads — no pointer casts, no `unsafe`. Every offset and
+//! size is bounds-checked in [`ElfFile::parse`], so callers may index into
+//! the image with the returned values without further validation.
+
+#![no_std]
+
+/// The four-byte ELF magic: `0x7F 'E' 'L' 'F'`.
+pub const ELF_MAGIC: [u8; 4] = [0x7F, b'E', b'L', b'F'];
+
+/// `e_ident[EI_CLASS]` value for 64-bit objects.
+pub const ELFCLASS64: u8 = 2;
+
+/// `e_ident[EI_DATA]` value for little-endian objects.
+pub const ELFDATA2LSB: u8 = 1;
+
+/// `e_ident[EI_VERSION]` / `e_version` value for the current ELF version.
+pub const EV_CURRENT: u8 = 1;
+
+/// `e_machine` value for AMD x86-64.
+pub const EM_X86_64: u16 = 62;
+
+/// Object file types (`e_type`).
+pub mod object_type {
+    /// Executable file (fixed load address).
+    pub const ET_EXEC: u16 = 2;
+    /// Shared object / position-independent executable.
+    pub const ET_DYN: u16 = 3;
+}
+
+/// Program header types (`p_type`).
+pub mod segment_type {
+    /// Unused entry.
+    pub const PT_NULL: u32 = 0;
+    /// Loadable segment.
+    pub const PT_LOAD: u32 = 1;
+    /// Dynamic linking information.
+    pub const PT_DYNAMIC: u32 = 2;
+    /// GNU stack permissions marker.
+    pub const PT_GNU_STACK: u32 = 0x6474_E551;
+}
+
+/// Dynamic section tags (`d_tag`).
+pub mod dynamic_tag {
+    /// Ends the dynamic array.
+    pub const DT_NULL: u64 = 0;
+    /// Virtual address of the `Elf64_Rela` table.
+    pub const DT_RELA: u64 = 7;
+    /// Size of the `Elf64_Rela` table in bytes.
+    pub const DT_RELASZ: u64 = 8;
+    /// Size of one `Elf64_Rela` entry.
+    pub const DT_RELAENT: u64 = 9;
+}
+
+/// x86-64 relocation types (`ELF64_R_TYPE(r_info)`).
+pub mod relocation_type {
+    /// No-op.
+    pub const R_X86_64_NONE: u32 = 0;
+    /// `*r_offset = load_base + r_addend` (64-bit).
+    pub const R_X86_64_RELATIVE: u32 = 8;
+}
+
+/// Program header permission flags (`p_flags`).
+pub mod segment_flags {
+    /// Segment is executable.
+    pub const PF_X: u32 = 1;
+    /// Segment is writable.
+    pub const PF_W: u32 = 2;
+    /// Segment is readable.
+    pub const PF_R: u32 = 4;
+}
+
+/// Size of the ELF64 file header in bytes.
+pub const ELF64_HEADER_SIZE: usize = 64;
+
+/// Size of one ELF64 program header entry in bytes.
+pub const ELF64_PHDR_SIZE: usize = 56;
+
+/// Size of one ELF64 dynamic section entry (`Elf64_Dyn`) in bytes.
+pub const ELF64_DYN_SIZE: usize = 16;
+
+/// Size of one ELF64 relocation entry with addend (`Elf64_Rela`) in bytes.
+pub const ELF64_RELA_SIZE: usize = 24;
+
+// ---------------------------------------------------------------------------
+// Errors
+// ---------------------------------------------------------------------------
+
+/// Errors returned by [`ElfFile::parse`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ElfError {
+    /// The buffer is smaller than the 64-byte ELF64 header.
+    TooShort,
+    /// The first four bytes are not `\x7FELF`.
+    BadMagic,
+    /// `EI_CLASS` is not `ELFCLASS64`.
+    NotElf64,
+    /// `EI_DATA` is not little-endian.
+    NotLittleEndian,
+    /// `EI_VERSION` or `e_version` is not `EV_CURRENT`.
+    UnsupportedVersion,
+    /// `e_machine` is not x86-64.
+    UnsupportedMachine(u16),
+    /// `e_type` is neither `ET_EXEC` nor `ET_DYN`.
+    UnsupportedType(u16),
+    /// `e_phentsize` does not match the ELF64 program header size.
+    BadProgramHeaderSize(u16),
+    /// The program header table extends past the end of the buffer.
+    ProgramHeadersOutOfBounds,
+    /// The image has no `PT_LOAD` segments.
+    NoLoadableSegments,
+    /// A `PT_LOAD` segment's file bytes extend past the end of the buffer.
+    SegmentOutOfBounds {
+        /// Index of the offending program header.
+        index: usize,
+    },
+    /// A `PT_LOAD` segment has `p_filesz > p_memsz`.
+    SegmentFileSizeExceedsMemSize {
+        /// Index of the offending program header.
+        index: usize,
+    },
+    /// `p_vaddr + p_memsz` (or `p_paddr + p_memsz`) overflows 64 bits.
+    SegmentAddressOverflow {
+        /// Index of the offending program header.
+        index: usize,
+    },
+    /// The `PT_DYNAMIC` segment extends past the end of the buffer.
+    DynamicOutOfBounds,
+    /// `DT_RELAENT` does not match the ELF64 `Elf64_Rela` size.
+    BadRelocationEntrySize(u64),
+    /// The `DT_RELA` table is not inside the file bytes of a `PT_LOAD`
+    /// segment.
+    RelocationsOutOfBounds,
+    /// A relocation type other than `R_X86_64_NONE`/`R_X86_64_RELATIVE`.
+    UnsupportedRelocation {
+        /// Offending `ELF64_R_TYPE`.
+        r_type: u32,
+        /// Its `r_offset`.
+        offset: u64,
+    },
+    /// A relocation targets bytes outside the loaded image.
+    RelocationTargetOutOfBounds {
+        /// Offending `r_offset`.
+        offset: u64,
+    },
+}
+
+// ---------------------------------------------------------------------------
+// Header types
+// ---------------------------------------------------------------------------
+
+/// Decoded ELF64 file header (the fields the loader cares about).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elf64Header {
+    /// Object file type (see [`object_type`]).
+    pub e_type: u16,
+    /// Target architecture.
+    pub e_machine: u16,
+    /// Virtual address of the entry point.
+    pub e_entry: u64,
+    /// File offset of the program header table.
+    pub e_phoff: u64,
+    /// File offset of the section header table.
+    pub e_shoff: u64,
+    /// Processor-specific flags.
+    pub e_flags: u32,
+    /// Size of one program header entry.
+    pub e_phentsize: u16,
+    /// Number of program header entries.
+    pub e_phnum: u16,
+}
+
+/// Decoded ELF64 program header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProgramHeader {
+    /// Segment type (see [`segment_type`]).
+    pub p_type: u32,
+    /// Permission flags (see [`segment_flags`]).
+    pub p_flags: u32,
+    /// File offset of the segment's first byte.
+    pub p_offset: u64,
+    /// Virtual address of the segment's first byte.
+    pub p_vaddr: u64,
+    /// Physical address of the segment's first byte.
+    pub p_paddr: u64,
+    /// Number of bytes in the file image.
+    pub p_filesz: u64,
+    /// Number of bytes in memory; the tail beyond `p_filesz` is `.bss`.
+    pub p_memsz: u64,
+    /// Required alignment.
+    pub p_align: u64,
+}
+
+impl ProgramHeader {
+    /// True for `PT_LOAD` segments.
+    #[inline]
+    pub fn is_load(&self) -> bool {
+        self.p_type == segment_type::PT_LOAD
+    }
+
+    /// True if the segment is writable.
+    #[inline]
+    pub fn is_writable(&self) -> bool {
+        self.p_flags & segment_flags::PF_W != 0
+    }
+
+    /// True if the segment is executable.
+    #[inline]
+    pub fn is_executable(&self) -> bool {
+        self.p_flags & segment_flags::PF_X != 0
+    }
+
+    /// Number of zero-filled bytes after the file image (the `.bss` tail).
+    #[inline]
+    pub fn bss_size(&self) -> u64 {
+        self.p_memsz - self.p_filesz
+    }
+}
+
+/// Decoded ELF64 relocation entry with addend (`Elf64_Rela`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rela {
+    /// Link-time virtual address of the bytes to patch.
+    pub r_offset: u64,
+    /// Symbol index (high 32 bits) and relocation type (low 32 bits).
+    pub r_info: u64,
+    /// Constant addend.
+    pub r_addend: i64,
+}
+
+impl Rela {
+    /// Relocation type (see [`relocation_type`]).
+    #[inline]
+    pub fn r_type(&self) -> u32 {
+        self.r_info as u32
+    }
+
+    /// Symbol table index.
+    #[inline]
+    pub fn r_sym(&self) -> u32 {
+        (self.r_info >> 32) as u32
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Parsed file
+// ---------------------------------------------------------------------------
+
+/// A validated ELF64 image borrowed from a byte buffer.
+///
+/// Construction via [`ElfFile::parse`] checks the header and every `PT_LOAD`
+/// segment, so the accessors below never panic on a successfully parsed file.
+#[derive(Debug, Clone, Copy)]
+pub struct ElfFile<'a> {
+    data: &'a [u8],
+    header: Elf64Header,
+}
+
+impl<'a> ElfFile<'a> {
+    /// Parse and validate an ELF64 x86-64 executable.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`ElfError`] describing the first problem found. Only
+    /// little-endian, 64-bit, x86-64 `ET_EXEC`/`ET_DYN` images with at least
+    /// one `PT_LOAD` segment are accepted.
+    pub fn parse(data: &'a [u8]) -> Result<Self, ElfError> {
+        if data.len() < ELF64_HEADER_SIZE {
+            return Err(ElfError::TooShort);
+        }
+        if data[0..4] != ELF_MAGIC {
+            return Err(ElfError::BadMagic);
+        }
+        if data[4] != ELFCLASS64 {
+            return Err(ElfError::NotElf64);
+        }
+        if data[5] != ELFDATA2LSB {
+            return Err(ElfError::NotLittleEndian);
+        }
+        if data[6] != EV_CURRENT || read_u32(data, 20) != EV_CURRENT as u32 {
+            return Err(ElfError::UnsupportedVersion);
+        }
+
+        let header = Elf64Header {
+            e_type: read_u16(data, 16),
+            e_machine: read_u16(data, 18),
+            e_entry: read_u64(data, 24),
+            e_phoff: read_u64(data, 32),
+            e_shoff: read_u64(data, 40),
+            e_flags: read_u32(data, 48),
+            e_phentsize: read_u16(data, 54),
+            e_phnum: read_u16(data, 56),
+        };
+
+        if header.e_machine != EM_X86_64 {
+            return Err(ElfError::UnsupportedMachine(header.e_machine));
+        }
+        if header.e_type != object_type::ET_EXEC && header.e_type != object_type::ET_DYN {
+            return Err(ElfError::UnsupportedType(header.e_type));
+        }
+        if header.e_phentsize as usize != ELF64_PHDR_SIZE {
+            return Err(ElfError::BadProgramHeaderSize(header.e_phentsize));
+        }
+
+        let table_size = header.e_phnum as u64 * ELF64_PHDR_SIZE as u64;
+        match header.e_phoff.checked_add(table_size) {
+            Some(end) if end <= data.len() as u64 => {}
+            _ => return Err(ElfError::ProgramHeadersOutOfBounds),
+        }
+
+        let elf = Self { data, header };
+
+        let mut load_count = 0usize;
+        for (index, ph) in elf.program_headers().enumerate() {
+            if !ph.is_load() {
+                continue;
+            }
+            load_count += 1;
+
+            if ph.p_filesz > ph.p_memsz {
+                return Err(ElfError::SegmentFileSizeExceedsMemSize { index });
+            }
+            match ph.p_offset.checked_add(ph.p_filesz) {
+                Some(end) if end <= data.len() as u64 => {}
+                _ => return Err(ElfError::SegmentOutOfBounds { index }),
+            }
+            if ph.p_vaddr.checked_add(ph.p_memsz).is_none()
+                || ph.p_paddr.checked_add(ph.p_memsz).is_none()
+            {
+                return Err(ElfError::SegmentAddressOverflow { index });
+            }
+        }
+
+        if load_count == 0 {
+            return Err(ElfError::NoLoadableSegments);
+        }
+
+        Ok(elf)
+    }
+
+    /// Returns the decoded file header.
+    #[inline]
+    pub fn header(&self) -> &Elf64Header {
+        &self.header
+    }
+
+    /// Virtual address of the entry point.
+    #[inline]
+    pub fn entry(&self) -> u64 {
+        self.header.e_entry
+    }
+
+    /// Iterates over every program header in table order.
+    pub fn program_headers(&self) -> impl Iterator<Item = ProgramHeader> + 'a {
+        let data = self.data;
+        let base = self.header.e_phoff as usize;
+        (0..self.header.e_phnum as usize).map(move |i| {
+            let off = base + i * ELF64_PHDR_SIZE;
+            ProgramHeader {
+                p_type: read_u32(data, off),
+                p_flags: read_u32(data, off + 4),
+                p_offset: read_u64(data, off + 8),
+                p_vaddr: read_u64(data, off + 16),
+                p_paddr: read_u64(data, off + 24),
+                p_filesz: read_u64(data, off + 32),
+                p_memsz: read_u64(data, off + 40),
+                p_align: read_u64(data, off + 48),
+            }
+        })
+    }
+
+    /// Iterates over the `PT_LOAD` segments only.
+    pub fn load_segments(&self) -> impl Iterator<Item = ProgramHeader> + 'a {
+        self.program_headers().filter(ProgramHeader::is_load)
+    }
+
+    /// Returns the file bytes backing `ph` (`p_filesz` bytes at `p_offset`).
+    ///
+    /// `ph` must come from this file's [`load_segments`](Self::load_segments);
+    /// its bounds were validated during [`parse`](Self::parse).
+    pub fn segment_data(&self, ph: &ProgramHeader) -> &'a [u8] {
+        let start = ph.p_offset as usize;
+        &self.data[start..start + ph.p_filesz as usize]
+    }
+
+    /// Lowest and highest (exclusive) physical address spanned by the
+    /// `PT_LOAD` segments, or `None` if every segment is empty.
+    pub fn physical_bounds(&self) -> Option<(u64, u64)> {
+        self.bounds(|ph| ph.p_paddr)
+    }
+
+    /// Lowest and highest (exclusive) virtual address spanned by the
+    /// `PT_LOAD` segments, or `None` if every segment is empty.
+    pub fn virtual_bounds(&self) -> Option<(u64, u64)> {
+        self.bounds(|ph| ph.p_vaddr)
+    }
+
+    fn bounds(&self, start: impl Fn(&ProgramHeader) -> u64) -> Option<(u64, u64)> {
+        self.load_segments()
+            .filter(|ph| ph.p_memsz != 0)
+            .fold(None, |acc, ph| {
+                let (lo, hi) = (start(&ph), start(&ph) + ph.p_memsz);
+                Some(match acc {
+                    None => (lo, hi),
+                    Some((a, b)) => (a.min(lo), b.max(hi)),
+                })
+            })
+    }
+
+    /// True for `ET_DYN` images, which may be loaded at any base once
+    /// [`relocate`](Self::relocate)d.
+    #[inline]
+    pub fn is_position_independent(&self) -> bool {
+        self.header.e_type == object_type::ET_DYN
+    }
+
+    /// The `DT_RELA` relocation table.
+    ///
+    /// Empty if the image has no `PT_DYNAMIC` segment or the dynamic
+    /// section has no `DT_RELA` entry.
+    ///
+    /// # Errors
+    ///
+    /// [`ElfError::DynamicOutOfBounds`], [`ElfError::BadRelocationEntrySize`]
+    /// or [`ElfError::RelocationsOutOfBounds`] if the tables cannot be read.
+    pub fn relocations(&self) -> Result<impl Iterator<Item = Rela> + 'a, ElfError> {
+        let table = self.rela_table()?;
+        Ok(table.chunks_exact(ELF64_RELA_SIZE).map(|entry| Rela {
+            r_offset: read_u64(entry, 0),
+            r_info: read_u64(entry, 8),
+            r_addend: read_u64(entry, 16) as i64,
+        }))
+    }
+
+    /// Apply the relocations to `image`, the loaded memory image whose
+    /// first byte has link-time virtual address `link_base`, so that it
+    /// runs `slide` bytes above its link address. Returns the number of
+    /// relocations applied.
+    ///
+    /// Only `R_X86_64_RELATIVE` is supported; `R_X86_64_NONE` is skipped.
+    /// On error, entries before the offending one have been applied.
+    ///
+    /// # Errors
+    ///
+    /// As for [`relocations`](Self::relocations), plus
+    /// [`ElfError::UnsupportedRelocation`] and
+    /// [`ElfError::RelocationTargetOutOfBounds`].
+    pub fn relocate(
+        &self,
+        image: &mut [u8],
+        link_base: u64,
+        slide: u64,
+    ) -> Result<usize, ElfError> {
+        let mut applied = 0;
+        for rela in self.relocations()? {
+            match rela.r_type() {
+                relocation_type::R_X86_64_NONE => continue,
+                relocation_type::R_X86_64_RELATIVE => {}
+                r_type => {
+                    return Err(ElfError::UnsupportedRelocation {
+                        r_type,
+                        offset: rela.r_offset,
+                    })
+                }
+            }
+            let target = rela
+                .r_offset
+                .checked_sub(link_base)
+                .and_then(|off| usize::try_from(off).ok())
+                .and_then(|off| image.get_mut(off..off.checked_add(8)?))
+                .ok_or(ElfError::RelocationTargetOutOfBounds {
+                    offset: rela.r_offset,
+                })?;
+            let value = (rela.r_addend as u64).wrapping_add(slide);
+            target.copy_from_slice(&value.to_le_bytes());
+            applied += 1;
+        }
+        Ok(applied)
+    }
+
+    /// File bytes of the `DT_RELA` table; empty if there is none.
+    fn rela_table(&self) -> Result<&'a [u8], ElfError> {
+        let Some(dynamic) = self
+            .program_headers()
+            .find(|ph| ph.p_type == segment_type::PT_DYNAMIC)
+        else {
+            return Ok(&[]);
+        };
+        let dynamic = dynamic
+            .p_offset
+            .checked_add(dynamic.p_filesz)
+            .filter(|&end| end <= self.data.len() as u64)
+            .map(|end| &self.data[dynamic.p_offset as usize..end as usize])
+            .ok_or(ElfError::DynamicOutOfBounds)?;
+
+        let (mut rela, mut relasz, mut relaent) = (None, 0, ELF64_RELA_SIZE as u64);
+        for entry in dynamic.chunks_exact(ELF64_DYN_SIZE) {
+            let value = read_u64(entry, 8);
+            match read_u64(entry, 0) {
+                dynamic_tag::DT_NULL => break,
+                dynamic_tag::DT_RELA => rela = Some(value),
+                dynamic_tag::DT_RELASZ => relasz = value,
+                dynamic_tag::DT_RELAENT => relaent = value,
+                _ => {}
+            }
+        }
+        let Some(rela) = rela else {
+            return Ok(&[]);
+        };
+        if relaent != ELF64_RELA_SIZE as u64 {
+            return Err(ElfError::BadRelocationEntrySize(relaent));
+        }
+
+        // DT_RELA is a virtual address; find the file bytes behind it.
+        self.load_segments()
+            .find_map(|ph| {
+                let start = rela.checked_sub(ph.p_vaddr)?;
+                let end = start.checked_add(relasz)?;
+                (end <= ph.p_filesz).then(|| {
+                    let off = (ph.p_offset + start) as usize;
+                    &self.data[off..off + relasz as usize]
+                })
+            })
+            .ok_or(ElfError::RelocationsOutOfBounds)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Little-endian field readers
+//
+// Callers guarantee `off + size_of::<T>() <= data.len()`; the header and
+// program-header bounds checks in `parse` establish this for every read.
+// ---------------------------------------------------------------------------
+
+fn read_u16(data: &[u8], off: usize) -> u16 {
+    u16::from_le_bytes([data[off], data[off + 1]])
+}
+
+fn read_u32(data: &[u8], off: usize) -> u32 {
+    let mut b = [0u8; 4];
+    b.copy_from_slice(&data[off..off + 4]);
+    u32::from_le_bytes(b)
+}
+
+fn read_u64(data: &[u8], off: usize) -> u64 {
+    let mut b = [0u8; 8];
+    b.copy_from_slice(&data[off..off + 8]);
+    u64::from_le_bytes(b)
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+extern crate std;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec;
+    use std::vec::Vec;
+
+    /// Description of one program header for [`build_elf`].
+    struct Seg {
+        p_type: u32,
+        flags: u32,
+        addr: u64,
+        data: Vec<u8>,
+        memsz: u64,
+    }
+
+    fn load(flags: u32, addr: u64, data: &[u8], memsz: u64) -> Seg {
+        Seg {
+            p_type: segment_type::PT_LOAD,
+            flags,
+            addr,
+            data: data.to_vec(),
+            memsz,
+        }
+    }
+
+    /// Assemble a minimal ELF64 image: header, program headers, then each
+    /// segment's file bytes back to back.
+    fn build_elf(e_type: u16, entry: u64, segs: &[Seg]) -> Vec<u8> {
+        let phoff = ELF64_HEADER_SIZE;
+        let mut data_off = phoff + segs.len() * ELF64_PHDR_SIZE;
+
+        let mut out = vec![0u8; data_off];
+        out[0..4].copy_from_slice(&ELF_MAGIC);
+        out[4] = ELFCLASS64;
+        out[5] = ELFDATA2LSB;
+        out[6] = EV_CURRENT;
+        out[16..18].copy_from_slice(&e_type.to_le_bytes());
+        out[18..20].copy_from_slice(&EM_X86_64.to_le_bytes());
+        out[20..24].copy_from_slice(&1u32.to_le_bytes());
+        out[24..32].copy_from_slice(&entry.to_le_bytes());
+        out[32..40].copy_from_slice(&(phoff as u64).to_le_bytes());
+        out[52..54].copy_from_slice(&(ELF64_HEADER_SIZE as u16).to_le_bytes());
+        out[54..56].copy_from_slice(&(ELF64_PHDR_SIZE as u16).to_le_bytes());
+        out[56..58].copy_from_slice(&(segs.len() as u16).to_le_bytes());
+
+        for (i, seg) in segs.iter().enumerate() {
+            let off = phoff + i * ELF64_PHDR_SIZE;
+            let ph = &mut out[off..off + ELF64_PHDR_SIZE];
+            ph[0..4].copy_from_slice(&seg.p_type.to_le_bytes());
+            ph[4..8].copy_from_slice(&seg.flags.to_le_bytes());
+            ph[8..16].copy_from_slice(&(data_off as u64).to_le_bytes());
+            ph[16..24].copy_from_slice(&seg.addr.to_le_bytes());
+            ph[24..32].copy_from_slice(&seg.addr.to_le_bytes());
+            ph[32..40].copy_from_slice(&(seg.data.len() as u64).to_le_bytes());
+            ph[40..48].copy_from_slice(&seg.memsz.to_le_bytes());
+            ph[48..56].copy_from_slice(&0x1000u64.to_le_bytes());
+            data_off += seg.data.len();
+        }
+        for seg in segs {
+            out.extend_from_slice(&seg.data);
+        }
+        out
+    }
+
+    fn sample_kernel() -> Vec<u8> {
+        use segment_flags::*;
+        build_elf(
+            object_type::ET_EXEC,
+            0x20_0000,
+            &[
+                load(PF_R | PF_X, 0x20_0000, &[0x90, 0x90, 0xF4], 3),
+                load(PF_R | PF_W, 0x20_1000, &[1, 2, 3, 4], 0x2000),
+            ],
+        )
+    }
+
+    // -----------------------------------------------------------------------
+    // Happy path
+    // -----------------------------------------------------------------------
+
+    #[test]
+    fn parses_minimal_kernel() {
+        let image = sample_kernel();
+        let elf = ElfFile::parse(&image).unwrap();
+        assert_eq!(elf.entry(), 0x20_0000);
+        assert_eq!(elf.header().e_type, object_type::ET_EXEC);
+        assert_eq!(elf.load_segments().count(), 2);
+    }
+
+    #[test]
+    fn segment_data_returns_file_bytes() {
+        let image = sample_kernel();
+        let elf = ElfFile::parse(&image).unwrap();
+        let segs: Vec<_> = elf.load_segments().collect();
+        assert_eq!(elf.segment_data(&segs[0]), &[0x90, 0x90, 0xF4]);
+        assert_eq!(elf.segment_data(&segs[1]), &[1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn bss_size_is_memsz_minus_filesz() {
+        let image = sample_kernel();
+        let elf = ElfFile::parse(&image).unwrap();
+        let data = elf.load_segments().nth(1).unwrap();
+        assert!(data.is_writable());
+        assert!(!data.is_executable());
+        assert_eq!(data.bss_size(), 0x2000 - 4);
+    }
+
+    #[test]
+    fn physical_bounds_span_all_segments() {
+        let image = sample_kernel();
+        let elf = ElfFile::parse(&image).unwrap();
+        assert_eq!(elf.physical_bounds(), Some((0x20_0000, 0x20_3000)));
+    }
+
+    #[test]
+    fn non_load_segments_are_skipped() {
+        let mut segs = vec![Seg {
+            p_type: segment_type::PT_GNU_STACK,
+            flags: segment_flags::PF_R | segment_flags::PF_W,
+            addr: 0,
+            data: Vec::new(),
+            memsz: 0,
+        }];
+        segs.push(load(segment_flags::PF_R, 0x10_0000, &[0xAA], 1));
+        let image = build_elf(object_type::ET_EXEC, 0x10_0000, &segs);
+        let elf = ElfFile::parse(&image).unwrap();
+        assert_eq!(elf.program_headers().count(), 2);
+        assert_eq!(elf.load_segments().count(), 1);
+    }
+
+    #[test]
+    fn position_independent_images_are_accepted() {
+        let image = build_elf(
+            object_type::ET_DYN,
+            0x1000,
+            &[load(segment_flags::PF_R, 0, &[0], 1)],
+        );
+        assert!(ElfFile::parse(&image).is_ok());
+    }
+
+    // -----------------------------------------------------------------------
+    // Header validation
+    // -----------------------------------------------------------------------
+
+    #[test]
+    fn short_buffer_is_rejected() {
+        assert_eq!(
+            ElfFile::parse(&[0x7F, b'E']).unwrap_err(),
+            ElfError::TooShort
+        );
+    }
+
+    #[test]
+    fn bad_magic_is_rejected() {
+        let mut image = sample_kernel();
+        image[0] = b'M';
+        assert_eq!(ElfFile::parse(&image).unwrap_err(), ElfError::BadMagic);
+    }
+
+    #[test]
+    fn elf32_is_rejected() {
+        let mut image = sample_kernel();
+        image[4] = 1; // ELFCLASS32
+        assert_eq!(ElfFile::parse(&image).unwrap_err(), ElfError::NotElf64);
+    }
+
+    #[test]
+    fn big_endian_is_rejected() {
+        let mut image = sample_kernel();
+        image[5] = 2; // ELFDATA2MSB
+        assert_eq!(
+            ElfFile::parse(&image).unwrap_err(),
+            ElfError::NotLittleEndian
+        );
+    }
+
+    #[test]
+    fn wrong_machine_is_rejected() {
+        let mut image = sample_kernel();
+        image[18..20].copy_from_slice(&183u16.to_le_bytes()); // EM_AARCH64
+        assert_eq!(
+            ElfFile::parse(&image).unwrap_err(),
+            ElfError::UnsupportedMachine(183)
+        );
+    }
+
+    #[test]
+    fn relocatable_object_is_rejected() {
+        let mut image = sample_kernel();
+        image[16..18].copy_from_slice(&1u16.to_le_bytes()); // ET_REL
+        assert_eq!(
+            ElfFile::parse(&image).unwrap_err(),
+            ElfError::UnsupportedType(1)
+        );
+    }
+
+    #[test]
+    fn wrong_phentsize_is_rejected() {
+        let mut image = sample_kernel();
+        image[54..56].copy_from_slice(&32u16.to_le_bytes());
+        assert_eq!(
+            ElfFile::parse(&image).unwrap_err(),
+            ElfError::BadProgramHeaderSize(32)
+        );
+    }
+
+    #[test]
+    fn truncated_program_header_table_is_rejected() {
+        let image = sample_kernel();
+        let cut = ELF64_HEADER_SIZE + ELF64_PHDR_SIZE + 8;
+        assert_eq!(
+            ElfFile::parse(&image[..cut]).unwrap_err(),
+            ElfError::ProgramHeadersOutOfBounds
+        );
+    }
+
+    #[test]
+    fn image_without_load_segments_is_rejected() {
+        let image = build_elf(object_type::ET_EXEC, 0, &[]);
+        assert_eq!(
+            ElfFile::parse(&image).unwrap_err(),
+            ElfError::NoLoadableSegments
+        );
+    }
+
+    // -----------------------------------------------------------------------
+    // Segment validation
+    // -----------------------------------------------------------------------
+
+    #[test]
+    fn segment_past_end_of_file_is_rejected() {
+        let mut image = sample_kernel();
+        image.truncate(image.len() - 2); // chop the data segment's bytes
+        assert_eq!(
+            ElfFile::parse(&image).unwrap_err(),
+            ElfError::SegmentOutOfBounds { index: 1 }
+        );
+    }
+
+    #[test]
+    fn filesz_larger_than_memsz_is_rejected() {
+        let image = build_elf(
+            object_type::ET_EXEC,
+            0,
+            &[load(segment_flags::PF_R, 0x1000, &[1, 2, 3], 2)],
+        );
+        assert_eq!(
+            ElfFile::parse(&image).unwrap_err(),
+            ElfError::SegmentFileSizeExceedsMemSize { index: 0 }
+        );
+    }
+
+    #[test]
+    fn address_overflow_is_rejected() {
+        let image = build_elf(
+            object_type::ET_EXEC,
+            0,
+            &[load(segment_flags::PF_R, u64::MAX - 1, &[0], 0x10)],
+        );
+        assert_eq!(
+            ElfFile::parse(&image).unwrap_err(),
+            ElfError::SegmentAddressOverflow { index: 0 }
+        );
+    }
+
+    // -----------------------------------------------------------------------
+    // Relocations
+    // -----------------------------------------------------------------------
+
+    fn rela(offset: u64, r_type: u32, addend: i64) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&offset.to_le_bytes());
+        out.extend_from_slice(&(r_type as u64).to_le_bytes());
+        out.extend_from_slice(&addend.to_le_bytes());
+        out
+    }
+
+    fn dynamic(entries: &[(u64, u64)]) -> Seg {
+        let mut data = Vec::new();
+        for &(tag, value) in entries.iter().chain(&[(dynamic_tag::DT_NULL, 0)]) {
+            data.extend_from_slice(&tag.to_le_bytes());
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        let memsz = data.len() as u64;
+        Seg {
+            p_type: segment_type::PT_DYNAMIC,
+            flags: segment_flags::PF_R | segment_flags::PF_W,
+            addr: 0x3000,
+            data,
+            memsz,
+        }
+    }
+
+    /// A PIE linked at 0x1000 whose RELA table (at 0x1000) patches two
+    /// pointers in the data segment at 0x2000.
+    fn sample_pie(relocs: &[Vec<u8>], relaent: u64) -> Vec<u8> {
+        use dynamic_tag::*;
+        use segment_flags::*;
+        let table = relocs.concat();
+        build_elf(
+            object_type::ET_DYN,
+            0x1000,
+            &[
+                load(PF_R, 0x1000, &table, table.len() as u64),
+                load(PF_R | PF_W, 0x2000, &[0; 16], 16),
+                dynamic(&[
+                    (DT_RELA, 0x1000),
+                    (DT_RELASZ, table.len() as u64),
+                    (DT_RELAENT, relaent),
+                ]),
+            ],
+        )
+    }
+
+    #[test]
+    fn relocations_are_read_from_the_dynamic_section() {
+        use relocation_type::*;
+        let image = sample_pie(
+            &[
+                rela(0x2000, R_X86_64_RELATIVE, 0x1010),
+                rela(0, R_X86_64_NONE, 0),
+            ],
+            ELF64_RELA_SIZE as u64,
+        );
+        let elf = ElfFile::parse(&image).unwrap();
+        assert!(elf.is_position_independent());
+        assert_eq!(elf.virtual_bounds(), Some((0x1000, 0x2010)));
+        let relocs: Vec<_> = elf.relocations().unwrap().collect();
+        assert_eq!(relocs.len(), 2);
+        assert_eq!(relocs[0].r_offset, 0x2000);
+        assert_eq!(relocs[0].r_type(), R_X86_64_RELATIVE);
+        assert_eq!(relocs[0].r_sym(), 0);
+        assert_eq!(relocs[0].r_addend, 0x1010);
+    }
+
+    #[test]
+    fn relative_relocations_add_the_slide() {
+        use relocation_type::*;
+        let image = sample_pie(
+            &[
+                rela(0x2000, R_X86_64_RELATIVE, 0x1010),
+                rela(0x2008, R_X86_64_RELATIVE, 0x2000),
+                rela(0, R_X86_64_NONE, 0),
+            ],
+            ELF64_RELA_SIZE as u64,
+        );
+        let elf = ElfFile::parse(&image).unwrap();
+        // Memory image of [0x1000, 0x3000).
+        let mut memory = vec![0u8; 0x2000];
+        let slide = 0xFFFF_8000_0000_0000;
+        assert_eq!(elf.relocate(&mut memory, 0x1000, slide), Ok(2));
+        assert_eq!(read_u64(&memory, 0x1000), slide + 0x1010);
+        assert_eq!(read_u64(&memory, 0x1008), slide + 0x2000);
+    }
+
+    #[test]
+    fn images_without_dynamic_section_have_no_relocations() {
+        let image = sample_kernel();
+        let elf = ElfFile::parse(&image).unwrap();
+        assert!(!elf.is_position_independent());
+        assert_eq!(elf.relocations().unwrap().count(), 0);
+        assert_eq!(elf.relocate(&mut [0; 16], 0x20_0000, 0x1000), Ok(0));
+    }
+
+    #[test]
+    fn unsupported_relocations_are_rejected() {
+        const R_X86_64_64: u32 = 1;
+        let image = sample_pie(&[rela(0x2000, R_X86_64_64, 0)], ELF64_RELA_SIZE as u64);
+        let elf = ElfFile::parse(&image).unwrap();
+        assert_eq!(
+            elf.relocate(&mut [0; 0x2000], 0x1000, 0),
+            Err(ElfError::UnsupportedRelocation {
+                r_type: R_X86_64_64,
+                offset: 0x2000
+            })
+        );
+    }
+
+    #[test]
+    fn relocation_targets_outside_the_image_are_rejected() {
+        use relocation_type::R_X86_64_RELATIVE;
+        let image = sample_pie(
+            &[rela(0x2FFC, R_X86_64_RELATIVE, 0)],
+            ELF64_RELA_SIZE as u64,
+        );
+        let elf = ElfFile::parse(&image).unwrap();
+        assert_eq!(
+            elf.relocate(&mut [0; 0x2000], 0x1000, 0),
+            Err(ElfError::RelocationTargetOutOfBounds { offset: 0x2FFC })
+        );
+        assert_eq!(
+            elf.relocate(&mut [0; 0x2000], 0x4000, 0),
+            Err(ElfError::RelocationTargetOutOfBounds { offset: 0x2FFC })
+        );
+    }
+
+    #[test]
+    fn bad_relocation_tables_are_rejected() {
+        use relocation_type::R_X86_64_RELATIVE;
+        let image = sample_pie(&[rela(0x2000, R_X86_64_RELATIVE, 0)], 16);
+        let elf = ElfFile::parse(&image).unwrap();
+        assert_eq!(
+            elf.relocations().err(),
+            Some(ElfError::BadRelocationEntrySize(16))
+        );
+
+        // DT_RELA pointing into the data segment's .bss tail.
+        use dynamic_tag::*;
+        let image = build_elf(
+            object_type::ET_DYN,
+            0,
+            &[
+                load(segment_flags::PF_R, 0x1000, &[0; 8], 0x100),
+                dynamic(&[(DT_RELA, 0x1040), (DT_RELASZ, 24)]),
+            ],
+        );
+        let elf = ElfFile::parse(&image).unwrap();
+        assert_eq!(
+            elf.relocations().err(),
+            Some(ElfError::RelocationsOutOfBounds)
+        );
+    }
+
+    // -----------------------------------------------------------------------
+    // Layout constants
+    // -----------------------------------------------------------------------
+
+    #[test]
+    fn header_sizes_match_elf64_spec() {
+        assert_eq!(ELF64_HEADER_SIZE, 64);
+        assert_eq!(ELF64_PHDR_SIZE, 56);
+        assert_eq!(ELF64_DYN_SIZE, 16);
+        assert_eq!(ELF64_RELA_SIZE, 24);
+    }
+}
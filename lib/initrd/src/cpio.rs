@@ -0,0 +1,130 @@
+//! SVR4 "newc" cpio archives (`cpio -H newc`, the Linux initramfs format).
+//!
+//! Each entry is a 110-byte ASCII header — the magic `070701` (or `070702`
+//! with checksums) and thirteen 8-digit hex fields — followed by the
+//! NUL-terminated name and the data, each padded to a multiple of four
+//! bytes. The entry named `TRAILER!!!` ends the archive.
+
+use crate::{str_field, Entry, EntryKind, EntryPath, InitrdError};
+
+/// Size of the fixed header.
+const HEADER_SIZE: usize = 110;
+
+/// Name of the entry that ends the archive.
+const TRAILER: &str = "TRAILER!!!";
+
+/// File type bits of `mode` (`S_IFMT`) and the types we distinguish.
+const S_IFMT: u32 = 0o170_000;
+const S_IFREG: u32 = 0o100_000;
+const S_IFDIR: u32 = 0o040_000;
+const S_IFLNK: u32 = 0o120_000;
+
+/// Header fields, in order after the magic.
+const FIELD_MODE: usize = 1;
+const FIELD_FILESIZE: usize = 6;
+const FIELD_NAMESIZE: usize = 11;
+
+/// True if `bytes` starts with a newc header.
+pub(crate) fn is_newc(bytes: &[u8]) -> bool {
+    bytes.starts_with(b"070701") || bytes.starts_with(b"070702")
+}
+
+/// Decode the entry whose header starts at `offset`, and the offset of the
+/// next header.
+///
+/// Returns `Ok(None)` at the trailer.
+pub(crate) fn entry_at(
+    bytes: &[u8],
+    offset: usize,
+) -> Result<Option<(Entry<'_>, usize)>, InitrdError> {
+    let header = bytes
+        .get(offset..offset + HEADER_SIZE)
+        .ok_or(InitrdError::Truncated { offset })?;
+    if !is_newc(header) {
+        return Err(InitrdError::BadHeader { offset });
+    }
+    let field = |index: usize| {
+        let start = 6 + 8 * index;
+        hex(&header[start..start + 8]).ok_or(InitrdError::BadHeader { offset })
+    };
+    let mode = field(FIELD_MODE)?;
+    let size = field(FIELD_FILESIZE)? as usize;
+    let name_size = field(FIELD_NAMESIZE)? as usize;
+
+    // The name includes its NUL terminator, so it is never empty.
+    let name_start = offset + HEADER_SIZE;
+    let name_end = name_start + name_size;
+    let raw_name = match name_size {
+        0 => return Err(InitrdError::BadHeader { offset }),
+        _ => bytes
+            .get(name_start..name_end)
+            .ok_or(InitrdError::Truncated { offset })?,
+    };
+    let name = str_field(raw_name).ok_or(InitrdError::InvalidPath { offset })?;
+    if name == TRAILER {
+        return Ok(None);
+    }
+
+    let data_start = name_end.next_multiple_of(4);
+    let data = bytes
+        .get(data_start..data_start + size)
+        .ok_or(InitrdError::Truncated { offset })?;
+    let next = (data_start + size).next_multiple_of(4);
+
+    let kind = match mode & S_IFMT {
+        S_IFREG => EntryKind::File,
+        S_IFDIR => EntryKind::Directory,
+        S_IFLNK => EntryKind::Symlink,
+        _ => EntryKind::Other,
+    };
+    // A symlink's data is its target.
+    let link = match kind {
+        EntryKind::Symlink => {
+            core::str::from_utf8(data).map_err(|_| InitrdError::InvalidPath { offset })?
+        }
+        _ => "",
+    };
+
+    let entry = Entry {
+        path: EntryPath::new("", name),
+        kind,
+        mode: mode & 0o7777,
+        data: if kind == EntryKind::Symlink {
+            &[]
+        } else {
+            data
+        },
+        link,
+    };
+    Ok(Some((entry, next)))
+}
+
+/// Parse an 8-digit hexadecimal field (either case).
+fn hex(field: &[u8]) -> Option<u32> {
+    let text = core::str::from_utf8(field).ok()?;
+    if !text.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    u32::from_str_radix(text, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_fields() {
+        assert_eq!(hex(b"000081A4"), Some(0o100_644));
+        assert_eq!(hex(b"0000001f"), Some(31));
+        assert_eq!(hex(b"+0000001"), None);
+        assert_eq!(hex(b"0000000g"), None);
+    }
+
+    #[test]
+    fn header_shorter_than_110_bytes_is_truncated() {
+        assert_eq!(
+            entry_at(b"070701000000", 0).unwrap_err(),
+            InitrdError::Truncated { offset: 0 }
+        );
+    }
+}
@@ -0,0 +1,482 @@
+//! Read-only initial ramdisk archives.
+//!
+//! The bootloader loads `\EFI\ferrous\initrd.tar` into memory unchanged and
+//! reports its physical range in boot info; the kernel opens it with
+//! [`Archive::parse`] to list entries and to read files by path. Two
+//! formats are recognised by their magic:
+//!
+//! - POSIX ustar — `tar --format=ustar -cf initrd.tar ...`, including GNU
+//!   long names and pax extended headers (`--format=gnu`, `--format=pax`)
+//! - SVR4 newc cpio — `find . | cpio -o -H newc > initrd.tar`
+//!
+//! File contents are borrowed straight from the archive buffer: no copies,
+//! no allocation, no `unsafe`. [`Archive::parse`] walks every header once
+//! and bounds-checks it, so the iterator and lookups cannot fail afterwards.
+//!
+//! Paths are compared after dropping a leading `/` or `./` and a trailing
+//! `/`, so `bin/init`, `./bin/init` and `/bin/init` name the same entry.
+
+#![no_std]
+
+mod cpio;
+mod ustar;
+
+use core::fmt;
+
+/// Archive format, detected from the first header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// POSIX (or GNU-flavoured) ustar tar.
+    Ustar,
+    /// SVR4 newc cpio, with or without checksums.
+    CpioNewc,
+}
+
+/// Errors returned by [`Archive::parse`]. `offset` is the byte offset of
+/// the offending header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InitrdError {
+    /// Neither a ustar nor a newc header at offset 0.
+    UnknownFormat,
+    /// A header, name or data runs past the end of the buffer.
+    Truncated { offset: usize },
+    /// Bad magic or a non-numeric field.
+    BadHeader { offset: usize },
+    /// ustar header checksum mismatch.
+    BadChecksum { offset: usize },
+    /// A path or link target is not UTF-8.
+    InvalidPath { offset: usize },
+}
+
+/// Type of an archive entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    File,
+    Directory,
+    Symlink,
+    /// Hard links, devices, FIFOs and the like.
+    Other,
+}
+
+/// Path of an entry: ustar splits long paths into a prefix and a name.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EntryPath<'a> {
+    prefix: &'a str,
+    name: &'a str,
+}
+
+impl<'a> EntryPath<'a> {
+    fn new(prefix: &'a str, name: &'a str) -> Self {
+        if prefix.is_empty() {
+            Self {
+                prefix: "",
+                name: normalise(name),
+            }
+        } else {
+            Self {
+                prefix: normalise(prefix),
+                name: name.trim_end_matches('/'),
+            }
+        }
+    }
+
+    /// True for the archive root (`.`).
+    pub fn is_empty(&self) -> bool {
+        self.prefix.is_empty() && self.name.is_empty()
+    }
+
+    /// True if this is `path`, ignoring a leading `/` or `./` and a
+    /// trailing `/`.
+    pub fn matches(&self, path: &str) -> bool {
+        let path = normalise(path);
+        if self.prefix.is_empty() {
+            return path == self.name;
+        }
+        path.strip_prefix(self.prefix)
+            .and_then(|rest| rest.strip_prefix('/'))
+            .is_some_and(|rest| rest == self.name)
+    }
+}
+
+impl fmt::Display for EntryPath<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.prefix.is_empty() {
+            f.write_str(self.name)
+        } else {
+            write!(f, "{}/{}", self.prefix, self.name)
+        }
+    }
+}
+
+/// One entry of an [`Archive`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry<'a> {
+    path: EntryPath<'a>,
+    kind: EntryKind,
+    mode: u32,
+    data: &'a [u8],
+    link: &'a str,
+}
+
+impl<'a> Entry<'a> {
+    /// Normalised path (see the crate documentation).
+    pub fn path(&self) -> EntryPath<'a> {
+        self.path
+    }
+
+    pub fn kind(&self) -> EntryKind {
+        self.kind
+    }
+
+    /// Permission bits (`0o7777`).
+    pub fn mode(&self) -> u32 {
+        self.mode
+    }
+
+    /// Contents of a file; empty for every other kind.
+    pub fn data(&self) -> &'a [u8] {
+        match self.kind {
+            EntryKind::File => self.data,
+            _ => &[],
+        }
+    }
+
+    /// Target of a symbolic link; empty for every other kind.
+    pub fn link_target(&self) -> &'a str {
+        self.link
+    }
+}
+
+/// A validated archive borrowed from its buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Archive<'a> {
+    bytes: &'a [u8],
+    format: Format,
+    len: usize,
+}
+
+impl<'a> Archive<'a> {
+    /// Detect the format and validate every header.
+    ///
+    /// # Errors
+    ///
+    /// [`InitrdError::UnknownFormat`] if `bytes` starts with neither magic,
+    /// otherwise the first problem found walking the headers.
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, InitrdError> {
+        let format = if cpio::is_newc(bytes) {
+            Format::CpioNewc
+        } else if ustar::is_ustar(bytes) {
+            Format::Ustar
+        } else {
+            return Err(InitrdError::UnknownFormat);
+        };
+        let mut archive = Self {
+            bytes,
+            format,
+            len: 0,
+        };
+        let mut offset = 0;
+        while let Some((entry, next)) = archive.entry_at(offset)? {
+            archive.len += usize::from(!entry.path.is_empty());
+            offset = next;
+        }
+        Ok(archive)
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Number of entries [`entries`](Self::entries) yields.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Entries in archive order, without the root directory. Long-name
+    /// records are folded into the entry they describe.
+    pub fn entries(&self) -> Entries<'a> {
+        Entries {
+            archive: *self,
+            offset: 0,
+        }
+    }
+
+    /// The entry at `path`, of any kind.
+    pub fn find(&self, path: &str) -> Option<Entry<'a>> {
+        self.entries().find(|e| e.path.matches(path))
+    }
+
+    /// Contents of the regular file at `path`.
+    pub fn file(&self, path: &str) -> Option<&'a [u8]> {
+        self.find(path)
+            .filter(|e| e.kind == EntryKind::File)
+            .map(|e| e.data)
+    }
+
+    fn entry_at(&self, offset: usize) -> Result<Option<(Entry<'a>, usize)>, InitrdError> {
+        match self.format {
+            Format::Ustar => ustar::entry_at(self.bytes, offset),
+            Format::CpioNewc => cpio::entry_at(self.bytes, offset),
+        }
+    }
+}
+
+/// Iterator over the entries of an [`Archive`].
+pub struct Entries<'a> {
+    archive: Archive<'a>,
+    offset: usize,
+}
+
+impl<'a> Iterator for Entries<'a> {
+    type Item = Entry<'a>;
+
+    fn next(&mut self) -> Option<Entry<'a>> {
+        loop {
+            // Validated by `Archive::parse`, so errors cannot occur here.
+            let (entry, next) = self.archive.entry_at(self.offset).ok()??;
+            self.offset = next;
+            if !entry.path.is_empty() {
+                return Some(entry);
+            }
+        }
+    }
+}
+
+/// Drop a leading `/` or `./` (repeatedly) and trailing `/`s; `.` alone
+/// becomes empty.
+fn normalise(path: &str) -> &str {
+    let mut path = path.trim_end_matches('/');
+    loop {
+        if let Some(rest) = path.strip_prefix("./") {
+            path = rest;
+        } else if let Some(rest) = path.strip_prefix('/') {
+            path = rest;
+        } else if path == "." {
+            return "";
+        } else {
+            return path;
+        }
+    }
+}
+
+/// A NUL-padded byte field as a string, or `None` if it is not UTF-8.
+fn str_field(field: &[u8]) -> Option<&str> {
+    let len = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    core::str::from_utf8(&field[..len]).ok()
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+//
+// The fixtures under `testdata/` were produced by GNU tar and bsdtar from the
+// same tree (see `testdata/make-fixtures.sh`).
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+extern crate std;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::string::{String, ToString};
+    use std::vec::Vec;
+
+    const TAR: &[u8] = include_bytes!("../testdata/sample.tar");
+    const GNU_TAR: &[u8] = include_bytes!("../testdata/sample-gnu.tar");
+    const PAX_TAR: &[u8] = include_bytes!("../testdata/sample-pax.tar");
+    const CPIO: &[u8] = include_bytes!("../testdata/sample.cpio");
+
+    const LONG_DIR: &str = "etc/deep/a-directory-name-long-enough-to-push-the-whole-path-past-the-hundred-byte-ustar-name";
+
+    fn paths(archive: &Archive<'_>) -> Vec<String> {
+        let mut paths: Vec<_> = archive.entries().map(|e| e.path().to_string()).collect();
+        paths.sort();
+        paths
+    }
+
+    fn expected_paths() -> Vec<String> {
+        let long_file = std::format!("{}/file.txt", LONG_DIR);
+        let mut paths: Vec<String> = [
+            "bin",
+            "bin/init",
+            "bin/motd-link",
+            "etc",
+            "etc/deep",
+            LONG_DIR,
+            &long_file,
+            "etc/empty",
+            "etc/motd",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        paths.sort();
+        paths
+    }
+
+    /// Checks shared by both fixtures: they hold the same tree.
+    fn check_sample(archive: &Archive<'_>) {
+        assert_eq!(paths(archive), expected_paths());
+        assert_eq!(archive.len(), 9);
+
+        assert_eq!(
+            archive.file("bin/init"),
+            Some(&b"\x7fELF\x02\x01\x01fake init binary\n"[..])
+        );
+        assert_eq!(
+            archive.file("/etc/motd"),
+            Some(&b"Welcome to Ferrous.\n"[..])
+        );
+        assert_eq!(archive.file("./etc/empty"), Some(&b""[..]));
+        let long_file = std::format!("{}/file.txt", LONG_DIR);
+        assert_eq!(archive.file(&long_file), Some(&b"deep\n"[..]));
+
+        let init = archive.find("bin/init").unwrap();
+        assert_eq!(init.kind(), EntryKind::File);
+        assert_eq!(init.mode(), 0o644);
+
+        let dir = archive.find("etc/").unwrap();
+        assert_eq!(dir.kind(), EntryKind::Directory);
+        assert_eq!(dir.data(), b"");
+        assert_eq!(archive.file("etc"), None, "directories are not files");
+
+        let link = archive.find("bin/motd-link").unwrap();
+        assert_eq!(link.kind(), EntryKind::Symlink);
+        assert_eq!(link.link_target(), "../etc/motd");
+        assert_eq!(archive.file("bin/motd-link"), None);
+
+        assert_eq!(archive.find("bin/missing"), None);
+        assert_eq!(archive.find("in/init"), None);
+    }
+
+    #[test]
+    fn ustar_fixture() {
+        let archive = Archive::parse(TAR).unwrap();
+        assert_eq!(archive.format(), Format::Ustar);
+        check_sample(&archive);
+    }
+
+    #[test]
+    fn cpio_fixture() {
+        let archive = Archive::parse(CPIO).unwrap();
+        assert_eq!(archive.format(), Format::CpioNewc);
+        check_sample(&archive);
+    }
+
+    #[test]
+    fn ustar_long_path_uses_the_prefix_field() {
+        let archive = Archive::parse(TAR).unwrap();
+        let long_file = std::format!("{}/file.txt", LONG_DIR);
+        assert!(long_file.len() > 100);
+        let entry = archive.find(&long_file).unwrap();
+        assert_eq!(entry.path().to_string(), long_file);
+        assert!(!entry.path().matches(LONG_DIR));
+        assert!(!entry.path().matches("file.txt"));
+    }
+
+    #[test]
+    fn gnu_long_name_applies_to_the_next_entry() {
+        let long_file = std::format!("{}/file.txt", LONG_DIR);
+        // The name only fits in a `././@LongLink` record of type 'L'.
+        assert!(GNU_TAR.windows(13).any(|w| w == b"././@LongLink"));
+        let archive = Archive::parse(GNU_TAR).unwrap();
+        assert_eq!(archive.format(), Format::Ustar);
+        check_sample(&archive);
+        let entry = archive.find(&long_file).unwrap();
+        assert_eq!(entry.path().to_string(), long_file);
+        assert_eq!(entry.data(), b"deep\n");
+    }
+
+    #[test]
+    fn pax_path_record_applies_to_the_next_entry() {
+        let long_file = std::format!("{}/file.txt", LONG_DIR);
+        let record = std::format!("112 path={}\n", long_file);
+        assert!(PAX_TAR
+            .windows(record.len())
+            .any(|w| w == record.as_bytes()));
+        let archive = Archive::parse(PAX_TAR).unwrap();
+        check_sample(&archive);
+        assert_eq!(
+            archive.find(&long_file).unwrap().path().to_string(),
+            long_file
+        );
+    }
+
+    #[test]
+    fn unknown_format_is_rejected() {
+        assert_eq!(Archive::parse(b""), Err(InitrdError::UnknownFormat));
+        assert_eq!(
+            Archive::parse(&[0u8; 1024]),
+            Err(InitrdError::UnknownFormat)
+        );
+        assert_eq!(
+            Archive::parse(b"\x1f\x8b\x08 gzip"),
+            Err(InitrdError::UnknownFormat)
+        );
+    }
+
+    #[test]
+    fn ustar_checksum_is_verified() {
+        let mut tar = TAR.to_vec();
+        // Corrupt the second header's name.
+        tar[512] ^= 1;
+        assert_eq!(
+            Archive::parse(&tar),
+            Err(InitrdError::BadChecksum { offset: 512 })
+        );
+    }
+
+    #[test]
+    fn truncated_archives_are_rejected() {
+        // bin/init's data block is cut short.
+        assert_eq!(
+            Archive::parse(&TAR[..1024 + 12]),
+            Err(InitrdError::Truncated { offset: 512 })
+        );
+        // No trailer: the next header is missing.
+        let trailer = CPIO.windows(10).position(|w| w == b"TRAILER!!!").unwrap();
+        let cut = trailer - 110;
+        assert!(matches!(
+            Archive::parse(&CPIO[..cut]),
+            Err(InitrdError::Truncated { .. })
+        ));
+    }
+
+    #[test]
+    fn cpio_bad_field_is_rejected() {
+        let mut cpio = CPIO.to_vec();
+        // First digit of the mode field.
+        cpio[14] = b'z';
+        assert_eq!(
+            Archive::parse(&cpio),
+            Err(InitrdError::BadHeader { offset: 0 })
+        );
+    }
+
+    #[test]
+    fn empty_archives_parse() {
+        assert!(
+            Archive::parse(&TAR[TAR.len() - 1024..]).is_err(),
+            "zeros have no magic"
+        );
+        let trailer = CPIO.windows(10).position(|w| w == b"TRAILER!!!").unwrap();
+        let archive = Archive::parse(&CPIO[trailer - 110..]).unwrap();
+        assert!(archive.is_empty());
+        assert_eq!(archive.entries().count(), 0);
+    }
+
+    #[test]
+    fn path_normalisation() {
+        assert_eq!(normalise("./bin/init"), "bin/init");
+        assert_eq!(normalise("/./etc/"), "etc");
+        assert_eq!(normalise("."), "");
+        assert_eq!(normalise("./"), "");
+        assert!(EntryPath::new("", "./etc/motd").matches("/etc/motd"));
+        assert!(EntryPath::new("usr/lib", "x.so").matches("usr/lib/x.so"));
+        assert!(!EntryPath::new("usr/lib", "x.so").matches("usr/libx.so"));
+    }
+}
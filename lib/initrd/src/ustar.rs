@@ -0,0 +1,292 @@
+//! POSIX ustar archives (`tar --format=ustar`).
+//!
+//! An archive is a sequence of 512-byte blocks: a header block per entry,
+//! followed by the entry's data padded to a whole block. Two zero blocks —
+//! or simply the end of the buffer — end the archive.
+//!
+//! Numeric fields are NUL- or space-terminated octal. The 155-byte `prefix`
+//! field (POSIX magic only) extends names past 100 bytes. Longer names
+//! written by GNU tar (`--format=gnu`) or in pax extended headers
+//! (`--format=pax`) are applied too:
+//!
+//! | Type | Record                | Applied to the next entry     |
+//! |------|-----------------------|-------------------------------|
+//! | `L`  | GNU long name         | path                          |
+//! | `K`  | GNU long link name    | symlink target                |
+//! | `x`  | pax extended header   | `path` and `linkpath` records |
+//! | `g`  | pax global header     | nothing (skipped)             |
+//!
+//! These records are never entries themselves.
+
+use crate::{str_field, Entry, EntryKind, EntryPath, InitrdError};
+
+/// Size of a header block and the unit of data padding.
+const BLOCK_SIZE: usize = 512;
+
+/// Offset and contents of the magic field of a POSIX header.
+const MAGIC_OFFSET: usize = 257;
+const POSIX_MAGIC: &[u8; 6] = b"ustar\0";
+const GNU_MAGIC: &[u8; 6] = b"ustar ";
+
+/// True if `bytes` starts with a ustar header (POSIX or GNU magic).
+pub(crate) fn is_ustar(bytes: &[u8]) -> bool {
+    bytes
+        .get(MAGIC_OFFSET..MAGIC_OFFSET + 6)
+        .is_some_and(|m| m == POSIX_MAGIC || m == GNU_MAGIC)
+}
+
+/// One validated header block and the data that follows it.
+struct Header<'a> {
+    block: &'a [u8],
+    posix: bool,
+    data: &'a [u8],
+    /// Offset of the following header.
+    next: usize,
+}
+
+/// Decode the entry whose first header starts at `offset`, and the offset
+/// of the next one.
+///
+/// Long-name records before the entry are consumed and applied to it.
+/// Returns `Ok(None)` at the end of the archive.
+pub(crate) fn entry_at(
+    bytes: &[u8],
+    mut offset: usize,
+) -> Result<Option<(Entry<'_>, usize)>, InitrdError> {
+    let mut long_name = None;
+    let mut long_link = None;
+    loop {
+        let Some(header) = header_at(bytes, offset)? else {
+            return Ok(None);
+        };
+        let path_error = InitrdError::InvalidPath { offset };
+        match header.block[156] {
+            b'L' => long_name = Some(str_field(header.data).ok_or(path_error)?),
+            b'K' => long_link = Some(str_field(header.data).ok_or(path_error)?),
+            b'x' => {
+                for record in PaxRecords::new(header.data, offset) {
+                    let (key, value) = record?;
+                    let slot = match key {
+                        b"path" => &mut long_name,
+                        b"linkpath" => &mut long_link,
+                        _ => continue,
+                    };
+                    *slot = Some(core::str::from_utf8(value).map_err(|_| path_error)?);
+                }
+            }
+            b'g' => {}
+            _ => {
+                let entry = decode(&header, offset, long_name, long_link)?;
+                return Ok(Some((entry, header.next)));
+            }
+        }
+        offset = header.next;
+    }
+}
+
+/// Validate the header block at `offset` and locate its data. `Ok(None)`
+/// at the end of the archive.
+fn header_at(bytes: &[u8], offset: usize) -> Result<Option<Header<'_>>, InitrdError> {
+    let Some(block) = bytes.get(offset..offset + BLOCK_SIZE) else {
+        // End of buffer (possibly a short tail) ends the archive.
+        return Ok(None);
+    };
+    if block.iter().all(|&b| b == 0) {
+        return Ok(None);
+    }
+
+    let magic = &block[MAGIC_OFFSET..MAGIC_OFFSET + 6];
+    let posix = magic == POSIX_MAGIC;
+    if !posix && magic != GNU_MAGIC {
+        return Err(InitrdError::BadHeader { offset });
+    }
+    let stored = octal(&block[148..156]).ok_or(InitrdError::BadHeader { offset })?;
+    if stored != checksum(block) {
+        return Err(InitrdError::BadChecksum { offset });
+    }
+
+    let size = octal(&block[124..136]).ok_or(InitrdError::BadHeader { offset })? as usize;
+    let data_start = offset + BLOCK_SIZE;
+    let data = bytes
+        .get(
+            data_start
+                ..data_start
+                    .checked_add(size)
+                    .ok_or(InitrdError::Truncated { offset })?,
+        )
+        .ok_or(InitrdError::Truncated { offset })?;
+    Ok(Some(Header {
+        block,
+        posix,
+        data,
+        next: data_start + size.next_multiple_of(BLOCK_SIZE),
+    }))
+}
+
+/// Build the entry described by `header`, with the path and link target
+/// replaced by those of preceding long-name records, if any.
+fn decode<'a>(
+    header: &Header<'a>,
+    offset: usize,
+    long_name: Option<&'a str>,
+    long_link: Option<&'a str>,
+) -> Result<Entry<'a>, InitrdError> {
+    let block = header.block;
+    let mode = octal(&block[100..108]).ok_or(InitrdError::BadHeader { offset })? as u32;
+
+    let path_error = InitrdError::InvalidPath { offset };
+    let path = match long_name {
+        Some(name) => EntryPath::new("", name),
+        None => {
+            let name = str_field(&block[0..100]).ok_or(path_error)?;
+            let prefix = if header.posix {
+                str_field(&block[345..500]).ok_or(path_error)?
+            } else {
+                ""
+            };
+            EntryPath::new(prefix, name)
+        }
+    };
+    let link = match long_link {
+        Some(link) => link,
+        None => str_field(&block[157..257]).ok_or(path_error)?,
+    };
+
+    let kind = match block[156] {
+        b'0' | 0 => EntryKind::File,
+        b'5' => EntryKind::Directory,
+        b'2' => EntryKind::Symlink,
+        _ => EntryKind::Other,
+    };
+    Ok(Entry {
+        path,
+        kind,
+        mode: mode & 0o7777,
+        data: if kind == EntryKind::Symlink {
+            &[]
+        } else {
+            header.data
+        },
+        link: if kind == EntryKind::Symlink { link } else { "" },
+    })
+}
+
+/// `key=value` pairs of a pax extended header: records of the form
+/// `"<length> <key>=<value>\n"`, where `length` counts the whole record.
+/// Values are bytes; only the keys used here need to be UTF-8.
+struct PaxRecords<'a> {
+    data: &'a [u8],
+    /// Offset of the header, for errors.
+    offset: usize,
+}
+
+impl<'a> PaxRecords<'a> {
+    fn new(data: &'a [u8], offset: usize) -> Self {
+        Self { data, offset }
+    }
+
+    /// Split off the first record as `(key, value)`.
+    fn split_first(&mut self) -> Option<(&'a [u8], &'a [u8])> {
+        let space = self.data.iter().position(|&b| b == b' ')?;
+        let len: usize = core::str::from_utf8(&self.data[..space])
+            .ok()?
+            .parse()
+            .ok()?;
+        let record = self.data.get(space + 1..len)?.strip_suffix(b"\n")?;
+        self.data = &self.data[len..];
+        let eq = record.iter().position(|&b| b == b'=')?;
+        Some((&record[..eq], &record[eq + 1..]))
+    }
+}
+
+impl<'a> Iterator for PaxRecords<'a> {
+    type Item = Result<(&'a [u8], &'a [u8]), InitrdError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Data may be NUL-padded after the last record.
+        if self.data.first().is_none_or(|&b| b == 0) {
+            return None;
+        }
+        let record = self.split_first();
+        if record.is_none() {
+            self.data = &[];
+        }
+        Some(record.ok_or(InitrdError::BadHeader {
+            offset: self.offset,
+        }))
+    }
+}
+
+/// Parse a NUL- or space-terminated octal field with optional leading
+/// spaces. Base-256 (GNU large file) values are rejected.
+fn octal(field: &[u8]) -> Option<u64> {
+    let digits = field
+        .iter()
+        .skip_while(|&&b| b == b' ')
+        .take_while(|&&b| b != 0 && b != b' ');
+    let mut value: u64 = 0;
+    let mut any = false;
+    for &b in digits {
+        if !(b'0'..=b'7').contains(&b) {
+            return None;
+        }
+        value = value.checked_mul(8)?.checked_add(u64::from(b - b'0'))?;
+        any = true;
+    }
+    // An all-NUL field is a valid zero (e.g. `size` of a directory).
+    (any || field.iter().all(|&b| b == 0 || b == b' ')).then_some(value)
+}
+
+/// Header checksum: the byte sum with the checksum field read as spaces.
+fn checksum(header: &[u8]) -> u64 {
+    header
+        .iter()
+        .enumerate()
+        .map(|(i, &b)| u64::from(if (148..156).contains(&i) { b' ' } else { b }))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn octal_fields() {
+        assert_eq!(octal(b"0000644\0"), Some(0o644));
+        assert_eq!(octal(b"   755 \0"), Some(0o755));
+        assert_eq!(octal(b"00000000024\0"), Some(20));
+        assert_eq!(octal(b"\0\0\0\0\0\0\0\0"), Some(0));
+        assert_eq!(octal(b"0000089\0"), None);
+        // Base-256 encoding sets the high bit of the first byte.
+        assert_eq!(octal(&[0x80, 0, 0, 0, 0, 0, 0, 1]), None);
+    }
+
+    #[test]
+    fn pax_records() {
+        let data = b"24 linkpath=../etc/motd\n8 a=b=c\n\0\0";
+        let records: std::vec::Vec<_> = PaxRecords::new(data, 0).collect();
+        assert_eq!(
+            records,
+            [
+                Ok((&b"linkpath"[..], &b"../etc/motd"[..])),
+                Ok((&b"a"[..], &b"b=c"[..]))
+            ]
+        );
+        // Length past the data, missing newline, no length.
+        for bad in [&b"99 path=x\n"[..], b"10 path=xy", b"path=x\n"] {
+            let mut records = PaxRecords::new(bad, 512);
+            assert_eq!(
+                records.next(),
+                Some(Err(InitrdError::BadHeader { offset: 512 }))
+            );
+            assert_eq!(records.next(), None);
+        }
+    }
+
+    #[test]
+    fn zero_block_and_short_tail_end_the_archive() {
+        let zeros = [0u8; 2 * BLOCK_SIZE];
+        assert_eq!(entry_at(&zeros, 0), Ok(None));
+        assert_eq!(entry_at(&zeros[..100], 0), Ok(None));
+    }
+}
@@ -0,0 +1,31 @@
+#!/bin/sh
+# Regenerate the archives used by the host tests in src/.
+#
+# Requires GNU tar and bsdtar (libarchive) for the cpio newc output. The
+# fixtures are checked in; run this only when the test tree changes.
+set -eu
+
+here=$(cd "$(dirname "$0")" && pwd)
+tree=$(mktemp -d)
+trap 'rm -rf "$tree"' EXIT
+
+cd "$tree"
+mkdir -p bin etc
+printf '\177ELF\002\001\001fake init binary\n' > bin/init
+printf 'Welcome to Ferrous.\n' > etc/motd
+: > etc/empty
+ln -s ../etc/motd bin/motd-link
+# 102-byte path: needs the ustar prefix field, a GNU long name or a pax
+# path record.
+long=etc/deep/a-directory-name-long-enough-to-push-the-whole-path-past-the-hundred-byte-ustar-name
+mkdir -p "$long"
+printf 'deep\n' > "$long/file.txt"
+
+opts="--owner=0 --group=0 --numeric-owner --mtime=2026-01-01 --sort=name"
+# Blocking factor 1 keeps the file small (no padding to 10 KiB records).
+tar --format=ustar $opts -b 1 -cf "$here/sample.tar" bin etc
+# The same tree with the long path in a GNU 'L' record and a pax 'x' record.
+tar --format=gnu $opts -b 1 -cf "$here/sample-gnu.tar" bin etc
+tar --format=pax $opts --pax-option=delete=atime,delete=ctime -b 1 \
+    -cf "$here/sample-pax.tar" bin etc
+find bin etc | LC_ALL=C sort | bsdtar --format newc --uid 0 --gid 0 -n -cf "$here/sample.cpio" -T -
@@ -0,0 +1,221 @@
+//! Typed physical and virtual addresses.
+
+use core::fmt;
+
+/// Size of a 4 KiB page.
+pub const PAGE_SIZE: u64 = 4096;
+
+/// Size of a 2 MiB huge page.
+pub const HUGE_PAGE_SIZE: u64 = 2 * 1024 * 1024;
+
+/// Size of a 1 GiB huge page.
+pub const GIANT_PAGE_SIZE: u64 = 1024 * 1024 * 1024;
+
+/// Highest physical address width supported by x86-64 (52 bits).
+const PHYS_ADDR_BITS: u32 = 52;
+
+// ---------------------------------------------------------------------------
+// PhysAddr
+// ---------------------------------------------------------------------------
+
+/// A physical address (at most 52 bits wide).
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct PhysAddr(u64);
+
+impl PhysAddr {
+    /// The physical address `addr`, or `None` if it exceeds 52 bits.
+    #[inline]
+    pub const fn new(addr: u64) -> Option<Self> {
+        if addr >> PHYS_ADDR_BITS == 0 {
+            Some(Self(addr))
+        } else {
+            None
+        }
+    }
+
+    /// The physical address `addr` with bits 52–63 cleared.
+    #[inline]
+    pub const fn new_truncate(addr: u64) -> Self {
+        Self(addr & ((1 << PHYS_ADDR_BITS) - 1))
+    }
+
+    /// The raw address.
+    #[inline]
+    pub const fn as_u64(self) -> u64 {
+        self.0
+    }
+
+    /// True if the address is a multiple of `align` (a power of two).
+    #[inline]
+    pub const fn is_aligned(self, align: u64) -> bool {
+        self.0 & (align - 1) == 0
+    }
+
+    /// The address rounded down to a multiple of `align` (a power of two).
+    #[inline]
+    pub const fn align_down(self, align: u64) -> Self {
+        Self(self.0 & !(align - 1))
+    }
+}
+
+impl fmt::Debug for PhysAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PhysAddr({:#x})", self.0)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// VirtAddr
+// ---------------------------------------------------------------------------
+
+/// A canonical 48-bit virtual address.
+///
+/// Bits 48–63 are a sign extension of bit 47, as required by the CPU for
+/// 4-level paging.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct VirtAddr(u64);
+
+impl VirtAddr {
+    /// The virtual address `addr`, or `None` if it is not canonical.
+    #[inline]
+    pub const fn new(addr: u64) -> Option<Self> {
+        let v = Self::new_truncate(addr);
+        if v.0 == addr {
+            Some(v)
+        } else {
+            None
+        }
+    }
+
+    /// The canonical address formed by sign-extending bit 47 of `addr`.
+    #[inline]
+    pub const fn new_truncate(addr: u64) -> Self {
+        Self((((addr << 16) as i64) >> 16) as u64)
+    }
+
+    /// The raw address.
+    #[inline]
+    pub const fn as_u64(self) -> u64 {
+        self.0
+    }
+
+    /// True if the address is a multiple of `align` (a power of two).
+    #[inline]
+    pub const fn is_aligned(self, align: u64) -> bool {
+        self.0 & (align - 1) == 0
+    }
+
+    /// The address rounded down to a multiple of `align` (a power of two).
+    #[inline]
+    pub const fn align_down(self, align: u64) -> Self {
+        Self(self.0 & !(align - 1))
+    }
+
+    /// Byte offset inside the 4 KiB page (bits 0–11).
+    #[inline]
+    pub const fn page_offset(self) -> u64 {
+        self.0 & (PAGE_SIZE - 1)
+    }
+
+    /// Index into the level-`level` table (4 = PML4, 1 = PT).
+    ///
+    /// # Panics
+    ///
+    /// If `level` is not in `1..=4`.
+    #[inline]
+    pub const fn table_index(self, level: u8) -> usize {
+        assert!(level >= 1 && level <= 4, "page table level out of range");
+        ((self.0 >> (12 + 9 * (level as u64 - 1))) & 0x1FF) as usize
+    }
+
+    /// PML4 index (bits 39–47).
+    #[inline]
+    pub const fn p4_index(self) -> usize {
+        self.table_index(4)
+    }
+
+    /// PDPT index (bits 30–38).
+    #[inline]
+    pub const fn p3_index(self) -> usize {
+        self.table_index(3)
+    }
+
+    /// Page directory index (bits 21–29).
+    #[inline]
+    pub const fn p2_index(self) -> usize {
+        self.table_index(2)
+    }
+
+    /// Page table index (bits 12–20).
+    #[inline]
+    pub const fn p1_index(self) -> usize {
+        self.table_index(1)
+    }
+}
+
+impl fmt::Debug for VirtAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "VirtAddr({:#x})", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phys_addr_rejects_more_than_52_bits() {
+        assert!(PhysAddr::new((1 << 52) - 1).is_some());
+        assert!(PhysAddr::new(1 << 52).is_none());
+        assert_eq!(PhysAddr::new_truncate(u64::MAX).as_u64(), (1 << 52) - 1);
+    }
+
+    #[test]
+    fn virt_addr_canonical_check() {
+        assert!(VirtAddr::new(0x0000_7FFF_FFFF_FFFF).is_some());
+        assert!(VirtAddr::new(0xFFFF_8000_0000_0000).is_some());
+        assert!(VirtAddr::new(0x0000_8000_0000_0000).is_none());
+        assert!(VirtAddr::new(0xFFFF_7FFF_FFFF_FFFF).is_none());
+    }
+
+    #[test]
+    fn virt_addr_truncate_sign_extends_bit_47() {
+        assert_eq!(
+            VirtAddr::new_truncate(0x0000_8000_0000_0000).as_u64(),
+            0xFFFF_8000_0000_0000
+        );
+        assert_eq!(VirtAddr::new_truncate(0x1234).as_u64(), 0x1234);
+    }
+
+    #[test]
+    fn virt_addr_table_indices() {
+        // 0xFFFF_FFFF_8000_0000: PML4 511, PDPT 510, PD 0, PT 0.
+        let v = VirtAddr::new(0xFFFF_FFFF_8000_0000).unwrap();
+        assert_eq!(v.p4_index(), 511);
+        assert_eq!(v.p3_index(), 510);
+        assert_eq!(v.p2_index(), 0);
+        assert_eq!(v.p1_index(), 0);
+
+        let v =
+            VirtAddr::new((1 << 39) * 3 + (1 << 30) * 5 + (1 << 21) * 7 + (1 << 12) * 9 + 0x123)
+                .unwrap();
+        assert_eq!(
+            (v.p4_index(), v.p3_index(), v.p2_index(), v.p1_index()),
+            (3, 5, 7, 9)
+        );
+        assert_eq!(v.page_offset(), 0x123);
+    }
+
+    #[test]
+    fn alignment_helpers() {
+        let v = VirtAddr::new(0x20_1234).unwrap();
+        assert!(!v.is_aligned(PAGE_SIZE));
+        assert_eq!(v.align_down(PAGE_SIZE).as_u64(), 0x20_1000);
+        assert_eq!(v.align_down(HUGE_PAGE_SIZE).as_u64(), 0x20_0000);
+        assert!(PhysAddr::new(0x4000_0000)
+            .unwrap()
+            .is_aligned(GIANT_PAGE_SIZE));
+    }
+}
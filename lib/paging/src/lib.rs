@@ -0,0 +1,35 @@
+//! x86-64 four-level paging.
+//!
+//! Typed addresses ([`PhysAddr`], [`VirtAddr`]), page table entries and
+//! flags ([`PageTableEntry`], [`PageTableFlags`], [`PageTable`]), and a
+//! table walker ([`PageTableMapper`]) with `map` / `unmap` / `translate`.
+//!
+//! The walker is independent of the running kernel: frames for new tables
+//! come from a [`FrameSource`], and tables are reached through a
+//! [`PhysTableAccess`]. The kernel plugs in its frame allocator and the
+//! direct map, the bootloader firmware pages and UEFI's identity mapping;
+//! the host tests plug in a simulated memory pool. TLB invalidation is left
+//! to the caller — see `kernel::memory::paging`.
+
+#![no_std]
+
+pub mod addr;
+pub mod mapper;
+pub mod table;
+
+pub use addr::{PhysAddr, VirtAddr, GIANT_PAGE_SIZE, HUGE_PAGE_SIZE, PAGE_SIZE};
+pub use mapper::{
+    FrameSource, MapError, OffsetTableAccess, PageSize, PageTableMapper, PhysTableAccess,
+    Translation, UnmapError,
+};
+pub use table::{PageTable, PageTableEntry, PageTableFlags, ENTRY_COUNT};
+
+// ---------------------------------------------------------------------------
+// Tests
+//
+// `cargo test` links std for the test binary; `extern crate std` makes it
+// available to the per-module test suites.
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+extern crate std;
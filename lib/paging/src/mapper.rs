@@ -0,0 +1,956 @@
+//! Four-level page table walker.
+//!
+//! [`PageTableMapper`] walks and edits a PML4 hierarchy. Two small traits
+//! keep it independent of the kernel's environment:
+//!
+//! - [`FrameSource`] supplies physical frames for new intermediate tables.
+//!   The kernel plugs in its frame allocator; tests plug in a pool.
+//! - [`PhysTableAccess`] turns the physical address of a table into a
+//!   pointer the current CPU can dereference. [`OffsetTableAccess`] covers
+//!   both the UEFI identity mapping (offset 0) and a direct map at a fixed
+//!   virtual offset.
+//!
+//! The mapper never touches the TLB: callers that change a live hierarchy
+//! must invalidate the affected addresses themselves (`invlpg`).
+
+use crate::addr::{PhysAddr, VirtAddr, GIANT_PAGE_SIZE, HUGE_PAGE_SIZE, PAGE_SIZE};
+use crate::table::{PageTable, PageTableEntry, PageTableFlags, ENTRY_COUNT};
+
+/// Source of 4 KiB frames for intermediate page tables.
+pub trait FrameSource {
+    /// Allocate one 4 KiB-aligned frame, or `None` if memory is exhausted.
+    ///
+    /// The contents need not be zeroed; the mapper clears new tables.
+    fn allocate_frame(&mut self) -> Option<PhysAddr>;
+}
+
+/// Converts the physical address of a page table into a usable pointer.
+pub trait PhysTableAccess {
+    /// Pointer through which the table at physical address `phys` can be
+    /// read and written.
+    fn table_ptr(&self, phys: PhysAddr) -> *mut PageTable;
+}
+
+/// Page tables are reachable at `phys + offset`.
+///
+/// `OffsetTableAccess::identity()` is correct while UEFI's identity mapping
+/// is active; a direct map of all physical memory at `offset` works the same
+/// way once the kernel owns its address space.
+#[derive(Debug, Clone, Copy)]
+pub struct OffsetTableAccess {
+    offset: u64,
+}
+
+impl OffsetTableAccess {
+    /// Tables are reachable at `phys + offset` (wrapping).
+    #[inline]
+    pub const fn new(offset: u64) -> Self {
+        Self { offset }
+    }
+
+    /// Tables are reachable at their physical address.
+    #[inline]
+    pub const fn identity() -> Self {
+        Self { offset: 0 }
+    }
+
+    /// The configured offset.
+    #[inline]
+    pub const fn offset(&self) -> u64 {
+        self.offset
+    }
+}
+
+impl PhysTableAccess for OffsetTableAccess {
+    #[inline]
+    fn table_ptr(&self, phys: PhysAddr) -> *mut PageTable {
+        phys.as_u64().wrapping_add(self.offset) as *mut PageTable
+    }
+}
+
+/// Size of the page backing a translation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageSize {
+    /// 4 KiB page (PT entry).
+    Size4KiB,
+    /// 2 MiB page (PD entry with `PS`).
+    Size2MiB,
+    /// 1 GiB page (PDPT entry with `PS`).
+    Size1GiB,
+}
+
+impl PageSize {
+    /// Page size in bytes.
+    #[inline]
+    pub const fn bytes(self) -> u64 {
+        match self {
+            Self::Size4KiB => PAGE_SIZE,
+            Self::Size2MiB => HUGE_PAGE_SIZE,
+            Self::Size1GiB => GIANT_PAGE_SIZE,
+        }
+    }
+}
+
+/// Result of a successful page walk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Translation {
+    /// Physical address corresponding to the queried virtual address
+    /// (page base plus offset).
+    pub phys: PhysAddr,
+    /// Physical base of the page containing the address.
+    pub frame: PhysAddr,
+    /// Flags of the leaf entry.
+    pub flags: PageTableFlags,
+    /// Size of the page.
+    pub size: PageSize,
+}
+
+/// Errors returned by [`PageTableMapper::map`] and
+/// [`PageTableMapper::map_2mib`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapError {
+    /// `virt` or `phys` is not aligned to the page size.
+    Unaligned,
+    /// `HUGE_PAGE` was passed to a 4 KiB mapping (in a PT entry bit 7 is
+    /// PAT, not PS).
+    InvalidFlags,
+    /// The address is already mapped.
+    AlreadyMapped {
+        /// Physical base of the existing mapping.
+        existing: PhysAddr,
+    },
+    /// A huge page covers the address at a higher level.
+    HugePageInPath {
+        /// Size of the covering huge page.
+        size: PageSize,
+    },
+    /// The frame source could not supply a table frame.
+    FrameAllocationFailed,
+    /// Nothing is mapped at the address ([`PageTableMapper::split_huge_page`]).
+    NotMapped,
+}
+
+/// Errors returned by [`PageTableMapper::unmap`] and
+/// [`PageTableMapper::update_flags`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnmapError {
+    /// Nothing is mapped at the address.
+    NotMapped,
+    /// The address is inside a page of `size` but not at its start.
+    NotPageStart {
+        /// Size of the page containing the address.
+        size: PageSize,
+    },
+}
+
+/// Walks and edits a four-level page table hierarchy.
+pub struct PageTableMapper<A: PhysTableAccess> {
+    root: PhysAddr,
+    access: A,
+}
+
+/// Flags given to newly created intermediate tables. Effective permissions
+/// are the intersection across all levels, so intermediates are permissive
+/// and the leaf decides.
+const TABLE_FLAGS: PageTableFlags = PageTableFlags::from_bits_truncate(
+    PageTableFlags::PRESENT.bits() | PageTableFlags::WRITABLE.bits(),
+);
+
+impl<A: PhysTableAccess> PageTableMapper<A> {
+    /// Create a mapper for the hierarchy rooted at the PML4 `root`.
+    ///
+    /// # Safety
+    ///
+    /// - `access.table_ptr(p)` must return a valid, exclusive pointer to a
+    ///   `PageTable` for `root`, for every table reachable from it, and for
+    ///   every frame later returned by a [`FrameSource`] passed to this
+    ///   mapper.
+    /// - No other code may modify the hierarchy while the mapper exists.
+    #[allow(unsafe_code)]
+    pub unsafe fn new(root: PhysAddr, access: A) -> Self {
+        Self { root, access }
+    }
+
+    /// Physical address of the PML4.
+    #[inline]
+    pub fn root(&self) -> PhysAddr {
+        self.root
+    }
+
+    /// The table access strategy.
+    #[inline]
+    pub fn access(&self) -> &A {
+        &self.access
+    }
+
+    /// Walk the tables for `virt`.
+    ///
+    /// Returns `None` if any level is not present.
+    pub fn translate(&self, virt: VirtAddr) -> Option<Translation> {
+        let l4 = self.table(self.root);
+        let e4 = l4[virt.p4_index()];
+        if !e4.flags().contains(PageTableFlags::PRESENT) {
+            return None;
+        }
+
+        let e3 = self.table(e4.addr())[virt.p3_index()];
+        if !e3.flags().contains(PageTableFlags::PRESENT) {
+            return None;
+        }
+        if e3.flags().contains(PageTableFlags::HUGE_PAGE) {
+            return Some(leaf_translation(virt, e3, PageSize::Size1GiB));
+        }
+
+        let e2 = self.table(e3.addr())[virt.p2_index()];
+        if !e2.flags().contains(PageTableFlags::PRESENT) {
+            return None;
+        }
+        if e2.flags().contains(PageTableFlags::HUGE_PAGE) {
+            return Some(leaf_translation(virt, e2, PageSize::Size2MiB));
+        }
+
+        let e1 = self.table(e2.addr())[virt.p1_index()];
+        if !e1.flags().contains(PageTableFlags::PRESENT) {
+            return None;
+        }
+        Some(leaf_translation(virt, e1, PageSize::Size4KiB))
+    }
+
+    /// Physical address `virt` maps to, if any.
+    #[inline]
+    pub fn translate_addr(&self, virt: VirtAddr) -> Option<PhysAddr> {
+        self.translate(virt).map(|t| t.phys)
+    }
+
+    /// Map the 4 KiB page at `virt` to the frame at `phys`.
+    ///
+    /// `PRESENT` is added to `flags`. Missing intermediate tables are taken
+    /// from `frames`; existing ones gain `WRITABLE`/`USER_ACCESSIBLE` if the
+    /// new leaf needs them.
+    ///
+    /// # Errors
+    ///
+    /// See [`MapError`]. On error the leaf is left untouched, although
+    /// intermediate tables created before the failure remain.
+    pub fn map(
+        &mut self,
+        virt: VirtAddr,
+        phys: PhysAddr,
+        flags: PageTableFlags,
+        frames: &mut impl FrameSource,
+    ) -> Result<(), MapError> {
+        if !virt.is_aligned(PAGE_SIZE) || !phys.is_aligned(PAGE_SIZE) {
+            return Err(MapError::Unaligned);
+        }
+        if flags.contains(PageTableFlags::HUGE_PAGE) {
+            return Err(MapError::InvalidFlags);
+        }
+        let l3 = self.next_table_create(self.root, virt.p4_index(), flags, frames, None)?;
+        let l2 =
+            self.next_table_create(l3, virt.p3_index(), flags, frames, Some(PageSize::Size1GiB))?;
+        let l1 =
+            self.next_table_create(l2, virt.p2_index(), flags, frames, Some(PageSize::Size2MiB))?;
+        let entry = &mut self.table_mut(l1)[virt.p1_index()];
+        set_leaf(entry, phys, flags | PageTableFlags::PRESENT)
+    }
+
+    /// Map the 2 MiB page at `virt` to the 2 MiB-aligned frame at `phys`.
+    ///
+    /// `PRESENT | HUGE_PAGE` are added to `flags`.
+    ///
+    /// # Errors
+    ///
+    /// See [`MapError`]. A PD entry that already points to a page table is
+    /// reported as [`MapError::AlreadyMapped`].
+    pub fn map_2mib(
+        &mut self,
+        virt: VirtAddr,
+        phys: PhysAddr,
+        flags: PageTableFlags,
+        frames: &mut impl FrameSource,
+    ) -> Result<(), MapError> {
+        if !virt.is_aligned(HUGE_PAGE_SIZE) || !phys.is_aligned(HUGE_PAGE_SIZE) {
+            return Err(MapError::Unaligned);
+        }
+        let l3 = self.next_table_create(self.root, virt.p4_index(), flags, frames, None)?;
+        let l2 =
+            self.next_table_create(l3, virt.p3_index(), flags, frames, Some(PageSize::Size1GiB))?;
+        let entry = &mut self.table_mut(l2)[virt.p2_index()];
+        set_leaf(
+            entry,
+            phys,
+            flags | PageTableFlags::PRESENT | PageTableFlags::HUGE_PAGE,
+        )
+    }
+
+    /// Remove the mapping of the page starting at `virt`.
+    ///
+    /// Works for 4 KiB, 2 MiB and 1 GiB pages; `virt` must be the start of
+    /// the page. Intermediate tables are not freed. Returns the translation
+    /// that was removed.
+    ///
+    /// # Errors
+    ///
+    /// See [`UnmapError`].
+    pub fn unmap(&mut self, virt: VirtAddr) -> Result<Translation, UnmapError> {
+        let (entry, size) = self.leaf_entry_mut(virt)?;
+        let old = *entry;
+        entry.set_unused();
+        Ok(leaf_translation(virt, old, size))
+    }
+
+    /// Replace the flags of the page starting at `virt`.
+    ///
+    /// `PRESENT` (and `HUGE_PAGE` for huge pages) are preserved. Returns the
+    /// updated translation.
+    ///
+    /// # Errors
+    ///
+    /// See [`UnmapError`].
+    pub fn update_flags(
+        &mut self,
+        virt: VirtAddr,
+        flags: PageTableFlags,
+    ) -> Result<Translation, UnmapError> {
+        let (entry, size) = self.leaf_entry_mut(virt)?;
+        let mut flags = flags | PageTableFlags::PRESENT;
+        if size == PageSize::Size4KiB {
+            flags.remove(PageTableFlags::HUGE_PAGE);
+        } else {
+            flags.insert(PageTableFlags::HUGE_PAGE);
+        }
+        entry.set_flags(flags);
+        let updated = *entry;
+        Ok(leaf_translation(virt, updated, size))
+    }
+
+    /// Break the huge page containing `virt` into 4 KiB pages.
+    ///
+    /// A 1 GiB page becomes 512 2 MiB pages, and the 2 MiB page containing
+    /// `virt` becomes 512 4 KiB pages, each with the flags of the original
+    /// leaf. Translations are unchanged; only the page containing `virt` is
+    /// split down to 4 KiB. A 4 KiB page is left as is.
+    ///
+    /// The PAT bit of a huge leaf is not carried over (bit 12 of a huge
+    /// entry is PAT, not part of the address).
+    ///
+    /// # Errors
+    ///
+    /// [`MapError::NotMapped`] if nothing maps `virt`, or
+    /// [`MapError::FrameAllocationFailed`] if `frames` runs out; a failed
+    /// 2 MiB split after a successful 1 GiB split leaves the 2 MiB pages.
+    pub fn split_huge_page(
+        &mut self,
+        virt: VirtAddr,
+        frames: &mut impl FrameSource,
+    ) -> Result<(), MapError> {
+        loop {
+            let t = self.translate(virt).ok_or(MapError::NotMapped)?;
+            let (child_size, child_flags) = match t.size {
+                PageSize::Size4KiB => return Ok(()),
+                PageSize::Size1GiB => (HUGE_PAGE_SIZE, t.flags),
+                PageSize::Size2MiB => (PAGE_SIZE, t.flags & !PageTableFlags::HUGE_PAGE),
+            };
+            let (table, index) = self.leaf_slot(virt, t.size);
+
+            let child = frames
+                .allocate_frame()
+                .ok_or(MapError::FrameAllocationFailed)?;
+            let base = t.frame.as_u64();
+            let child_table = self.table_mut(child);
+            for i in 0..ENTRY_COUNT {
+                let phys = PhysAddr::new_truncate(base + i as u64 * child_size);
+                child_table[i].set(phys, child_flags);
+            }
+
+            // The parent entry keeps the permission bits of the old leaf so
+            // the effective permissions of every child are unchanged.
+            let parent_flags = t.flags
+                & (PageTableFlags::PRESENT
+                    | PageTableFlags::WRITABLE
+                    | PageTableFlags::USER_ACCESSIBLE
+                    | PageTableFlags::NO_EXECUTE);
+            self.table_mut(table)[index].set(child, parent_flags);
+        }
+    }
+
+    /// Call `f` with the physical address of every table in the hierarchy:
+    /// the PML4 first, then each PDPT, page directory and page table
+    /// reachable through a present, non-huge entry.
+    ///
+    /// Use it to find the frames the hierarchy occupies, for example before
+    /// handing the memory they live in back to an allocator.
+    pub fn for_each_table(&self, mut f: impl FnMut(PhysAddr)) {
+        f(self.root);
+        self.visit_children(self.root, 3, &mut f);
+    }
+
+    // -----------------------------------------------------------------------
+    // Internals
+    // -----------------------------------------------------------------------
+
+    #[allow(unsafe_code)]
+    fn table(&self, phys: PhysAddr) -> &PageTable {
+        // SAFETY: the contract of `new` guarantees `table_ptr` yields a valid
+        // pointer for every table in the hierarchy.
+        unsafe { &*self.access.table_ptr(phys) }
+    }
+
+    #[allow(unsafe_code)]
+    fn table_mut(&mut self, phys: PhysAddr) -> &mut PageTable {
+        // SAFETY: as for `table`; `&mut self` ensures no other reference
+        // derived from this mapper is live.
+        unsafe { &mut *self.access.table_ptr(phys) }
+    }
+
+    /// Report the tables referenced by `table`, which sits `levels` above
+    /// the page tables (3 for a PML4), and recurse into them.
+    fn visit_children(&self, table: PhysAddr, levels: u8, f: &mut impl FnMut(PhysAddr)) {
+        if levels == 0 {
+            return;
+        }
+        for entry in self.table(table).iter() {
+            let flags = entry.flags();
+            if !flags.contains(PageTableFlags::PRESENT) || flags.contains(PageTableFlags::HUGE_PAGE)
+            {
+                continue;
+            }
+            f(entry.addr());
+            self.visit_children(entry.addr(), levels - 1, f);
+        }
+    }
+
+    /// Follow entry `index` of the table at `table` to the next level,
+    /// creating the next table if the entry is unused.
+    ///
+    /// `huge` is the page size a `PS` bit in this entry would denote, or
+    /// `None` at the PML4 level.
+    fn next_table_create(
+        &mut self,
+        table: PhysAddr,
+        index: usize,
+        leaf_flags: PageTableFlags,
+        frames: &mut impl FrameSource,
+        huge: Option<PageSize>,
+    ) -> Result<PhysAddr, MapError> {
+        let needed = leaf_flags & (PageTableFlags::WRITABLE | PageTableFlags::USER_ACCESSIBLE);
+        let entry = self.table(table)[index];
+
+        if entry.flags().contains(PageTableFlags::PRESENT) {
+            if let Some(size) = huge {
+                if entry.flags().contains(PageTableFlags::HUGE_PAGE) {
+                    return Err(MapError::HugePageInPath { size });
+                }
+            }
+            if !entry.flags().contains(needed) {
+                self.table_mut(table)[index].set_flags(entry.flags() | needed);
+            }
+            return Ok(entry.addr());
+        }
+
+        let frame = frames
+            .allocate_frame()
+            .ok_or(MapError::FrameAllocationFailed)?;
+        self.table_mut(frame).zero();
+        self.table_mut(table)[index].set(frame, TABLE_FLAGS | needed);
+        Ok(frame)
+    }
+
+    /// The leaf entry for the page starting at `virt`, and its page size.
+    fn leaf_entry_mut(
+        &mut self,
+        virt: VirtAddr,
+    ) -> Result<(&mut PageTableEntry, PageSize), UnmapError> {
+        let t = self.translate(virt).ok_or(UnmapError::NotMapped)?;
+        if !virt.is_aligned(t.size.bytes()) {
+            return Err(UnmapError::NotPageStart { size: t.size });
+        }
+        let (table, index) = self.leaf_slot(virt, t.size);
+        Ok((&mut self.table_mut(table)[index], t.size))
+    }
+
+    /// Table and index of the leaf entry mapping `virt`, which must already
+    /// be mapped by a page of `size`.
+    fn leaf_slot(&self, virt: VirtAddr, size: PageSize) -> (PhysAddr, usize) {
+        let l3 = self.table(self.root)[virt.p4_index()].addr();
+        match size {
+            PageSize::Size1GiB => (l3, virt.p3_index()),
+            PageSize::Size2MiB => (self.table(l3)[virt.p3_index()].addr(), virt.p2_index()),
+            PageSize::Size4KiB => {
+                let l2 = self.table(l3)[virt.p3_index()].addr();
+                (self.table(l2)[virt.p2_index()].addr(), virt.p1_index())
+            }
+        }
+    }
+}
+
+/// Write a leaf entry unless it is already present.
+fn set_leaf(
+    entry: &mut PageTableEntry,
+    phys: PhysAddr,
+    flags: PageTableFlags,
+) -> Result<(), MapError> {
+    if entry.flags().contains(PageTableFlags::PRESENT) {
+        return Err(MapError::AlreadyMapped {
+            existing: entry.addr(),
+        });
+    }
+    entry.set(phys, flags);
+    Ok(())
+}
+
+fn leaf_translation(virt: VirtAddr, entry: PageTableEntry, size: PageSize) -> Translation {
+    let frame = entry.addr().align_down(size.bytes());
+    Translation {
+        phys: PhysAddr::new_truncate(frame.as_u64() + (virt.as_u64() & (size.bytes() - 1))),
+        frame,
+        flags: entry.flags(),
+        size,
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+//
+// Simulated physical memory: a boxed pool of page tables that pretends to
+// live at physical address POOL_BASE. OffsetTableAccess maps the fake
+// physical addresses onto the pool, exactly as a direct map would.
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::boxed::Box;
+    use std::vec;
+
+    const POOL_BASE: u64 = 0x1000_0000;
+
+    struct Pool {
+        tables: Box<[PageTable]>,
+    }
+
+    struct PoolFrames {
+        next: u64,
+        end: u64,
+        handed_out: usize,
+    }
+
+    impl FrameSource for PoolFrames {
+        fn allocate_frame(&mut self) -> Option<PhysAddr> {
+            if self.next >= self.end {
+                return None;
+            }
+            let frame = PhysAddr::new(self.next).unwrap();
+            self.next += PAGE_SIZE;
+            self.handed_out += 1;
+            Some(frame)
+        }
+    }
+
+    /// A pool of `n` tables: table 0 is the (empty) root, the rest are
+    /// handed out by the returned frame source.
+    #[allow(unsafe_code)]
+    fn setup(n: usize) -> (Pool, PageTableMapper<OffsetTableAccess>, PoolFrames) {
+        let pool = Pool {
+            tables: vec![PageTable::new(); n].into_boxed_slice(),
+        };
+        let offset = (pool.tables.as_ptr() as u64).wrapping_sub(POOL_BASE);
+        // SAFETY: every fake physical address in POOL_BASE.. maps into the
+        // pool, which outlives the mapper in each test.
+        let mapper = unsafe {
+            PageTableMapper::new(
+                PhysAddr::new(POOL_BASE).unwrap(),
+                OffsetTableAccess::new(offset),
+            )
+        };
+        let frames = PoolFrames {
+            next: POOL_BASE + PAGE_SIZE,
+            end: POOL_BASE + n as u64 * PAGE_SIZE,
+            handed_out: 0,
+        };
+        (pool, mapper, frames)
+    }
+
+    fn va(a: u64) -> VirtAddr {
+        VirtAddr::new(a).unwrap()
+    }
+
+    fn pa(a: u64) -> PhysAddr {
+        PhysAddr::new(a).unwrap()
+    }
+
+    const RW: PageTableFlags = PageTableFlags::WRITABLE;
+
+    // -----------------------------------------------------------------------
+    // map / translate
+    // -----------------------------------------------------------------------
+
+    #[test]
+    fn translate_empty_hierarchy_is_none() {
+        let (_pool, mapper, _) = setup(1);
+        assert_eq!(mapper.translate(va(0x1000)), None);
+    }
+
+    #[test]
+    fn map_then_translate_4kib() {
+        let (_pool, mut mapper, mut frames) = setup(8);
+        mapper
+            .map(va(0x40_0000), pa(0x20_0000), RW, &mut frames)
+            .unwrap();
+        let t = mapper.translate(va(0x40_0123)).unwrap();
+        assert_eq!(t.phys, pa(0x20_0123));
+        assert_eq!(t.frame, pa(0x20_0000));
+        assert_eq!(t.size, PageSize::Size4KiB);
+        assert!(t
+            .flags
+            .contains(PageTableFlags::PRESENT | PageTableFlags::WRITABLE));
+        assert_eq!(mapper.translate(va(0x40_1000)), None);
+    }
+
+    #[test]
+    fn map_allocates_three_intermediate_tables_once() {
+        let (_pool, mut mapper, mut frames) = setup(8);
+        mapper
+            .map(va(0x40_0000), pa(0x1000), RW, &mut frames)
+            .unwrap();
+        assert_eq!(frames.handed_out, 3);
+        // Same PT: no new tables.
+        mapper
+            .map(va(0x40_1000), pa(0x2000), RW, &mut frames)
+            .unwrap();
+        assert_eq!(frames.handed_out, 3);
+        // Same PD, next PT: one new table.
+        mapper
+            .map(va(0x60_0000), pa(0x3000), RW, &mut frames)
+            .unwrap();
+        assert_eq!(frames.handed_out, 4);
+    }
+
+    #[test]
+    fn map_higher_half_address() {
+        let (_pool, mut mapper, mut frames) = setup(8);
+        let v = va(0xFFFF_FFFF_8000_0000);
+        mapper
+            .map(v, pa(0x20_0000), PageTableFlags::GLOBAL, &mut frames)
+            .unwrap();
+        assert_eq!(mapper.translate_addr(v), Some(pa(0x20_0000)));
+        // Does not alias the low half.
+        assert_eq!(mapper.translate(va(0x8000_0000)), None);
+    }
+
+    #[test]
+    fn map_twice_reports_existing_frame() {
+        let (_pool, mut mapper, mut frames) = setup(8);
+        mapper.map(va(0x1000), pa(0x5000), RW, &mut frames).unwrap();
+        assert_eq!(
+            mapper.map(va(0x1000), pa(0x6000), RW, &mut frames),
+            Err(MapError::AlreadyMapped {
+                existing: pa(0x5000)
+            })
+        );
+        assert_eq!(mapper.translate_addr(va(0x1000)), Some(pa(0x5000)));
+    }
+
+    #[test]
+    fn map_rejects_unaligned_addresses_and_ps_flag() {
+        let (_pool, mut mapper, mut frames) = setup(8);
+        assert_eq!(
+            mapper.map(va(0x1001), pa(0x5000), RW, &mut frames),
+            Err(MapError::Unaligned)
+        );
+        assert_eq!(
+            mapper.map(va(0x1000), pa(0x5001), RW, &mut frames),
+            Err(MapError::Unaligned)
+        );
+        assert_eq!(
+            mapper.map(
+                va(0x1000),
+                pa(0x5000),
+                PageTableFlags::HUGE_PAGE,
+                &mut frames
+            ),
+            Err(MapError::InvalidFlags)
+        );
+        assert_eq!(frames.handed_out, 0);
+    }
+
+    #[test]
+    fn map_fails_cleanly_when_frames_run_out() {
+        // Root + 2 frames: not enough for PDPT + PD + PT.
+        let (_pool, mut mapper, mut frames) = setup(3);
+        assert_eq!(
+            mapper.map(va(0x1000), pa(0x5000), RW, &mut frames),
+            Err(MapError::FrameAllocationFailed)
+        );
+        assert_eq!(mapper.translate(va(0x1000)), None);
+    }
+
+    #[test]
+    fn new_tables_are_zeroed() {
+        let (mut pool, mut mapper, mut frames) = setup(8);
+        // Dirty the frames the source will hand out.
+        for t in pool.tables[1..].iter_mut() {
+            t[42].set(pa(0xDEAD_0000), PageTableFlags::PRESENT);
+        }
+        mapper.map(va(0x1000), pa(0x5000), RW, &mut frames).unwrap();
+        // Index 42 in the new tables must not leak through.
+        assert_eq!(mapper.translate(va(42 << 12)), None);
+        assert_eq!(mapper.translate(va(42 << 21)), None);
+    }
+
+    // -----------------------------------------------------------------------
+    // Flag propagation
+    // -----------------------------------------------------------------------
+
+    #[test]
+    fn intermediate_tables_are_present_writable_never_nx() {
+        let (pool, mut mapper, mut frames) = setup(8);
+        let flags = PageTableFlags::NO_EXECUTE;
+        mapper
+            .map(va(0x1000), pa(0x5000), flags, &mut frames)
+            .unwrap();
+        let e4 = pool.tables[0][0];
+        assert_eq!(
+            e4.flags(),
+            PageTableFlags::PRESENT | PageTableFlags::WRITABLE
+        );
+        let t = mapper.translate(va(0x1000)).unwrap();
+        assert!(t.flags.contains(PageTableFlags::NO_EXECUTE));
+        assert!(!t.flags.contains(PageTableFlags::WRITABLE));
+    }
+
+    #[test]
+    fn user_flag_propagates_to_existing_intermediates() {
+        let (pool, mut mapper, mut frames) = setup(8);
+        mapper.map(va(0x1000), pa(0x5000), RW, &mut frames).unwrap();
+        assert!(!pool.tables[0][0]
+            .flags()
+            .contains(PageTableFlags::USER_ACCESSIBLE));
+        mapper
+            .map(
+                va(0x2000),
+                pa(0x6000),
+                PageTableFlags::USER_ACCESSIBLE,
+                &mut frames,
+            )
+            .unwrap();
+        assert!(pool.tables[0][0]
+            .flags()
+            .contains(PageTableFlags::USER_ACCESSIBLE));
+    }
+
+    // -----------------------------------------------------------------------
+    // Huge pages
+    // -----------------------------------------------------------------------
+
+    #[test]
+    fn map_2mib_and_translate_with_offset() {
+        let (_pool, mut mapper, mut frames) = setup(8);
+        mapper
+            .map_2mib(va(0x4000_0000), pa(0x20_0000), RW, &mut frames)
+            .unwrap();
+        assert_eq!(frames.handed_out, 2);
+        let t = mapper.translate(va(0x4012_3456)).unwrap();
+        assert_eq!(t.size, PageSize::Size2MiB);
+        assert_eq!(t.phys, pa(0x32_3456));
+        assert!(t.flags.contains(PageTableFlags::HUGE_PAGE));
+    }
+
+    #[test]
+    fn map_2mib_requires_2mib_alignment() {
+        let (_pool, mut mapper, mut frames) = setup(8);
+        assert_eq!(
+            mapper.map_2mib(va(0x1000), pa(0x20_0000), RW, &mut frames),
+            Err(MapError::Unaligned)
+        );
+        assert_eq!(
+            mapper.map_2mib(va(0x20_0000), pa(0x1000), RW, &mut frames),
+            Err(MapError::Unaligned)
+        );
+    }
+
+    #[test]
+    fn map_4kib_inside_huge_page_is_rejected() {
+        let (_pool, mut mapper, mut frames) = setup(8);
+        mapper.map_2mib(va(0), pa(0), RW, &mut frames).unwrap();
+        assert_eq!(
+            mapper.map(va(0x1000), pa(0x9000), RW, &mut frames),
+            Err(MapError::HugePageInPath {
+                size: PageSize::Size2MiB
+            })
+        );
+    }
+
+    #[test]
+    fn map_2mib_over_page_table_is_rejected() {
+        let (_pool, mut mapper, mut frames) = setup(8);
+        mapper.map(va(0x1000), pa(0x9000), RW, &mut frames).unwrap();
+        assert!(matches!(
+            mapper.map_2mib(va(0), pa(0x20_0000), RW, &mut frames),
+            Err(MapError::AlreadyMapped { .. })
+        ));
+    }
+
+    #[test]
+    fn split_2mib_page_preserves_translations() {
+        let (_pool, mut mapper, mut frames) = setup(8);
+        mapper
+            .map_2mib(va(0x20_0000), pa(0x40_0000), RW, &mut frames)
+            .unwrap();
+        let before = frames.handed_out;
+        mapper.split_huge_page(va(0x20_5000), &mut frames).unwrap();
+        assert_eq!(frames.handed_out, before + 1);
+        for off in [0u64, 0x5123, 0x1F_F000] {
+            let t = mapper.translate(va(0x20_0000 + off)).unwrap();
+            assert_eq!(t.size, PageSize::Size4KiB);
+            assert_eq!(t.phys, pa(0x40_0000 + off));
+            assert!(t.flags.contains(PageTableFlags::WRITABLE));
+            assert!(!t.flags.contains(PageTableFlags::HUGE_PAGE));
+        }
+        // Now a single 4 KiB page can be unmapped.
+        mapper.unmap(va(0x20_5000)).unwrap();
+        assert_eq!(mapper.translate(va(0x20_5000)), None);
+        assert!(mapper.translate(va(0x20_6000)).is_some());
+    }
+
+    #[test]
+    fn for_each_table_visits_root_and_every_intermediate_table() {
+        let (_pool, mut mapper, mut frames) = setup(16);
+        mapper
+            .map(va(0x40_0000), pa(0x1000), RW, &mut frames)
+            .unwrap();
+        mapper
+            .map(va(0x60_0000), pa(0x2000), RW, &mut frames)
+            .unwrap();
+        // A huge leaf adds its PD's parent chain but no page table.
+        mapper
+            .map_2mib(va(0x8000_0000), pa(0x20_0000), RW, &mut frames)
+            .unwrap();
+
+        let mut seen = std::vec::Vec::new();
+        mapper.for_each_table(|t| seen.push(t));
+        assert_eq!(seen[0], mapper.root());
+        // PML4 + PDPT + 2 PDs + 2 PTs.
+        assert_eq!(seen.len(), 6);
+        assert_eq!(seen.len() - 1, frames.handed_out);
+        seen.sort();
+        seen.dedup();
+        assert_eq!(seen.len(), 6);
+    }
+
+    #[test]
+    fn split_4kib_page_is_a_no_op_and_unmapped_is_an_error() {
+        let (_pool, mut mapper, mut frames) = setup(8);
+        mapper.map(va(0x1000), pa(0x5000), RW, &mut frames).unwrap();
+        let before = frames.handed_out;
+        mapper.split_huge_page(va(0x1000), &mut frames).unwrap();
+        assert_eq!(frames.handed_out, before);
+        assert_eq!(
+            mapper.split_huge_page(va(0x9000_0000), &mut frames),
+            Err(MapError::NotMapped)
+        );
+    }
+
+    #[test]
+    fn translate_1gib_page() {
+        let (mut pool, mut mapper, mut frames) = setup(8);
+        // Build PML4 → PDPT by mapping something, then plant a 1 GiB leaf.
+        mapper.map(va(0x1000), pa(0x9000), RW, &mut frames).unwrap();
+        let pdpt = (pool.tables[0][0].addr().as_u64() - POOL_BASE) / PAGE_SIZE;
+        pool.tables[pdpt as usize][1].set(
+            pa(0x8000_0000),
+            PageTableFlags::PRESENT | PageTableFlags::HUGE_PAGE,
+        );
+        let t = mapper.translate(va(0x4000_1234)).unwrap();
+        assert_eq!(t.size, PageSize::Size1GiB);
+        assert_eq!(t.phys, pa(0x8000_1234));
+        assert_eq!(
+            mapper.map(va(0x4000_0000), pa(0), RW, &mut frames),
+            Err(MapError::HugePageInPath {
+                size: PageSize::Size1GiB
+            })
+        );
+    }
+
+    #[test]
+    fn split_1gib_page_down_to_4kib() {
+        let (mut pool, mut mapper, mut frames) = setup(8);
+        mapper.map(va(0x1000), pa(0x9000), RW, &mut frames).unwrap();
+        let pdpt = (pool.tables[0][0].addr().as_u64() - POOL_BASE) / PAGE_SIZE;
+        pool.tables[pdpt as usize][1].set(
+            pa(0x8000_0000),
+            PageTableFlags::PRESENT | PageTableFlags::HUGE_PAGE | PageTableFlags::NO_EXECUTE,
+        );
+        let before = frames.handed_out;
+        mapper
+            .split_huge_page(va(0x4060_1000), &mut frames)
+            .unwrap();
+        assert_eq!(frames.handed_out, before + 2);
+
+        let t = mapper.translate(va(0x4060_1234)).unwrap();
+        assert_eq!(t.size, PageSize::Size4KiB);
+        assert_eq!(t.phys, pa(0x8060_1234));
+        assert!(t.flags.contains(PageTableFlags::NO_EXECUTE));
+        // Neighbouring 2 MiB pages stay huge.
+        let t = mapper.translate(va(0x4000_0000)).unwrap();
+        assert_eq!(t.size, PageSize::Size2MiB);
+        assert_eq!(t.phys, pa(0x8000_0000));
+    }
+
+    // -----------------------------------------------------------------------
+    // unmap / update_flags
+    // -----------------------------------------------------------------------
+
+    #[test]
+    fn unmap_removes_mapping_and_returns_it() {
+        let (_pool, mut mapper, mut frames) = setup(8);
+        mapper.map(va(0x1000), pa(0x5000), RW, &mut frames).unwrap();
+        let t = mapper.unmap(va(0x1000)).unwrap();
+        assert_eq!(t.frame, pa(0x5000));
+        assert_eq!(mapper.translate(va(0x1000)), None);
+        assert_eq!(mapper.unmap(va(0x1000)), Err(UnmapError::NotMapped));
+        // The slot can be reused.
+        mapper.map(va(0x1000), pa(0x6000), RW, &mut frames).unwrap();
+        assert_eq!(frames.handed_out, 3);
+    }
+
+    #[test]
+    fn unmap_huge_page_requires_page_start() {
+        let (_pool, mut mapper, mut frames) = setup(8);
+        mapper
+            .map_2mib(va(0x20_0000), pa(0), RW, &mut frames)
+            .unwrap();
+        assert_eq!(
+            mapper.unmap(va(0x20_1000)),
+            Err(UnmapError::NotPageStart {
+                size: PageSize::Size2MiB
+            })
+        );
+        let t = mapper.unmap(va(0x20_0000)).unwrap();
+        assert_eq!(t.size, PageSize::Size2MiB);
+        assert_eq!(mapper.translate(va(0x20_1000)), None);
+    }
+
+    #[test]
+    fn update_flags_keeps_frame_and_present() {
+        let (_pool, mut mapper, mut frames) = setup(8);
+        mapper.map(va(0x1000), pa(0x5000), RW, &mut frames).unwrap();
+        let t = mapper
+            .update_flags(va(0x1000), PageTableFlags::NO_EXECUTE)
+            .unwrap();
+        assert_eq!(t.frame, pa(0x5000));
+        assert_eq!(
+            t.flags,
+            PageTableFlags::PRESENT | PageTableFlags::NO_EXECUTE
+        );
+    }
+
+    #[test]
+    fn update_flags_on_huge_page_keeps_ps() {
+        let (_pool, mut mapper, mut frames) = setup(8);
+        mapper.map_2mib(va(0), pa(0), RW, &mut frames).unwrap();
+        let t = mapper.update_flags(va(0), PageTableFlags::empty()).unwrap();
+        assert!(t.flags.contains(PageTableFlags::HUGE_PAGE));
+        assert!(!t.flags.contains(PageTableFlags::WRITABLE));
+    }
+}
@@ -0,0 +1,358 @@
+//! Page table entries, flags and tables.
+//!
+//! Layout per Intel SDM Vol 3A §4.5 (4-level paging). All four levels share
+//! the same 64-bit entry format; bit 7 (`PS`) means "huge page" in PDPT and
+//! PD entries and is reserved in PML4 entries.
+
+use core::fmt;
+use core::ops::{BitAnd, BitOr, BitOrAssign, Index, IndexMut, Not};
+
+use crate::addr::PhysAddr;
+
+/// Entries per page table.
+pub const ENTRY_COUNT: usize = 512;
+
+/// Bits 12–51 of an entry hold the physical address of the next table or
+/// the mapped frame.
+const ADDR_MASK: u64 = 0x000F_FFFF_FFFF_F000;
+
+// ---------------------------------------------------------------------------
+// PageTableFlags
+// ---------------------------------------------------------------------------
+
+/// Page table entry flag bits.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[repr(transparent)]
+pub struct PageTableFlags(u64);
+
+impl PageTableFlags {
+    /// P — the entry is valid.
+    pub const PRESENT: Self = Self(1 << 0);
+    /// RW — writes are allowed.
+    pub const WRITABLE: Self = Self(1 << 1);
+    /// US — ring 3 may access the page.
+    pub const USER_ACCESSIBLE: Self = Self(1 << 2);
+    /// PWT — write-through caching.
+    pub const WRITE_THROUGH: Self = Self(1 << 3);
+    /// PCD — caching disabled.
+    pub const NO_CACHE: Self = Self(1 << 4);
+    /// A — set by the CPU on access.
+    pub const ACCESSED: Self = Self(1 << 5);
+    /// D — set by the CPU on write (leaf entries only).
+    pub const DIRTY: Self = Self(1 << 6);
+    /// PS — the entry maps a 2 MiB (PD) or 1 GiB (PDPT) page.
+    pub const HUGE_PAGE: Self = Self(1 << 7);
+    /// G — the translation survives CR3 reloads (requires CR4.PGE).
+    pub const GLOBAL: Self = Self(1 << 8);
+    /// XD — instruction fetches are not allowed (requires EFER.NXE).
+    pub const NO_EXECUTE: Self = Self(1 << 63);
+
+    /// Every flag bit this type knows about.
+    const ALL: u64 = 0x1FF | (1 << 63);
+
+    /// No flags set.
+    #[inline]
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Flags from raw bits; unknown bits are dropped.
+    #[inline]
+    pub const fn from_bits_truncate(bits: u64) -> Self {
+        Self(bits & Self::ALL)
+    }
+
+    /// Raw flag bits.
+    #[inline]
+    pub const fn bits(self) -> u64 {
+        self.0
+    }
+
+    /// True if every flag in `other` is set in `self`.
+    #[inline]
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// True if no flag is set.
+    #[inline]
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Set every flag in `other`.
+    #[inline]
+    pub fn insert(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+
+    /// Clear every flag in `other`.
+    #[inline]
+    pub fn remove(&mut self, other: Self) {
+        self.0 &= !other.0;
+    }
+}
+
+impl BitOr for PageTableFlags {
+    type Output = Self;
+
+    #[inline]
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for PageTableFlags {
+    #[inline]
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl BitAnd for PageTableFlags {
+    type Output = Self;
+
+    #[inline]
+    fn bitand(self, rhs: Self) -> Self {
+        Self(self.0 & rhs.0)
+    }
+}
+
+impl Not for PageTableFlags {
+    type Output = Self;
+
+    #[inline]
+    fn not(self) -> Self {
+        Self(!self.0 & Self::ALL)
+    }
+}
+
+impl fmt::Debug for PageTableFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const NAMES: [(PageTableFlags, &str); 10] = [
+            (PageTableFlags::PRESENT, "P"),
+            (PageTableFlags::WRITABLE, "RW"),
+            (PageTableFlags::USER_ACCESSIBLE, "US"),
+            (PageTableFlags::WRITE_THROUGH, "PWT"),
+            (PageTableFlags::NO_CACHE, "PCD"),
+            (PageTableFlags::ACCESSED, "A"),
+            (PageTableFlags::DIRTY, "D"),
+            (PageTableFlags::HUGE_PAGE, "PS"),
+            (PageTableFlags::GLOBAL, "G"),
+            (PageTableFlags::NO_EXECUTE, "NX"),
+        ];
+        let mut first = true;
+        for (flag, name) in NAMES {
+            if self.contains(flag) {
+                if !first {
+                    f.write_str(" | ")?;
+                }
+                f.write_str(name)?;
+                first = false;
+            }
+        }
+        if first {
+            f.write_str("(empty)")?;
+        }
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PageTableEntry
+// ---------------------------------------------------------------------------
+
+/// A single 64-bit page table entry.
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct PageTableEntry(u64);
+
+impl PageTableEntry {
+    /// An all-zero (not present) entry.
+    #[inline]
+    pub const fn new() -> Self {
+        Self(0)
+    }
+
+    /// True if every bit is zero.
+    #[inline]
+    pub const fn is_unused(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Zero the entry.
+    #[inline]
+    pub fn set_unused(&mut self) {
+        self.0 = 0;
+    }
+
+    /// Flag bits of the entry.
+    #[inline]
+    pub const fn flags(&self) -> PageTableFlags {
+        PageTableFlags::from_bits_truncate(self.0)
+    }
+
+    /// Physical address stored in the entry (bits 12–51).
+    #[inline]
+    pub const fn addr(&self) -> PhysAddr {
+        PhysAddr::new_truncate(self.0 & ADDR_MASK)
+    }
+
+    /// Point the entry at `addr` with `flags`.
+    ///
+    /// `addr` must be 4 KiB aligned; low bits are masked off.
+    #[inline]
+    pub fn set(&mut self, addr: PhysAddr, flags: PageTableFlags) {
+        self.0 = (addr.as_u64() & ADDR_MASK) | flags.bits();
+    }
+
+    /// Replace the flags, keeping the address.
+    #[inline]
+    pub fn set_flags(&mut self, flags: PageTableFlags) {
+        self.0 = (self.0 & ADDR_MASK) | flags.bits();
+    }
+
+    /// Raw entry value.
+    #[inline]
+    pub const fn raw(&self) -> u64 {
+        self.0
+    }
+}
+
+impl Default for PageTableEntry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for PageTableEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "PageTableEntry({:#x}, {:?})",
+            self.addr().as_u64(),
+            self.flags()
+        )
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PageTable
+// ---------------------------------------------------------------------------
+
+/// One 4 KiB page table (any level): 512 entries, page-aligned.
+#[repr(C, align(4096))]
+#[derive(Clone)]
+pub struct PageTable {
+    entries: [PageTableEntry; ENTRY_COUNT],
+}
+
+impl PageTable {
+    /// A table with every entry unused.
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            entries: [PageTableEntry::new(); ENTRY_COUNT],
+        }
+    }
+
+    /// Mark every entry unused.
+    pub fn zero(&mut self) {
+        self.entries.fill(PageTableEntry::new());
+    }
+
+    /// Iterate over the entries.
+    pub fn iter(&self) -> impl Iterator<Item = &PageTableEntry> {
+        self.entries.iter()
+    }
+
+    /// True if every entry is unused.
+    pub fn is_empty(&self) -> bool {
+        self.entries.iter().all(PageTableEntry::is_unused)
+    }
+}
+
+impl Default for PageTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Index<usize> for PageTable {
+    type Output = PageTableEntry;
+
+    #[inline]
+    fn index(&self, index: usize) -> &PageTableEntry {
+        &self.entries[index]
+    }
+}
+
+impl IndexMut<usize> for PageTable {
+    #[inline]
+    fn index_mut(&mut self, index: usize) -> &mut PageTableEntry {
+        &mut self.entries[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::format;
+
+    #[test]
+    fn page_table_is_one_page() {
+        assert_eq!(core::mem::size_of::<PageTable>(), 4096);
+        assert_eq!(core::mem::align_of::<PageTable>(), 4096);
+    }
+
+    #[test]
+    fn flag_bit_positions_match_sdm() {
+        assert_eq!(PageTableFlags::PRESENT.bits(), 1);
+        assert_eq!(PageTableFlags::WRITABLE.bits(), 2);
+        assert_eq!(PageTableFlags::USER_ACCESSIBLE.bits(), 4);
+        assert_eq!(PageTableFlags::HUGE_PAGE.bits(), 0x80);
+        assert_eq!(PageTableFlags::GLOBAL.bits(), 0x100);
+        assert_eq!(PageTableFlags::NO_EXECUTE.bits(), 1 << 63);
+    }
+
+    #[test]
+    fn entry_round_trips_address_and_flags() {
+        let mut e = PageTableEntry::new();
+        assert!(e.is_unused());
+        let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::NO_EXECUTE;
+        e.set(PhysAddr::new(0x000F_FFFF_FFFF_F000).unwrap(), flags);
+        assert_eq!(e.addr().as_u64(), 0x000F_FFFF_FFFF_F000);
+        assert_eq!(e.flags(), flags);
+        e.set_flags(PageTableFlags::PRESENT);
+        assert_eq!(e.addr().as_u64(), 0x000F_FFFF_FFFF_F000);
+        assert_eq!(e.flags(), PageTableFlags::PRESENT);
+    }
+
+    #[test]
+    fn entry_ignores_os_available_bits_in_flags() {
+        // Bits 9–11 and 52–62 are available to software.
+        let e = PageTableEntry(0x7FF0_0000_0000_0E01);
+        assert_eq!(e.flags(), PageTableFlags::PRESENT);
+        assert_eq!(e.addr().as_u64(), 0);
+    }
+
+    #[test]
+    fn flag_set_operations() {
+        let mut f = PageTableFlags::PRESENT;
+        f.insert(PageTableFlags::WRITABLE | PageTableFlags::GLOBAL);
+        assert!(f.contains(PageTableFlags::PRESENT | PageTableFlags::GLOBAL));
+        f.remove(PageTableFlags::GLOBAL);
+        assert!(!f.contains(PageTableFlags::GLOBAL));
+        assert_eq!(
+            (!PageTableFlags::PRESENT) & PageTableFlags::PRESENT,
+            PageTableFlags::empty()
+        );
+    }
+
+    #[test]
+    fn flags_debug_lists_names() {
+        let f = PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::NO_EXECUTE;
+        assert_eq!(format!("{f:?}"), "P | RW | NX");
+        assert_eq!(format!("{:?}", PageTableFlags::empty()), "(empty)");
+    }
+}
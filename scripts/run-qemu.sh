@@ -11,6 +11,9 @@
 #
 # Usage:
 #   ./scripts/run-qemu.sh [--release]
+#
+# Set INITRD to a ustar or newc cpio archive to boot with an initial ramdisk:
+#   INITRD=initrd.tar ./scripts/run-qemu.sh
 
 set -euo pipefail
 
@@ -96,6 +99,21 @@ build_bootloader() {
     cd "$PROJECT_ROOT"
 }
 
+# Build the kernel ELF
+build_kernel() {
+    info "Building kernel (${BUILD_MODE})..."
+
+    cd "$PROJECT_ROOT/kernel"
+
+    if [[ "$BUILD_MODE" == "release" ]]; then
+        cargo build --release
+    else
+        cargo build
+    fi
+
+    cd "$PROJECT_ROOT"
+}
+
 # Create the EFI boot disk structure
 create_boot_disk() {
     info "Creating boot disk structure..."
@@ -114,6 +132,25 @@ create_boot_disk() {
 
     cp "$BOOTLOADER_PATH" "$EFI_DIR/BOOTX64.EFI"
 
+    # Copy the kernel to the path the bootloader loads it from
+    KERNEL_PATH="$PROJECT_ROOT/target/x86_64-unknown-none/${BUILD_MODE}/ferrous-kernel"
+
+    if [[ ! -f "$KERNEL_PATH" ]]; then
+        error "Kernel not found at $KERNEL_PATH"
+    fi
+
+    mkdir -p "$BOOT_DISK/EFI/ferrous"
+    cp "$KERNEL_PATH" "$BOOT_DISK/EFI/ferrous/kernel.elf"
+
+    # Optional initial ramdisk; a stale copy from an earlier run is removed
+    rm -f "$BOOT_DISK/EFI/ferrous/initrd.tar"
+    if [[ -n "${INITRD:-}" ]]; then
+        if [[ ! -f "$INITRD" ]]; then
+            error "Initrd not found at $INITRD"
+        fi
+        cp "$INITRD" "$BOOT_DISK/EFI/ferrous/initrd.tar"
+    fi
+
     info "Boot disk created at $BOOT_DISK"
 }
 
@@ -159,6 +196,7 @@ main() {
 
     check_requirements
     build_bootloader
+    build_kernel
     create_boot_disk
 
     # Try with KVM first, fall back to without
@@ -116,6 +116,14 @@ build() {
     else
         cargo build 2>&1
     fi
+
+    info "Building kernel (${BUILD_MODE})..."
+    cd "$PROJECT_ROOT/kernel"
+    if [[ "$BUILD_MODE" == "release" ]]; then
+        cargo build --release 2>&1
+    else
+        cargo build 2>&1
+    fi
     cd "$PROJECT_ROOT"
 }
 
@@ -134,6 +142,14 @@ create_boot_disk() {
         exit 1
     fi
     cp "$BOOTLOADER" "$EFI_DIR/BOOTX64.EFI"
+
+    KERNEL="$PROJECT_ROOT/target/x86_64-unknown-none/${BUILD_MODE}/ferrous-kernel"
+    if [[ ! -f "$KERNEL" ]]; then
+        fail "Kernel binary not found at $KERNEL"
+        exit 1
+    fi
+    mkdir -p "$BOOT_DISK/EFI/ferrous"
+    cp "$KERNEL" "$BOOT_DISK/EFI/ferrous/kernel.elf"
 }
 
 # ---------------------------------------------------------------------------
@@ -4,8 +4,15 @@
 //! - GDT segment descriptor bit patterns match the Intel SDM specification
 //! - IDT gate descriptor address encoding is correct
 //! - Exception vector properties (which vectors push error codes)
+//! - TSS descriptor encoding
 //! - Kernel stack layout constants
 //! - ExceptionFrame conceptual field layout
+//! - InterruptContext layout and stub stride
+//! - 8259 PIC remap vectors, cascade wiring and spurious IRQ detection
+//!   (compiled from the kernel source)
+//! - #PF and selector error-code decoding (compiled from the kernel source)
+//! - Kernel command line and `boot.cfg` parsing (compiled from the kernel
+//!   and bootloader sources)
 //!
 //! They do NOT test runtime behaviour (loading GDTR/IDTR, firing interrupts)
 //! which requires QEMU — that is covered by `scripts/verify-boot.sh`.
@@ -163,6 +170,56 @@ fn gdt_has_three_entries_at_expected_selectors() {
     assert_eq!(KERNEL_DATA_SEL, 0x10);
 }
 
+// ---------------------------------------------------------------------------
+// TSS descriptor encoding tests
+//
+// The 64-bit TSS descriptor is a 16-byte system descriptor (S=0) spanning
+// GDT slots 3–4: slot 3 uses the layout above with Type=0x9 (available
+// 64-bit TSS), slot 4 holds base bits 63:32. Mirrors `gdt::tss_descriptor`.
+// ---------------------------------------------------------------------------
+
+/// Size of the 64-bit TSS (Intel SDM Vol 3A §8.7).
+const TSS_SIZE: u64 = 104;
+
+/// TSS selector (GDT index 3, TI=0, RPL=0).
+const TSS_SEL: u16 = 0x0018;
+
+fn tss_descriptor(base: u64) -> [u64; 2] {
+    let limit = TSS_SIZE - 1;
+    let low = (limit & 0xFFFF)
+        | ((base & 0xFF_FFFF) << 16)
+        | (0x9 << 40)
+        | (1 << 47)
+        | (((limit >> 16) & 0xF) << 48)
+        | (((base >> 24) & 0xFF) << 56);
+    [low, base >> 32]
+}
+
+#[test]
+fn tss_descriptor_is_present_available_system_descriptor() {
+    let [low, _] = tss_descriptor(0x20_3000);
+    assert_ne!(low & (1 << 47), 0, "P bit must be set");
+    assert_eq!(low & (1 << 44), 0, "S bit must be 0 (system descriptor)");
+    assert_eq!((low >> 40) & 0xF, 0x9, "type must be available 64-bit TSS");
+    assert_eq!((low >> 45) & 0b11, 0, "DPL must be 0");
+}
+
+#[test]
+fn tss_descriptor_encodes_limit_and_split_base() {
+    let base = 0xFFFF_FFFF_8012_3456;
+    let [low, high] = tss_descriptor(base);
+    assert_eq!(low & 0xFFFF, TSS_SIZE - 1, "limit[15:0]");
+    assert_eq!((low >> 48) & 0xF, 0, "limit[19:16]");
+    let decoded = ((low >> 16) & 0xFF_FFFF) | (((low >> 56) & 0xFF) << 24) | (high << 32);
+    assert_eq!(decoded, base);
+}
+
+#[test]
+fn tss_selector_follows_data_segment() {
+    assert_eq!(TSS_SEL, 0x18, "TSS selector = GDT index 3");
+    assert_eq!(TSS_SEL, KERNEL_DATA_SEL + 8);
+}
+
 // ---------------------------------------------------------------------------
 // IDT gate descriptor encoding tests
 //
@@ -242,6 +299,35 @@ fn idt_attr_kernel_trap_gate_value() {
     assert_eq!(ATTR_KERNEL_TRAP & 0x0F, 0xF, "gate type must be 0xF (trap)");
 }
 
+/// Reproduce the `IdtEntry::with_gate_type` / `with_dpl` attribute encoding.
+fn idt_attr(gate: u8, dpl: u8) -> u8 {
+    0x80 | (dpl << 5) | gate
+}
+
+#[test]
+fn idt_attr_builder_matches_constants() {
+    assert_eq!(idt_attr(0xE, 0), 0x8E, "ATTR_KERNEL_INTERRUPT");
+    assert_eq!(idt_attr(0xF, 0), 0x8F, "ATTR_KERNEL_TRAP");
+    // DPL=3 trap gate, as used for `int3` from user mode.
+    assert_eq!(idt_attr(0xF, 3), 0xEF);
+}
+
+#[test]
+fn idt_ist_assignments_are_distinct_and_in_range() {
+    // (vector, IST index): #DF → IST1, NMI → IST2, #MC → IST3.
+    const IST_GATES: [(u8, u8); 3] = [(8, 1), (2, 2), (18, 3)];
+    for (vector, ist) in IST_GATES {
+        assert!((1..=7).contains(&ist), "vector {vector}: IST must be 1-7");
+        // Byte 4 of the gate: bits 2:0 = IST, bits 7:3 reserved.
+        assert_eq!(ist & !0x7, 0);
+    }
+    let mut seen = [false; 8];
+    for (_, ist) in IST_GATES {
+        assert!(!seen[ist as usize], "IST{ist} assigned twice");
+        seen[ist as usize] = true;
+    }
+}
+
 #[test]
 fn idt_has_256_vectors() {
     // The IDT must have exactly 256 entries (0–255) per the x86-64 architecture.
@@ -353,6 +439,107 @@ fn hardware_irq_vectors_do_not_push_error_codes() {
     }
 }
 
+// ---------------------------------------------------------------------------
+// 8259 PIC remap
+//
+// Compiled straight from the kernel source: `pic8259_layout.rs` depends only
+// on `core`. After firmware the master PIC delivers IRQ 0–7 at vectors 8–15,
+// on top of the CPU exceptions; the kernel moves the pair to 32–47.
+// ---------------------------------------------------------------------------
+
+#[path = "../kernel/src/drivers/pic8259_layout.rs"]
+mod pic8259_layout;
+
+use pic8259_layout::{
+    irq_vector, is_lowest_priority, is_spurious_delivery, vector_irq, CASCADE_IRQ, ICW1_INIT_ICW4,
+    ICW3_MASTER_SLAVE_ON_IRQ2, ICW3_SLAVE_ID, ICW4_8086, IRQ_COUNT, OCW2_EOI, OCW3_READ_ISR,
+    PIC1_VECTOR_BASE, PIC2_VECTOR_BASE,
+};
+
+const BIOS_PIC1_VECTOR_BASE: u8 = 8;
+
+#[test]
+fn bios_pic_layout_aliases_exceptions() {
+    // IRQ 0 (timer) would arrive as #DF, IRQ 6 as #PF.
+    assert!(has_error_code(BIOS_PIC1_VECTOR_BASE as u64));
+    assert_eq!(BIOS_PIC1_VECTOR_BASE + 6, 14);
+}
+
+#[test]
+fn remapped_irqs_follow_the_exceptions() {
+    assert_eq!(irq_vector(0), 32, "first vector after the 32 exceptions");
+    assert_eq!(irq_vector(8), PIC2_VECTOR_BASE);
+    assert_eq!(irq_vector(IRQ_COUNT - 1), 47);
+    // ICW2 requires the low three bits of each base to be zero.
+    assert_eq!(PIC1_VECTOR_BASE & 7, 0);
+    assert_eq!(PIC2_VECTOR_BASE & 7, 0);
+    for irq in 0..IRQ_COUNT {
+        let vector = irq_vector(irq);
+        assert!(!has_error_code(vector as u64));
+        assert_eq!(vector_irq(vector), Some(irq));
+    }
+}
+
+#[test]
+fn vectors_outside_the_pic_range_have_no_irq() {
+    assert_eq!(vector_irq(PIC1_VECTOR_BASE - 1), None);
+    assert_eq!(vector_irq(PIC1_VECTOR_BASE + IRQ_COUNT), None);
+    assert_eq!(vector_irq(BIOS_PIC1_VECTOR_BASE), None);
+    assert_eq!(vector_irq(u8::MAX), None);
+}
+
+#[test]
+fn command_words_match_the_8259a_encoding() {
+    // ICW1: D4 marks initialisation; IC4 (D0) set, SNGL (D1) and LTIM (D3)
+    // clear for cascade mode with edge triggering.
+    assert_eq!(ICW1_INIT_ICW4 & 0x10, 0x10);
+    assert_eq!(ICW1_INIT_ICW4 & 0x0B, 0x01);
+    // ICW4: uPM (D0) selects 8086 mode; AEOI (D1) clear.
+    assert_eq!(ICW4_8086 & 0x03, 0x01);
+    // OCW2: D4–D3 = 00; R SL EOI = 001 is a non-specific EOI.
+    assert_eq!(OCW2_EOI & 0x18, 0);
+    assert_eq!(OCW2_EOI >> 5, 0b001);
+    // OCW3: D4–D3 = 01; RR RIS = 11 reads the ISR.
+    assert_eq!(OCW3_READ_ISR & 0x18, 0x08);
+    assert_eq!(OCW3_READ_ISR & 0x03, 0x03);
+}
+
+#[test]
+fn cascade_wiring_matches_on_both_chips() {
+    // The master's ICW3 is a bit mask of slave lines, the slave's its line
+    // number.
+    assert_eq!(ICW3_MASTER_SLAVE_ON_IRQ2, 1 << CASCADE_IRQ);
+    assert_eq!(
+        ICW3_MASTER_SLAVE_ON_IRQ2.trailing_zeros(),
+        ICW3_SLAVE_ID as u32
+    );
+}
+
+#[test]
+fn spurious_irqs_are_each_chips_lowest_priority_line() {
+    let lowest: Vec<u8> = (0..IRQ_COUNT)
+        .filter(|&irq| is_lowest_priority(irq))
+        .collect();
+    assert_eq!(lowest, [7, 15]);
+    assert_eq!(irq_vector(7), 39);
+    assert_eq!(irq_vector(15), 47);
+}
+
+#[test]
+fn spurious_delivery_needs_a_clear_in_service_bit() {
+    for irq in [7, 15] {
+        assert!(is_spurious_delivery(irq, 0));
+        // Other lines in service do not make it real.
+        assert!(is_spurious_delivery(irq, 0x7F));
+        assert!(!is_spurious_delivery(irq, 0x80));
+        assert!(!is_spurious_delivery(irq, 0xFF));
+    }
+    // Higher-priority lines are never spurious, whatever the ISR says.
+    for irq in (0..IRQ_COUNT).filter(|&irq| irq != 7 && irq != 15) {
+        assert!(!is_spurious_delivery(irq, 0));
+    }
+}
+
 // ---------------------------------------------------------------------------
 // ExceptionFrame field layout
 //
@@ -369,8 +556,8 @@ fn exception_frame_is_five_quadwords() {
 
 #[test]
 fn exception_frame_field_offsets() {
-    // Offsets from the base of the frame (RSP at handler entry, after any
-    // error code has been popped by isr_stub_ec).
+    // Offsets from the base of the frame (RSP at handler entry for vectors
+    // without an error code; the tail of InterruptContext).
     const RIP_OFFSET: usize = 0;
     const CS_OFFSET: usize = 8;
     const RFLAGS_OFFSET: usize = 16;
@@ -384,6 +571,244 @@ fn exception_frame_field_offsets() {
     assert_eq!(SS_OFFSET, RSP_OFFSET + 8);
 }
 
+// ---------------------------------------------------------------------------
+// InterruptContext layout
+//
+// Mirrors `arch::x86_64::interrupts::InterruptContext`: the registers pushed
+// by `__interrupt_common` (last push = lowest address), then the vector and
+// error code pushed by the per-vector stub, then the CPU frame.
+// ---------------------------------------------------------------------------
+
+#[repr(C)]
+#[allow(dead_code)]
+struct InterruptContext {
+    r15: u64,
+    r14: u64,
+    r13: u64,
+    r12: u64,
+    r11: u64,
+    r10: u64,
+    r9: u64,
+    r8: u64,
+    rbp: u64,
+    rdi: u64,
+    rsi: u64,
+    rdx: u64,
+    rcx: u64,
+    rbx: u64,
+    rax: u64,
+    vector: u64,
+    error_code: u64,
+    rip: u64,
+    cs: u64,
+    rflags: u64,
+    rsp: u64,
+    ss: u64,
+}
+
+/// Registers pushed by `__interrupt_common`, in push order.
+const SAVED_REGISTERS: usize = 15;
+
+#[test]
+fn interrupt_context_keeps_stack_16_byte_aligned() {
+    // The CPU aligns RSP to 16 before pushing its frame; everything pushed
+    // after that must total a multiple of 16 for the SysV `call`.
+    let size = core::mem::size_of::<InterruptContext>();
+    assert_eq!(size, (SAVED_REGISTERS + 2 + 5) * 8);
+    assert_eq!(size % 16, 0);
+}
+
+#[test]
+fn interrupt_context_saves_every_gpr_but_rsp() {
+    use core::mem::offset_of;
+    // RSP comes from the CPU frame; the other 15 are pushed RAX first.
+    assert_eq!(offset_of!(InterruptContext, r15), 0);
+    assert_eq!(offset_of!(InterruptContext, rax), (SAVED_REGISTERS - 1) * 8);
+    assert_eq!(offset_of!(InterruptContext, rbp), 8 * 8);
+}
+
+#[test]
+fn interrupt_context_frame_follows_vector_and_error_code() {
+    use core::mem::offset_of;
+    assert_eq!(offset_of!(InterruptContext, vector), SAVED_REGISTERS * 8);
+    assert_eq!(
+        offset_of!(InterruptContext, error_code),
+        SAVED_REGISTERS * 8 + 8
+    );
+    assert_eq!(offset_of!(InterruptContext, rip), SAVED_REGISTERS * 8 + 16);
+    assert_eq!(
+        offset_of!(InterruptContext, ss),
+        SAVED_REGISTERS * 8 + 16 + 32
+    );
+}
+
+#[test]
+fn isr_stub_fits_in_stride() {
+    // Stubs are laid out every 16 bytes so vector → address is a multiply.
+    // Worst case: push imm8 (2) + push imm32 for vectors ≥ 128 (5) + jmp rel32 (5).
+    const STUB_STRIDE: usize = 16;
+    let worst_case_stub: usize = [2, 5, 5].iter().sum();
+    assert!(worst_case_stub <= STUB_STRIDE);
+}
+
+// ---------------------------------------------------------------------------
+// Exception error-code decoders
+//
+// Unlike the mirrors above, the decoders are compiled straight from the
+// kernel source: `error_code.rs` depends only on `core`.
+// ---------------------------------------------------------------------------
+
+#[path = "../kernel/src/arch/x86_64/error_code.rs"]
+mod error_code;
+
+use error_code::{
+    decode_mxcsr, decode_page_fault, decode_selector, decode_x87, DescriptorTable,
+    SelectorErrorCode, StackFault,
+};
+
+#[test]
+fn page_fault_non_present_write_from_kernel() {
+    let cause = decode_page_fault(0x2);
+    assert!(!cause.present && cause.write && !cause.user);
+    assert_eq!(cause.to_string(), "write to non-present page from ring 0");
+}
+
+#[test]
+fn page_fault_read_and_fetch_wording() {
+    assert_eq!(
+        decode_page_fault(0x0).to_string(),
+        "read from non-present page from ring 0"
+    );
+    assert_eq!(
+        decode_page_fault(0x5).to_string(),
+        "read from protected page from ring 3"
+    );
+    // I/D wins over W/R: instruction fetches never report a write.
+    assert_eq!(
+        decode_page_fault(0x11).to_string(),
+        "instruction fetch from protected page from ring 0"
+    );
+}
+
+#[test]
+fn page_fault_extra_bits_are_appended() {
+    assert_eq!(
+        decode_page_fault(0x9).to_string(),
+        "read from protected page from ring 0, reserved bit set in page table"
+    );
+    assert_eq!(
+        decode_page_fault(0x27).to_string(),
+        "write to protected page from ring 3, protection-key violation"
+    );
+    assert_eq!(
+        decode_page_fault(0x43).to_string(),
+        "shadow-stack write to protected page from ring 0"
+    );
+    assert_eq!(
+        decode_page_fault(0x8001).to_string(),
+        "read from protected page from ring 0, SGX access-control violation"
+    );
+}
+
+#[test]
+fn page_fault_decodes_every_defined_bit() {
+    let all = decode_page_fault(0x807F);
+    assert!(all.present && all.write && all.user && all.reserved_bit);
+    assert!(all.instruction_fetch && all.protection_key && all.shadow_stack && all.sgx);
+    let none = decode_page_fault(!0x807F);
+    assert_eq!(none, decode_page_fault(0));
+}
+
+#[test]
+fn selector_zero_is_not_selector_related() {
+    assert_eq!(decode_selector(0), None);
+}
+
+#[test]
+fn selector_gdt_entry() {
+    let sel = decode_selector(0x18).unwrap();
+    assert_eq!(
+        sel,
+        SelectorErrorCode {
+            external: false,
+            table: DescriptorTable::Gdt,
+            index: 3,
+        }
+    );
+    assert_eq!(sel.selector(), 0x18);
+    assert_eq!(sel.to_string(), "GDT selector 0x18 invalid");
+}
+
+#[test]
+fn selector_ldt_and_idt_entries() {
+    let ldt = decode_selector(0x1C).unwrap();
+    assert_eq!(ldt.table, DescriptorTable::Ldt);
+    assert_eq!(ldt.to_string(), "LDT selector 0x1c invalid");
+
+    // IDT bit set: index is a vector; TI is ignored.
+    let idt = decode_selector((32 << 3) | 0b110).unwrap();
+    assert_eq!(idt.table, DescriptorTable::Idt);
+    assert_eq!(idt.index, 32);
+    assert_eq!(idt.to_string(), "IDT vector 32 invalid");
+}
+
+#[test]
+fn selector_external_event_flag() {
+    let sel = decode_selector((13 << 3) | 0b011).unwrap();
+    assert!(sel.external);
+    assert_eq!(
+        sel.to_string(),
+        "IDT vector 13 invalid (during external event delivery)"
+    );
+}
+
+#[test]
+fn selector_ignores_upper_error_code_bits() {
+    assert_eq!(decode_selector(0xFFFF_0000), None);
+    assert_eq!(decode_selector(0x1_0010), decode_selector(0x10));
+}
+
+#[test]
+fn x87_exceptions_respect_control_word_masks() {
+    // FCW after FNINIT masks everything: nothing is reported.
+    assert!(decode_x87(0x003F, 0x037F).is_empty());
+    // Divide by zero unmasked (FCW bit 2 clear), zero-divide and inexact
+    // pending: only the unmasked one counts.
+    let cause = decode_x87(0x0024, 0x037B);
+    assert!(cause.zero_divide && !cause.precision);
+    assert_eq!(cause.to_string(), "divide by zero");
+}
+
+#[test]
+fn x87_stack_faults_are_decoded() {
+    // IE + SF, C1 set: push onto a full stack.
+    let overflow = decode_x87(0x0241, 0x0000);
+    assert_eq!(overflow.stack_fault, Some(StackFault::Overflow));
+    assert_eq!(overflow.to_string(), "invalid operation (stack overflow)");
+    let underflow = decode_x87(0x0041, 0x0000);
+    assert_eq!(underflow.stack_fault, Some(StackFault::Underflow));
+    // SF without an unmasked IE is not reported.
+    assert_eq!(decode_x87(0x0041, 0x0001).stack_fault, None);
+}
+
+#[test]
+fn mxcsr_exceptions_use_mask_bits_7_to_12() {
+    assert!(decode_mxcsr(0x1F80 | 0x3F).is_empty());
+    // Invalid and overflow unmasked (bits 7 and 10 clear) and pending.
+    let cause = decode_mxcsr(0x1B00 | 0x09);
+    assert!(cause.invalid && cause.overflow);
+    assert_eq!(cause.to_string(), "invalid operation, overflow");
+    assert_eq!(
+        decode_mxcsr(0x3F).to_string(),
+        "invalid operation, denormal operand, divide by zero, overflow, underflow, \
+         inexact result"
+    );
+    assert_eq!(
+        decode_mxcsr(0x1F80).to_string(),
+        "no unmasked exception pending"
+    );
+}
+
 // ---------------------------------------------------------------------------
 // Kernel stack layout constants
 // ---------------------------------------------------------------------------
@@ -391,6 +816,7 @@ fn exception_frame_field_offsets() {
 const KERNEL_STACK_SIZE: usize = 64 * 1024;
 const KERNEL_STACK_GUARD_SIZE: usize = 4 * 1024;
 const BOOTSTRAP_STACK_SIZE: usize = 16 * 1024;
+const IST_STACK_SIZE: usize = 16 * 1024;
 
 #[test]
 fn kernel_stack_is_64_kib() {
@@ -423,6 +849,553 @@ fn stack_sizes_are_page_aligned() {
     assert_eq!(KERNEL_STACK_SIZE % PAGE_SIZE, 0);
     assert_eq!(KERNEL_STACK_GUARD_SIZE % PAGE_SIZE, 0);
     assert_eq!(BOOTSTRAP_STACK_SIZE % PAGE_SIZE, 0);
+    assert_eq!(IST_STACK_SIZE % PAGE_SIZE, 0);
+}
+
+#[test]
+fn guard_page_is_exactly_one_page() {
+    // `KernelStack` is page-aligned, so the guard is a whole page that can
+    // be unmapped without affecting neighbouring statics.
+    assert_eq!(KERNEL_STACK_GUARD_SIZE, 4096);
+    assert_eq!(KERNEL_STACK_SIZE % KERNEL_STACK_GUARD_SIZE, 0);
+}
+
+// ---------------------------------------------------------------------------
+// CPUID decoding
+//
+// Compiled straight from the kernel source: `cpuid.rs` depends only on
+// `core`. The leaves below are canned register values, so the tests do not
+// depend on the host CPU.
+// ---------------------------------------------------------------------------
+
+#[path = "../kernel/src/arch/x86_64/cpuid.rs"]
+mod cpuid;
+
+use cpuid::{CacheInfo, CacheKind, CpuFeatures, CpuidLeaf, Feature, Signature, Vendor};
+
+const fn leaf(eax: u32, ebx: u32, ecx: u32, edx: u32) -> CpuidLeaf {
+    CpuidLeaf { eax, ebx, ecx, edx }
+}
+
+/// Leaf 0 for a 12-byte vendor string (EBX, EDX, ECX order).
+fn vendor_leaf(max_leaf: u32, vendor: &[u8; 12]) -> CpuidLeaf {
+    let reg = |i: usize| u32::from_le_bytes(vendor[i..i + 4].try_into().unwrap());
+    leaf(max_leaf, reg(0), reg(8), reg(4))
+}
+
+/// Leaves 8000_0002h–8000_0004h for a brand string of up to 47 bytes.
+fn brand_leaves(brand: &str) -> Vec<((u32, u32), CpuidLeaf)> {
+    let mut bytes = [0u8; 48];
+    bytes[..brand.len()].copy_from_slice(brand.as_bytes());
+    let reg = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+    (0..3)
+        .map(|i| {
+            let at = i * 16;
+            let regs = leaf(reg(at), reg(at + 4), reg(at + 8), reg(at + 12));
+            ((0x8000_0002 + i as u32, 0), regs)
+        })
+        .collect()
+}
+
+/// Deterministic cache parameters subleaf (leaf 4 / 8000_001Dh layout).
+const fn cache_leaf(kind: u32, level: u32, shared_by: u32, ways: u32, sets: u32) -> CpuidLeaf {
+    leaf(
+        kind | (level << 5) | ((shared_by - 1) << 14),
+        63 | ((ways - 1) << 22),
+        sets - 1,
+        0,
+    )
+}
+
+/// Run `CpuFeatures::read` against `leaves`, recording every query.
+/// Leaves not in the table read as zero.
+fn read_canned(leaves: &[((u32, u32), CpuidLeaf)]) -> (CpuFeatures, Vec<(u32, u32)>) {
+    let mut queries = Vec::new();
+    let features = CpuFeatures::read(|l, sub| {
+        queries.push((l, sub));
+        leaves
+            .iter()
+            .find(|(key, _)| *key == (l, sub))
+            .map_or(CpuidLeaf::default(), |(_, regs)| *regs)
+    });
+    (features, queries)
+}
+
+/// An Ivy Bridge-like Intel CPU with four caches and leaf 0Bh topology.
+fn intel_leaves() -> Vec<((u32, u32), CpuidLeaf)> {
+    let mut leaves = vec![
+        ((0, 0), vendor_leaf(0xD, b"GenuineIntel")),
+        (
+            (1, 0),
+            leaf(
+                0x0003_06A9,
+                (2 << 24) | (16 << 16),
+                (1 << 0) | (1 << 9) | (1 << 17) | (1 << 26) | (1 << 27) | (1 << 28) | (1 << 31),
+                (1 << 0)
+                    | (1 << 4)
+                    | (1 << 6)
+                    | (1 << 9)
+                    | (1 << 24)
+                    | (1 << 25)
+                    | (1 << 26)
+                    | (1 << 28),
+            ),
+        ),
+        ((4, 0), cache_leaf(1, 1, 2, 8, 64)),
+        ((4, 1), cache_leaf(2, 1, 2, 8, 64)),
+        ((4, 2), cache_leaf(3, 2, 2, 8, 512)),
+        ((4, 3), cache_leaf(3, 3, 16, 16, 8192)),
+        ((7, 0), leaf(0, (1 << 0) | (1 << 7), 0, 0)),
+        ((0xB, 0), leaf(1, 2, 1 << 8, 5)),
+        ((0xB, 1), leaf(4, 8, (2 << 8) | 1, 5)),
+        ((0xB, 2), leaf(0, 0, 2, 5)),
+        ((0x8000_0000, 0), leaf(0x8000_0008, 0, 0, 0)),
+        (
+            (0x8000_0001, 0),
+            leaf(0, 0, 1, (1 << 11) | (1 << 20) | (1 << 27) | (1 << 29)),
+        ),
+        ((0x8000_0007, 0), leaf(0, 0, 0, 1 << 8)),
+        ((0x8000_0008, 0), leaf(0x3027, 0, 0, 0)),
+    ];
+    leaves.extend(brand_leaves(
+        "       Intel(R) Core(TM) i7-3770 CPU @ 3.40GHz",
+    ));
+    leaves
+}
+
+#[test]
+fn cpuid_intel_identification() {
+    let (cpu, _) = read_canned(&intel_leaves());
+    assert_eq!(cpu.vendor(), Vendor::Intel);
+    assert_eq!(cpu.vendor_id(), "GenuineIntel");
+    assert_eq!(cpu.brand(), "Intel(R) Core(TM) i7-3770 CPU @ 3.40GHz");
+    assert_eq!(cpu.max_leaf, 0xD);
+    assert_eq!(cpu.max_ext_leaf, 0x8000_0008);
+    assert_eq!(
+        cpu.signature,
+        Signature {
+            family: 6,
+            model: 0x3A,
+            stepping: 9
+        }
+    );
+    assert_eq!((cpu.phys_addr_bits, cpu.virt_addr_bits), (39, 48));
+}
+
+#[test]
+fn cpuid_intel_features() {
+    let (cpu, _) = read_canned(&intel_leaves());
+    for feature in [
+        Feature::FPU,
+        Feature::PAE,
+        Feature::SSE2,
+        Feature::PCID,
+        Feature::XSAVE,
+        Feature::OSXSAVE,
+        Feature::AVX,
+        Feature::HYPERVISOR,
+        Feature::FSGSBASE,
+        Feature::SMEP,
+        Feature::SYSCALL,
+        Feature::NX,
+        Feature::RDTSCP,
+        Feature::LONG_MODE,
+        Feature::INVARIANT_TSC,
+    ] {
+        assert!(cpu.has(feature), "{} missing", feature.name());
+    }
+    for feature in [
+        Feature::SMAP,
+        Feature::UMIP,
+        Feature::LA57,
+        Feature::PAGE_1GB,
+        Feature::X2APIC,
+        Feature::RDRAND,
+        Feature::TOPOEXT,
+    ] {
+        assert!(!cpu.has(feature), "{} reported", feature.name());
+    }
+    assert_eq!(
+        cpu.feature_names().to_string(),
+        "fpu tsc pae apic fxsr sse sse2 ht sse3 ssse3 pcid xsave osxsave avx hypervisor \
+         fsgsbase smep syscall nx rdtscp lm invtsc"
+    );
+}
+
+#[test]
+fn cpuid_intel_caches_and_topology() {
+    let (cpu, _) = read_canned(&intel_leaves());
+    let caches: Vec<CacheInfo> = cpu.caches().copied().collect();
+    assert_eq!(caches.len(), 4);
+    assert_eq!(
+        caches[0],
+        CacheInfo {
+            level: 1,
+            kind: CacheKind::Data,
+            size: 32 * 1024,
+            line_size: 64,
+            ways: 8,
+            sets: 64,
+            shared_by: 2,
+        }
+    );
+    assert_eq!(caches[1].kind, CacheKind::Instruction);
+    assert_eq!((caches[2].level, caches[2].size), (2, 256 * 1024));
+    assert_eq!((caches[3].level, caches[3].size), (3, 8 << 20));
+    assert_eq!(
+        caches[3].to_string(),
+        "L3 8192 KiB, 16-way, 64-byte lines, shared by 16"
+    );
+    assert_eq!(
+        caches[0].to_string(),
+        "L1d 32 KiB, 8-way, 64-byte lines, shared by 2"
+    );
+
+    // Leaf 0Bh wins over leaf 1's 8-bit APIC ID and HTT count.
+    assert_eq!(cpu.topology.apic_id, 5);
+    assert_eq!(cpu.topology.threads_per_core, 2);
+    assert_eq!(cpu.topology.logical_per_package, 8);
+}
+
+#[test]
+fn cpuid_summary_line() {
+    let (cpu, _) = read_canned(&intel_leaves());
+    assert_eq!(
+        cpu.to_string(),
+        "GenuineIntel \"Intel(R) Core(TM) i7-3770 CPU @ 3.40GHz\", family 0x6 model 0x3a \
+         stepping 9, 39-bit physical / 48-bit virtual, APIC ID 5, 8 logical per package, \
+         2 per core"
+    );
+}
+
+#[test]
+fn cpuid_amd_uses_extended_family_and_cache_leaf() {
+    let leaves = [
+        ((0, 0), vendor_leaf(0x10, b"AuthenticAMD")),
+        (
+            (1, 0),
+            leaf(0x00A2_0F10, (3 << 24) | (12 << 16), 0, 1 << 28),
+        ),
+        ((4, 0), cache_leaf(1, 1, 1, 4, 64)),
+        ((0x8000_0000, 0), leaf(0x8000_0021, 0, 0, 0)),
+        ((0x8000_0001, 0), leaf(0, 0, 1 << 22, 1 << 26)),
+        ((0x8000_001D, 0), cache_leaf(1, 1, 2, 8, 64)),
+        ((0x8000_001D, 1), cache_leaf(3, 2, 2, 8, 1024)),
+    ];
+    let (cpu, queries) = read_canned(&leaves);
+    assert_eq!(cpu.vendor(), Vendor::Amd);
+    assert_eq!(
+        cpu.signature,
+        Signature {
+            family: 0x19,
+            model: 0x21,
+            stepping: 0
+        }
+    );
+    assert!(cpu.has(Feature::TOPOEXT) && cpu.has(Feature::PAGE_1GB));
+    assert!(
+        !queries.iter().any(|&(l, _)| l == 4),
+        "leaf 4 is Intel-only"
+    );
+    let caches: Vec<(u8, u64)> = cpu.caches().map(|c| (c.level, c.size)).collect();
+    assert_eq!(caches, [(1, 32 * 1024), (2, 512 * 1024)]);
+    // No leaf 0Bh: leaf 1 supplies the APIC ID and the HTT count.
+    assert_eq!(cpu.topology.apic_id, 3);
+    assert_eq!(cpu.topology.logical_per_package, 12);
+    assert_eq!(cpu.topology.threads_per_core, 1);
+}
+
+#[test]
+fn cpuid_leaves_above_maximum_are_not_queried() {
+    // A CPU that reports only leaf 1 and echoes leaf 1 EAX for 8000_0000h.
+    let leaves = [
+        ((0, 0), vendor_leaf(1, b"CyrixInstead")),
+        ((1, 0), leaf(0x0001_0543, 0, 0, 1 << 6)),
+        ((0x8000_0000, 0), leaf(0x0001_0543, 0, 0, 0)),
+    ];
+    let (cpu, queries) = read_canned(&leaves);
+    assert_eq!(queries, [(0, 0), (1, 0), (0x8000_0000, 0)]);
+    assert_eq!(cpu.vendor(), Vendor::Other);
+    assert_eq!(cpu.vendor_id(), "CyrixInstead");
+    assert_eq!(cpu.brand(), "");
+    assert_eq!(cpu.max_ext_leaf, 0);
+    // Extended model only applies to families 06h and 0Fh.
+    assert_eq!(
+        cpu.signature,
+        Signature {
+            family: 5,
+            model: 4,
+            stepping: 3
+        }
+    );
+    assert_eq!((cpu.phys_addr_bits, cpu.virt_addr_bits), (36, 32));
+    assert_eq!(cpu.caches().count(), 0);
+    assert!(!cpu.has(Feature::NX) && !cpu.has(Feature::SMEP));
+}
+
+#[test]
+fn cpuid_feature_table_is_consistent() {
+    let empty = CpuFeatures::empty();
+    assert_eq!(empty.features().count(), 0);
+    for (i, a) in Feature::ALL.iter().enumerate() {
+        assert!(!a.name().is_empty());
+        for b in &Feature::ALL[i + 1..] {
+            assert_ne!(a, b, "{} listed twice", a.name());
+            assert_ne!(a.name(), b.name());
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Kernel command line
+//
+// Compiled straight from the kernel source: `cmdline.rs` depends only on
+// `core`.
+// ---------------------------------------------------------------------------
+
+#[path = "../kernel/src/cmdline.rs"]
+mod cmdline;
+
+use cmdline::{KernelOptions, LogLevel, SerialPortId, Warning};
+
+fn parse_collecting(line: &str) -> (KernelOptions<'_>, Vec<Warning<'_>>) {
+    let mut warnings = Vec::new();
+    let options = cmdline::parse(line, |w| warnings.push(w));
+    (options, warnings)
+}
+
+#[test]
+fn empty_cmdline_gives_defaults() {
+    let (options, warnings) = parse_collecting("   ");
+    assert_eq!(options, KernelOptions::default());
+    assert_eq!(options.log, LogLevel::Info);
+    assert_eq!(options.serial, SerialPortId::Com1);
+    assert!(warnings.is_empty());
+}
+
+#[test]
+fn every_option_is_parsed() {
+    let (options, warnings) =
+        parse_collecting("log=debug  serial=com2\tmem=512M noreclaim nokaslr test=paging");
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    assert_eq!(options.log, LogLevel::Debug);
+    assert_eq!(options.serial, SerialPortId::Com2);
+    assert_eq!(options.serial.io_base(), 0x2F8);
+    assert_eq!(options.mem_limit, Some(512 << 20));
+    assert!(options.noreclaim);
+    assert!(options.nokaslr);
+    assert_eq!(options.test, Some("paging"));
+}
+
+#[test]
+fn later_words_override_earlier_ones() {
+    let (options, _) = parse_collecting("log=trace log=warn");
+    assert_eq!(options.log, LogLevel::Warn);
+    assert!(LogLevel::Warn < LogLevel::Info);
+}
+
+#[test]
+fn problems_are_warnings_not_failures() {
+    let (options, warnings) =
+        parse_collecting("quiet log=loud serial= noreclaim=1 mem=12X test nokaslr=yes log=error");
+    assert_eq!(
+        warnings,
+        [
+            Warning::UnknownKey("quiet"),
+            Warning::InvalidValue {
+                key: "log",
+                value: "loud"
+            },
+            Warning::MissingValue("serial"),
+            Warning::UnexpectedValue {
+                key: "noreclaim",
+                value: "1"
+            },
+            Warning::InvalidValue {
+                key: "mem",
+                value: "12X"
+            },
+            Warning::MissingValue("test"),
+            Warning::UnexpectedValue {
+                key: "nokaslr",
+                value: "yes"
+            },
+        ]
+    );
+    // Valid words still apply; rejected ones keep their defaults.
+    assert_eq!(options.log, LogLevel::Error);
+    assert!(!options.noreclaim);
+    assert!(!options.nokaslr);
+    assert_eq!(options.mem_limit, None);
+    assert_eq!(warnings[0].to_string(), "unknown option `quiet`");
+}
+
+#[test]
+fn mem_sizes_accept_units_and_round_to_pages() {
+    use cmdline::parse_size;
+    assert_eq!(parse_size("4096"), Some(4096));
+    assert_eq!(parse_size("5000"), Some(4096));
+    assert_eq!(parse_size("64k"), Some(64 << 10));
+    assert_eq!(parse_size("2G"), Some(2 << 30));
+    assert_eq!(parse_size("100"), None, "below one page");
+    assert_eq!(parse_size("0M"), None);
+    assert_eq!(parse_size("M"), None);
+    assert_eq!(parse_size("-1G"), None);
+    assert_eq!(parse_size("99999999999999999999"), None);
+    assert_eq!(parse_size("17179869184G"), None, "overflows u64");
+}
+
+// ---------------------------------------------------------------------------
+// Boot configuration (`boot.cfg`)
+//
+// Compiled straight from the bootloader source: `config.rs` depends only on
+// `core` and `alloc`.
+// ---------------------------------------------------------------------------
+
+extern crate alloc;
+
+#[path = "../boot/src/config.rs"]
+mod boot_config;
+
+use boot_config::{BootConfig, ModuleSpec, WarningKind};
+
+fn parse_config(text: &str) -> (BootConfig<'_>, Vec<boot_config::Warning<'_>>) {
+    let mut warnings = Vec::new();
+    let config = boot_config::parse(text, |w| warnings.push(w));
+    (config, warnings)
+}
+
+const SAMPLE_CFG: &str = r"# Ferrous boot configuration
+timeout 3
+default test
+
+entry Ferrous (known good)
+    kernel  kernel.elf
+    cmdline log=info  serial=com1
+    initrd  initrd.tar
+
+entry test
+    kernel  test/kernel.elf
+    cmdline log=debug test=paging
+    module  init init.elf
+    module  drivers \EFI\ferrous\drivers.tar
+";
+
+#[test]
+fn boot_cfg_entries_are_parsed() {
+    let (config, warnings) = parse_config(SAMPLE_CFG);
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    assert_eq!(config.timeout_secs, 3);
+    assert_eq!(config.entries.len(), 2);
+    assert_eq!(config.default, 1, "selected by title");
+
+    let good = &config.entries[0];
+    assert_eq!(good.title, "Ferrous (known good)");
+    assert_eq!(good.kernel, "kernel.elf");
+    assert_eq!(good.cmdline, Some("log=info  serial=com1"));
+    assert_eq!(good.initrd, Some("initrd.tar"));
+    assert!(good.modules.is_empty());
+
+    let test = &config.entries[1];
+    assert_eq!(test.initrd, None);
+    assert_eq!(
+        test.modules,
+        [
+            ModuleSpec {
+                name: "init",
+                path: "init.elf"
+            },
+            ModuleSpec {
+                name: "drivers",
+                path: "\\EFI\\ferrous\\drivers.tar"
+            },
+        ]
+    );
+}
+
+#[test]
+fn boot_cfg_defaults() {
+    let (config, warnings) = parse_config("entry only\nkernel k.elf\n");
+    assert!(warnings.is_empty());
+    assert_eq!(config.default, 0);
+    assert_eq!(config.timeout_secs, boot_config::DEFAULT_TIMEOUT_SECS);
+
+    let (config, _) = parse_config("default 1\nentry a\nkernel a\nentry b\nkernel b\n");
+    assert_eq!(config.default, 1, "selected by index");
+
+    let (config, warnings) = parse_config("# nothing here\n\n");
+    assert!(config.entries.is_empty());
+    assert!(warnings.is_empty());
+}
+
+#[test]
+fn boot_cfg_problems_are_warnings() {
+    let text = "\
+kernel early.elf
+timeout soon
+splash on
+entry broken
+    cmdline log=debug
+entry fine
+    kernel ok.elf
+    module lonely
+    initrd
+default missing
+";
+    let (config, warnings) = parse_config(text);
+    let kinds: Vec<_> = warnings.iter().map(|w| (w.line, w.kind)).collect();
+    assert_eq!(
+        kinds,
+        [
+            (1, WarningKind::OutsideEntry("kernel")),
+            (
+                2,
+                WarningKind::InvalidValue {
+                    key: "timeout",
+                    value: "soon"
+                }
+            ),
+            (3, WarningKind::UnknownKey("splash")),
+            (4, WarningKind::NoKernel("broken")),
+            (
+                8,
+                WarningKind::InvalidValue {
+                    key: "module",
+                    value: "lonely"
+                }
+            ),
+            (9, WarningKind::MissingValue("initrd")),
+            (10, WarningKind::UnknownDefault("missing")),
+        ]
+    );
+    assert_eq!(config.entries.len(), 1);
+    assert_eq!(config.entries[0].title, "fine");
+    assert_eq!(config.default, 0);
+    assert_eq!(config.timeout_secs, boot_config::DEFAULT_TIMEOUT_SECS);
+    assert_eq!(
+        warnings[3].to_string(),
+        "line 4: entry `broken` has no kernel; dropped"
+    );
+}
+
+#[test]
+fn boot_paths_are_resolved_against_the_boot_dir() {
+    use boot_config::resolve_path;
+    assert_eq!(resolve_path("kernel.elf"), "\\EFI\\ferrous\\kernel.elf");
+    assert_eq!(
+        resolve_path("test/kernel.elf"),
+        "\\EFI\\ferrous\\test\\kernel.elf"
+    );
+    assert_eq!(resolve_path("/EFI/other/k.elf"), "\\EFI\\other\\k.elf");
+    assert_eq!(resolve_path("\\k.elf"), "\\k.elf");
+    assert_eq!(
+        boot_config::parse_module("init  init.elf"),
+        Some(ModuleSpec {
+            name: "init",
+            path: "init.elf"
+        })
+    );
+    assert_eq!(boot_config::parse_module("a b c"), None);
 }
 
 // ---------------------------------------------------------------------------